@@ -1,6 +1,6 @@
 use super::bmesh::{EdgeKey, VertKey};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BMDiskLink {
     next: Option<EdgeKey>,
     prev: Option<EdgeKey>,
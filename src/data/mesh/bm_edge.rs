@@ -4,12 +4,15 @@ use super::{
     bmesh::{BMesh, LoopKey, VertKey},
 };
 
+#[derive(Clone)]
 pub struct BMEdge {
     pub v0: VertKey,
     pub v1: VertKey,
     pub r#loop: Option<LoopKey>,
     pub v0_disk_link: BMDiskLink,
     pub v1_disk_link: BMDiskLink,
+    /// Selected in edit mode
+    pub selected: bool,
 }
 
 pub fn bm_edge_create(bmesh: &mut BMesh, v0: VertKey, v1: VertKey) -> super::bmesh::EdgeKey {
@@ -19,6 +22,7 @@ pub fn bm_edge_create(bmesh: &mut BMesh, v0: VertKey, v1: VertKey) -> super::bme
         r#loop: None,
         v0_disk_link: BMDiskLink::new(),
         v1_disk_link: BMDiskLink::new(),
+        selected: false,
     });
 
     bmesh_disk_edge_append(bmesh, e_key, v0);
@@ -27,7 +31,6 @@ pub fn bm_edge_create(bmesh: &mut BMesh, v0: VertKey, v1: VertKey) -> super::bme
     e_key
 }
 
-#[allow(dead_code)]
 pub fn bm_edge_kill(bmesh: &mut BMesh, edge: super::bmesh::EdgeKey) {
     while let Some(loop_key) = bmesh.edges.get(edge).and_then(|e| e.r#loop) {
         let face_key = bmesh.loops[loop_key].face;
@@ -8,9 +8,12 @@ use super::{
     bmesh::{BMesh, EdgeKey, FaceKey, LoopKey, VertKey},
 };
 
+#[derive(Clone)]
 pub struct BMFace {
     pub loop_start: Option<LoopKey>,
     pub loop_len: usize,
+    /// Selected in edit mode
+    pub selected: bool,
 }
 
 pub fn bm_face_create(
@@ -45,6 +48,7 @@ pub fn bm_face_create_internal(bmesh: &mut BMesh) -> FaceKey {
     bmesh.faces.insert(BMFace {
         loop_len: 0,
         loop_start: None,
+        selected: false,
     })
 }
 
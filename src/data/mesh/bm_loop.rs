@@ -1,5 +1,6 @@
 use super::bmesh::{BMesh, EdgeKey, FaceKey, LoopKey, VertKey};
 
+#[derive(Clone)]
 pub struct BMLoop {
     pub vertex: VertKey,
     pub edge: Option<EdgeKey>,
@@ -0,0 +1,435 @@
+//! Edit mode selection of vertices, edges and faces
+//!
+//! Every element carries its own `selected` flag, and the select mode decides which of them the
+//! user picks directly. Selecting an element selects the vertices and edges that make it up, and
+//! [`bm_select_flush`] then brings the rest in line with the elements of the current mode.
+
+use glam::{Mat4, Vec2};
+
+use crate::data::polygon::{closest_point_on_segment, contains_point, signed_area};
+
+use super::{
+    bm_edge::bm_edge_kill,
+    bm_face::bm_face_kill,
+    bm_loop::BMLoopIterator,
+    bm_vert::bm_vert_kill,
+    bmesh::{bm_face_positions, BMesh, EdgeKey, FaceKey, VertKey},
+};
+
+/// Which kind of element edit mode picks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MeshSelectMode {
+    #[default]
+    Vertex,
+    Edge,
+    Face,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MeshElement {
+    Vert(VertKey),
+    Edge(EdgeKey),
+    Face(FaceKey),
+}
+
+pub fn bm_select_all(bmesh: &mut BMesh, selected: bool) {
+    bmesh
+        .vertices
+        .values_mut()
+        .for_each(|v| v.selected = selected);
+    bmesh.edges.values_mut().for_each(|e| e.selected = selected);
+    bmesh.faces.values_mut().for_each(|f| f.selected = selected);
+}
+
+pub fn bm_is_selected(bmesh: &BMesh, element: MeshElement) -> bool {
+    match element {
+        MeshElement::Vert(v) => bmesh.vertices.get(v).is_some_and(|v| v.selected),
+        MeshElement::Edge(e) => bmesh.edges.get(e).is_some_and(|e| e.selected),
+        MeshElement::Face(f) => bmesh.faces.get(f).is_some_and(|f| f.selected),
+    }
+}
+
+/// Select or deselect an element along with the vertices and edges it is made of
+pub fn bm_select_element(bmesh: &mut BMesh, element: MeshElement, selected: bool) {
+    match element {
+        MeshElement::Vert(v) => {
+            if let Some(vert) = bmesh.vertices.get_mut(v) {
+                vert.selected = selected;
+            }
+        }
+        MeshElement::Edge(e) => {
+            let Some(edge) = bmesh.edges.get_mut(e) else {
+                return;
+            };
+            edge.selected = selected;
+            let (v0, v1) = (edge.v0, edge.v1);
+            bmesh.vertices[v0].selected = selected;
+            bmesh.vertices[v1].selected = selected;
+        }
+        MeshElement::Face(f) => {
+            let Some(face) = bmesh.faces.get_mut(f) else {
+                return;
+            };
+            face.selected = selected;
+            for (v, e) in bm_face_elements(bmesh, f) {
+                bmesh.vertices[v].selected = selected;
+                if let Some(e) = e {
+                    bmesh.edges[e].selected = selected;
+                }
+            }
+        }
+    }
+}
+
+/// Make the selection consistent with the elements `mode` picks
+///
+/// In vertex mode an edge or face is selected when all of its vertices are. In edge mode faces
+/// follow their edges and vertices are selected when any edge using them is, and in face mode
+/// edges and vertices are selected when any selected face uses them.
+pub fn bm_select_flush(bmesh: &mut BMesh, mode: MeshSelectMode) {
+    let faces = bmesh.faces.keys().collect::<Vec<_>>();
+
+    match mode {
+        MeshSelectMode::Vertex => {
+            for edge in bmesh.edges.values_mut() {
+                edge.selected =
+                    bmesh.vertices[edge.v0].selected && bmesh.vertices[edge.v1].selected;
+            }
+            for f in faces {
+                let selected = bm_face_elements(bmesh, f)
+                    .iter()
+                    .all(|&(v, _)| bmesh.vertices[v].selected);
+                bmesh.faces[f].selected = selected;
+            }
+        }
+        MeshSelectMode::Edge => {
+            for f in faces {
+                let selected = bm_face_elements(bmesh, f)
+                    .iter()
+                    .all(|&(_, e)| e.is_some_and(|e| bmesh.edges[e].selected));
+                bmesh.faces[f].selected = selected;
+            }
+            flush_edges_to_verts(bmesh);
+        }
+        MeshSelectMode::Face => {
+            bmesh.edges.values_mut().for_each(|e| e.selected = false);
+            for f in faces.into_iter().filter(|&f| bmesh.faces[f].selected) {
+                for (_, e) in bm_face_elements(bmesh, f) {
+                    if let Some(e) = e {
+                        bmesh.edges[e].selected = true;
+                    }
+                }
+            }
+            flush_edges_to_verts(bmesh);
+        }
+    }
+}
+
+fn flush_edges_to_verts(bmesh: &mut BMesh) {
+    bmesh.vertices.values_mut().for_each(|v| v.selected = false);
+    for e in bmesh.edges.keys().collect::<Vec<_>>() {
+        let edge = &bmesh.edges[e];
+        if edge.selected {
+            let (v0, v1) = (edge.v0, edge.v1);
+            bmesh.vertices[v0].selected = true;
+            bmesh.vertices[v1].selected = true;
+        }
+    }
+}
+
+/// Vertices and edges around a face, in loop order
+fn bm_face_elements(bmesh: &BMesh, face: FaceKey) -> Vec<(VertKey, Option<EdgeKey>)> {
+    match bmesh.faces.get(face).and_then(|f| f.loop_start) {
+        Some(loop_start) => BMLoopIterator::new(bmesh, loop_start)
+            .map(|l| (bmesh.loops[l].vertex, bmesh.loops[l].edge))
+            .collect(),
+        None => vec![],
+    }
+}
+
+pub fn bm_selected_verts(bmesh: &BMesh) -> Vec<VertKey> {
+    bmesh
+        .vertices
+        .iter()
+        .filter(|(_, v)| v.selected)
+        .map(|(key, _)| key)
+        .collect()
+}
+
+/// Average of a face's vertex positions
+pub fn bm_face_centre(bmesh: &BMesh, face: FaceKey) -> Vec2 {
+    let positions = bm_face_positions(bmesh, face);
+    positions.iter().sum::<Vec2>() / positions.len().max(1) as f32
+}
+
+/// The element of the kind `mode` picks under `point`
+///
+/// `matrix` takes the mesh into the space `point` and `radius` are in. Vertices and edges are
+/// picked by the nearest one within `radius`, faces by the smallest one containing the point.
+pub fn bm_pick(
+    bmesh: &BMesh,
+    mode: MeshSelectMode,
+    matrix: Mat4,
+    point: Vec2,
+    radius: f32,
+) -> Option<MeshElement> {
+    let to_space = |pos: Vec2| matrix.transform_point3(pos.extend(0.0)).truncate();
+    let nearest = |elements: Vec<(MeshElement, f32)>| {
+        elements
+            .into_iter()
+            .filter(|&(_, distance)| distance <= radius)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(element, _)| element)
+    };
+
+    match mode {
+        MeshSelectMode::Vertex => nearest(
+            bmesh
+                .vertices
+                .iter()
+                .map(|(v, vert)| {
+                    let distance = to_space(vert.vertex.pos).distance(point);
+                    (MeshElement::Vert(v), distance)
+                })
+                .collect(),
+        ),
+        MeshSelectMode::Edge => nearest(
+            bmesh
+                .edges
+                .iter()
+                .map(|(e, edge)| {
+                    let a = to_space(bmesh.vertices[edge.v0].vertex.pos);
+                    let b = to_space(bmesh.vertices[edge.v1].vertex.pos);
+                    (MeshElement::Edge(e), segment_distance(a, b, point))
+                })
+                .collect(),
+        ),
+        MeshSelectMode::Face => bmesh
+            .faces
+            .keys()
+            .filter_map(|f| {
+                let polygon = bm_face_positions(bmesh, f)
+                    .into_iter()
+                    .map(to_space)
+                    .collect::<Vec<_>>();
+                contains_point(&polygon, point).then(|| (f, signed_area(&polygon).abs()))
+            })
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(f, _)| MeshElement::Face(f)),
+    }
+}
+
+/// Elements of the kind `mode` picks that lie inside a shape
+///
+/// `inside` is given positions after `matrix`. Edges need both ends inside and faces their
+/// centre.
+pub fn bm_elements_inside(
+    bmesh: &BMesh,
+    mode: MeshSelectMode,
+    matrix: Mat4,
+    inside: impl Fn(Vec2) -> bool,
+) -> Vec<MeshElement> {
+    let inside = |pos: Vec2| inside(matrix.transform_point3(pos.extend(0.0)).truncate());
+
+    match mode {
+        MeshSelectMode::Vertex => bmesh
+            .vertices
+            .iter()
+            .filter(|(_, vert)| inside(vert.vertex.pos))
+            .map(|(v, _)| MeshElement::Vert(v))
+            .collect(),
+        MeshSelectMode::Edge => bmesh
+            .edges
+            .iter()
+            .filter(|(_, edge)| {
+                inside(bmesh.vertices[edge.v0].vertex.pos)
+                    && inside(bmesh.vertices[edge.v1].vertex.pos)
+            })
+            .map(|(e, _)| MeshElement::Edge(e))
+            .collect(),
+        MeshSelectMode::Face => bmesh
+            .faces
+            .keys()
+            .filter(|&f| inside(bm_face_centre(bmesh, f)))
+            .map(MeshElement::Face)
+            .collect(),
+    }
+}
+
+/// Delete the selected elements of the kind `mode` picks
+///
+/// Deleting an element deletes everything built on it, so removing a vertex removes its edges
+/// and faces. Selected vertices and edges left with nothing using them go too.
+pub fn bm_delete_selected(bmesh: &mut BMesh, mode: MeshSelectMode) {
+    if mode == MeshSelectMode::Face {
+        let faces = bmesh
+            .faces
+            .iter()
+            .filter(|(_, f)| f.selected)
+            .map(|(key, _)| key)
+            .collect::<Vec<_>>();
+        for f in faces {
+            bm_face_kill(bmesh, f);
+        }
+    }
+
+    if mode != MeshSelectMode::Vertex {
+        let edges = bmesh
+            .edges
+            .iter()
+            .filter(|(_, e)| e.selected && (mode == MeshSelectMode::Edge || e.r#loop.is_none()))
+            .map(|(key, _)| key)
+            .collect::<Vec<_>>();
+        for e in edges {
+            bm_edge_kill(bmesh, e);
+        }
+    }
+
+    let verts = bmesh
+        .vertices
+        .iter()
+        .filter(|(_, v)| v.selected && (mode == MeshSelectMode::Vertex || v.edge.is_none()))
+        .map(|(key, _)| key)
+        .collect::<Vec<_>>();
+    for v in verts {
+        bm_vert_kill(bmesh, v);
+    }
+}
+
+fn segment_distance(a: Vec2, b: Vec2, point: Vec2) -> f32 {
+    point.distance(closest_point_on_segment(a, b, point))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::mesh::bmesh::bm_from_polygons;
+
+    use super::*;
+
+    /// Two unit squares side by side, sharing no vertices
+    fn two_squares() -> BMesh {
+        let square = |x: f32| {
+            vec![
+                Vec2::new(x, 0.0),
+                Vec2::new(x + 1.0, 0.0),
+                Vec2::new(x + 1.0, 1.0),
+                Vec2::new(x, 1.0),
+            ]
+        };
+        bm_from_polygons(&[square(0.0), square(2.0)])
+    }
+
+    fn counts(bmesh: &BMesh) -> [usize; 3] {
+        [
+            bmesh.vertices.values().filter(|v| v.selected).count(),
+            bmesh.edges.values().filter(|e| e.selected).count(),
+            bmesh.faces.values().filter(|f| f.selected).count(),
+        ]
+    }
+
+    #[test]
+    fn selection_flushes_by_mode() {
+        let mut bmesh = two_squares();
+
+        // Two corners of the left square select the edge between them but not the face
+        let picked = bm_elements_inside(&bmesh, MeshSelectMode::Vertex, Mat4::IDENTITY, |p| {
+            p.x < 1.5 && p.y < 0.5
+        });
+        for &element in &picked {
+            bm_select_element(&mut bmesh, element, true);
+        }
+        bm_select_flush(&mut bmesh, MeshSelectMode::Vertex);
+        assert_eq!(counts(&bmesh), [2, 1, 0]);
+
+        // Picking the face selects everything around it
+        let face = bm_pick(
+            &bmesh,
+            MeshSelectMode::Face,
+            Mat4::IDENTITY,
+            Vec2::new(2.5, 0.5),
+            0.0,
+        )
+        .unwrap();
+        bm_select_element(&mut bmesh, face, true);
+        bm_select_flush(&mut bmesh, MeshSelectMode::Face);
+        assert_eq!(counts(&bmesh), [4, 4, 1]);
+
+        // Deselecting one of its edges in edge mode drops the face and keeps the other vertices
+        let edge = bm_pick(
+            &bmesh,
+            MeshSelectMode::Edge,
+            Mat4::IDENTITY,
+            Vec2::new(2.5, 0.05),
+            0.1,
+        )
+        .unwrap();
+        bm_select_element(&mut bmesh, edge, false);
+        bm_select_flush(&mut bmesh, MeshSelectMode::Edge);
+        assert_eq!(counts(&bmesh), [4, 3, 0]);
+    }
+
+    #[test]
+    fn picking_works_in_the_space_of_the_matrix() {
+        let bmesh = two_squares();
+        let matrix = Mat4::from_scale(glam::Vec3::new(10.0, 10.0, 1.0));
+
+        let vert = bm_pick(
+            &bmesh,
+            MeshSelectMode::Vertex,
+            matrix,
+            Vec2::new(30.5, 9.0),
+            2.0,
+        );
+        let MeshElement::Vert(v) = vert.unwrap() else {
+            panic!("expected a vertex");
+        };
+        assert_eq!(bmesh.vertices[v].vertex.pos, Vec2::new(3.0, 1.0));
+
+        assert_eq!(
+            bm_pick(
+                &bmesh,
+                MeshSelectMode::Vertex,
+                matrix,
+                Vec2::new(15.0, 5.0),
+                2.0
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn deleting_removes_what_is_built_on_the_selection() {
+        // A vertex takes its edges and the face with it
+        let mut bmesh = two_squares();
+        let v = bm_pick(
+            &bmesh,
+            MeshSelectMode::Vertex,
+            Mat4::IDENTITY,
+            Vec2::ZERO,
+            0.1,
+        )
+        .unwrap();
+        bm_select_element(&mut bmesh, v, true);
+        bm_delete_selected(&mut bmesh, MeshSelectMode::Vertex);
+        assert_eq!(bmesh.vertices.len(), 7);
+        assert_eq!(bmesh.edges.len(), 6);
+        assert_eq!(bmesh.faces.len(), 1);
+
+        // A face leaves nothing of its own behind, but keeps what it shares
+        let mut bmesh = two_squares();
+        let f = bm_pick(
+            &bmesh,
+            MeshSelectMode::Face,
+            Mat4::IDENTITY,
+            Vec2::new(0.5, 0.5),
+            0.0,
+        )
+        .unwrap();
+        bm_select_element(&mut bmesh, f, true);
+        bm_select_flush(&mut bmesh, MeshSelectMode::Face);
+        bm_delete_selected(&mut bmesh, MeshSelectMode::Face);
+        assert_eq!(bmesh.vertices.len(), 4);
+        assert_eq!(bmesh.edges.len(), 4);
+        assert_eq!(bmesh.faces.len(), 1);
+    }
+}
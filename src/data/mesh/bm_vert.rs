@@ -5,10 +5,12 @@ use super::{
     bmesh::{BMesh, EdgeKey},
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct BMVert {
     pub edge: Option<EdgeKey>,
     pub vertex: Vertex,
+    /// Selected in edit mode
+    pub selected: bool,
 }
 
 impl From<(f32, f32)> for BMVert {
@@ -16,6 +18,7 @@ impl From<(f32, f32)> for BMVert {
         BMVert {
             edge: None,
             vertex: Vertex::from(input),
+            selected: false,
         }
     }
 }
@@ -24,7 +27,6 @@ pub fn bm_vert_create(bmesh: &mut BMesh) -> super::bmesh::VertKey {
     bmesh.vertices.insert(BMVert::from((0.0, 0.0)))
 }
 
-#[allow(dead_code)]
 pub fn bm_vert_kill(bmesh: &mut BMesh, vert: super::bmesh::VertKey) {
     while let Some(edge) = bmesh.vertices.get(vert).and_then(|v| v.edge) {
         bm_edge_kill(bmesh, edge);
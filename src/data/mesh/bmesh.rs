@@ -1,12 +1,18 @@
+use std::collections::HashMap;
+
+use glam::Vec2;
 use slotmap::{new_key_type, SlotMap};
 
-use crate::data::vertex::{Index, Vertex};
+use crate::data::{
+    polygon::{contains_point, signed_area, Polygon},
+    vertex::{Index, Vertex},
+};
 
 use super::{
-    bm_edge::BMEdge,
-    bm_face::BMFace,
+    bm_edge::{bm_edge_create, BMEdge},
+    bm_face::{bm_face_create, BMFace},
     bm_loop::{BMLoop, BMLoopIterator},
-    bm_vert::BMVert,
+    bm_vert::{bm_vert_create, BMVert},
 };
 
 new_key_type! {
@@ -16,6 +22,8 @@ new_key_type! {
     pub struct FaceKey;
 }
 
+/// Cloning keeps every key, so keys into the original name the same elements in the copy
+#[derive(Clone)]
 pub struct BMesh {
     pub vertices: SlotMap<VertKey, BMVert>,
     pub edges: SlotMap<EdgeKey, BMEdge>,
@@ -39,34 +47,86 @@ impl BMesh {
     }
 }
 
+/// Triangulate every face of the mesh
+///
+/// Counter-clockwise faces are solid. A clockwise face that lies inside a solid face is cut out
+/// of the smallest solid face containing it. Clockwise faces that are not inside any solid face
+/// are filled as if they were solid.
 #[allow(dead_code)]
 pub fn bm_triangulate(bmesh: &BMesh) -> (Vec<Vertex>, Vec<Index>) {
     let mut all_bm_vertices: Vec<VertKey> = vec![];
     let mut all_indices: Vec<Index> = vec![];
 
-    for (_, face) in &bmesh.faces {
-        if let Some(loop_start) = face.loop_start {
-            let vertices = BMLoopIterator::new(bmesh, loop_start)
+    let face_vertices = bmesh
+        .faces
+        .values()
+        .filter_map(|face| face.loop_start)
+        .map(|loop_start| {
+            BMLoopIterator::new(bmesh, loop_start)
                 .map(|l| bmesh.loops[l].vertex)
-                .collect::<Vec<VertKey>>();
+                .collect::<Vec<VertKey>>()
+        })
+        .filter(|vertices| vertices.len() >= 3)
+        .collect::<Vec<Vec<VertKey>>>();
+
+    let face_positions = face_vertices
+        .iter()
+        .map(|vertices| {
+            vertices
+                .iter()
+                .map(|v| bmesh.vertices[*v].vertex.pos)
+                .collect::<Polygon>()
+        })
+        .collect::<Vec<Polygon>>();
+    let face_areas = face_positions
+        .iter()
+        .map(|positions| signed_area(positions))
+        .collect::<Vec<f32>>();
+
+    // Assign each clockwise face to the smallest solid face around it
+    let mut face_holes: Vec<Vec<usize>> = vec![vec![]; face_vertices.len()];
+    let mut is_hole = vec![false; face_vertices.len()];
+    for hole in (0..face_vertices.len()).filter(|&i| face_areas[i] < 0.0) {
+        let outer = (0..face_vertices.len())
+            .filter(|&i| face_areas[i] > 0.0)
+            .filter(|&i| contains_point(&face_positions[i], face_positions[hole][0]))
+            .min_by(|&a, &b| face_areas[a].total_cmp(&face_areas[b]));
+
+        if let Some(outer) = outer {
+            face_holes[outer].push(hole);
+            is_hole[hole] = true;
+        }
+    }
+
+    for face in (0..face_vertices.len()).filter(|&i| !is_hole[i]) {
+        let mut vertices = face_vertices[face].clone();
+        let mut hole_indices = vec![];
+        for &hole in &face_holes[face] {
+            hole_indices.push(vertices.len());
+            vertices.extend_from_slice(&face_vertices[hole]);
+        }
+
+        let flattened_verts = vertices
+            .iter()
+            .flat_map(|v| {
+                [
+                    bmesh.vertices[*v].vertex.pos.x,
+                    bmesh.vertices[*v].vertex.pos.y,
+                ]
+            })
+            .collect::<Vec<f32>>();
+
+        let indices = earcutr::earcut(&flattened_verts, &hole_indices, 2).unwrap();
 
-            let flattened_verts = vertices
+        for index in indices {
+            if let Some(position) = all_bm_vertices
                 .iter()
-                .flat_map(|v| [bmesh.vertices[*v].vertex.pos.x, bmesh.vertices[*v].vertex.pos.y])
-                .collect::<Vec<f32>>();
-
-            let indices = earcutr::earcut(&flattened_verts, &[], 2).unwrap();
-
-            for index in indices {
-                if let Some(position) = all_bm_vertices
-                    .iter()
-                    .position(|val| val == &vertices[index])
-                {
-                    all_indices.push(position as u32);
-                } else {
-                    all_bm_vertices.push(vertices[index]);
-                    all_indices.push((all_bm_vertices.len() - 1) as Index);
-                }
+                .position(|val| val == &vertices[index])
+            {
+                all_indices.push(position as u32);
+            } else {
+                all_bm_vertices.push(vertices[index]);
+                all_indices.push((all_bm_vertices.len() - 1) as Index);
             }
         }
     }
@@ -78,12 +138,151 @@ pub fn bm_triangulate(bmesh: &BMesh) -> (Vec<Vertex>, Vec<Index>) {
     (all_vertices, all_indices)
 }
 
-pub fn bm_edge_list(bmesh: &BMesh) -> Vec<Vertex> {
+/// Positions of a face's vertices in loop order
+pub fn bm_face_positions(bmesh: &BMesh, face: FaceKey) -> Vec<Vec2> {
+    match bmesh.faces.get(face).and_then(|f| f.loop_start) {
+        Some(loop_start) => BMLoopIterator::new(bmesh, loop_start)
+            .map(|l| bmesh.vertices[bmesh.loops[l].vertex].vertex.pos)
+            .collect(),
+        None => vec![],
+    }
+}
+
+/// Positions of every face in the mesh, one polygon per face
+pub fn bm_face_polygons(bmesh: &BMesh) -> Vec<Vec<Vec2>> {
+    bmesh
+        .faces
+        .keys()
+        .map(|f| bm_face_positions(bmesh, f))
+        .filter(|polygon| polygon.len() >= 3)
+        .collect()
+}
+
+/// Create a closed polygon face from a list of positions, with its own vertices and edges
+pub fn bm_polygon_create(bmesh: &mut BMesh, points: &[Vec2]) -> FaceKey {
+    let verts = points
+        .iter()
+        .map(|point| {
+            let v = bm_vert_create(bmesh);
+            bmesh.vertices[v].vertex = Vertex { pos: *point };
+            v
+        })
+        .collect::<Vec<VertKey>>();
+
+    let edges = (0..verts.len())
+        .map(|i| bm_edge_create(bmesh, verts[i], verts[(i + 1) % verts.len()]))
+        .collect::<Vec<EdgeKey>>();
+
+    bm_face_create(bmesh, &verts, &edges)
+}
+
+/// Build a new mesh with one face per polygon
+pub fn bm_from_polygons(polygons: &[Vec<Vec2>]) -> BMesh {
+    let mut bmesh = BMesh::new();
+
+    for polygon in polygons.iter().filter(|polygon| polygon.len() >= 3) {
+        bm_polygon_create(&mut bmesh, polygon);
+    }
+
+    bmesh
+}
+
+/// Build a new mesh with one face per polygon, joining faces where they touch
+///
+/// Points closer than `tolerance` become one vertex and faces running along the same pair of
+/// vertices share the edge between them, so faces that were split apart as polygons are
+/// connected again. Points that collapse onto their neighbour are dropped, along with faces left
+/// with fewer than three.
+pub fn bm_from_polygons_welded(polygons: &[Vec<Vec2>], tolerance: f32) -> BMesh {
+    let mut bmesh = BMesh::new();
+    let mut cells: HashMap<(i64, i64), Vec<VertKey>> = HashMap::new();
+    let mut edges: HashMap<(VertKey, VertKey), EdgeKey> = HashMap::new();
+
+    let cell = |pos: Vec2| {
+        let cell = (pos / tolerance).floor();
+        (cell.x as i64, cell.y as i64)
+    };
+
+    for polygon in polygons {
+        let mut verts: Vec<VertKey> = Vec::with_capacity(polygon.len());
+        for &point in polygon {
+            let (x, y) = cell(point);
+            let found = (x - 1..=x + 1)
+                .flat_map(|x| (y - 1..=y + 1).map(move |y| (x, y)))
+                .filter_map(|key| cells.get(&key))
+                .flatten()
+                .copied()
+                .find(|&v| bmesh.vertices[v].vertex.pos.distance(point) < tolerance);
+            let v = found.unwrap_or_else(|| {
+                let v = bm_vert_create(&mut bmesh);
+                bmesh.vertices[v].vertex = Vertex { pos: point };
+                cells.entry((x, y)).or_default().push(v);
+                v
+            });
+            if verts.last() != Some(&v) {
+                verts.push(v);
+            }
+        }
+        if verts.len() > 1 && verts.first() == verts.last() {
+            verts.pop();
+        }
+        if verts.len() < 3 {
+            continue;
+        }
+
+        let face_edges = (0..verts.len())
+            .map(|i| {
+                let (v0, v1) = (verts[i], verts[(i + 1) % verts.len()]);
+                let key = if v0 < v1 { (v0, v1) } else { (v1, v0) };
+                *edges
+                    .entry(key)
+                    .or_insert_with(|| bm_edge_create(&mut bmesh, v0, v1))
+            })
+            .collect::<Vec<EdgeKey>>();
+
+        bm_face_create(&mut bmesh, &verts, &face_edges);
+    }
+
+    // Lone points left behind by dropped faces
+    let loose = bmesh
+        .vertices
+        .iter()
+        .filter(|(_, v)| v.edge.is_none())
+        .map(|(key, _)| key)
+        .collect::<Vec<_>>();
+    for v in loose {
+        bmesh.vertices.remove(v);
+    }
+
+    bmesh
+}
+
+/// Local-space bounds of all vertices in the mesh, or `None` if the mesh is empty
+pub fn bm_bounds(bmesh: &BMesh) -> Option<(Vec2, Vec2)> {
+    bmesh.vertices.values().fold(None, |bounds, v| {
+        let pos = v.vertex.pos;
+        Some(match bounds {
+            Some((min, max)) => (min.min(pos), max.max(pos)),
+            None => (pos, pos),
+        })
+    })
+}
+
+/// Every edge with the vertices at its ends
+pub fn bm_edge_list(bmesh: &BMesh) -> Vec<(EdgeKey, [Vertex; 2])> {
     bmesh
         .edges
         .iter()
-        .flat_map(|(_, edge)| [bmesh.vertices[edge.v0].vertex, bmesh.vertices[edge.v1].vertex])
-        .collect::<Vec<Vertex>>()
+        .map(|(key, edge)| {
+            (
+                key,
+                [
+                    bmesh.vertices[edge.v0].vertex,
+                    bmesh.vertices[edge.v1].vertex,
+                ],
+            )
+        })
+        .collect()
 }
 
 #[cfg(test)]
@@ -97,7 +296,7 @@ mod tests {
         vertex::Vertex,
     };
 
-    use super::BMesh;
+    use super::{bm_from_polygons, bm_triangulate, BMesh};
 
     #[test]
     fn create_edge_remove_vert() {
@@ -190,6 +389,29 @@ mod tests {
         assert_eq!(bmesh.loops.len(), 0);
     }
 
+    #[test]
+    fn triangulate_face_with_hole() {
+        let outer = [(-2.0, -2.0), (2.0, -2.0), (2.0, 2.0), (-2.0, 2.0)];
+        let hole = [(-1.0, -1.0), (-1.0, 1.0), (1.0, 1.0), (1.0, -1.0)];
+        let bmesh = bm_from_polygons(&[
+            outer.map(|p| Vertex::from(p).pos).to_vec(),
+            hole.map(|p| Vertex::from(p).pos).to_vec(),
+        ]);
+
+        let (vertices, indices) = bm_triangulate(&bmesh);
+
+        let area = indices
+            .chunks(3)
+            .map(|tri| {
+                let [a, b, c] = [0, 1, 2].map(|i| vertices[tri[i] as usize].pos);
+                ((b - a).perp_dot(c - a) / 2.0).abs()
+            })
+            .sum::<f32>();
+
+        assert_eq!(vertices.len(), 8);
+        assert!((area - 12.0).abs() < 1e-4);
+    }
+
     #[test]
     fn create_square() {
         let mut bmesh = BMesh::new();
@@ -2,5 +2,6 @@ pub mod bm_disk_link;
 pub mod bm_edge;
 pub mod bm_face;
 pub mod bm_loop;
+pub mod bm_select;
 pub mod bm_vert;
 pub mod bmesh;
@@ -1,2 +1,4 @@
 pub mod mesh;
+pub mod modifiers;
+pub mod polygon;
 pub mod vertex;
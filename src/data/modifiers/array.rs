@@ -0,0 +1,70 @@
+use glam::{Mat2, Vec2};
+use serde::{Deserialize, Serialize};
+
+use crate::data::polygon::Polygon;
+
+/// Repeat the mesh `count` times, each copy shifted by `offset` from the last
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LinearArray {
+    pub count: u32,
+    pub offset: Vec2,
+}
+
+impl Default for LinearArray {
+    fn default() -> Self {
+        Self {
+            count: 2,
+            offset: Vec2::new(2.5, 0.0),
+        }
+    }
+}
+
+impl LinearArray {
+    pub fn evaluate(&self, polygons: Vec<Polygon>) -> Vec<Polygon> {
+        (0..self.count.max(1))
+            .flat_map(|i| {
+                let shift = self.offset * i as f32;
+                polygons
+                    .iter()
+                    .map(move |polygon| polygon.iter().map(|p| *p + shift).collect())
+            })
+            .collect()
+    }
+}
+
+/// Repeat the mesh `count` times around `centre`, spread evenly over `angle` radians
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RadialArray {
+    pub count: u32,
+    pub centre: Vec2,
+    pub angle: f32,
+}
+
+impl Default for RadialArray {
+    fn default() -> Self {
+        Self {
+            count: 6,
+            centre: Vec2::new(0.0, -3.0),
+            angle: std::f32::consts::TAU,
+        }
+    }
+}
+
+impl RadialArray {
+    pub fn evaluate(&self, polygons: Vec<Polygon>) -> Vec<Polygon> {
+        let count = self.count.max(1);
+        let step = self.angle / count as f32;
+
+        (0..count)
+            .flat_map(|i| {
+                let rotation = Mat2::from_angle(step * i as f32);
+                polygons.iter().map(move |polygon| {
+                    polygon
+                        .iter()
+                        .map(|p| self.centre + rotation * (*p - self.centre))
+                        .collect()
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,48 @@
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::data::polygon::Polygon;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MirrorAxis {
+    /// Flip across a vertical line, mirroring left and right
+    X,
+    /// Flip across a horizontal line, mirroring top and bottom
+    Y,
+}
+
+/// Keep the mesh and add a reflected copy across an axis line
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Mirror {
+    pub axis: MirrorAxis,
+    /// Position of the mirror line along the chosen axis
+    pub centre: f32,
+}
+
+impl Default for Mirror {
+    fn default() -> Self {
+        Self {
+            axis: MirrorAxis::X,
+            centre: 0.0,
+        }
+    }
+}
+
+impl Mirror {
+    fn reflect(&self, p: Vec2) -> Vec2 {
+        match self.axis {
+            MirrorAxis::X => Vec2::new(2.0 * self.centre - p.x, p.y),
+            MirrorAxis::Y => Vec2::new(p.x, 2.0 * self.centre - p.y),
+        }
+    }
+
+    pub fn evaluate(&self, polygons: Vec<Polygon>) -> Vec<Polygon> {
+        // Reflection flips the winding, so walk each loop backwards to keep solids and holes intact
+        let mirrored = polygons
+            .iter()
+            .map(|polygon| polygon.iter().rev().map(|p| self.reflect(*p)).collect())
+            .collect::<Vec<Polygon>>();
+
+        polygons.into_iter().chain(mirrored).collect()
+    }
+}
@@ -0,0 +1,299 @@
+//! Non-destructive modifiers
+//!
+//! Every object owns an ordered [`ModifierStack`]. The stack takes the object's base
+//! [`BMesh`] and runs each enabled modifier in turn, producing an evaluated mesh. Only
+//! the evaluated mesh is triangulated and uploaded to the GPU, the base mesh is left
+//! untouched until a modifier is applied.
+//!
+//! Modifiers work on the face loops of the mesh as plain polygons. Counter-clockwise
+//! loops are solid and clockwise loops are holes, so every modifier has to keep the
+//! winding of the loops it produces.
+
+pub mod array;
+pub mod mirror;
+pub mod offset;
+pub mod round_corners;
+
+use serde::{Deserialize, Serialize};
+
+use super::{
+    mesh::bmesh::{bm_face_polygons, bm_from_polygons_welded, BMesh},
+    polygon::Polygon,
+};
+
+pub use array::{LinearArray, RadialArray};
+pub use mirror::{Mirror, MirrorAxis};
+pub use offset::Offset;
+pub use round_corners::RoundCorners;
+
+/// Distance in local units below which evaluated points are joined into one vertex
+const WELD_DISTANCE: f32 = 1e-4;
+
+/// The operation a modifier performs
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ModifierKind {
+    LinearArray(LinearArray),
+    RadialArray(RadialArray),
+    Mirror(Mirror),
+    Offset(Offset),
+    RoundCorners(RoundCorners),
+}
+
+impl ModifierKind {
+    /// Display name used by the UI
+    pub fn name(&self) -> &'static str {
+        match self {
+            ModifierKind::LinearArray(_) => "Linear Array",
+            ModifierKind::RadialArray(_) => "Radial Array",
+            ModifierKind::Mirror(_) => "Mirror",
+            ModifierKind::Offset(_) => "Offset",
+            ModifierKind::RoundCorners(_) => "Round Corners",
+        }
+    }
+
+    pub fn evaluate(&self, polygons: Vec<Polygon>) -> Vec<Polygon> {
+        match self {
+            ModifierKind::LinearArray(m) => m.evaluate(polygons),
+            ModifierKind::RadialArray(m) => m.evaluate(polygons),
+            ModifierKind::Mirror(m) => m.evaluate(polygons),
+            ModifierKind::Offset(m) => m.evaluate(polygons),
+            ModifierKind::RoundCorners(m) => m.evaluate(polygons),
+        }
+    }
+}
+
+/// A single entry in a modifier stack
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Modifier {
+    pub kind: ModifierKind,
+    /// Disabled modifiers stay in the stack but are skipped during evaluation
+    pub enabled: bool,
+}
+
+impl Modifier {
+    pub fn new(kind: ModifierKind) -> Self {
+        Self {
+            kind,
+            enabled: true,
+        }
+    }
+}
+
+/// Ordered list of modifiers, evaluated first to last
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModifierStack {
+    modifiers: Vec<Modifier>,
+}
+
+impl ModifierStack {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn modifiers(&self) -> &[Modifier] {
+        &self.modifiers
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Modifier> {
+        self.modifiers.get_mut(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.modifiers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.modifiers.is_empty()
+    }
+
+    /// Add a modifier to the end of the stack
+    pub fn push(&mut self, kind: ModifierKind) {
+        self.modifiers.push(Modifier::new(kind));
+    }
+
+    pub fn remove(&mut self, index: usize) -> Option<Modifier> {
+        (index < self.modifiers.len()).then(|| self.modifiers.remove(index))
+    }
+
+    /// Remove the first `count` modifiers, returning them as a stack of their own
+    pub fn take_first(&mut self, count: usize) -> ModifierStack {
+        let count = count.min(self.modifiers.len());
+        ModifierStack {
+            modifiers: self.modifiers.drain(..count).collect(),
+        }
+    }
+
+    pub fn set_enabled(&mut self, index: usize, enabled: bool) {
+        if let Some(modifier) = self.modifiers.get_mut(index) {
+            modifier.enabled = enabled;
+        }
+    }
+
+    /// Move a modifier one step earlier in the stack
+    pub fn move_up(&mut self, index: usize) {
+        if index > 0 && index < self.modifiers.len() {
+            self.modifiers.swap(index - 1, index);
+        }
+    }
+
+    /// Move a modifier one step later in the stack
+    pub fn move_down(&mut self, index: usize) {
+        if index + 1 < self.modifiers.len() {
+            self.modifiers.swap(index, index + 1);
+        }
+    }
+
+    /// True if at least one modifier would change the base mesh
+    pub fn has_enabled(&self) -> bool {
+        self.modifiers.iter().any(|m| m.enabled)
+    }
+
+    /// Run every enabled modifier over the base mesh
+    ///
+    /// The faces are welded back together afterwards, so vertices and edges that faces shared
+    /// before are shared again and an applied result can be edited like the base mesh.
+    /// Returns `None` when there is nothing to evaluate and the base mesh can be used directly
+    pub fn evaluate(&self, base: &BMesh) -> Option<BMesh> {
+        if !self.has_enabled() {
+            return None;
+        }
+
+        let polygons = self
+            .modifiers
+            .iter()
+            .filter(|m| m.enabled)
+            .fold(bm_face_polygons(base), |polygons, m| {
+                m.kind.evaluate(polygons)
+            });
+
+        Some(bm_from_polygons_welded(&polygons, WELD_DISTANCE))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use glam::Vec2;
+
+    use crate::{
+        data::{
+            mesh::bmesh::{bm_face_polygons, BMesh},
+            polygon::signed_area,
+        },
+        shapes::square::create_square,
+    };
+
+    use super::*;
+
+    fn evaluated_polygons(stack: &ModifierStack, base: &BMesh) -> Vec<Polygon> {
+        bm_face_polygons(&stack.evaluate(base).unwrap())
+    }
+
+    #[test]
+    fn empty_stack_does_not_evaluate() {
+        let mut stack = ModifierStack::new();
+        assert!(stack.evaluate(&create_square()).is_none());
+
+        stack.push(ModifierKind::Offset(Offset { distance: 1.0 }));
+        stack.set_enabled(0, false);
+        assert!(stack.evaluate(&create_square()).is_none());
+    }
+
+    #[test]
+    fn linear_array_copies_faces() {
+        let mut stack = ModifierStack::new();
+        stack.push(ModifierKind::LinearArray(LinearArray {
+            count: 3,
+            offset: Vec2::new(3.0, 0.0),
+        }));
+
+        let polygons = evaluated_polygons(&stack, &create_square());
+        assert_eq!(polygons.len(), 3);
+        assert!(polygons[2].contains(&Vec2::new(7.0, 1.0)));
+    }
+
+    #[test]
+    fn radial_array_rotates_about_centre() {
+        let mut stack = ModifierStack::new();
+        stack.push(ModifierKind::RadialArray(RadialArray {
+            count: 4,
+            centre: Vec2::new(0.0, -5.0),
+            angle: 2.0 * PI,
+        }));
+
+        let polygons = evaluated_polygons(&stack, &create_square());
+        assert_eq!(polygons.len(), 4);
+        // Half a turn about (0, -5) puts the square's centre at (0, -10)
+        let centre = polygons[2].iter().sum::<Vec2>() / 4.0;
+        assert!((centre - Vec2::new(0.0, -10.0)).length() < 1e-4);
+    }
+
+    #[test]
+    fn mirror_keeps_winding() {
+        let mut stack = ModifierStack::new();
+        stack.push(ModifierKind::Mirror(Mirror {
+            axis: MirrorAxis::X,
+            centre: 2.0,
+        }));
+
+        let polygons = evaluated_polygons(&stack, &create_square());
+        assert_eq!(polygons.len(), 2);
+        assert!(polygons.iter().all(|p| signed_area(p) > 0.0));
+        assert!(polygons[1].contains(&Vec2::new(5.0, 1.0)));
+    }
+
+    #[test]
+    fn offset_grows_solids_and_shrinks_holes() {
+        let square = vec![
+            Vec2::new(-1.0, -1.0),
+            Vec2::new(1.0, -1.0),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(-1.0, 1.0),
+        ];
+        let hole = square.iter().rev().map(|p| *p * 0.5).collect::<Polygon>();
+
+        let result = Offset { distance: 0.25 }.evaluate(vec![square, hole]);
+
+        assert!((signed_area(&result[0]) - 2.5 * 2.5).abs() < 1e-4);
+        assert!((signed_area(&result[1]) + 0.5 * 0.5).abs() < 1e-4);
+    }
+
+    #[test]
+    fn round_corners_adds_arc_points() {
+        let mut stack = ModifierStack::new();
+        stack.push(ModifierKind::RoundCorners(RoundCorners {
+            radius: 0.5,
+            segments: 4,
+        }));
+
+        let polygons = evaluated_polygons(&stack, &create_square());
+        assert_eq!(polygons[0].len(), 4 * 5);
+        // Each corner loses the area between a 0.5 radius quarter circle and its bounding square,
+        // less a little more for the straight segments cutting inside the arc
+        let area = signed_area(&polygons[0]);
+        let expected = 4.0 - 4.0 * 0.25 * (1.0 - PI / 4.0);
+        assert!(area < expected && area > expected - 0.05);
+    }
+
+    #[test]
+    fn reorder_changes_result() {
+        let mut stack = ModifierStack::new();
+        stack.push(ModifierKind::Offset(Offset { distance: 1.0 }));
+        stack.push(ModifierKind::RoundCorners(RoundCorners {
+            radius: 0.5,
+            segments: 4,
+        }));
+        let offset_first = evaluated_polygons(&stack, &create_square());
+
+        stack.move_up(1);
+        assert!(matches!(
+            stack.modifiers()[0].kind,
+            ModifierKind::RoundCorners(_)
+        ));
+        let round_first = evaluated_polygons(&stack, &create_square());
+
+        assert_eq!(offset_first[0].len(), round_first[0].len());
+        assert_ne!(offset_first[0], round_first[0]);
+    }
+}
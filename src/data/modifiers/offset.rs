@@ -0,0 +1,62 @@
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::data::polygon::Polygon;
+
+/// Longest a mitred corner may reach, as a multiple of the offset distance
+const MITER_LIMIT: f32 = 4.0;
+
+/// Grow (or shrink, with a negative distance) the mesh outline
+///
+/// Solid loops move outwards and hole loops move inwards, so the filled area grows.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Offset {
+    pub distance: f32,
+}
+
+impl Default for Offset {
+    fn default() -> Self {
+        Self { distance: 0.1 }
+    }
+}
+
+impl Offset {
+    pub fn evaluate(&self, polygons: Vec<Polygon>) -> Vec<Polygon> {
+        polygons
+            .into_iter()
+            .map(|polygon| offset_polygon(&polygon, self.distance))
+            .collect()
+    }
+}
+
+/// Move every vertex along its mitred normal
+/// Edge normals point to the right of the direction of travel, which is outwards for
+/// counter-clockwise solids and into the empty space for clockwise holes
+fn offset_polygon(polygon: &[Vec2], distance: f32) -> Polygon {
+    let n = polygon.len();
+
+    (0..n)
+        .map(|i| {
+            let prev = polygon[(i + n - 1) % n];
+            let current = polygon[i];
+            let next = polygon[(i + 1) % n];
+
+            let normal_in = edge_normal(prev, current);
+            let normal_out = edge_normal(current, next);
+
+            let bisector = (normal_in + normal_out).normalize_or_zero();
+            if bisector == Vec2::ZERO {
+                // The edges fold back on themselves, fall back to the outgoing normal
+                return current + normal_out * distance;
+            }
+
+            let cos_half = bisector.dot(normal_out).max(1.0 / MITER_LIMIT);
+            current + bisector * (distance / cos_half)
+        })
+        .collect()
+}
+
+fn edge_normal(a: Vec2, b: Vec2) -> Vec2 {
+    let dir = (b - a).normalize_or_zero();
+    Vec2::new(dir.y, -dir.x)
+}
@@ -0,0 +1,91 @@
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::data::polygon::Polygon;
+
+/// Replace every corner with a circular arc
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoundCorners {
+    pub radius: f32,
+    /// Number of straight segments used for each arc
+    pub segments: u32,
+}
+
+impl Default for RoundCorners {
+    fn default() -> Self {
+        Self {
+            radius: 0.25,
+            segments: 6,
+        }
+    }
+}
+
+impl RoundCorners {
+    pub fn evaluate(&self, polygons: Vec<Polygon>) -> Vec<Polygon> {
+        polygons
+            .into_iter()
+            .map(|polygon| self.round_polygon(&polygon))
+            .collect()
+    }
+
+    fn round_polygon(&self, polygon: &[Vec2]) -> Polygon {
+        let n = polygon.len();
+        if self.radius <= 0.0 || self.segments == 0 || n < 3 {
+            return polygon.to_vec();
+        }
+
+        let mut result = Vec::with_capacity(n * (self.segments as usize + 1));
+
+        for i in 0..n {
+            let prev = polygon[(i + n - 1) % n];
+            let corner = polygon[i];
+            let next = polygon[(i + 1) % n];
+
+            let to_prev = prev - corner;
+            let to_next = next - corner;
+            let dir_prev = to_prev.normalize_or_zero();
+            let dir_next = to_next.normalize_or_zero();
+
+            // Interior angle between the two edges
+            let angle = dir_prev.angle_between(dir_next).abs();
+            let tan_half = (angle / 2.0).tan();
+
+            if dir_prev == Vec2::ZERO || dir_next == Vec2::ZERO || tan_half < 1e-4 {
+                result.push(corner);
+                continue;
+            }
+
+            // Distance from the corner to where the arc meets each edge, never more than
+            // half an edge so neighbouring corners cannot overlap
+            let tangent_length = (self.radius / tan_half)
+                .min(to_prev.length() / 2.0)
+                .min(to_next.length() / 2.0);
+            let radius = tangent_length * tan_half;
+
+            let start = corner + dir_prev * tangent_length;
+            let end = corner + dir_next * tangent_length;
+            let bisector = (dir_prev + dir_next).normalize_or_zero();
+            let centre = corner + bisector * (radius / (angle / 2.0).sin());
+
+            let start_angle = angle_of(start - centre);
+            let mut sweep = angle_of(end - centre) - start_angle;
+            // Always take the short way round the arc
+            if sweep > std::f32::consts::PI {
+                sweep -= std::f32::consts::TAU;
+            } else if sweep < -std::f32::consts::PI {
+                sweep += std::f32::consts::TAU;
+            }
+
+            for s in 0..=self.segments {
+                let t = s as f32 / self.segments as f32;
+                result.push(centre + Vec2::from_angle(start_angle + sweep * t) * radius);
+            }
+        }
+
+        result
+    }
+}
+
+fn angle_of(v: Vec2) -> f32 {
+    v.y.atan2(v.x)
+}
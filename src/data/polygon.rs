@@ -0,0 +1,60 @@
+use glam::Vec2;
+
+/// A closed loop of positions
+pub type Polygon = Vec<Vec2>;
+
+/// Signed area of a polygon, positive for counter-clockwise winding
+pub fn signed_area(polygon: &[Vec2]) -> f32 {
+    let n = polygon.len();
+    (0..n)
+        .map(|i| polygon[i].perp_dot(polygon[(i + 1) % n]))
+        .sum::<f32>()
+        / 2.0
+}
+
+/// Number of times the polygon winds around a point
+/// Counter-clockwise loops count positive, clockwise loops count negative
+pub fn winding_number(polygon: &[Vec2], point: Vec2) -> i32 {
+    let n = polygon.len();
+    let mut winding = 0;
+
+    for i in 0..n {
+        let a = polygon[i];
+        let b = polygon[(i + 1) % n];
+        let side = (b - a).perp_dot(point - a);
+
+        if a.y <= point.y {
+            if b.y > point.y && side > 0.0 {
+                winding += 1;
+            }
+        } else if b.y <= point.y && side < 0.0 {
+            winding -= 1;
+        }
+    }
+
+    winding
+}
+
+/// Point on the segment from `a` to `b` nearest to `point`
+pub fn closest_point_on_segment(a: Vec2, b: Vec2, point: Vec2) -> Vec2 {
+    let ab = b - a;
+    let t = if ab.length_squared() > 0.0 {
+        ((point - a).dot(ab) / ab.length_squared()).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    a + ab * t
+}
+
+/// Check if a point lies inside the polygon, regardless of its winding
+pub fn contains_point(polygon: &[Vec2], point: Vec2) -> bool {
+    winding_number(polygon, point) != 0
+}
+
+/// Reverse the polygon if needed so it winds counter-clockwise (`ccw`) or clockwise
+pub fn orient(mut polygon: Polygon, ccw: bool) -> Polygon {
+    if (signed_area(&polygon) > 0.0) != ccw {
+        polygon.reverse();
+    }
+    polygon
+}
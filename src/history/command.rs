@@ -0,0 +1,509 @@
+//! Reversible edits to the scene
+//!
+//! A command holds both sides of an edit so the history can step it either way. Objects taken
+//! out of the scene come back under new keys, so undoing or redoing a command reports the keys it
+//! changed and the history rewrites every other command that refers to them.
+
+use std::{
+    cell::RefCell,
+    mem::{size_of, size_of_val},
+    rc::Rc,
+};
+
+use crate::{
+    data::modifiers::{Modifier, ModifierStack},
+    opengl::{
+        matrices::Transform2D,
+        scene::{ObjectKey, SceneData},
+        structs::{Colour, Material, Mesh, Object},
+    },
+};
+
+/// An object's old key and the key it was given when it came back
+pub type KeyRemap = (ObjectKey, ObjectKey);
+
+/// The base mesh and modifier stack of an object
+#[derive(Clone)]
+pub struct MeshState {
+    pub mesh: Rc<RefCell<Mesh>>,
+    pub modifiers: ModifierStack,
+}
+
+impl MeshState {
+    pub fn of(object: &Object) -> Self {
+        Self {
+            mesh: object.mesh().clone(),
+            modifiers: object.modifiers().clone(),
+        }
+    }
+
+    pub fn restore(&self, object: &mut Object) {
+        if !Rc::ptr_eq(object.mesh(), &self.mesh) {
+            object.set_mesh(self.mesh.clone());
+        }
+        if object.modifiers() != &self.modifiers {
+            *object.modifiers_mut() = self.modifiers.clone();
+        }
+    }
+
+    fn memory_size(&self) -> usize {
+        mesh_memory_size(&self.mesh) + self.modifiers.len() * size_of::<Modifier>()
+    }
+}
+
+/// Where an object sits in the hierarchy
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ParentState {
+    pub parent: Option<ObjectKey>,
+    /// Transform relative to `parent`
+    pub transform: Transform2D,
+}
+
+impl ParentState {
+    pub fn of(scene_data: &SceneData, key: ObjectKey) -> Option<Self> {
+        let object = scene_data.objects().get(key)?;
+        Some(Self {
+            parent: object.parent(),
+            transform: object.transform(),
+        })
+    }
+
+    fn restore(&self, scene_data: &mut SceneData, key: ObjectKey) {
+        if scene_data.set_parent(key, self.parent, false).is_ok() {
+            scene_data.edit_object(key, |object| object.set_transform(self.transform));
+        }
+    }
+}
+
+/// An object's name and whether it is hidden or locked, as the outliner edits them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectInfo {
+    pub name: String,
+    pub hidden: bool,
+    pub locked: bool,
+}
+
+impl ObjectInfo {
+    pub fn of(object: &Object) -> Self {
+        Self {
+            name: object.name.clone(),
+            hidden: object.hidden,
+            locked: object.locked,
+        }
+    }
+
+    fn restore(&self, scene_data: &mut SceneData, key: ObjectKey) {
+        scene_data.set_hidden(key, self.hidden);
+        if let Some(object) = scene_data.objects_mut().get_mut(key) {
+            object.name = self.name.clone();
+            object.locked = self.locked;
+        }
+    }
+}
+
+/// An object taken out of the scene, with what is needed to put it back as it was
+pub struct RemovedObject {
+    object: Object,
+    parent: Option<ObjectKey>,
+    /// Position in the draw order
+    index: usize,
+    /// Children that were handed to the object's parent, with their transforms relative to it
+    children: Vec<(ObjectKey, Transform2D)>,
+}
+
+impl RemovedObject {
+    /// Take objects out of the scene one after another, as one batch
+    fn take_all(scene_data: &mut SceneData, keys: &[ObjectKey]) -> Vec<Option<Self>> {
+        scene_data
+            .remove_objects(keys, |scene_data, key, index| {
+                let children = scene_data
+                    .children(key)
+                    .iter()
+                    .map(|&child| (child, scene_data.objects()[child].transform()))
+                    .collect();
+                (scene_data.parent(key), index, children)
+            })
+            .into_iter()
+            .map(|removed| {
+                let ((parent, index, children), object) = removed?;
+                Some(Self {
+                    object,
+                    parent,
+                    index,
+                    children,
+                })
+            })
+            .collect()
+    }
+
+    fn restore(self, scene_data: &mut SceneData) -> ObjectKey {
+        let key = scene_data.add_object(self.object);
+        scene_data.set_parent(key, self.parent, false).ok();
+
+        let index = self.index.min(scene_data.object_order().len() - 1);
+        scene_data.reorder_objects(|order| {
+            order.pop();
+            order.insert(index, key);
+        });
+
+        for (child, transform) in self.children {
+            if scene_data.set_parent(child, Some(key), false).is_ok() {
+                scene_data.edit_object(child, |object| object.set_transform(transform));
+            }
+        }
+
+        key
+    }
+
+    fn remap(&mut self, old: ObjectKey, new: ObjectKey) {
+        let children = self.children.iter_mut().map(|(child, _)| child);
+        for key in self.parent.iter_mut().chain(children) {
+            remap_key(key, old, new);
+        }
+    }
+
+    fn memory_size(&self) -> usize {
+        size_of::<Self>()
+            + self.children.len() * size_of::<(ObjectKey, Transform2D)>()
+            + mesh_memory_size(self.object.mesh())
+            + self.object.modifiers().len() * size_of::<Modifier>()
+    }
+}
+
+pub enum Command {
+    /// `removed` holds the object while the command is undone
+    AddObject {
+        key: ObjectKey,
+        removed: Option<RemovedObject>,
+    },
+    /// `removed` holds the object while the command is done
+    RemoveObject {
+        key: ObjectKey,
+        removed: Option<RemovedObject>,
+    },
+    Transform {
+        key: ObjectKey,
+        before: Transform2D,
+        after: Transform2D,
+    },
+    Parent {
+        key: ObjectKey,
+        before: ParentState,
+        after: ParentState,
+    },
+    Info {
+        key: ObjectKey,
+        before: ObjectInfo,
+        after: ObjectInfo,
+    },
+    MaterialColour {
+        material: Rc<RefCell<Material>>,
+        before: Colour,
+        after: Colour,
+    },
+    /// Modifier edits and anything else that changes an object's mesh
+    Mesh {
+        key: ObjectKey,
+        before: MeshState,
+        after: MeshState,
+    },
+    Reorder {
+        before: Vec<ObjectKey>,
+        after: Vec<ObjectKey>,
+    },
+    /// Several commands made as one step, undone in reverse order
+    Group(Vec<Command>),
+}
+
+impl Command {
+    /// Add `object` on top of the others when the command is done
+    pub fn add_object(object: Object) -> Self {
+        Command::AddObject {
+            key: ObjectKey::default(),
+            removed: Some(RemovedObject {
+                parent: object.parent(),
+                object,
+                index: usize::MAX,
+                children: vec![],
+            }),
+        }
+    }
+
+    pub fn remove_object(key: ObjectKey) -> Self {
+        Command::RemoveObject { key, removed: None }
+    }
+
+    /// Make the change, returning the keys of objects that came back under new keys
+    pub fn redo(&mut self, scene_data: &mut SceneData) -> Vec<KeyRemap> {
+        match self {
+            Command::AddObject { key, removed } => restore(scene_data, key, removed),
+            Command::RemoveObject { .. } => {
+                take_objects(scene_data, &mut [self], false);
+                vec![]
+            }
+            Command::Group(commands) => {
+                let mut remaps = vec![];
+                let mut index = 0;
+                while index < commands.len() {
+                    // Objects removed one after another are taken out as one batch
+                    let run = commands[index..]
+                        .iter()
+                        .take_while(|command| command.removes(false))
+                        .count();
+                    if run > 0 {
+                        let mut run = commands[index..index + run].iter_mut().collect::<Vec<_>>();
+                        take_objects(scene_data, &mut run, false);
+                        index += run.len();
+                        continue;
+                    }
+
+                    let changed = commands[index].redo(scene_data);
+                    remap_all(commands, &changed);
+                    remaps.extend(changed);
+                    index += 1;
+                }
+                remaps
+            }
+            _ => {
+                self.set(scene_data, false);
+                vec![]
+            }
+        }
+    }
+
+    /// Revert the change, returning the keys of objects that came back under new keys
+    pub fn undo(&mut self, scene_data: &mut SceneData) -> Vec<KeyRemap> {
+        match self {
+            Command::AddObject { .. } => {
+                take_objects(scene_data, &mut [self], true);
+                vec![]
+            }
+            Command::RemoveObject { key, removed } => restore(scene_data, key, removed),
+            Command::Group(commands) => {
+                let mut remaps = vec![];
+                let mut end = commands.len();
+                while end > 0 {
+                    let run = commands[..end]
+                        .iter()
+                        .rev()
+                        .take_while(|command| command.removes(true))
+                        .count();
+                    if run > 0 {
+                        let mut run = commands[end - run..end]
+                            .iter_mut()
+                            .rev()
+                            .collect::<Vec<_>>();
+                        take_objects(scene_data, &mut run, true);
+                        end -= run.len();
+                        continue;
+                    }
+
+                    end -= 1;
+                    let changed = commands[end].undo(scene_data);
+                    remap_all(commands, &changed);
+                    remaps.extend(changed);
+                }
+                remaps
+            }
+            _ => {
+                self.set(scene_data, true);
+                vec![]
+            }
+        }
+    }
+
+    /// Whether doing the command, or undoing it with `undo`, takes an object out of the scene
+    fn removes(&self, undo: bool) -> bool {
+        matches!(
+            (self, undo),
+            (Command::RemoveObject { .. }, false) | (Command::AddObject { .. }, true)
+        )
+    }
+
+    /// Put back one side of a command that only changes existing objects
+    fn set(&self, scene_data: &mut SceneData, undo: bool) {
+        match self {
+            Command::Transform { key, before, after } => {
+                let transform = *pick(undo, before, after);
+                scene_data.edit_object(*key, |object| object.set_transform(transform));
+            }
+            Command::Parent { key, before, after } => {
+                pick(undo, before, after).restore(scene_data, *key);
+            }
+            Command::Info { key, before, after } => {
+                pick(undo, before, after).restore(scene_data, *key);
+            }
+            Command::MaterialColour {
+                material,
+                before,
+                after,
+            } => material.borrow_mut().colour = *pick(undo, before, after),
+            Command::Mesh { key, before, after } => {
+                scene_data.edit_object(*key, |object| pick(undo, before, after).restore(object));
+            }
+            Command::Reorder { before, after } => {
+                let order = pick(undo, before, after).clone();
+                scene_data.reorder_objects(|current| *current = order);
+            }
+            Command::AddObject { .. } | Command::RemoveObject { .. } | Command::Group(_) => {
+                unreachable!("handled by redo and undo")
+            }
+        }
+    }
+
+    /// Point references to `old` at `new`
+    pub fn remap(&mut self, old: ObjectKey, new: ObjectKey) {
+        match self {
+            Command::AddObject { key, removed } | Command::RemoveObject { key, removed } => {
+                remap_key(key, old, new);
+                if let Some(removed) = removed {
+                    removed.remap(old, new);
+                }
+            }
+            Command::Transform { key, .. }
+            | Command::Info { key, .. }
+            | Command::Mesh { key, .. } => remap_key(key, old, new),
+            Command::Parent { key, before, after } => {
+                let parents = before.parent.iter_mut().chain(after.parent.iter_mut());
+                for key in std::iter::once(key).chain(parents) {
+                    remap_key(key, old, new);
+                }
+            }
+            Command::MaterialColour { .. } => {}
+            Command::Reorder { before, after } => {
+                for key in before.iter_mut().chain(after.iter_mut()) {
+                    remap_key(key, old, new);
+                }
+            }
+            Command::Group(commands) => {
+                for command in commands {
+                    command.remap(old, new);
+                }
+            }
+        }
+    }
+
+    /// Whether `next` continues the same edit, so the two can be stored as one step
+    pub fn can_merge(&self, next: &Command) -> bool {
+        match (self, next) {
+            (Command::Transform { key, .. }, Command::Transform { key: next, .. })
+            | (Command::Mesh { key, .. }, Command::Mesh { key: next, .. }) => key == next,
+            (
+                Command::MaterialColour { material, .. },
+                Command::MaterialColour { material: next, .. },
+            ) => Rc::ptr_eq(material, next),
+            (Command::Reorder { .. }, Command::Reorder { .. }) => true,
+            (Command::Group(commands), Command::Group(next)) => {
+                commands.len() == next.len()
+                    && commands
+                        .iter()
+                        .zip(next)
+                        .all(|(command, next)| command.can_merge(next))
+            }
+            _ => false,
+        }
+    }
+
+    /// Take the end state of `next`, which [`Command::can_merge`] must allow
+    pub fn merge(&mut self, next: Command) {
+        match (self, next) {
+            (Command::Transform { after, .. }, Command::Transform { after: next, .. }) => {
+                *after = next
+            }
+            (Command::Mesh { after, .. }, Command::Mesh { after: next, .. }) => *after = next,
+            (
+                Command::MaterialColour { after, .. },
+                Command::MaterialColour { after: next, .. },
+            ) => *after = next,
+            (Command::Reorder { after, .. }, Command::Reorder { after: next, .. }) => *after = next,
+            (Command::Group(commands), Command::Group(next)) => {
+                for (command, next) in commands.iter_mut().zip(next) {
+                    command.merge(next);
+                }
+            }
+            _ => debug_assert!(false, "commands can't be merged"),
+        }
+    }
+
+    /// Rough number of bytes the command keeps alive
+    ///
+    /// Meshes are counted in full even when the scene or other commands share them.
+    pub fn memory_size(&self) -> usize {
+        let contents = match self {
+            Command::AddObject { removed, .. } | Command::RemoveObject { removed, .. } => {
+                removed.as_ref().map_or(0, RemovedObject::memory_size)
+            }
+            Command::Mesh { before, after, .. } => before.memory_size() + after.memory_size(),
+            Command::Info { before, after, .. } => before.name.len() + after.name.len(),
+            Command::Reorder { before, after } => {
+                (before.len() + after.len()) * size_of::<ObjectKey>()
+            }
+            Command::Group(commands) => commands.iter().map(Command::memory_size).sum(),
+            Command::Transform { .. } | Command::Parent { .. } | Command::MaterialColour { .. } => {
+                0
+            }
+        };
+        size_of::<Command>() + contents
+    }
+}
+
+/// Take out the objects of commands that [`Command::removes`] them, in order, as one batch
+fn take_objects(scene_data: &mut SceneData, commands: &mut [&mut Command], undo: bool) {
+    let keys = commands
+        .iter()
+        .map(|command| match command {
+            Command::AddObject { key, .. } | Command::RemoveObject { key, .. } => *key,
+            _ => unreachable!("only commands that remove objects are taken"),
+        })
+        .collect::<Vec<_>>();
+    debug_assert!(commands.iter().all(|command| command.removes(undo)));
+
+    let taken = RemovedObject::take_all(scene_data, &keys);
+    for (command, taken) in commands.iter_mut().zip(taken) {
+        if let Command::AddObject { removed, .. } | Command::RemoveObject { removed, .. } = command
+        {
+            *removed = taken;
+        }
+    }
+}
+
+/// Put a removed object back, recording its new key
+fn restore(
+    scene_data: &mut SceneData,
+    key: &mut ObjectKey,
+    removed: &mut Option<RemovedObject>,
+) -> Vec<KeyRemap> {
+    let Some(object) = removed.take() else {
+        return vec![];
+    };
+    let old = *key;
+    *key = object.restore(scene_data);
+    vec![(old, *key)]
+}
+
+fn pick<'a, T>(undo: bool, before: &'a T, after: &'a T) -> &'a T {
+    if undo {
+        before
+    } else {
+        after
+    }
+}
+
+fn remap_all(commands: &mut [Command], remaps: &[KeyRemap]) {
+    for &(old, new) in remaps {
+        for command in commands.iter_mut() {
+            command.remap(old, new);
+        }
+    }
+}
+
+fn remap_key(key: &mut ObjectKey, old: ObjectKey, new: ObjectKey) {
+    if *key == old {
+        *key = new;
+    }
+}
+
+fn mesh_memory_size(mesh: &Rc<RefCell<Mesh>>) -> usize {
+    let mesh = mesh.borrow();
+    size_of::<Mesh>() + size_of_val(mesh.vertices()) + size_of_val(mesh.indices())
+}
@@ -0,0 +1,466 @@
+//! Undo and redo
+//!
+//! Every edit to the scene is recorded as a [`Command`] that knows how to revert and repeat it.
+//! Commands are kept in steps, and a step can be left open so that an edit made over many frames,
+//! like dragging a value, is undone in one go. Old steps are dropped once the history holds more
+//! than its memory limit.
+
+pub mod command;
+
+use std::collections::VecDeque;
+
+use crate::opengl::{
+    scene::{ObjectKey, SceneData},
+    structs::Object,
+};
+
+pub use command::{Command, KeyRemap, MeshState, ObjectInfo};
+
+/// Memory the history may use before old steps are dropped
+pub const DEFAULT_MEMORY_LIMIT: usize = 64 * 1024 * 1024;
+
+struct Step {
+    command: Command,
+    /// [`Command::memory_size`] when the step was last changed
+    size: usize,
+}
+
+impl Step {
+    fn new(command: Command) -> Self {
+        let size = command.memory_size();
+        Self { command, size }
+    }
+}
+
+pub struct History {
+    undo_steps: VecDeque<Step>,
+    redo_steps: Vec<Step>,
+    memory_limit: usize,
+    memory_used: usize,
+    /// Whether [`History::push_merged`] may still add to the last step
+    open: bool,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::with_memory_limit(DEFAULT_MEMORY_LIMIT)
+    }
+
+    /// The last step is always kept, even if it alone is over the limit
+    pub fn with_memory_limit(memory_limit: usize) -> Self {
+        Self {
+            undo_steps: VecDeque::new(),
+            redo_steps: Vec::new(),
+            memory_limit,
+            memory_used: 0,
+            open: false,
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_steps.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_steps.is_empty()
+    }
+
+    /// Rough number of bytes held by the undo and redo steps
+    pub fn memory_used(&self) -> usize {
+        self.memory_used
+    }
+
+    /// Make a change and record it as a new step
+    pub fn execute(&mut self, scene_data: &mut SceneData, mut command: Command) {
+        let remaps = command.redo(scene_data);
+        self.remap(&remaps);
+        self.push(command);
+    }
+
+    /// Add an object as a new step
+    pub fn add_object(&mut self, scene_data: &mut SceneData, object: Object) -> ObjectKey {
+        let mut command = Command::add_object(object);
+        command.redo(scene_data);
+        let Command::AddObject { key, .. } = command else {
+            unreachable!("add_object makes an AddObject command");
+        };
+        self.push(command);
+        key
+    }
+
+    /// Record a change that has already been made as a new step
+    pub fn push(&mut self, command: Command) {
+        self.push_step(command);
+        self.open = false;
+    }
+
+    /// Record a change that has already been made, adding it to the last step if that step is
+    /// still open and holds the same kind of edit to the same things
+    ///
+    /// The step stays open until [`History::seal`], so a drag records a single step.
+    pub fn push_merged(&mut self, command: Command) {
+        match self.undo_steps.back_mut() {
+            Some(step) if self.open && step.command.can_merge(&command) => {
+                step.command.merge(command);
+                let size = step.command.memory_size();
+                self.memory_used = self.memory_used - step.size + size;
+                step.size = size;
+                self.drop_redo_steps();
+                self.trim();
+            }
+            _ => self.push_step(command),
+        }
+        self.open = true;
+    }
+
+    /// Stop the last step from taking in merged edits
+    pub fn seal(&mut self) {
+        self.open = false;
+    }
+
+    /// Revert the last step
+    /// Returns false if there was nothing to undo
+    pub fn undo(&mut self, scene_data: &mut SceneData) -> bool {
+        let Some(mut step) = self.undo_steps.pop_back() else {
+            return false;
+        };
+        self.open = false;
+
+        let remaps = step.command.undo(scene_data);
+        self.redo_steps.push(step);
+        self.remap(&remaps);
+        true
+    }
+
+    /// Repeat the last undone step
+    /// Returns false if there was nothing to redo
+    pub fn redo(&mut self, scene_data: &mut SceneData) -> bool {
+        let Some(mut step) = self.redo_steps.pop() else {
+            return false;
+        };
+        self.open = false;
+
+        let remaps = step.command.redo(scene_data);
+        self.undo_steps.push_back(step);
+        self.remap(&remaps);
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.undo_steps.clear();
+        self.redo_steps.clear();
+        self.memory_used = 0;
+        self.open = false;
+    }
+
+    fn drop_redo_steps(&mut self) {
+        for step in self.redo_steps.drain(..) {
+            self.memory_used -= step.size;
+        }
+    }
+
+    fn push_step(&mut self, command: Command) {
+        self.drop_redo_steps();
+
+        let step = Step::new(command);
+        self.memory_used += step.size;
+        self.undo_steps.push_back(step);
+        self.trim();
+    }
+
+    /// Drop the oldest steps until the history fits its memory limit, always keeping the last
+    fn trim(&mut self) {
+        while self.memory_used > self.memory_limit && self.undo_steps.len() > 1 {
+            if let Some(step) = self.undo_steps.pop_front() {
+                self.memory_used -= step.size;
+            }
+        }
+    }
+
+    /// Point every step at the new keys of objects that came back
+    fn remap(&mut self, remaps: &[KeyRemap]) {
+        for &(old, new) in remaps {
+            for step in self.undo_steps.iter_mut().chain(self.redo_steps.iter_mut()) {
+                step.command.remap(old, new);
+            }
+        }
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use glam::{Mat4, Vec2};
+    use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+    use crate::{
+        data::modifiers::{ModifierKind, ModifierStack, Offset},
+        opengl::{
+            matrices::Transform2D,
+            structs::{Colour, Material, Mesh},
+        },
+        shapes::square::create_square,
+    };
+
+    use super::{command::ParentState, *};
+
+    /// Everything an edit can change about an object, with parents given by draw order position
+    #[derive(Debug, PartialEq)]
+    struct ObjectSnapshot {
+        transform: Transform2D,
+        model_matrix: Mat4,
+        parent: Option<usize>,
+        colour: Colour,
+        mesh: *const RefCell<Mesh>,
+        modifiers: ModifierStack,
+        selected: bool,
+        info: ObjectInfo,
+    }
+
+    fn snapshot(scene_data: &SceneData) -> Vec<ObjectSnapshot> {
+        let order = scene_data.object_order();
+        order
+            .iter()
+            .map(|&key| {
+                let object = &scene_data.objects()[key];
+                ObjectSnapshot {
+                    transform: object.transform(),
+                    model_matrix: object.get_model_matrix(),
+                    parent: object
+                        .parent()
+                        .map(|parent| order.iter().position(|&k| k == parent).unwrap()),
+                    colour: object.borrow_material().colour,
+                    mesh: Rc::as_ptr(object.mesh()),
+                    modifiers: object.modifiers().clone(),
+                    selected: object.selected,
+                    info: ObjectInfo::of(object),
+                }
+            })
+            .collect()
+    }
+
+    fn square_object(rng: &mut StdRng) -> Object {
+        let (square, _, _) = Mesh::new(create_square(), 0);
+        let material = Rc::new(RefCell::new(Material {
+            colour: Colour::new(rng.random(), rng.random(), rng.random(), 1.0),
+        }));
+        Object::new(
+            square,
+            Vec2::new(rng.random_range(-50.0..50.0), rng.random_range(-50.0..50.0)),
+            rng.random_range(-3.0..3.0),
+            Vec2::splat(rng.random_range(0.5..2.0)),
+            material,
+        )
+    }
+
+    /// Make a random edit through the history
+    fn random_edit(rng: &mut StdRng, scene_data: &mut SceneData, history: &mut History) {
+        let keys = scene_data.object_order().to_vec();
+        let key = keys[rng.random_range(0..keys.len())];
+
+        match rng.random_range(0..9) {
+            0 => {
+                let object = square_object(rng);
+                history.add_object(scene_data, object);
+            }
+            1 if keys.len() > 2 => {
+                // Sometimes delete an object along with one of its children
+                let mut commands = vec![Command::remove_object(key)];
+                if let Some(&child) = scene_data.children(key).first() {
+                    commands.push(Command::remove_object(child));
+                    commands.swap(0, rng.random_range(0..2));
+                }
+                history.execute(scene_data, Command::Group(commands));
+            }
+            2 => {
+                let before = scene_data.objects()[key].transform();
+                let mut after = before;
+                after.translation += Vec2::new(rng.random(), rng.random());
+                after.rotation += rng.random::<f32>();
+                history.execute(scene_data, Command::Transform { key, before, after });
+            }
+            3 => {
+                let parent = keys[rng.random_range(0..keys.len())];
+                let parent = (!scene_data.is_ancestor(key, parent)).then_some(parent);
+                let before = ParentState::of(scene_data, key).unwrap();
+                scene_data.set_parent(key, parent, true).unwrap();
+                let after = ParentState::of(scene_data, key).unwrap();
+                history.push(Command::Parent { key, before, after });
+            }
+            4 => {
+                let material = scene_data.objects()[key].material().clone();
+                let before = material.borrow().colour;
+                history.execute(
+                    scene_data,
+                    Command::MaterialColour {
+                        material,
+                        before,
+                        after: Colour::new(rng.random(), 0.5, 0.5, 1.0),
+                    },
+                );
+            }
+            5 => {
+                let object = &mut scene_data.objects_mut()[key];
+                let before = MeshState::of(object);
+                object.add_modifier(ModifierKind::Offset(Offset {
+                    distance: rng.random(),
+                }));
+                history.push(Command::Mesh {
+                    key,
+                    before,
+                    after: MeshState::of(object),
+                });
+            }
+            6 if !scene_data.objects()[key].modifiers().is_empty() => {
+                let object = &mut scene_data.objects_mut()[key];
+                let before = MeshState::of(object);
+                object.apply_modifier(0);
+                history.push(Command::Mesh {
+                    key,
+                    before,
+                    after: MeshState::of(object),
+                });
+            }
+            7 => {
+                let before = ObjectInfo::of(&scene_data.objects()[key]);
+                let after = ObjectInfo {
+                    name: format!("Object {}", rng.random_range(0..100)),
+                    hidden: rng.random(),
+                    locked: rng.random(),
+                };
+                history.execute(scene_data, Command::Info { key, before, after });
+            }
+            _ => {
+                let before = keys.clone();
+                let mut after = keys;
+                after.shuffle(rng);
+                history.execute(scene_data, Command::Reorder { before, after });
+            }
+        }
+    }
+
+    #[test]
+    fn undo_and_redo_restore_equal_scenes() {
+        for seed in 0..20 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let objects = (0..6).map(|_| square_object(&mut rng)).collect();
+            let mut scene_data = SceneData::new(objects);
+            let mut history = History::new();
+
+            let mut snapshots = vec![snapshot(&scene_data)];
+            for _ in 0..40 {
+                random_edit(&mut rng, &mut scene_data, &mut history);
+                snapshots.push(snapshot(&scene_data));
+            }
+
+            for expected in snapshots.iter().rev().skip(1) {
+                assert!(history.undo(&mut scene_data));
+                assert_eq!(&snapshot(&scene_data), expected, "seed {seed}");
+            }
+            assert!(!history.undo(&mut scene_data));
+
+            for expected in snapshots.iter().skip(1) {
+                assert!(history.redo(&mut scene_data));
+                assert_eq!(&snapshot(&scene_data), expected, "seed {seed}");
+            }
+            assert!(!history.redo(&mut scene_data));
+
+            // Undoing part way and making a new edit drops the steps that were undone
+            for _ in 0..10 {
+                history.undo(&mut scene_data);
+            }
+            random_edit(&mut rng, &mut scene_data, &mut history);
+            assert!(!history.can_redo());
+            assert!(history.undo(&mut scene_data));
+            assert_eq!(snapshot(&scene_data), snapshots[snapshots.len() - 11]);
+        }
+    }
+
+    #[test]
+    fn drags_merge_into_one_step_until_sealed() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut scene_data = SceneData::new(vec![square_object(&mut rng)]);
+        let key = scene_data.object_order()[0];
+        let start = scene_data.objects()[key].transform();
+        let mut history = History::new();
+
+        let drag_to = |scene_data: &mut SceneData, history: &mut History, x: f32| {
+            let before = scene_data.objects()[key].transform();
+            scene_data.edit_object(key, |object| object.set_translation(Vec2::new(x, 0.0)));
+            let after = scene_data.objects()[key].transform();
+            history.push_merged(Command::Transform { key, before, after });
+        };
+
+        for x in 1..10 {
+            drag_to(&mut scene_data, &mut history, x as f32);
+        }
+        history.seal();
+        let after_first_drag = scene_data.objects()[key].transform();
+        drag_to(&mut scene_data, &mut history, 20.0);
+        drag_to(&mut scene_data, &mut history, 30.0);
+
+        history.undo(&mut scene_data);
+        assert_eq!(scene_data.objects()[key].transform(), after_first_drag);
+        history.undo(&mut scene_data);
+        assert_eq!(scene_data.objects()[key].transform(), start);
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn old_steps_are_dropped_over_the_memory_limit() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut scene_data = SceneData::new(vec![]);
+        let mut history = History::with_memory_limit(4096);
+
+        for _ in 0..100 {
+            let object = square_object(&mut rng);
+            history.add_object(&mut scene_data, object);
+            assert!(history.memory_used() <= 4096 || history.undo_steps.len() == 1);
+        }
+
+        let mut undone = 0;
+        while history.undo(&mut scene_data) {
+            undone += 1;
+        }
+        assert!(undone > 0 && undone < 100);
+        assert_eq!(scene_data.objects().len(), 100 - undone);
+
+        // Undone steps still count until they are dropped by a new edit
+        let used = history.memory_used();
+        history.add_object(&mut scene_data, square_object(&mut rng));
+        assert!(history.memory_used() < used);
+    }
+
+    #[test]
+    fn merged_steps_are_held_to_the_memory_limit() {
+        let mut history = History::with_memory_limit(4096);
+        for _ in 0..4 {
+            let transform = Transform2D::new(Vec2::ZERO, 0.0, Vec2::ONE);
+            history.push(Command::Transform {
+                key: ObjectKey::default(),
+                before: transform,
+                after: transform,
+            });
+        }
+
+        // A drag that keeps growing its step pushes the older steps out
+        for len in [1, 10, 1000] {
+            history.push_merged(Command::Reorder {
+                before: vec![],
+                after: vec![ObjectKey::default(); len],
+            });
+        }
+        assert_eq!(history.undo_steps.len(), 1);
+        assert_eq!(
+            history.memory_used(),
+            history.undo_steps[0].command.memory_size()
+        );
+    }
+}
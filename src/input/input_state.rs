@@ -0,0 +1,200 @@
+use std::collections::{HashMap, HashSet};
+
+use glam::Vec2;
+use miniquad::{KeyCode, KeyMods, MouseButton};
+
+/// Keys, modifiers and mouse as of the last event
+///
+/// The event handlers feed every event in, and tools read from it rather than keeping their own
+/// copies. Modifiers are taken from the `KeyMods` miniquad passes with key events and corrected by
+/// the modifier keys themselves, since platforms disagree on whether a modifier's own press or
+/// release is included.
+#[derive(Debug, Default)]
+pub struct InputState {
+    keys_down: HashSet<KeyCode>,
+    mods: KeyMods,
+    /// Buttons held down, with the screen position each was pressed at
+    buttons_down: HashMap<MouseButton, Vec2>,
+    mouse_position: Vec2,
+    mouse_world: Vec2,
+    /// How far the mouse moved in the last motion event, in screen pixels
+    mouse_delta: Vec2,
+}
+
+impl InputState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn key_down(&mut self, keycode: KeyCode, mods: KeyMods) {
+        self.keys_down.insert(keycode);
+        self.set_mods(keycode, mods);
+    }
+
+    pub fn key_up(&mut self, keycode: KeyCode, mods: KeyMods) {
+        self.keys_down.remove(&keycode);
+        self.set_mods(keycode, mods);
+    }
+
+    /// Take the reported modifiers, except the one `keycode` belongs to, which is held while
+    /// either its left or right key is
+    fn set_mods(&mut self, keycode: KeyCode, mods: KeyMods) {
+        self.mods = mods;
+        let (modifier, [left, right]) = match keycode {
+            KeyCode::LeftShift | KeyCode::RightShift => (
+                &mut self.mods.shift,
+                [KeyCode::LeftShift, KeyCode::RightShift],
+            ),
+            KeyCode::LeftControl | KeyCode::RightControl => (
+                &mut self.mods.ctrl,
+                [KeyCode::LeftControl, KeyCode::RightControl],
+            ),
+            KeyCode::LeftAlt | KeyCode::RightAlt => {
+                (&mut self.mods.alt, [KeyCode::LeftAlt, KeyCode::RightAlt])
+            }
+            KeyCode::LeftSuper | KeyCode::RightSuper => (
+                &mut self.mods.logo,
+                [KeyCode::LeftSuper, KeyCode::RightSuper],
+            ),
+            _ => return,
+        };
+        *modifier = self.keys_down.contains(&left) || self.keys_down.contains(&right);
+    }
+
+    pub fn mouse_button_down(&mut self, button: MouseButton, position: Vec2) {
+        self.buttons_down.insert(button, position);
+        self.mouse_position = position;
+    }
+
+    pub fn mouse_button_up(&mut self, button: MouseButton, position: Vec2) {
+        self.buttons_down.remove(&button);
+        self.mouse_position = position;
+    }
+
+    /// Move the mouse to `position` on screen, which is `world` in world space
+    pub fn mouse_motion(&mut self, position: Vec2, world: Vec2) {
+        self.mouse_delta = position - self.mouse_position;
+        self.mouse_position = position;
+        self.mouse_world = world;
+    }
+
+    /// Update the world position under the mouse after the view changed
+    pub fn set_mouse_world(&mut self, world: Vec2) {
+        self.mouse_world = world;
+    }
+
+    pub fn is_key_down(&self, keycode: KeyCode) -> bool {
+        self.keys_down.contains(&keycode)
+    }
+
+    pub fn mods(&self) -> KeyMods {
+        self.mods
+    }
+
+    pub fn shift(&self) -> bool {
+        self.mods.shift
+    }
+
+    pub fn ctrl(&self) -> bool {
+        self.mods.ctrl
+    }
+
+    pub fn alt(&self) -> bool {
+        self.mods.alt
+    }
+
+    pub fn is_button_down(&self, button: MouseButton) -> bool {
+        self.buttons_down.contains_key(&button)
+    }
+
+    /// Mouse position in screen pixels, from the top left
+    pub fn mouse_position(&self) -> Vec2 {
+        self.mouse_position
+    }
+
+    /// World position under the mouse
+    pub fn mouse_world(&self) -> Vec2 {
+        self.mouse_world
+    }
+
+    /// How far the mouse moved in the last motion event, in screen pixels
+    pub fn mouse_delta(&self) -> Vec2 {
+        self.mouse_delta
+    }
+
+    /// How far the mouse has moved since `button` was pressed, if it is still held
+    pub fn drag_delta(&self, button: MouseButton) -> Option<Vec2> {
+        self.buttons_down
+            .get(&button)
+            .map(|start| self.mouse_position - *start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modifier_keys_override_reported_mods() {
+        let mut input = InputState::new();
+
+        // Some platforms leave a modifier out of the mods sent with its own press
+        input.key_down(KeyCode::LeftShift, KeyMods::default());
+        assert!(input.shift());
+        input.key_down(
+            KeyCode::LeftControl,
+            KeyMods {
+                shift: true,
+                ..Default::default()
+            },
+        );
+        assert!(input.shift() && input.ctrl());
+        assert!(input.is_key_down(KeyCode::LeftShift));
+
+        // ... and others still include it with its release
+        input.key_up(
+            KeyCode::LeftShift,
+            KeyMods {
+                shift: true,
+                ctrl: true,
+                ..Default::default()
+            },
+        );
+        assert!(!input.shift() && input.ctrl());
+        assert!(!input.is_key_down(KeyCode::LeftShift));
+
+        // Releasing one side of a modifier leaves it held while the other side is down
+        let shift = KeyMods {
+            shift: true,
+            ..Default::default()
+        };
+        input.key_down(KeyCode::LeftShift, shift);
+        input.key_down(KeyCode::RightShift, shift);
+        input.key_up(KeyCode::LeftShift, KeyMods::default());
+        assert!(input.shift());
+        input.key_up(KeyCode::RightShift, shift);
+        assert!(!input.shift());
+    }
+
+    #[test]
+    fn tracks_drags_per_button() {
+        let mut input = InputState::new();
+        input.mouse_motion(Vec2::new(10.0, 10.0), Vec2::ZERO);
+        input.mouse_button_down(MouseButton::Left, Vec2::new(10.0, 10.0));
+        input.mouse_motion(Vec2::new(15.0, 8.0), Vec2::new(1.0, 2.0));
+        input.mouse_motion(Vec2::new(20.0, 4.0), Vec2::new(3.0, 4.0));
+
+        assert!(input.is_button_down(MouseButton::Left));
+        assert_eq!(input.mouse_delta(), Vec2::new(5.0, -4.0));
+        assert_eq!(
+            input.drag_delta(MouseButton::Left),
+            Some(Vec2::new(10.0, -6.0))
+        );
+        assert_eq!(input.drag_delta(MouseButton::Middle), None);
+        assert_eq!(input.mouse_world(), Vec2::new(3.0, 4.0));
+
+        input.mouse_button_up(MouseButton::Left, Vec2::new(20.0, 4.0));
+        assert!(!input.is_button_down(MouseButton::Left));
+        assert_eq!(input.drag_delta(MouseButton::Left), None);
+    }
+}
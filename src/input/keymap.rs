@@ -0,0 +1,720 @@
+//! Which keys and mouse buttons trigger which actions
+//!
+//! Every viewport interaction is an [`Action`], and the [`Keymap`] maps each action to the chords
+//! that trigger it. A chord is an input with the modifiers that must be held, written like
+//! `ctrl+shift+z`, `middle_mouse` or `alt+wheel`. Starting a chord with `any+` makes it match
+//! whatever modifiers are held, which drags like box select need since the modifiers change how
+//! they select.
+//!
+//! The defaults can be overridden by a JSON file that maps action names to lists of chords:
+//!
+//! ```json
+//! { "zoom": ["ctrl+wheel"], "scroll_pan": ["wheel"], "undo": ["ctrl+z", "logo+z"] }
+//! ```
+//!
+//! Actions left out of the file keep their default chords, and an empty list unbinds an action.
+//! Two actions that can run at the same time may not share a chord. Edit mode actions are looked
+//! up before viewport ones while editing a mesh, so they may take over viewport chords. Pen
+//! actions take over in the same way while drawing, with only the view actions left to fall
+//! back to.
+
+use std::{collections::HashMap, fmt, path::PathBuf, str::FromStr};
+
+use miniquad::{KeyCode, KeyMods, MouseButton};
+use serde::Deserialize;
+
+/// File the user's keymap is read from, under the config directory
+const USER_KEYMAP_PATH: &str = "flat-blend/keymap.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    /// Drag to move the view
+    Pan,
+    /// Drag around the middle of the view to turn it
+    RotateView,
+    /// Fit the view to the selected objects
+    FrameSelected,
+    /// Fit the view to every object
+    FrameAll,
+    /// Remember the view under the number of the key pressed
+    StoreBookmark,
+    /// Go back to the view remembered under the number of the key pressed
+    RecallBookmark,
+    /// Scroll to zoom the view
+    Zoom,
+    /// Scroll to move the view, for trackpads
+    ScrollPan,
+    /// Drag a box to select, or click to select an object
+    BoxSelect,
+    /// Drag a lasso to select, or click to select an object
+    LassoSelect,
+    Grab,
+    Rotate,
+    Scale,
+    Delete,
+    Undo,
+    Redo,
+    Save,
+    RenderImage,
+    /// Finish a running transform
+    Confirm,
+    /// Put back the objects a running transform moved
+    Cancel,
+    ConstrainX,
+    ConstrainY,
+    /// Switch between editing the active object's mesh and working with objects
+    ToggleEditMode,
+    /// Pick vertices in edit mode
+    VertexSelectMode,
+    /// Pick edges in edit mode
+    EdgeSelectMode,
+    /// Pick faces in edit mode
+    FaceSelectMode,
+    /// Start drawing a new polygon object
+    Pen,
+    /// Click to place a point, or drag to pull out its handles
+    AddPoint,
+    /// Make an object from the points placed so far
+    ClosePath,
+    /// Take back the last point placed
+    RemovePoint,
+    /// Stop drawing without making an object
+    CancelPath,
+}
+
+/// When an action can run, which decides the chords it may not share
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ActionContext {
+    Viewport,
+    /// While a grab, rotate or scale is running
+    Transform,
+    /// While editing a mesh, before falling back to the viewport actions
+    Edit,
+    /// While drawing with the pen, before falling back to the view actions
+    Pen,
+}
+
+impl Action {
+    pub const ALL: [Action; 31] = [
+        Action::Pan,
+        Action::RotateView,
+        Action::FrameSelected,
+        Action::FrameAll,
+        Action::StoreBookmark,
+        Action::RecallBookmark,
+        Action::Zoom,
+        Action::ScrollPan,
+        Action::BoxSelect,
+        Action::LassoSelect,
+        Action::Grab,
+        Action::Rotate,
+        Action::Scale,
+        Action::Delete,
+        Action::Undo,
+        Action::Redo,
+        Action::Save,
+        Action::RenderImage,
+        Action::Confirm,
+        Action::Cancel,
+        Action::ConstrainX,
+        Action::ConstrainY,
+        Action::ToggleEditMode,
+        Action::VertexSelectMode,
+        Action::EdgeSelectMode,
+        Action::FaceSelectMode,
+        Action::Pen,
+        Action::AddPoint,
+        Action::ClosePath,
+        Action::RemovePoint,
+        Action::CancelPath,
+    ];
+
+    pub fn context(self) -> ActionContext {
+        match self {
+            Action::Confirm | Action::Cancel | Action::ConstrainX | Action::ConstrainY => {
+                ActionContext::Transform
+            }
+            Action::VertexSelectMode | Action::EdgeSelectMode | Action::FaceSelectMode => {
+                ActionContext::Edit
+            }
+            Action::AddPoint | Action::ClosePath | Action::RemovePoint | Action::CancelPath => {
+                ActionContext::Pen
+            }
+            _ => ActionContext::Viewport,
+        }
+    }
+
+    /// Whether the action only moves the view, so it can run while a tool is in use
+    pub fn moves_view(self) -> bool {
+        matches!(
+            self,
+            Action::Pan
+                | Action::RotateView
+                | Action::FrameSelected
+                | Action::FrameAll
+                | Action::RecallBookmark
+                | Action::Zoom
+                | Action::ScrollPan
+        )
+    }
+
+    fn default_chords(self) -> &'static [&'static str] {
+        match self {
+            Action::Pan => &["middle_mouse"],
+            Action::RotateView => &["ctrl+middle_mouse"],
+            Action::FrameSelected => &["f", "kp_decimal"],
+            Action::FrameAll => &["home"],
+            Action::StoreBookmark => &[
+                "ctrl+1", "ctrl+2", "ctrl+3", "ctrl+4", "ctrl+5", "ctrl+6", "ctrl+7", "ctrl+8",
+                "ctrl+9",
+            ],
+            Action::RecallBookmark => &["1", "2", "3", "4", "5", "6", "7", "8", "9"],
+            Action::Zoom => &["wheel"],
+            Action::ScrollPan => &[],
+            Action::BoxSelect => &["any+left_mouse"],
+            Action::LassoSelect => &["any+right_mouse"],
+            Action::Grab => &["g"],
+            Action::Rotate => &["r"],
+            Action::Scale => &["s"],
+            Action::Delete => &["delete"],
+            Action::Undo => &["ctrl+z"],
+            Action::Redo => &["ctrl+shift+z"],
+            Action::Save => &["ctrl+s"],
+            Action::RenderImage => &["f12"],
+            Action::Confirm => &["any+enter", "any+kp_enter", "any+left_mouse"],
+            Action::Cancel => &["any+escape", "any+right_mouse"],
+            Action::ConstrainX => &["any+x"],
+            Action::ConstrainY => &["any+y"],
+            Action::ToggleEditMode => &["tab"],
+            Action::VertexSelectMode => &["1"],
+            Action::EdgeSelectMode => &["2"],
+            Action::FaceSelectMode => &["3"],
+            Action::Pen => &["p"],
+            Action::AddPoint => &["any+left_mouse"],
+            Action::ClosePath => &["enter", "kp_enter"],
+            Action::RemovePoint => &["backspace", "ctrl+z"],
+            Action::CancelPath => &["escape", "right_mouse"],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Input {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    Wheel,
+}
+
+/// Modifier keys, comparable unlike miniquad's `KeyMods`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub logo: bool,
+}
+
+impl From<KeyMods> for Modifiers {
+    fn from(mods: KeyMods) -> Self {
+        Self {
+            shift: mods.shift,
+            ctrl: mods.ctrl,
+            alt: mods.alt,
+            logo: mods.logo,
+        }
+    }
+}
+
+/// An input and the modifiers held with it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chord {
+    pub input: Input,
+    /// `None` matches any modifiers
+    pub mods: Option<Modifiers>,
+}
+
+impl Chord {
+    pub fn matches(&self, input: Input, mods: Modifiers) -> bool {
+        self.input == input && self.mods.is_none_or(|own| own == mods)
+    }
+
+    /// Whether some input and modifiers would trigger both chords
+    pub fn overlaps(&self, other: &Chord) -> bool {
+        self.input == other.input
+            && match (self.mods, other.mods) {
+                (Some(mods), Some(other)) => mods == other,
+                _ => true,
+            }
+    }
+}
+
+impl FromStr for Chord {
+    type Err = KeymapError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || KeymapError::InvalidChord(text.to_string());
+
+        let mut parts = text.split('+').map(str::trim).collect::<Vec<_>>();
+        let input = parts.pop().and_then(input_from_name).ok_or_else(invalid)?;
+
+        let mut mods = Some(Modifiers::default());
+        for part in parts {
+            let held = mods.as_mut();
+            match (part, held) {
+                ("any", _) => mods = None,
+                ("shift", Some(held)) => held.shift = true,
+                ("ctrl", Some(held)) => held.ctrl = true,
+                ("alt", Some(held)) => held.alt = true,
+                ("logo" | "super", Some(held)) => held.logo = true,
+                _ => return Err(invalid()),
+            }
+        }
+
+        Ok(Self { input, mods })
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.mods {
+            None => write!(f, "any+")?,
+            Some(mods) => {
+                for (held, name) in [
+                    (mods.ctrl, "ctrl"),
+                    (mods.shift, "shift"),
+                    (mods.alt, "alt"),
+                    (mods.logo, "logo"),
+                ] {
+                    if held {
+                        write!(f, "{name}+")?;
+                    }
+                }
+            }
+        }
+        write!(f, "{}", input_name(self.input))
+    }
+}
+
+#[derive(Debug)]
+pub enum KeymapError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    /// A chord names a key, button or modifier that doesn't exist
+    InvalidChord(String),
+    /// Two actions that can run at the same time share a chord
+    Conflict(Action, Action, Chord),
+}
+
+impl fmt::Display for KeymapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeymapError::Io(err) => write!(f, "could not read keymap: {err}"),
+            KeymapError::Json(err) => write!(f, "malformed keymap: {err}"),
+            KeymapError::InvalidChord(chord) => write!(f, "unknown key chord \"{chord}\""),
+            KeymapError::Conflict(first, second, chord) => {
+                write!(f, "{first:?} and {second:?} are both bound to {chord}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for KeymapError {}
+
+impl From<std::io::Error> for KeymapError {
+    fn from(err: std::io::Error) -> Self {
+        KeymapError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for KeymapError {
+    fn from(err: serde_json::Error) -> Self {
+        KeymapError::Json(err)
+    }
+}
+
+pub struct Keymap {
+    bindings: HashMap<Action, Vec<Chord>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = Action::ALL
+            .into_iter()
+            .map(|action| {
+                let chords = action
+                    .default_chords()
+                    .iter()
+                    .map(|chord| chord.parse().expect("default chords are valid"))
+                    .collect();
+                (action, chords)
+            })
+            .collect();
+        Self { bindings }
+    }
+}
+
+impl Keymap {
+    /// Read the user's keymap, falling back to the defaults if there is none or it is broken
+    pub fn load_user() -> Self {
+        let Some(path) = user_keymap_path().filter(|path| path.exists()) else {
+            return Self::default();
+        };
+
+        std::fs::read_to_string(&path)
+            .map_err(KeymapError::from)
+            .and_then(|text| Self::from_json(&text))
+            .unwrap_or_else(|err| {
+                eprintln!("ignoring keymap {}: {}", path.display(), err);
+                Self::default()
+            })
+    }
+
+    /// The defaults with the actions in `text` rebound
+    pub fn from_json(text: &str) -> Result<Self, KeymapError> {
+        let overrides: HashMap<Action, Vec<String>> = serde_json::from_str(text)?;
+
+        let mut keymap = Self::default();
+        for (action, chords) in overrides {
+            let chords = chords
+                .iter()
+                .map(|chord| chord.parse())
+                .collect::<Result<Vec<_>, _>>()?;
+            keymap.bindings.insert(action, chords);
+        }
+
+        match keymap.conflicts().into_iter().next() {
+            Some((first, second, chord)) => Err(KeymapError::Conflict(first, second, chord)),
+            None => Ok(keymap),
+        }
+    }
+
+    pub fn chords(&self, action: Action) -> &[Chord] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// The action in `context` that `input` triggers with `mods` held
+    pub fn action(
+        &self,
+        context: ActionContext,
+        input: Input,
+        mods: impl Into<Modifiers>,
+    ) -> Option<Action> {
+        let mods = mods.into();
+        Action::ALL.into_iter().find(|&action| {
+            action.context() == context
+                && self
+                    .chords(action)
+                    .iter()
+                    .any(|chord| chord.matches(input, mods))
+        })
+    }
+
+    /// Pairs of actions in the same context with overlapping chords
+    pub fn conflicts(&self) -> Vec<(Action, Action, Chord)> {
+        let mut conflicts = vec![];
+        for (i, &first) in Action::ALL.iter().enumerate() {
+            for &second in &Action::ALL[i + 1..] {
+                if first.context() != second.context() {
+                    continue;
+                }
+                for chord in self.chords(first) {
+                    if self
+                        .chords(second)
+                        .iter()
+                        .any(|other| chord.overlaps(other))
+                    {
+                        conflicts.push((first, second, *chord));
+                    }
+                }
+            }
+        }
+        conflicts
+    }
+}
+
+/// `$XDG_CONFIG_HOME/flat-blend/keymap.json`, or under `~/.config` if that isn't set
+pub fn user_keymap_path() -> Option<PathBuf> {
+    let config = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config.join(USER_KEYMAP_PATH))
+}
+
+fn input_from_name(name: &str) -> Option<Input> {
+    match name {
+        "left_mouse" => Some(Input::Mouse(MouseButton::Left)),
+        "right_mouse" => Some(Input::Mouse(MouseButton::Right)),
+        "middle_mouse" => Some(Input::Mouse(MouseButton::Middle)),
+        "wheel" => Some(Input::Wheel),
+        _ => KEY_NAMES
+            .iter()
+            .find(|(key_name, _)| *key_name == name)
+            .map(|&(_, keycode)| Input::Key(keycode)),
+    }
+}
+
+fn input_name(input: Input) -> &'static str {
+    match input {
+        Input::Mouse(MouseButton::Left) => "left_mouse",
+        Input::Mouse(MouseButton::Right) => "right_mouse",
+        Input::Mouse(MouseButton::Middle) => "middle_mouse",
+        Input::Mouse(MouseButton::Unknown) => "unknown_mouse",
+        Input::Wheel => "wheel",
+        Input::Key(keycode) => KEY_NAMES
+            .iter()
+            .find(|&&(_, key)| key == keycode)
+            .map_or("unknown", |&(name, _)| name),
+    }
+}
+
+const KEY_NAMES: &[(&str, KeyCode)] = &[
+    ("space", KeyCode::Space),
+    ("apostrophe", KeyCode::Apostrophe),
+    ("comma", KeyCode::Comma),
+    ("minus", KeyCode::Minus),
+    ("period", KeyCode::Period),
+    ("slash", KeyCode::Slash),
+    ("0", KeyCode::Key0),
+    ("1", KeyCode::Key1),
+    ("2", KeyCode::Key2),
+    ("3", KeyCode::Key3),
+    ("4", KeyCode::Key4),
+    ("5", KeyCode::Key5),
+    ("6", KeyCode::Key6),
+    ("7", KeyCode::Key7),
+    ("8", KeyCode::Key8),
+    ("9", KeyCode::Key9),
+    ("semicolon", KeyCode::Semicolon),
+    ("equal", KeyCode::Equal),
+    ("a", KeyCode::A),
+    ("b", KeyCode::B),
+    ("c", KeyCode::C),
+    ("d", KeyCode::D),
+    ("e", KeyCode::E),
+    ("f", KeyCode::F),
+    ("g", KeyCode::G),
+    ("h", KeyCode::H),
+    ("i", KeyCode::I),
+    ("j", KeyCode::J),
+    ("k", KeyCode::K),
+    ("l", KeyCode::L),
+    ("m", KeyCode::M),
+    ("n", KeyCode::N),
+    ("o", KeyCode::O),
+    ("p", KeyCode::P),
+    ("q", KeyCode::Q),
+    ("r", KeyCode::R),
+    ("s", KeyCode::S),
+    ("t", KeyCode::T),
+    ("u", KeyCode::U),
+    ("v", KeyCode::V),
+    ("w", KeyCode::W),
+    ("x", KeyCode::X),
+    ("y", KeyCode::Y),
+    ("z", KeyCode::Z),
+    ("left_bracket", KeyCode::LeftBracket),
+    ("backslash", KeyCode::Backslash),
+    ("right_bracket", KeyCode::RightBracket),
+    ("grave_accent", KeyCode::GraveAccent),
+    ("world_1", KeyCode::World1),
+    ("world_2", KeyCode::World2),
+    ("escape", KeyCode::Escape),
+    ("enter", KeyCode::Enter),
+    ("tab", KeyCode::Tab),
+    ("backspace", KeyCode::Backspace),
+    ("insert", KeyCode::Insert),
+    ("delete", KeyCode::Delete),
+    ("right", KeyCode::Right),
+    ("left", KeyCode::Left),
+    ("down", KeyCode::Down),
+    ("up", KeyCode::Up),
+    ("page_up", KeyCode::PageUp),
+    ("page_down", KeyCode::PageDown),
+    ("home", KeyCode::Home),
+    ("end", KeyCode::End),
+    ("caps_lock", KeyCode::CapsLock),
+    ("scroll_lock", KeyCode::ScrollLock),
+    ("num_lock", KeyCode::NumLock),
+    ("print_screen", KeyCode::PrintScreen),
+    ("pause", KeyCode::Pause),
+    ("f1", KeyCode::F1),
+    ("f2", KeyCode::F2),
+    ("f3", KeyCode::F3),
+    ("f4", KeyCode::F4),
+    ("f5", KeyCode::F5),
+    ("f6", KeyCode::F6),
+    ("f7", KeyCode::F7),
+    ("f8", KeyCode::F8),
+    ("f9", KeyCode::F9),
+    ("f10", KeyCode::F10),
+    ("f11", KeyCode::F11),
+    ("f12", KeyCode::F12),
+    ("f13", KeyCode::F13),
+    ("f14", KeyCode::F14),
+    ("f15", KeyCode::F15),
+    ("f16", KeyCode::F16),
+    ("f17", KeyCode::F17),
+    ("f18", KeyCode::F18),
+    ("f19", KeyCode::F19),
+    ("f20", KeyCode::F20),
+    ("f21", KeyCode::F21),
+    ("f22", KeyCode::F22),
+    ("f23", KeyCode::F23),
+    ("f24", KeyCode::F24),
+    ("f25", KeyCode::F25),
+    ("kp_0", KeyCode::Kp0),
+    ("kp_1", KeyCode::Kp1),
+    ("kp_2", KeyCode::Kp2),
+    ("kp_3", KeyCode::Kp3),
+    ("kp_4", KeyCode::Kp4),
+    ("kp_5", KeyCode::Kp5),
+    ("kp_6", KeyCode::Kp6),
+    ("kp_7", KeyCode::Kp7),
+    ("kp_8", KeyCode::Kp8),
+    ("kp_9", KeyCode::Kp9),
+    ("kp_decimal", KeyCode::KpDecimal),
+    ("kp_divide", KeyCode::KpDivide),
+    ("kp_multiply", KeyCode::KpMultiply),
+    ("kp_subtract", KeyCode::KpSubtract),
+    ("kp_add", KeyCode::KpAdd),
+    ("kp_enter", KeyCode::KpEnter),
+    ("kp_equal", KeyCode::KpEqual),
+    ("left_shift", KeyCode::LeftShift),
+    ("left_control", KeyCode::LeftControl),
+    ("left_alt", KeyCode::LeftAlt),
+    ("left_super", KeyCode::LeftSuper),
+    ("right_shift", KeyCode::RightShift),
+    ("right_control", KeyCode::RightControl),
+    ("right_alt", KeyCode::RightAlt),
+    ("right_super", KeyCode::RightSuper),
+    ("menu", KeyCode::Menu),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mods(shift: bool, ctrl: bool) -> Modifiers {
+        Modifiers {
+            shift,
+            ctrl,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn defaults_have_no_conflicts() {
+        let keymap = Keymap::default();
+        assert!(keymap.conflicts().is_empty());
+
+        // Modifiers must match exactly unless the chord takes any
+        let z = Input::Key(KeyCode::Z);
+        let viewport = ActionContext::Viewport;
+        assert_eq!(
+            keymap.action(viewport, z, mods(false, true)),
+            Some(Action::Undo)
+        );
+        assert_eq!(
+            keymap.action(viewport, z, mods(true, true)),
+            Some(Action::Redo)
+        );
+        assert_eq!(keymap.action(viewport, z, mods(false, false)), None);
+
+        let left = Input::Mouse(MouseButton::Left);
+        assert_eq!(
+            keymap.action(viewport, left, mods(true, true)),
+            Some(Action::BoxSelect)
+        );
+        assert_eq!(
+            keymap.action(ActionContext::Transform, left, mods(false, true)),
+            Some(Action::Confirm)
+        );
+
+        // Edit mode takes over the bookmark keys
+        let two = Input::Key(KeyCode::Key2);
+        assert_eq!(
+            keymap.action(ActionContext::Edit, two, mods(false, false)),
+            Some(Action::EdgeSelectMode)
+        );
+        assert_eq!(
+            keymap.action(viewport, two, mods(false, false)),
+            Some(Action::RecallBookmark)
+        );
+    }
+
+    #[test]
+    fn chords_round_trip_through_text() {
+        for text in [
+            "ctrl+shift+z",
+            "any+left_mouse",
+            "alt+wheel",
+            "kp_enter",
+            "page_up",
+            "7",
+        ] {
+            let chord: Chord = text.parse().unwrap();
+            assert_eq!(chord.to_string(), text);
+        }
+        assert_eq!(
+            "shift+ctrl+s".parse::<Chord>().unwrap().to_string(),
+            "ctrl+shift+s"
+        );
+        assert!("ctrl+nothing".parse::<Chord>().is_err());
+        assert!("hyper+a".parse::<Chord>().is_err());
+    }
+
+    #[test]
+    fn file_rebinds_actions_and_rejects_conflicts() {
+        let keymap = Keymap::from_json(
+            r#"{
+                "pan": ["alt+left_mouse"],
+                "box_select": ["left_mouse", "shift+left_mouse", "ctrl+left_mouse", "ctrl+shift+left_mouse"],
+                "zoom": ["ctrl+wheel"],
+                "scroll_pan": ["wheel"]
+            }"#,
+        )
+        .unwrap();
+        let viewport = ActionContext::Viewport;
+        assert_eq!(
+            keymap.action(viewport, Input::Wheel, Modifiers::default()),
+            Some(Action::ScrollPan)
+        );
+        assert_eq!(
+            keymap.action(viewport, Input::Wheel, mods(false, true)),
+            Some(Action::Zoom)
+        );
+        assert_eq!(
+            keymap.action(
+                viewport,
+                Input::Mouse(MouseButton::Left),
+                Modifiers {
+                    alt: true,
+                    ..Default::default()
+                }
+            ),
+            Some(Action::Pan)
+        );
+        // Untouched actions keep their defaults
+        assert_eq!(
+            keymap.action(viewport, Input::Key(KeyCode::G), Modifiers::default()),
+            Some(Action::Grab)
+        );
+
+        // By default box select takes the left button with any modifiers
+        assert!(matches!(
+            Keymap::from_json(r#"{ "pan": ["alt+left_mouse"] }"#),
+            Err(KeymapError::Conflict(Action::Pan, Action::BoxSelect, _))
+        ));
+        assert!(matches!(
+            Keymap::from_json(r#"{ "grab": ["r"] }"#),
+            Err(KeymapError::Conflict(Action::Grab, Action::Rotate, _))
+        ));
+        // The same chord is fine for actions that never run at the same time
+        assert!(Keymap::from_json(r#"{ "confirm": ["space"], "grab": ["space"] }"#).is_ok());
+        assert!(matches!(
+            Keymap::from_json(r#"{ "fly": ["f"] }"#),
+            Err(KeymapError::Json(_))
+        ));
+    }
+}
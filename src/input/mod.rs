@@ -1 +1,2 @@
 pub mod input_state;
+pub mod keymap;
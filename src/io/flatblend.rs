@@ -0,0 +1,939 @@
+//! Native `.flatblend` document format
+//!
+//! A document is JSON with a `format` and `version` header, followed by the camera, its
+//! bookmarks, the grid settings and flat lists of meshes, materials and objects. Objects are
+//! listed in draw order and refer to their mesh, material and parent by index, so objects that
+//! share a mesh or material still share it after loading. Meshes store the full BMesh topology:
+//! vertices, edges, and each face as a loop of vertex and edge indices.
+//!
+//! Older documents are upgraded on load by running them through [`MIGRATIONS`] as plain JSON
+//! before they are deserialized. To change the format, add a migration that turns the current
+//! version into the new one; the format version is the number of migrations plus one.
+
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, HashMap},
+    fmt,
+    io::Write,
+    path::Path,
+    rc::Rc,
+};
+
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use slotmap::SecondaryMap;
+
+use crate::{
+    data::{
+        mesh::{
+            bm_edge::bm_edge_create,
+            bm_face::bm_face_create,
+            bm_loop::BMLoopIterator,
+            bm_vert::bm_vert_create,
+            bmesh::{BMesh, EdgeKey, VertKey},
+        },
+        modifiers::ModifierStack,
+        vertex::Vertex,
+    },
+    opengl::{
+        pipelines::grid::GridSettings,
+        scene::SceneData,
+        structs::{Colour, Material, Mesh, Object, Stroke},
+    },
+};
+
+/// Value of the `format` header field
+const FORMAT_NAME: &str = "flatblend";
+
+/// Upgrades a JSON document by one version
+type Migration = fn(&mut Value) -> Result<(), FlatBlendError>;
+
+/// Migrations from each older version to the next, the first upgrades version 1 to version 2
+const MIGRATIONS: &[Migration] = &[
+    add_object_parents,
+    add_camera_rotation_and_bookmarks,
+    add_grid_settings,
+    add_object_names_and_visibility,
+];
+
+/// Version written by [`save_flatblend`]
+pub const FORMAT_VERSION: u32 = MIGRATIONS.len() as u32 + 1;
+
+#[derive(Debug)]
+pub enum FlatBlendError {
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    /// The file does not have a `flatblend` format header
+    NotFlatBlend,
+    /// The file was written by a newer version, or has a version that never existed
+    UnsupportedVersion(u32),
+    /// The document parsed, but refers to things that are not in it
+    Invalid(String),
+}
+
+impl fmt::Display for FlatBlendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FlatBlendError::Io(err) => write!(f, "could not access document: {err}"),
+            FlatBlendError::Json(err) => write!(f, "malformed document: {err}"),
+            FlatBlendError::NotFlatBlend => write!(f, "not a flatblend document"),
+            FlatBlendError::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported document version {version}, the newest supported is {FORMAT_VERSION}"
+            ),
+            FlatBlendError::Invalid(reason) => write!(f, "invalid document: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for FlatBlendError {}
+
+impl From<std::io::Error> for FlatBlendError {
+    fn from(err: std::io::Error) -> Self {
+        FlatBlendError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for FlatBlendError {
+    fn from(err: serde_json::Error) -> Self {
+        FlatBlendError::Json(err)
+    }
+}
+
+/// Camera position, zoom and rotation, as stored in a document
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct CameraState {
+    pub position: Vec2,
+    pub zoom: f32,
+    pub rotation: f32,
+}
+
+impl Default for CameraState {
+    fn default() -> Self {
+        Self {
+            position: Vec2::ZERO,
+            zoom: 1.0,
+            rotation: 0.0,
+        }
+    }
+}
+
+/// How the document was last looked at, saved alongside the scene
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ViewState {
+    pub camera: CameraState,
+    /// Cameras stored by number
+    pub bookmarks: BTreeMap<u8, CameraState>,
+    pub grid: GridSettings,
+}
+
+/// A loaded document
+pub struct FlatBlendDocument {
+    pub scene_data: SceneData,
+    pub view: ViewState,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DocumentData {
+    format: String,
+    version: u32,
+    camera: CameraState,
+    bookmarks: BTreeMap<u8, CameraState>,
+    grid: GridSettings,
+    meshes: Vec<MeshData>,
+    materials: Vec<MaterialData>,
+    /// Objects in draw order, bottom to top
+    objects: Vec<ObjectData>,
+    /// Index into `objects`
+    active_object: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MeshData {
+    vertices: Vec<Vec2>,
+    /// Pairs of indices into `vertices`
+    edges: Vec<[usize; 2]>,
+    faces: Vec<FaceData>,
+}
+
+/// A face loop, where `edges[i]` joins `verts[i]` to the next vertex
+#[derive(Serialize, Deserialize)]
+struct FaceData {
+    verts: Vec<usize>,
+    edges: Vec<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct MaterialData {
+    colour: Colour,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ObjectData {
+    name: String,
+    mesh: usize,
+    material: usize,
+    translation: Vec2,
+    rotation: f32,
+    scale: Vec2,
+    stroke: Option<StrokeData>,
+    modifiers: ModifierStack,
+    selected: bool,
+    hidden: bool,
+    locked: bool,
+    /// Index into `objects` of the parent, whose space the transform is in
+    parent: Option<usize>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StrokeData {
+    colour: Colour,
+    width: f32,
+}
+
+/// Save the scene and view to a document file
+pub fn save_flatblend_file(
+    scene_data: &SceneData,
+    view: &ViewState,
+    path: impl AsRef<Path>,
+) -> Result<(), FlatBlendError> {
+    std::fs::write(path, save_flatblend(scene_data, view))?;
+    Ok(())
+}
+
+/// Save the scene and view to a document file that doesn't exist yet
+/// Fails with an [`std::io::ErrorKind::AlreadyExists`] error rather than overwriting a file.
+pub fn save_new_flatblend_file(
+    scene_data: &SceneData,
+    view: &ViewState,
+    path: impl AsRef<Path>,
+) -> Result<(), FlatBlendError> {
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(path)?;
+    file.write_all(save_flatblend(scene_data, view).as_bytes())?;
+    Ok(())
+}
+
+/// Save the scene and view as a document
+pub fn save_flatblend(scene_data: &SceneData, view: &ViewState) -> String {
+    let mut meshes = vec![];
+    let mut materials = vec![];
+    let mut mesh_indices = HashMap::new();
+    let mut material_indices = HashMap::new();
+
+    let ordered_keys = scene_data
+        .object_order()
+        .iter()
+        .copied()
+        .filter(|&key| scene_data.objects().contains_key(key))
+        .collect::<Vec<_>>();
+
+    let object_indices = ordered_keys
+        .iter()
+        .enumerate()
+        .map(|(index, &key)| (key, index))
+        .collect::<HashMap<_, _>>();
+
+    let objects = ordered_keys
+        .iter()
+        .map(|&key| {
+            let object = &scene_data.objects()[key];
+
+            let mesh = *mesh_indices
+                .entry(Rc::as_ptr(object.mesh()))
+                .or_insert_with(|| {
+                    meshes.push(mesh_data(&object.borrow_mesh().raw_mesh));
+                    meshes.len() - 1
+                });
+            let material = *material_indices
+                .entry(Rc::as_ptr(object.material()))
+                .or_insert_with(|| {
+                    materials.push(MaterialData {
+                        colour: object.borrow_material().colour,
+                    });
+                    materials.len() - 1
+                });
+
+            ObjectData {
+                name: object.name.clone(),
+                mesh,
+                material,
+                translation: object.translation(),
+                rotation: object.rotation(),
+                scale: object.scale(),
+                stroke: object.stroke().map(|stroke| StrokeData {
+                    colour: stroke.colour,
+                    width: stroke.width,
+                }),
+                modifiers: object.modifiers().clone(),
+                selected: object.selected,
+                hidden: object.hidden,
+                locked: object.locked,
+                parent: object
+                    .parent()
+                    .and_then(|parent| object_indices.get(&parent).copied()),
+            }
+        })
+        .collect();
+
+    let document = DocumentData {
+        format: FORMAT_NAME.to_string(),
+        version: FORMAT_VERSION,
+        camera: view.camera,
+        bookmarks: view.bookmarks.clone(),
+        grid: view.grid,
+        meshes,
+        materials,
+        objects,
+        active_object: scene_data
+            .active_object()
+            .and_then(|active| ordered_keys.iter().position(|&key| key == active)),
+    };
+
+    serde_json::to_string_pretty(&document).expect("document is always serializable")
+}
+
+/// Load a document file
+pub fn load_flatblend_file(path: impl AsRef<Path>) -> Result<FlatBlendDocument, FlatBlendError> {
+    let text = std::fs::read_to_string(path)?;
+    load_flatblend(&text)
+}
+
+/// Load a document, upgrading it first if it was saved by an older version
+pub fn load_flatblend(text: &str) -> Result<FlatBlendDocument, FlatBlendError> {
+    let mut value: Value = serde_json::from_str(text)?;
+    migrate(&mut value, MIGRATIONS)?;
+    let document: DocumentData = serde_json::from_value(value)?;
+
+    let meshes = document
+        .meshes
+        .iter()
+        .map(|mesh| Ok(Mesh::new(build_bmesh(mesh)?, 0).0))
+        .collect::<Result<Vec<_>, FlatBlendError>>()?;
+    let materials = document
+        .materials
+        .iter()
+        .map(|material| {
+            Rc::new(RefCell::new(Material {
+                colour: material.colour,
+            }))
+        })
+        .collect::<Vec<_>>();
+
+    let parents = document
+        .objects
+        .iter()
+        .map(|data| data.parent)
+        .collect::<Vec<_>>();
+
+    let objects = document
+        .objects
+        .into_iter()
+        .map(|data| {
+            let mesh = meshes
+                .get(data.mesh)
+                .ok_or_else(|| invalid(format!("object uses missing mesh {}", data.mesh)))?;
+            let material = materials.get(data.material).ok_or_else(|| {
+                invalid(format!("object uses missing material {}", data.material))
+            })?;
+
+            let mut object = Object::new(
+                mesh.clone(),
+                data.translation,
+                data.rotation,
+                data.scale,
+                material.clone(),
+            );
+            object.set_stroke(data.stroke.map(|stroke| Stroke {
+                colour: stroke.colour,
+                width: stroke.width,
+            }));
+            *object.modifiers_mut() = data.modifiers;
+            object.evaluate_modifiers();
+            object.name = data.name;
+            object.selected = data.selected;
+            object.hidden = data.hidden;
+            object.locked = data.locked;
+
+            Ok(object)
+        })
+        .collect::<Result<Vec<_>, FlatBlendError>>()?;
+
+    let mut scene_data = SceneData::new(objects);
+    let keys = scene_data.object_order().to_vec();
+    for (&key, parent) in keys.iter().zip(parents) {
+        let Some(parent) = parent else {
+            continue;
+        };
+        let parent_key = *keys
+            .get(parent)
+            .ok_or_else(|| invalid(format!("object has missing parent {parent}")))?;
+        scene_data
+            .set_parent(key, Some(parent_key), false)
+            .map_err(|err| invalid(err.to_string()))?;
+    }
+
+    let active_object = document
+        .active_object
+        .and_then(|index| scene_data.object_order().get(index).copied());
+    scene_data.set_active_object(active_object);
+
+    Ok(FlatBlendDocument {
+        scene_data,
+        view: ViewState {
+            camera: document.camera,
+            bookmarks: document.bookmarks,
+            grid: document.grid,
+        },
+    })
+}
+
+/// Check the header and run every migration newer than the document's version
+fn migrate(document: &mut Value, migrations: &[Migration]) -> Result<(), FlatBlendError> {
+    if document.get("format").and_then(Value::as_str) != Some(FORMAT_NAME) {
+        return Err(FlatBlendError::NotFlatBlend);
+    }
+
+    let version = document
+        .get("version")
+        .and_then(Value::as_u64)
+        .ok_or(FlatBlendError::NotFlatBlend)? as u32;
+    let newest = migrations.len() as u32 + 1;
+    if version == 0 || version > newest {
+        return Err(FlatBlendError::UnsupportedVersion(version));
+    }
+
+    for migration in &migrations[version as usize - 1..] {
+        migration(document)?;
+    }
+    document["version"] = Value::from(newest);
+
+    Ok(())
+}
+
+/// Version 2 added object parents
+fn add_object_parents(document: &mut Value) -> Result<(), FlatBlendError> {
+    for object in object_entries(document)? {
+        object.insert("parent".to_string(), Value::Null);
+    }
+    Ok(())
+}
+
+/// Version 3 added camera rotation and bookmarks
+///
+/// Some version 2 documents already have a rotation, which is kept.
+fn add_camera_rotation_and_bookmarks(document: &mut Value) -> Result<(), FlatBlendError> {
+    if let Some(camera) = document.get_mut("camera").and_then(Value::as_object_mut) {
+        camera.entry("rotation").or_insert_with(|| Value::from(0.0));
+    }
+    document["bookmarks"] = Value::Object(Default::default());
+    Ok(())
+}
+
+/// Version 4 added grid settings, starting from the grid that was always drawn before
+fn add_grid_settings(document: &mut Value) -> Result<(), FlatBlendError> {
+    document["grid"] = serde_json::to_value(GridSettings::default())?;
+    Ok(())
+}
+
+/// Version 5 added object names and hiding and locking objects
+fn add_object_names_and_visibility(document: &mut Value) -> Result<(), FlatBlendError> {
+    for (index, object) in object_entries(document)?.into_iter().enumerate() {
+        object.insert(
+            "name".to_string(),
+            Value::from(format!("Object {}", index + 1)),
+        );
+        object.insert("hidden".to_string(), Value::from(false));
+        object.insert("locked".to_string(), Value::from(false));
+    }
+    Ok(())
+}
+
+/// Entries of the document's object list, for migrations to edit
+fn object_entries(document: &mut Value) -> Result<Vec<&mut Map<String, Value>>, FlatBlendError> {
+    let Some(objects) = document.get_mut("objects").and_then(Value::as_array_mut) else {
+        return Ok(Vec::new());
+    };
+    objects
+        .iter_mut()
+        .enumerate()
+        .map(|(index, object)| {
+            object
+                .as_object_mut()
+                .ok_or_else(|| invalid(format!("object {index} is not an object")))
+        })
+        .collect()
+}
+
+fn invalid(reason: String) -> FlatBlendError {
+    FlatBlendError::Invalid(reason)
+}
+
+fn mesh_data(bmesh: &BMesh) -> MeshData {
+    let mut vert_indices = SecondaryMap::<VertKey, usize>::new();
+    let vertices = bmesh
+        .vertices
+        .iter()
+        .enumerate()
+        .map(|(i, (key, vert))| {
+            vert_indices.insert(key, i);
+            vert.vertex.pos
+        })
+        .collect();
+
+    let mut edge_indices = SecondaryMap::<EdgeKey, usize>::new();
+    let edges = bmesh
+        .edges
+        .iter()
+        .enumerate()
+        .map(|(i, (key, edge))| {
+            edge_indices.insert(key, i);
+            [vert_indices[edge.v0], vert_indices[edge.v1]]
+        })
+        .collect();
+
+    let faces = bmesh
+        .faces
+        .values()
+        .filter_map(|face| face.loop_start)
+        .map(|loop_start| {
+            let (verts, edges) = BMLoopIterator::new(bmesh, loop_start)
+                .filter_map(|l| {
+                    let l = &bmesh.loops[l];
+                    Some((vert_indices[l.vertex], edge_indices[l.edge?]))
+                })
+                .unzip();
+            FaceData { verts, edges }
+        })
+        .collect();
+
+    MeshData {
+        vertices,
+        edges,
+        faces,
+    }
+}
+
+fn build_bmesh(data: &MeshData) -> Result<BMesh, FlatBlendError> {
+    let mut bmesh = BMesh::new();
+
+    let verts = data
+        .vertices
+        .iter()
+        .map(|pos| {
+            let v = bm_vert_create(&mut bmesh);
+            bmesh.vertices[v].vertex = Vertex { pos: *pos };
+            v
+        })
+        .collect::<Vec<_>>();
+    let vert = |index: usize| {
+        verts
+            .get(index)
+            .copied()
+            .ok_or_else(|| invalid(format!("mesh uses missing vertex {index}")))
+    };
+
+    let edges = data
+        .edges
+        .iter()
+        .map(|&[v0, v1]| {
+            if v0 == v1 {
+                return Err(invalid(format!("edge joins vertex {v0} to itself")));
+            }
+            Ok(bm_edge_create(&mut bmesh, vert(v0)?, vert(v1)?))
+        })
+        .collect::<Result<Vec<_>, FlatBlendError>>()?;
+    let edge = |index: usize| {
+        edges
+            .get(index)
+            .copied()
+            .ok_or_else(|| invalid(format!("mesh uses missing edge {index}")))
+    };
+
+    for face in &data.faces {
+        if face.verts.len() < 3 || face.verts.len() != face.edges.len() {
+            return Err(invalid("face loop is malformed".to_string()));
+        }
+        // Each edge has to run from its vertex to the next one around the loop
+        for (i, &e) in face.edges.iter().enumerate() {
+            let ends = [face.verts[i], face.verts[(i + 1) % face.verts.len()]];
+            let [v0, v1] = *data
+                .edges
+                .get(e)
+                .ok_or_else(|| invalid(format!("mesh uses missing edge {e}")))?;
+            if [v0, v1] != ends && [v1, v0] != ends {
+                return Err(invalid(format!(
+                    "face loop edge {e} doesn't join its vertices"
+                )));
+            }
+        }
+
+        let face_verts = face
+            .verts
+            .iter()
+            .map(|&v| vert(v))
+            .collect::<Result<Vec<_>, _>>()?;
+        let face_edges = face
+            .edges
+            .iter()
+            .map(|&e| edge(e))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        bm_face_create(&mut bmesh, &face_verts, &face_edges);
+    }
+
+    Ok(bmesh)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use glam::Vec2;
+
+    use crate::{
+        data::{mesh::bmesh::bm_face_polygons, modifiers::ModifierKind, modifiers::Offset},
+        shapes::{square::create_square, star::create_star},
+    };
+
+    use super::*;
+
+    fn material(colour: Colour) -> Rc<RefCell<Material>> {
+        Rc::new(RefCell::new(Material { colour }))
+    }
+
+    fn test_scene() -> SceneData {
+        let (square, _, _) = Mesh::new(create_square(), 0);
+        let (star, _, _) = Mesh::new(create_star(), 0);
+        let red = material(Colour::new(1.0, 0.0, 0.0, 1.0));
+        let blue = material(Colour::new(0.0, 0.0, 1.0, 0.5));
+
+        let mut objects = vec![
+            Object::new(
+                star.clone(),
+                Vec2::new(1.0, 2.0),
+                0.5,
+                Vec2::ONE,
+                red.clone(),
+            ),
+            Object::new(square, Vec2::new(-3.0, 0.0), 0.0, Vec2::new(2.0, 1.0), blue),
+            Object::new(star, Vec2::new(8.0, 8.0), 1.0, Vec2::splat(3.0), red),
+        ];
+        objects[1].set_stroke(Some(Stroke {
+            colour: Colour::new(0.0, 0.0, 0.0, 1.0),
+            width: 0.25,
+        }));
+        objects[1].add_modifier(ModifierKind::Offset(Offset { distance: 0.5 }));
+        objects[2].selected = true;
+        objects[0].name = "Star".to_string();
+        objects[1].hidden = true;
+        objects[2].locked = true;
+
+        let mut scene_data = SceneData::new(objects);
+        scene_data.evaluate_modifiers();
+        // Draw the square on top, so the saved order differs from insertion order
+        scene_data.reorder_objects(|order| order.swap(1, 2));
+        let active = scene_data.object_order()[1];
+        scene_data.set_active_object(Some(active));
+        scene_data
+    }
+
+    fn ordered(scene_data: &SceneData) -> Vec<&Object> {
+        scene_data
+            .object_order()
+            .iter()
+            .map(|&key| &scene_data.objects()[key])
+            .collect()
+    }
+
+    #[test]
+    fn round_trips_scene() {
+        let scene_data = test_scene();
+        let camera = CameraState {
+            position: Vec2::new(12.0, -4.0),
+            zoom: 0.25,
+            rotation: 0.5,
+        };
+        let view = ViewState {
+            camera,
+            bookmarks: BTreeMap::from([
+                (1, CameraState::default()),
+                (
+                    7,
+                    CameraState {
+                        zoom: 3.0,
+                        ..camera
+                    },
+                ),
+            ]),
+            grid: GridSettings {
+                spacing: 64.0,
+                subdivisions: 4,
+                major_colour: Colour::new(0.2, 0.3, 0.9, 1.0),
+                show_axes: false,
+                isometric: true,
+                ..Default::default()
+            },
+        };
+
+        let loaded = load_flatblend(&save_flatblend(&scene_data, &view)).unwrap();
+        assert_eq!(loaded.view, view);
+
+        let before = ordered(&scene_data);
+        let after = ordered(&loaded.scene_data);
+        assert_eq!(before.len(), after.len());
+
+        for (a, b) in before.iter().zip(&after) {
+            assert_eq!(a.get_model_matrix(), b.get_model_matrix());
+            assert_eq!(a.borrow_material().colour, b.borrow_material().colour);
+            assert_eq!(a.stroke(), b.stroke());
+            assert_eq!(a.modifiers(), b.modifiers());
+            assert_eq!(a.selected, b.selected);
+            assert_eq!((&a.name, a.hidden, a.locked), (&b.name, b.hidden, b.locked));
+            assert_eq!(
+                bm_face_polygons(&a.borrow_evaluated_mesh().raw_mesh),
+                bm_face_polygons(&b.borrow_evaluated_mesh().raw_mesh)
+            );
+            assert_eq!(a.borrow_mesh().tris, b.borrow_mesh().tris);
+        }
+
+        let active = loaded.scene_data.active_object().unwrap();
+        assert_eq!(loaded.scene_data.object_order()[1], active);
+    }
+
+    #[test]
+    fn preserves_shared_meshes_and_materials() {
+        let loaded = load_flatblend(&save_flatblend(&test_scene(), &ViewState::default()))
+            .unwrap()
+            .scene_data;
+        let objects = ordered(&loaded);
+
+        // The two stars are first and second in draw order after the swap
+        assert!(Rc::ptr_eq(objects[0].mesh(), objects[1].mesh()));
+        assert!(Rc::ptr_eq(objects[0].material(), objects[1].material()));
+        assert!(!Rc::ptr_eq(objects[0].mesh(), objects[2].mesh()));
+    }
+
+    #[test]
+    fn round_trips_hierarchy() {
+        let mut scene_data = test_scene();
+        let order = scene_data.object_order().to_vec();
+        scene_data
+            .set_parent(order[0], Some(order[2]), true)
+            .unwrap();
+        scene_data
+            .set_parent(order[1], Some(order[0]), false)
+            .unwrap();
+
+        let loaded = load_flatblend(&save_flatblend(&scene_data, &ViewState::default()))
+            .unwrap()
+            .scene_data;
+        let loaded_order = loaded.object_order();
+
+        assert_eq!(loaded.parent(loaded_order[0]), Some(loaded_order[2]));
+        assert_eq!(loaded.parent(loaded_order[1]), Some(loaded_order[0]));
+        assert_eq!(loaded.parent(loaded_order[2]), None);
+        for (a, b) in ordered(&scene_data).iter().zip(ordered(&loaded)) {
+            assert!(a.get_model_matrix().abs_diff_eq(b.get_model_matrix(), 1e-5));
+        }
+
+        // A document that parents an object to its own child is rejected
+        let mut document: Value =
+            serde_json::from_str(&save_flatblend(&scene_data, &ViewState::default())).unwrap();
+        document["objects"][2]["parent"] = Value::from(1);
+        assert!(matches!(
+            load_flatblend(&document.to_string()),
+            Err(FlatBlendError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_faces_whose_edges_do_not_join_their_vertices() {
+        let saved: Value =
+            serde_json::from_str(&save_flatblend(&test_scene(), &ViewState::default())).unwrap();
+        assert!(load_flatblend(&saved.to_string()).is_ok());
+
+        // A face loop with two edges swapped, and an edge joining a vertex to itself
+        let mut swapped = saved.clone();
+        let edges = &mut swapped["meshes"][0]["faces"][0]["edges"];
+        let (first, second) = (edges[0].clone(), edges[1].clone());
+        edges[0] = second;
+        edges[1] = first;
+
+        let mut degenerate = saved.clone();
+        let edge = &mut degenerate["meshes"][0]["edges"][0];
+        edge[1] = edge[0].clone();
+
+        for document in [swapped, degenerate] {
+            assert!(matches!(
+                load_flatblend(&document.to_string()),
+                Err(FlatBlendError::Invalid(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn loads_version_1_documents() {
+        let mut document: Value =
+            serde_json::from_str(&save_flatblend(&test_scene(), &ViewState::default())).unwrap();
+        document["version"] = Value::from(1);
+        for object in document["objects"].as_array_mut().unwrap() {
+            let object = object.as_object_mut().unwrap();
+            for field in ["parent", "name", "hidden", "locked"] {
+                object.remove(field);
+            }
+        }
+        document["camera"]
+            .as_object_mut()
+            .unwrap()
+            .remove("rotation");
+        document.as_object_mut().unwrap().remove("bookmarks");
+        document.as_object_mut().unwrap().remove("grid");
+
+        let loaded = load_flatblend(&document.to_string()).unwrap();
+        let scene_data = loaded.scene_data;
+        assert_eq!(scene_data.object_order().len(), 3);
+        assert!(scene_data
+            .object_order()
+            .iter()
+            .all(|&key| scene_data.parent(key).is_none()));
+        let last = &scene_data.objects()[scene_data.object_order()[2]];
+        assert_eq!(last.name, "Object 3");
+        assert!(!last.hidden && !last.locked);
+        assert_eq!(loaded.view, ViewState::default());
+    }
+
+    /// The test scene as version 2 wrote it, which had parents but no names, bookmarks or grid
+    fn version_2_document(camera: CameraState) -> Value {
+        let view = ViewState {
+            camera,
+            ..Default::default()
+        };
+        let mut document: Value =
+            serde_json::from_str(&save_flatblend(&test_scene(), &view)).unwrap();
+        document["version"] = Value::from(2);
+        for object in document["objects"].as_array_mut().unwrap() {
+            let object = object.as_object_mut().unwrap();
+            for field in ["name", "hidden", "locked"] {
+                object.remove(field);
+            }
+        }
+        document.as_object_mut().unwrap().remove("bookmarks");
+        document.as_object_mut().unwrap().remove("grid");
+        document
+    }
+
+    #[test]
+    fn camera_rotation_is_versioned() {
+        // Only the migration from version 2 fills in a missing rotation
+        let mut document: Value =
+            serde_json::from_str(&save_flatblend(&test_scene(), &ViewState::default())).unwrap();
+        document["camera"]
+            .as_object_mut()
+            .unwrap()
+            .remove("rotation");
+        assert!(matches!(
+            load_flatblend(&document.to_string()),
+            Err(FlatBlendError::Json(_))
+        ));
+
+        let mut document = version_2_document(CameraState::default());
+        document["camera"]
+            .as_object_mut()
+            .unwrap()
+            .remove("rotation");
+        let loaded = load_flatblend(&document.to_string()).unwrap();
+        assert_eq!(loaded.view.camera.rotation, 0.0);
+    }
+
+    #[test]
+    fn keeps_rotation_of_version_2_documents() {
+        let camera = CameraState {
+            rotation: 1.25,
+            ..Default::default()
+        };
+        let loaded = load_flatblend(&version_2_document(camera).to_string()).unwrap();
+        assert_eq!(loaded.view.camera, camera);
+        assert!(loaded.view.bookmarks.is_empty());
+    }
+
+    #[test]
+    fn preserves_topology() {
+        let bmesh = create_star();
+        let rebuilt = build_bmesh(&mesh_data(&bmesh)).unwrap();
+
+        assert_eq!(rebuilt.vertices.len(), bmesh.vertices.len());
+        assert_eq!(rebuilt.edges.len(), bmesh.edges.len());
+        assert_eq!(rebuilt.loops.len(), bmesh.loops.len());
+        assert_eq!(rebuilt.faces.len(), bmesh.faces.len());
+        assert_eq!(bm_face_polygons(&rebuilt), bm_face_polygons(&bmesh));
+    }
+
+    #[test]
+    fn runs_migrations_from_older_versions() {
+        fn rename_color(document: &mut Value) -> Result<(), FlatBlendError> {
+            for material in document["materials"].as_array_mut().unwrap() {
+                let colour = material.as_object_mut().unwrap().remove("color").unwrap();
+                material["colour"] = colour;
+            }
+            Ok(())
+        }
+
+        let mut document = serde_json::json!({
+            "format": "flatblend",
+            "version": 1,
+            "materials": [{ "color": [1.0, 0.0, 0.0, 1.0] }],
+        });
+        migrate(&mut document, &[rename_color]).unwrap();
+
+        assert_eq!(document["version"], 2);
+        assert_eq!(
+            document["materials"][0]["colour"],
+            serde_json::json!([1.0, 0.0, 0.0, 1.0])
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_objects_when_migrating() {
+        for version in [1, 4] {
+            let document =
+                format!(r#"{{ "format": "flatblend", "version": {version}, "objects": [1] }}"#);
+            assert!(matches!(
+                load_flatblend(&document),
+                Err(FlatBlendError::Invalid(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn saving_a_new_file_does_not_overwrite() {
+        let path =
+            std::env::temp_dir().join(format!("flatblend-test-{}.flatblend", std::process::id()));
+        std::fs::write(&path, "precious").unwrap();
+
+        let result = save_new_flatblend_file(&test_scene(), &ViewState::default(), &path);
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(
+            result,
+            Err(FlatBlendError::Io(err)) if err.kind() == std::io::ErrorKind::AlreadyExists
+        ));
+        assert_eq!(contents, "precious");
+    }
+
+    #[test]
+    fn rejects_unknown_documents() {
+        assert!(matches!(
+            load_flatblend(r#"{ "format": "flatblend", "version": 999 }"#),
+            Err(FlatBlendError::UnsupportedVersion(999))
+        ));
+        assert!(matches!(
+            load_flatblend(r#"{ "format": "something else", "version": 1 }"#),
+            Err(FlatBlendError::NotFlatBlend)
+        ));
+    }
+}
@@ -0,0 +1,3 @@
+pub mod flatblend;
+pub mod svg_export;
+pub mod svg_import;
@@ -0,0 +1,270 @@
+//! SVG export
+//!
+//! Writes every visible object in the scene, bottom to top, as a `<path>` element. The path is
+//! built from the face loops of the object's evaluated mesh in local space, and the object's
+//! model matrix becomes the path's `transform`. Solid loops are counter-clockwise and holes are
+//! clockwise, so the paths are filled with the non-zero rule.
+//!
+//! The scene's y axis points up while SVG's points down, so all paths sit in a group that
+//! flips them back. The document reads back in through the SVG importer unchanged.
+
+use std::{fmt::Write, path::Path};
+
+use glam::{Mat4, Vec2, Vec4Swizzles};
+
+use crate::{
+    data::mesh::bmesh::bm_face_polygons,
+    opengl::{
+        frustum::AABB2D,
+        scene::SceneData,
+        structs::{Colour, Object},
+    },
+};
+
+/// Export the scene to an SVG file
+pub fn export_svg_file(scene_data: &SceneData, path: impl AsRef<Path>) -> std::io::Result<()> {
+    std::fs::write(path, export_svg(scene_data))
+}
+
+/// Export the scene to an SVG document
+pub fn export_svg(scene_data: &SceneData) -> String {
+    let objects = scene_data
+        .object_order()
+        .iter()
+        .filter_map(|&key| scene_data.objects().get(key))
+        .filter(|object| !object.hidden)
+        .collect::<Vec<_>>();
+
+    let mut svg = String::new();
+    svg.push_str(r#"<svg xmlns="http://www.w3.org/2000/svg""#);
+    if let Some(bounds) = document_bounds(&objects) {
+        let size = bounds.max - bounds.min;
+        // The view box is in SVG coordinates, after the y flip
+        write!(
+            svg,
+            r#" viewBox="{} {} {} {}" width="{}" height="{}""#,
+            bounds.min.x, -bounds.max.y, size.x, size.y, size.x, size.y
+        )
+        .unwrap();
+    }
+    svg.push_str(">\n");
+    svg.push_str("  <g transform=\"scale(1 -1)\">\n");
+
+    for object in objects {
+        write_object(&mut svg, object);
+    }
+
+    svg.push_str("  </g>\n");
+    svg.push_str("</svg>\n");
+    svg
+}
+
+fn write_object(svg: &mut String, object: &Object) {
+    let polygons = bm_face_polygons(&object.borrow_evaluated_mesh().raw_mesh);
+    if polygons.is_empty() {
+        return;
+    }
+
+    let mut data = String::new();
+    for polygon in &polygons {
+        for (i, p) in polygon.iter().enumerate() {
+            let command = if i == 0 { 'M' } else { 'L' };
+            write!(data, "{}{} {} ", command, p.x, p.y).unwrap();
+        }
+        data.push('Z');
+    }
+
+    write!(
+        svg,
+        r#"    <path d="{}" transform="{}""#,
+        data,
+        svg_matrix(object.get_model_matrix())
+    )
+    .unwrap();
+
+    write_paint(svg, "fill", object.borrow_material().colour);
+    svg.push_str(r#" fill-rule="nonzero""#);
+
+    if let Some(stroke) = object.stroke() {
+        write_paint(svg, "stroke", stroke.colour);
+        write!(svg, r#" stroke-width="{}""#, stroke.width).unwrap();
+    }
+
+    svg.push_str("/>\n");
+}
+
+/// Write a paint attribute, with a separate opacity attribute for translucent colours
+fn write_paint(svg: &mut String, name: &str, colour: Colour) {
+    let [r, g, b] = [colour.x, colour.y, colour.z].map(|c| (c.clamp(0.0, 1.0) * 255.0).round());
+    write!(svg, r#" {}="rgb({},{},{})""#, name, r, g, b).unwrap();
+    if colour.w < 1.0 {
+        write!(svg, r#" {}-opacity="{}""#, name, colour.w.max(0.0)).unwrap();
+    }
+}
+
+/// The 2D part of a model matrix as an SVG `matrix(a b c d e f)` transform
+fn svg_matrix(matrix: Mat4) -> String {
+    format!(
+        "matrix({} {} {} {} {} {})",
+        matrix.x_axis.x,
+        matrix.x_axis.y,
+        matrix.y_axis.x,
+        matrix.y_axis.y,
+        matrix.w_axis.x,
+        matrix.w_axis.y
+    )
+}
+
+/// Union of the objects' bounds, padded by their strokes
+fn document_bounds(objects: &[&Object]) -> Option<AABB2D> {
+    objects
+        .iter()
+        .map(|object| {
+            let aabb = object.get_aabb();
+            // Strokes are centred on the outline, and scale with the object
+            let matrix = object.get_model_matrix();
+            let scale = matrix.x_axis.xy().length().max(matrix.y_axis.xy().length());
+            let padding = object
+                .stroke()
+                .map_or(0.0, |stroke| stroke.width * scale / 2.0);
+            AABB2D::new(
+                aabb.min - Vec2::splat(padding),
+                aabb.max + Vec2::splat(padding),
+            )
+        })
+        .reduce(|a, b| AABB2D::new(a.min.min(b.min), a.max.max(b.max)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use glam::Vec2;
+
+    use crate::{
+        data::mesh::bmesh::bm_from_polygons,
+        io::svg_import::{import_svg, SvgImportOptions},
+        opengl::structs::{Material, Mesh, Stroke},
+        shapes::square::create_square,
+    };
+
+    use super::*;
+
+    fn material(colour: Colour) -> Rc<RefCell<Material>> {
+        Rc::new(RefCell::new(Material { colour }))
+    }
+
+    /// Face loops of an object in world space
+    fn world_polygons(object: &Object) -> Vec<Vec<Vec2>> {
+        let matrix = object.get_model_matrix();
+        bm_face_polygons(&object.borrow_evaluated_mesh().raw_mesh)
+            .into_iter()
+            .map(|polygon| {
+                polygon
+                    .into_iter()
+                    .map(|p| (matrix * p.extend(0.0).extend(1.0)).xy())
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// True if both loops visit the same points in the same direction, from any starting point
+    fn same_loop(a: &[Vec2], b: &[Vec2]) -> bool {
+        a.len() == b.len()
+            && (0..b.len()).any(|start| {
+                a.iter()
+                    .enumerate()
+                    .all(|(i, p)| (*p - b[(start + i) % b.len()]).length() < 1e-3)
+            })
+    }
+
+    #[test]
+    fn writes_paths_with_transforms() {
+        let (mesh, _, _) = Mesh::new(create_square(), 0);
+        let mut object = Object::new(
+            mesh,
+            Vec2::new(10.0, 20.0),
+            0.0,
+            Vec2::splat(2.0),
+            material(Colour::new(1.0, 0.0, 0.0, 0.5)),
+        );
+        object.set_stroke(Some(Stroke {
+            colour: Colour::new(0.0, 0.0, 1.0, 1.0),
+            width: 0.5,
+        }));
+
+        let svg = export_svg(&SceneData::new(vec![object]));
+
+        assert!(svg.contains(r#"transform="matrix(2 0 0 2 10 20)""#));
+        assert!(svg.contains(r#"fill="rgb(255,0,0)" fill-opacity="0.5""#));
+        assert!(svg.contains(r#"stroke="rgb(0,0,255)" stroke-width="0.5""#));
+        // The square spans 8..12 by 18..22, plus half the scaled stroke on each side
+        assert!(svg.contains(r#"viewBox="7.5 -22.5 5 5""#));
+    }
+
+    #[test]
+    fn round_trips_through_importer() {
+        let outer = vec![
+            Vec2::new(-2.0, -2.0),
+            Vec2::new(2.0, -2.0),
+            Vec2::new(2.0, 2.0),
+            Vec2::new(-2.0, 2.0),
+        ];
+        let hole = outer.iter().rev().map(|p| *p * 0.5).collect::<Vec<_>>();
+        let (ring, _, _) = Mesh::new(bm_from_polygons(&[outer, hole]), 0);
+        let (square, _, _) = Mesh::new(create_square(), 0);
+
+        let mut stroked = Object::new(
+            ring,
+            Vec2::new(5.0, -3.0),
+            0.7,
+            Vec2::new(1.5, 0.5),
+            material(Colour::new(0.2, 0.4, 0.6, 1.0)),
+        );
+        stroked.set_stroke(Some(Stroke {
+            colour: Colour::new(0.0, 0.0, 0.0, 1.0),
+            width: 1.0,
+        }));
+        let plain = Object::new(
+            square,
+            Vec2::new(-20.0, 8.0),
+            -1.2,
+            Vec2::splat(3.0),
+            material(Colour::new(1.0, 1.0, 1.0, 0.25)),
+        );
+
+        let scene = SceneData::new(vec![stroked, plain]);
+        let exported = scene
+            .object_order()
+            .iter()
+            .map(|&key| &scene.objects()[key])
+            .collect::<Vec<_>>();
+
+        let imported = import_svg(&export_svg(&scene), &SvgImportOptions::default()).unwrap();
+        assert_eq!(imported.len(), exported.len());
+
+        for (before, after) in exported.iter().zip(&imported) {
+            let before_loops = world_polygons(before);
+            let after_loops = world_polygons(after);
+            assert_eq!(before_loops.len(), after_loops.len());
+            for (a, b) in before_loops.iter().zip(&after_loops) {
+                assert!(same_loop(a, b));
+            }
+
+            let colour_before = before.borrow_material().colour;
+            let colour_after = after.borrow_material().colour;
+            assert!((colour_before - colour_after).abs().max_element() < 1.0 / 255.0);
+
+            // The importer bakes the scale into the mesh, so the stroke is scaled with it
+            let scale = before.get_model_matrix().determinant().abs().sqrt();
+            match (before.stroke(), after.stroke()) {
+                (Some(a), Some(b)) => {
+                    assert_eq!(a.colour, b.colour);
+                    assert!((a.width * scale - b.width).abs() < 1e-4);
+                }
+                (None, None) => {}
+                _ => panic!("stroke was not preserved"),
+            }
+        }
+    }
+}
@@ -0,0 +1,776 @@
+//! SVG import
+//!
+//! Reads `<path>`, `<rect>`, `<circle>`, `<ellipse>`, `<polygon>` and `<polyline>` elements,
+//! including ones nested in `<g>` groups with transforms, and turns each filled shape into an
+//! [`Object`] with its own [`BMesh`].
+//!
+//! BMesh edges are straight, so curves and arcs are flattened into line segments no further
+//! than [`SvgImportOptions::tolerance`] from the true curve. The fill rule of each shape is
+//! resolved while importing: every contour that bounds filled space becomes a
+//! counter-clockwise face and every contour that bounds a hole becomes a clockwise face.
+//!
+//! Strokes are not turned into geometry. They are kept as the object's [`Stroke`] so they
+//! survive a round trip through the SVG exporter.
+//!
+//! SVG's y axis points down while the scene's points up, so imported shapes are flipped
+//! vertically to keep them the right way up.
+
+use std::{cell::RefCell, collections::HashMap, fmt, path::Path, rc::Rc, str::FromStr};
+
+use glam::{Affine2, DVec2, Vec2};
+use lyon::geom::{point, CubicBezierSegment, QuadraticBezierSegment};
+use roxmltree::Node;
+use svgtypes::{Paint, PointsParser, SimplePathSegment, SimplifyingPathParser};
+
+use crate::{
+    data::{
+        mesh::bmesh::bm_from_polygons,
+        polygon::{orient, winding_number, Polygon},
+    },
+    opengl::structs::{Colour, Material, Mesh, Object, Stroke},
+};
+
+/// Control point distance for approximating a quarter circle with a cubic Bézier
+const KAPPA: f32 = 0.552_284_8;
+
+#[derive(Debug, Clone)]
+pub struct SvgImportOptions {
+    /// Maximum distance between a curve and the line segments that replace it, in scene units
+    ///
+    /// Curves are flattened after their element transforms are applied, so a curve scaled up
+    /// by a transform is still within the tolerance once imported.
+    pub tolerance: f32,
+}
+
+impl Default for SvgImportOptions {
+    fn default() -> Self {
+        Self { tolerance: 0.1 }
+    }
+}
+
+#[derive(Debug)]
+pub enum SvgImportError {
+    Io(std::io::Error),
+    Xml(roxmltree::Error),
+    /// The document's root element is not `<svg>`
+    NotSvg,
+}
+
+impl fmt::Display for SvgImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SvgImportError::Io(err) => write!(f, "could not read SVG file: {err}"),
+            SvgImportError::Xml(err) => write!(f, "invalid SVG document: {err}"),
+            SvgImportError::NotSvg => write!(f, "root element is not <svg>"),
+        }
+    }
+}
+
+impl std::error::Error for SvgImportError {}
+
+impl From<std::io::Error> for SvgImportError {
+    fn from(err: std::io::Error) -> Self {
+        SvgImportError::Io(err)
+    }
+}
+
+impl From<roxmltree::Error> for SvgImportError {
+    fn from(err: roxmltree::Error) -> Self {
+        SvgImportError::Xml(err)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FillRule {
+    NonZero,
+    EvenOdd,
+}
+
+/// Presentation attributes inherited down the element tree
+#[derive(Debug, Clone)]
+struct Style {
+    fill: Option<Colour>,
+    fill_rule: FillRule,
+    fill_opacity: f32,
+    stroke: Option<Colour>,
+    stroke_width: f32,
+    stroke_opacity: f32,
+    opacity: f32,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        // SVG shapes are filled black unless told otherwise
+        Self {
+            fill: Some(Colour::new(0.0, 0.0, 0.0, 1.0)),
+            fill_rule: FillRule::NonZero,
+            fill_opacity: 1.0,
+            stroke: None,
+            stroke_width: 1.0,
+            stroke_opacity: 1.0,
+            opacity: 1.0,
+        }
+    }
+}
+
+impl Style {
+    /// Resolve the style of `node`, given the style of its parent
+    fn inherit(&self, node: &Node) -> Style {
+        let mut style = self.clone();
+        // Opacity applies to the element and everything in it, so it multiplies down the tree
+        style.opacity = 1.0;
+
+        for (name, value) in presentation_attributes(node) {
+            match name {
+                "fill" => {
+                    if let Some(paint) = parse_paint(value) {
+                        style.fill = paint;
+                    }
+                }
+                "fill-rule" => {
+                    style.fill_rule = match value.trim() {
+                        "evenodd" => FillRule::EvenOdd,
+                        _ => FillRule::NonZero,
+                    }
+                }
+                "fill-opacity" => style.fill_opacity = parse_number(value).unwrap_or(1.0),
+                "stroke" => {
+                    if let Some(paint) = parse_paint(value) {
+                        style.stroke = paint;
+                    }
+                }
+                "stroke-width" => {
+                    style.stroke_width = svgtypes::Length::from_str(value)
+                        .map(|length| length.number as f32)
+                        .unwrap_or(1.0)
+                }
+                "stroke-opacity" => style.stroke_opacity = parse_number(value).unwrap_or(1.0),
+                "opacity" => style.opacity = parse_number(value).unwrap_or(1.0),
+                _ => {}
+            }
+        }
+
+        style.opacity *= self.opacity;
+        style
+    }
+
+    fn fill_colour(&self) -> Option<Colour> {
+        self.fill.map(|mut colour| {
+            colour.w *= self.fill_opacity * self.opacity;
+            colour
+        })
+    }
+
+    /// The stroke of a shape drawn with `transform`, in the units of the baked mesh
+    fn stroke(&self, transform: Affine2) -> Option<Stroke> {
+        let mut colour = self.stroke?;
+        colour.w *= self.stroke_opacity * self.opacity;
+
+        // The transform is baked into the mesh, so the width has to be scaled to match
+        let scale = transform.matrix2.determinant().abs().sqrt();
+        let width = self.stroke_width * scale;
+
+        (width > 0.0).then_some(Stroke { colour, width })
+    }
+}
+
+/// Import every filled shape in an SVG file
+pub fn import_svg_file(
+    path: impl AsRef<Path>,
+    options: &SvgImportOptions,
+) -> Result<Vec<Object>, SvgImportError> {
+    let text = std::fs::read_to_string(path)?;
+    import_svg(&text, options)
+}
+
+/// Import every filled shape in an SVG document
+/// Shapes that share a fill colour share a [`Material`]
+pub fn import_svg(text: &str, options: &SvgImportOptions) -> Result<Vec<Object>, SvgImportError> {
+    let document = roxmltree::Document::parse(text)?;
+    let root = document.root_element();
+    if root.tag_name().name() != "svg" {
+        return Err(SvgImportError::NotSvg);
+    }
+
+    let mut importer = Importer {
+        options,
+        objects: vec![],
+        materials: HashMap::new(),
+    };
+    // Flip the y axis so the drawing is the right way up in the scene
+    let flip = Affine2::from_scale(Vec2::new(1.0, -1.0));
+    importer.visit(root, flip, &Style::default());
+
+    Ok(importer.objects)
+}
+
+struct Importer<'a> {
+    options: &'a SvgImportOptions,
+    objects: Vec<Object>,
+    materials: HashMap<[u32; 4], Rc<RefCell<Material>>>,
+}
+
+impl Importer<'_> {
+    fn visit(&mut self, node: Node, parent_transform: Affine2, parent_style: &Style) {
+        if !node.is_element() {
+            return;
+        }
+
+        let transform = parent_transform * parse_transform(node.attribute("transform"));
+        let style = parent_style.inherit(&node);
+
+        match node.tag_name().name() {
+            "svg" | "g" => {
+                for child in node.children() {
+                    self.visit(child, transform, &style);
+                }
+            }
+            "path" | "rect" | "circle" | "ellipse" | "polygon" | "polyline" => {
+                if let Some(colour) = style.fill_colour() {
+                    let contours = shape_contours(node, transform, self.options.tolerance);
+                    let polygons = resolve_fill_rule(contours, style.fill_rule);
+                    if !polygons.is_empty() {
+                        let name = node.attribute("id").unwrap_or(node.tag_name().name());
+                        self.add_object(name, polygons, colour, style.stroke(transform));
+                    }
+                }
+            }
+            // Anything else, including <defs>, is not drawn
+            _ => {}
+        }
+    }
+
+    fn add_object(
+        &mut self,
+        name: &str,
+        mut polygons: Vec<Polygon>,
+        colour: Colour,
+        stroke: Option<Stroke>,
+    ) {
+        // Centre the mesh on its bounds so the object's translation sits in the middle of the shape
+        let (min, max) = polygons.iter().flatten().fold(
+            (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+            |(min, max), p| (min.min(*p), max.max(*p)),
+        );
+        let centre = (min + max) / 2.0;
+        for p in polygons.iter_mut().flatten() {
+            *p -= centre;
+        }
+
+        let (mesh, _, _) = Mesh::new(bm_from_polygons(&polygons), 0);
+        let material = self
+            .materials
+            .entry(colour.to_array().map(f32::to_bits))
+            .or_insert_with(|| Rc::new(RefCell::new(Material { colour })))
+            .clone();
+
+        let mut object = Object::new(mesh, centre, 0.0, Vec2::ONE, material);
+        object.name = name.to_string();
+        object.set_stroke(stroke);
+        self.objects.push(object);
+    }
+}
+
+/// Attributes and `style` declarations of a node, with declarations taking priority
+fn presentation_attributes<'a>(node: &'a Node) -> Vec<(&'a str, &'a str)> {
+    let mut attributes = node
+        .attributes()
+        .map(|a| (a.name(), a.value()))
+        .collect::<Vec<_>>();
+
+    if let Some(style) = node.attribute("style") {
+        attributes.extend(style.split(';').filter_map(|declaration| {
+            let (name, value) = declaration.split_once(':')?;
+            Some((name.trim(), value.trim()))
+        }));
+    }
+
+    attributes
+}
+
+fn colour_from_svg(c: svgtypes::Color) -> Colour {
+    Colour::new(
+        c.red as f32 / 255.0,
+        c.green as f32 / 255.0,
+        c.blue as f32 / 255.0,
+        c.alpha as f32 / 255.0,
+    )
+}
+
+/// Parse a fill or stroke paint
+/// Returns `Some(None)` for `none`, and `None` for paints that cannot be represented by a colour
+fn parse_paint(value: &str) -> Option<Option<Colour>> {
+    match Paint::from_str(value) {
+        Ok(Paint::None) => Some(None),
+        Ok(Paint::Color(c)) => Some(Some(colour_from_svg(c))),
+        Ok(Paint::FuncIRI(_, Some(svgtypes::PaintFallback::Color(c)))) => {
+            Some(Some(colour_from_svg(c)))
+        }
+        _ => None,
+    }
+}
+
+fn parse_number(value: &str) -> Option<f32> {
+    svgtypes::Number::from_str(value).ok().map(|n| n.0 as f32)
+}
+
+fn number_attribute(node: Node, name: &str) -> f32 {
+    node.attribute(name)
+        .and_then(|value| svgtypes::Length::from_str(value).ok())
+        .map(|length| length.number as f32)
+        .unwrap_or(0.0)
+}
+
+fn parse_transform(value: Option<&str>) -> Affine2 {
+    value
+        .and_then(|value| svgtypes::Transform::from_str(value).ok())
+        .map(|t| Affine2::from_cols_array(&[t.a, t.b, t.c, t.d, t.e, t.f].map(|v| v as f32)))
+        .unwrap_or(Affine2::IDENTITY)
+}
+
+/// Builds flattened contours from path commands, transforming points as it goes
+struct ContourBuilder {
+    transform: Affine2,
+    tolerance: f32,
+    contours: Vec<Polygon>,
+    current: Polygon,
+    /// Untransformed position of the pen, for curves that start from it
+    last: Vec2,
+}
+
+impl ContourBuilder {
+    fn new(transform: Affine2, tolerance: f32) -> Self {
+        Self {
+            transform,
+            tolerance,
+            contours: vec![],
+            current: vec![],
+            last: Vec2::ZERO,
+        }
+    }
+
+    fn move_to(&mut self, p: Vec2) {
+        self.close();
+        self.current.push(self.transform.transform_point2(p));
+        self.last = p;
+    }
+
+    fn line_to(&mut self, p: Vec2) {
+        self.current.push(self.transform.transform_point2(p));
+        self.last = p;
+    }
+
+    fn quadratic_to(&mut self, ctrl: Vec2, to: Vec2) {
+        // An affine transform maps a curve onto a curve, so flatten in scene units
+        let [from, ctrl, end] = [self.last, ctrl, to].map(|p| self.lyon_point(p));
+        let segment = QuadraticBezierSegment {
+            from,
+            ctrl,
+            to: end,
+        };
+        self.current.extend(
+            segment
+                .flattened(self.tolerance)
+                .map(|p| Vec2::new(p.x, p.y)),
+        );
+        self.last = to;
+    }
+
+    fn cubic_to(&mut self, ctrl1: Vec2, ctrl2: Vec2, to: Vec2) {
+        let [from, ctrl1, ctrl2, end] = [self.last, ctrl1, ctrl2, to].map(|p| self.lyon_point(p));
+        let segment = CubicBezierSegment {
+            from,
+            ctrl1,
+            ctrl2,
+            to: end,
+        };
+        self.current.extend(
+            segment
+                .flattened(self.tolerance)
+                .map(|p| Vec2::new(p.x, p.y)),
+        );
+        self.last = to;
+    }
+
+    fn lyon_point(&self, p: Vec2) -> lyon::math::Point {
+        let p = self.transform.transform_point2(p);
+        point(p.x, p.y)
+    }
+
+    /// Finish the current contour, dropping a repeated closing point
+    fn close(&mut self) {
+        let mut contour = std::mem::take(&mut self.current);
+        if contour.len() > 1 && contour.first() == contour.last() {
+            contour.pop();
+        }
+        if contour.len() >= 3 {
+            self.contours.push(contour);
+        }
+    }
+
+    fn finish(mut self) -> Vec<Polygon> {
+        self.close();
+        self.contours
+    }
+
+    /// Add an axis-aligned ellipse, or a quarter of one per corner of a rounded rectangle
+    fn ellipse(&mut self, centre: Vec2, radius: Vec2) {
+        let k = radius * KAPPA;
+        let [right, top, left, bottom] = [
+            Vec2::new(radius.x, 0.0),
+            Vec2::new(0.0, radius.y),
+            Vec2::new(-radius.x, 0.0),
+            Vec2::new(0.0, -radius.y),
+        ]
+        .map(|offset| centre + offset);
+
+        self.move_to(right);
+        self.cubic_to(right + Vec2::new(0.0, k.y), top + Vec2::new(k.x, 0.0), top);
+        self.cubic_to(top - Vec2::new(k.x, 0.0), left + Vec2::new(0.0, k.y), left);
+        self.cubic_to(
+            left - Vec2::new(0.0, k.y),
+            bottom - Vec2::new(k.x, 0.0),
+            bottom,
+        );
+        self.cubic_to(
+            bottom + Vec2::new(k.x, 0.0),
+            right - Vec2::new(0.0, k.y),
+            right,
+        );
+        self.close();
+    }
+}
+
+/// Flattened, transformed contours of a shape element
+fn shape_contours(node: Node, transform: Affine2, tolerance: f32) -> Vec<Polygon> {
+    let mut builder = ContourBuilder::new(transform, tolerance);
+
+    match node.tag_name().name() {
+        "path" => {
+            let data = node.attribute("d").unwrap_or("");
+            for segment in SimplifyingPathParser::from(data) {
+                // Stop at the first error, as SVG renderers do, keeping what was parsed so far
+                let Ok(segment) = segment else { break };
+                let p = |x: f64, y: f64| DVec2::new(x, y).as_vec2();
+                match segment {
+                    SimplePathSegment::MoveTo { x, y } => builder.move_to(p(x, y)),
+                    SimplePathSegment::LineTo { x, y } => builder.line_to(p(x, y)),
+                    SimplePathSegment::Quadratic { x1, y1, x, y } => {
+                        builder.quadratic_to(p(x1, y1), p(x, y))
+                    }
+                    SimplePathSegment::CurveTo {
+                        x1,
+                        y1,
+                        x2,
+                        y2,
+                        x,
+                        y,
+                    } => builder.cubic_to(p(x1, y1), p(x2, y2), p(x, y)),
+                    SimplePathSegment::ClosePath => builder.close(),
+                }
+            }
+        }
+        "rect" => {
+            let origin = Vec2::new(number_attribute(node, "x"), number_attribute(node, "y"));
+            let size = Vec2::new(
+                number_attribute(node, "width"),
+                number_attribute(node, "height"),
+            );
+            // A missing rx or ry takes the value of the other one
+            let (rx, ry) = match (node.attribute("rx"), node.attribute("ry")) {
+                (None, None) => (0.0, 0.0),
+                (Some(_), None) => (number_attribute(node, "rx"), number_attribute(node, "rx")),
+                (None, Some(_)) => (number_attribute(node, "ry"), number_attribute(node, "ry")),
+                (Some(_), Some(_)) => (number_attribute(node, "rx"), number_attribute(node, "ry")),
+            };
+            let radius = Vec2::new(rx, ry).min(size / 2.0).max(Vec2::ZERO);
+
+            if size.x > 0.0 && size.y > 0.0 {
+                rounded_rect(&mut builder, origin, size, radius);
+            }
+        }
+        "circle" => {
+            let r = number_attribute(node, "r");
+            if r > 0.0 {
+                let centre = Vec2::new(number_attribute(node, "cx"), number_attribute(node, "cy"));
+                builder.ellipse(centre, Vec2::splat(r));
+            }
+        }
+        "ellipse" => {
+            let radius = Vec2::new(number_attribute(node, "rx"), number_attribute(node, "ry"));
+            if radius.x > 0.0 && radius.y > 0.0 {
+                let centre = Vec2::new(number_attribute(node, "cx"), number_attribute(node, "cy"));
+                builder.ellipse(centre, radius);
+            }
+        }
+        "polygon" | "polyline" => {
+            let points = node.attribute("points").unwrap_or("");
+            for (i, (x, y)) in PointsParser::from(points).enumerate() {
+                let p = DVec2::new(x, y).as_vec2();
+                if i == 0 {
+                    builder.move_to(p);
+                } else {
+                    builder.line_to(p);
+                }
+            }
+        }
+        _ => {}
+    }
+
+    builder.finish()
+}
+
+fn rounded_rect(builder: &mut ContourBuilder, origin: Vec2, size: Vec2, radius: Vec2) {
+    let max = origin + size;
+
+    if radius.x <= 0.0 || radius.y <= 0.0 {
+        builder.move_to(origin);
+        builder.line_to(Vec2::new(max.x, origin.y));
+        builder.line_to(max);
+        builder.line_to(Vec2::new(origin.x, max.y));
+        builder.close();
+        return;
+    }
+
+    let k = radius * KAPPA;
+    let (x0, y0, x1, y1) = (origin.x, origin.y, max.x, max.y);
+    let (rx, ry) = (radius.x, radius.y);
+
+    builder.move_to(Vec2::new(x0 + rx, y0));
+    builder.line_to(Vec2::new(x1 - rx, y0));
+    builder.cubic_to(
+        Vec2::new(x1 - rx + k.x, y0),
+        Vec2::new(x1, y0 + ry - k.y),
+        Vec2::new(x1, y0 + ry),
+    );
+    builder.line_to(Vec2::new(x1, y1 - ry));
+    builder.cubic_to(
+        Vec2::new(x1, y1 - ry + k.y),
+        Vec2::new(x1 - rx + k.x, y1),
+        Vec2::new(x1 - rx, y1),
+    );
+    builder.line_to(Vec2::new(x0 + rx, y1));
+    builder.cubic_to(
+        Vec2::new(x0 + rx - k.x, y1),
+        Vec2::new(x0, y1 - ry + k.y),
+        Vec2::new(x0, y1 - ry),
+    );
+    builder.line_to(Vec2::new(x0, y0 + ry));
+    builder.cubic_to(
+        Vec2::new(x0, y0 + ry - k.y),
+        Vec2::new(x0 + rx - k.x, y0),
+        Vec2::new(x0 + rx, y0),
+    );
+    builder.close();
+}
+
+/// Decide which contours bound filled space and which bound holes
+///
+/// Contours are assumed not to cross each other. For each contour, the fill rule is evaluated
+/// just inside and just outside of it. If only the inside is filled the contour is an outer
+/// boundary and is wound counter-clockwise, if only the outside is filled it is a hole and is
+/// wound clockwise, and if both sides agree the contour changes nothing and is dropped.
+pub fn resolve_fill_rule(contours: Vec<Polygon>, fill_rule: FillRule) -> Vec<Polygon> {
+    let filled = |winding: i32| match fill_rule {
+        FillRule::NonZero => winding != 0,
+        FillRule::EvenOdd => winding % 2 != 0,
+    };
+
+    contours
+        .iter()
+        .enumerate()
+        .filter_map(|(i, contour)| {
+            let sample = (contour[0] + contour[1]) / 2.0;
+            let own = if orient(contour.clone(), true) == *contour {
+                1
+            } else {
+                -1
+            };
+
+            let others = contours
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, other)| match fill_rule {
+                    FillRule::NonZero => winding_number(other, sample),
+                    // Even-odd only counts crossings, so direction does not matter
+                    FillRule::EvenOdd => (winding_number(other, sample) != 0) as i32,
+                })
+                .sum::<i32>();
+
+            let inside = filled(others + own);
+            let outside = filled(others);
+
+            match (inside, outside) {
+                (true, false) => Some(orient(contour.clone(), true)),
+                (false, true) => Some(orient(contour.clone(), false)),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use glam::Vec2;
+
+    use crate::{
+        data::polygon::signed_area,
+        opengl::structs::{Colour, Object},
+    };
+
+    use super::*;
+
+    fn import(text: &str) -> Vec<Object> {
+        import_svg(text, &SvgImportOptions::default()).unwrap()
+    }
+
+    /// Filled area of an object's triangulated mesh
+    fn filled_area(object: &Object) -> f32 {
+        let mesh = object.borrow_mesh();
+        mesh.indices()
+            .chunks(3)
+            .map(|tri| {
+                let [a, b, c] = [0, 1, 2].map(|i| mesh.vertices()[tri[i] as usize].pos);
+                ((b - a).perp_dot(c - a) / 2.0).abs()
+            })
+            .sum()
+    }
+
+    #[test]
+    fn imports_basic_shapes() {
+        let objects = import(include_str!("../../fixtures/svg/basic_shapes.svg"));
+        assert_eq!(objects.len(), 5);
+
+        // rect 0,0 100x50 becomes an object centred at (50, -25) after the y flip
+        let rect = &objects[0];
+        assert_eq!(rect.get_aabb().min, Vec2::new(0.0, -50.0));
+        assert_eq!(rect.get_aabb().max, Vec2::new(100.0, 0.0));
+        assert!((filled_area(rect) - 5000.0).abs() < 1e-2);
+
+        // circle r=20, flattening cuts a little off the curve
+        let circle = &objects[1];
+        let expected = std::f32::consts::PI * 400.0;
+        assert!((filled_area(circle) - expected).abs() < expected * 0.01);
+        assert!((circle.get_aabb().min - Vec2::new(180.0, -70.0)).length() < 0.1);
+
+        // ellipse rx=30 ry=10
+        let expected = std::f32::consts::PI * 300.0;
+        assert!((filled_area(&objects[2]) - expected).abs() < expected * 0.01);
+
+        // triangle polygon
+        assert!((filled_area(&objects[3]) - 50.0).abs() < 1e-2);
+
+        // rounded rect loses (4 - pi) r^2 to its corners
+        let expected = 40.0 * 20.0 - (4.0 - std::f32::consts::PI) * 25.0;
+        assert!((filled_area(&objects[4]) - expected).abs() < 1.0);
+    }
+
+    #[test]
+    fn maps_fill_colours_to_shared_materials() {
+        let objects = import(include_str!("../../fixtures/svg/basic_shapes.svg"));
+
+        assert_eq!(
+            objects[0].borrow_material().colour,
+            Colour::new(1.0, 0.0, 0.0, 1.0)
+        );
+        // The circle uses fill-opacity from a style attribute
+        assert_eq!(
+            objects[1].borrow_material().colour,
+            Colour::new(0.0, 0.0, 1.0, 0.5)
+        );
+        // The rect and the polygon are both red
+        assert!(Rc::ptr_eq(objects[0].material(), objects[3].material()));
+    }
+
+    #[test]
+    fn applies_group_transforms() {
+        let objects = import(include_str!("../../fixtures/svg/groups.svg"));
+        assert_eq!(objects.len(), 2);
+
+        // 10x10 square at the origin, translated by (100, 50) then scaled by 2
+        let aabb = objects[0].get_aabb();
+        assert!((aabb.min - Vec2::new(200.0, -120.0)).length() < 1e-3);
+        assert!((aabb.max - Vec2::new(220.0, -100.0)).length() < 1e-3);
+
+        // The second square inherits the group's fill but is rotated 90 degrees about its corner
+        let aabb = objects[1].get_aabb();
+        assert!((aabb.min - Vec2::new(180.0, -120.0)).length() < 1e-3);
+        assert!((aabb.max - Vec2::new(200.0, -100.0)).length() < 1e-3);
+        assert_eq!(
+            objects[1].borrow_material().colour,
+            Colour::new(0.0, 128.0 / 255.0, 0.0, 1.0)
+        );
+    }
+
+    #[test]
+    fn respects_fill_rules() {
+        let objects = import(include_str!("../../fixtures/svg/fill_rules.svg"));
+        assert_eq!(objects.len(), 2);
+
+        // Both squares are drawn in the same direction, so only even-odd punches the hole
+        assert!((filled_area(&objects[0]) - 10000.0).abs() < 1e-2);
+        assert!((filled_area(&objects[1]) - 7500.0).abs() < 1e-2);
+    }
+
+    #[test]
+    fn nonzero_holes_follow_direction() {
+        let objects = import(include_str!("../../fixtures/svg/holes.svg"));
+        assert_eq!(objects.len(), 1);
+
+        // Outer ring, a reversed inner square, and an island inside that hole
+        assert!((filled_area(&objects[0]) - (10000.0 - 2500.0 + 100.0)).abs() < 1e-2);
+    }
+
+    #[test]
+    fn resolves_windings() {
+        let square = |size: f32| {
+            vec![
+                Vec2::new(-size, -size),
+                Vec2::new(size, -size),
+                Vec2::new(size, size),
+                Vec2::new(-size, size),
+            ]
+        };
+
+        let polygons = resolve_fill_rule(vec![square(2.0), square(1.0)], FillRule::EvenOdd);
+        assert!(signed_area(&polygons[0]) > 0.0);
+        assert!(signed_area(&polygons[1]) < 0.0);
+
+        let polygons = resolve_fill_rule(vec![square(2.0), square(1.0)], FillRule::NonZero);
+        assert_eq!(polygons.len(), 1);
+    }
+
+    #[test]
+    fn curved_paths_are_flattened() {
+        let objects = import(include_str!("../../fixtures/svg/curves.svg"));
+        assert_eq!(objects.len(), 2);
+
+        // A semicircle of radius 50 made from an arc command
+        let expected = std::f32::consts::PI * 2500.0 / 2.0;
+        assert!((filled_area(&objects[0]) - expected).abs() < expected * 0.01);
+        assert!(objects[0].borrow_mesh().vertices().len() > 10);
+
+        // A cubic curve with a tighter tolerance gives more vertices than a looser one
+        let fine = import_svg(
+            include_str!("../../fixtures/svg/curves.svg"),
+            &SvgImportOptions { tolerance: 0.01 },
+        )
+        .unwrap();
+        assert!(fine[1].borrow_mesh().vertices().len() > objects[1].borrow_mesh().vertices().len());
+    }
+
+    #[test]
+    fn rejects_non_svg_documents() {
+        assert!(matches!(
+            import_svg("<html></html>", &SvgImportOptions::default()),
+            Err(SvgImportError::NotSvg)
+        ));
+        assert!(matches!(
+            import_svg("<svg", &SvgImportOptions::default()),
+            Err(SvgImportError::Xml(_))
+        ));
+    }
+}
@@ -1,24 +1,70 @@
 #![allow(dead_code)]
 mod data;
+mod history;
 mod input;
+mod io;
 mod opengl;
+mod operators;
+mod raster;
 mod shapes;
 mod ui;
 
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, path::PathBuf, rc::Rc};
 
 use glam::Vec2;
+use input::keymap::Keymap;
+use io::{
+    flatblend::{load_flatblend_file, ViewState},
+    svg_import::{import_svg_file, SvgImportOptions},
+};
 use opengl::{
     flat_blend_state::FlatBlendState,
+    scene::SceneData,
     structs::{Mesh, Object},
 };
 use rand::Rng;
-use shapes::square::create_square;
 
 use crate::opengl::structs::{Colour, Material};
 
 fn main() {
-    let square = create_square();
+    // A document or SVG passed on the command line replaces the demo scene
+    let path = std::env::args().nth(1).map(PathBuf::from);
+    let (scene_data, view, document_path) = match path {
+        Some(path) if path.extension().is_some_and(|ext| ext == "svg") => {
+            let objects = import_svg_file(&path, &SvgImportOptions::default())
+                .unwrap_or_else(|err| exit_with_error(&path, err));
+            (SceneData::new(objects), ViewState::default(), None)
+        }
+        Some(path) => {
+            let document =
+                load_flatblend_file(&path).unwrap_or_else(|err| exit_with_error(&path, err));
+            (document.scene_data, document.view, Some(path))
+        }
+        None => (SceneData::new(demo_scene()), ViewState::default(), None),
+    };
+
+    println!("objects: {}", scene_data.objects().len());
+
+    miniquad::start(
+        miniquad::conf::Conf::default(),
+        move |ctx: &mut miniquad::Context| {
+            Box::new(FlatBlendState::new(
+                ctx,
+                scene_data,
+                view,
+                document_path,
+                Keymap::load_user(),
+            ))
+        },
+    );
+}
+
+fn exit_with_error(path: &std::path::Path, err: impl std::fmt::Display) -> ! {
+    eprintln!("failed to open {}: {}", path.display(), err);
+    std::process::exit(1);
+}
+
+fn demo_scene() -> Vec<Object> {
     let star = shapes::star::create_star();
 
     let (mesh, _verts, _indices) = Mesh::new(star, 0);
@@ -28,7 +74,7 @@ fn main() {
 
     for y in 0..500 {
         for x in 0..500 {
-            objects.push(Object::new(
+            let mut object = Object::new(
                 mesh.clone(),
                 Vec2::new(x as f32 * 250.0, y as f32 * 250.0),
                 std::f32::consts::PI * rng.random::<f32>() * 2.0,
@@ -36,14 +82,11 @@ fn main() {
                 Rc::new(RefCell::new(Material {
                     colour: Colour::new(rng.random(), rng.random(), rng.random(), 1.0),
                 })),
-            ))
+            );
+            object.name = format!("Star {}", objects.len() + 1);
+            objects.push(object);
         }
     }
 
-    println!("objects: {}", objects.len());
-
-    miniquad::start(
-        miniquad::conf::Conf::default(),
-        |ctx: &mut miniquad::Context| Box::new(FlatBlendState::new(ctx, objects, vec![mesh])),
-    );
+    objects
 }
@@ -0,0 +1,273 @@
+use std::f32::consts::{PI, TAU};
+
+use glam::{Mat4, Vec2, Vec3};
+
+use super::{
+    frustum::AABB2D,
+    matrices::{get_ortho_matrix, get_view_matrix},
+};
+
+/// The view onto the scene
+///
+/// A plain value owned by the state and handed to whatever draws or picks with it. A world point
+/// `w` ends up `zoom * rotate(-rotation) * (w + position)` pixels from the viewport centre, so
+/// the centre of the view is at `-position` and rotation turns the camera rather than the scene.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Camera2D {
+    pub position: Vec2,
+    pub zoom: f32,
+    /// Radians anticlockwise
+    pub rotation: f32,
+    /// Viewport size in pixels
+    pub viewport: Vec2,
+}
+
+impl Camera2D {
+    pub fn new(position: Vec2, zoom: f32, rotation: f32, viewport: Vec2) -> Self {
+        Self {
+            position,
+            zoom,
+            rotation,
+            viewport,
+        }
+    }
+
+    pub fn view_matrix(&self) -> Mat4 {
+        get_view_matrix(self.position, self.zoom, self.rotation)
+    }
+
+    pub fn projection_matrix(&self) -> Mat4 {
+        get_ortho_matrix(self.viewport.x, self.viewport.y)
+    }
+
+    pub fn view_projection_matrix(&self) -> Mat4 {
+        self.projection_matrix() * self.view_matrix()
+    }
+
+    /// World point under `screen`, given in pixels from the top left
+    pub fn screen_to_world(&self, screen: Vec2) -> Vec2 {
+        let centred = Vec2::new(
+            screen.x - self.viewport.x / 2.0,
+            self.viewport.y / 2.0 - screen.y,
+        );
+        Vec2::from_angle(self.rotation).rotate(centred / self.zoom) - self.position
+    }
+
+    /// Pixels from the top left of the viewport that `world` is drawn at
+    pub fn world_to_screen(&self, world: Vec2) -> Vec2 {
+        let centred = Vec2::from_angle(-self.rotation).rotate(world + self.position) * self.zoom;
+        Vec2::new(
+            centred.x + self.viewport.x / 2.0,
+            self.viewport.y / 2.0 - centred.y,
+        )
+    }
+
+    /// Matrix taking world points to screen pixels, like [`Camera2D::world_to_screen`]
+    pub fn screen_matrix(&self) -> Mat4 {
+        Mat4::from_translation((self.viewport / 2.0).extend(0.0))
+            * Mat4::from_scale(Vec3::new(1.0, -1.0, 1.0))
+            * self.view_matrix()
+    }
+
+    /// Move the view so the world follows the mouse moving by `screen_delta` pixels
+    pub fn pan(&mut self, screen_delta: Vec2) {
+        let delta = Vec2::new(screen_delta.x, -screen_delta.y) / self.zoom;
+        self.position += Vec2::from_angle(self.rotation).rotate(delta);
+    }
+
+    /// Change the zoom, keeping the world point under `anchor` on screen in place
+    pub fn zoom_about(&mut self, zoom: f32, anchor: Vec2) {
+        let before = self.screen_to_world(anchor);
+        self.zoom = zoom;
+        self.position += self.screen_to_world(anchor) - before;
+    }
+
+    /// Turn the camera by `angle` about the viewport centre
+    pub fn rotate(&mut self, angle: f32) {
+        self.rotation += angle;
+    }
+
+    /// This camera moved and zoomed to fit `bounds`, leaving `padding` pixels around it
+    /// Bounds with no size keep the current zoom.
+    pub fn framing(&self, bounds: &AABB2D, padding: f32) -> Self {
+        // Size of the bounds along the camera's axes, which are turned when the view is
+        let size = bounds.max - bounds.min;
+        let (sin, cos) = self.rotation.sin_cos();
+        let (sin, cos) = (sin.abs(), cos.abs());
+        let turned = Vec2::new(cos * size.x + sin * size.y, sin * size.x + cos * size.y);
+
+        let available = (self.viewport - 2.0 * padding).max(Vec2::ONE);
+        let zoom = (available / turned).min_element();
+
+        Self {
+            position: -(bounds.min + bounds.max) / 2.0,
+            zoom: if zoom.is_finite() { zoom } else { self.zoom },
+            ..*self
+        }
+    }
+}
+
+/// The camera moving from one view to another over a fixed time
+///
+/// Zoom is eased in log space like wheel zoom, and rotation takes the short way round.
+#[derive(Debug, Clone, Copy)]
+pub struct CameraTransition {
+    from: Camera2D,
+    to: Camera2D,
+    duration: f32,
+    elapsed: f32,
+}
+
+impl CameraTransition {
+    pub fn new(from: Camera2D, to: Camera2D, duration: f32) -> Self {
+        Self {
+            from,
+            to,
+            duration,
+            elapsed: 0.0,
+        }
+    }
+
+    /// Move `camera` on by `dt` seconds, returning whether the transition has finished
+    /// The camera keeps its own viewport, in case the window was resized on the way.
+    pub fn advance(&mut self, camera: &mut Camera2D, dt: f32) -> bool {
+        self.elapsed += dt;
+        let t = if self.duration > 0.0 {
+            (self.elapsed / self.duration).min(1.0)
+        } else {
+            1.0
+        };
+        // Smoothstep, so the camera eases in and out
+        let t = t * t * (3.0 - 2.0 * t);
+
+        let (from, to) = (self.from, self.to);
+        camera.position = from.position.lerp(to.position, t);
+        camera.zoom = (from.zoom.ln() + (to.zoom.ln() - from.zoom.ln()) * t).exp();
+        let turn = (to.rotation - from.rotation + PI).rem_euclid(TAU) - PI;
+        camera.rotation = from.rotation + turn * t;
+
+        t >= 1.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec4;
+
+    use super::*;
+
+    fn cameras() -> [Camera2D; 3] {
+        let viewport = Vec2::new(800.0, 600.0);
+        [
+            Camera2D::new(Vec2::ZERO, 1.0, 0.0, viewport),
+            Camera2D::new(Vec2::new(-30.0, 45.0), 2.5, 0.7, viewport),
+            Camera2D::new(Vec2::new(1200.0, -80.0), 0.01, -2.0, viewport),
+        ]
+    }
+
+    #[test]
+    fn screen_and_world_conversions_match_the_matrices() {
+        for camera in cameras() {
+            let tolerance = 1e-3 / camera.zoom.min(1.0);
+            for screen in [Vec2::ZERO, Vec2::new(400.0, 300.0), Vec2::new(650.0, 120.0)] {
+                let world = camera.screen_to_world(screen);
+                assert!(camera.world_to_screen(world).abs_diff_eq(screen, 1e-2));
+                let matrix = camera.screen_matrix();
+                assert!(matrix
+                    .transform_point3(world.extend(0.0))
+                    .truncate()
+                    .abs_diff_eq(screen, 1e-2));
+
+                // The matrices take the world point to the same place in clip space
+                let clip = camera.view_projection_matrix() * Vec4::new(world.x, world.y, 0.0, 1.0);
+                let ndc = Vec2::new(
+                    screen.x / camera.viewport.x * 2.0 - 1.0,
+                    1.0 - screen.y / camera.viewport.y * 2.0,
+                );
+                assert!(
+                    clip.truncate().truncate().abs_diff_eq(ndc, 1e-4),
+                    "{clip} != {ndc}"
+                );
+            }
+            assert!(camera
+                .screen_to_world(camera.viewport / 2.0)
+                .abs_diff_eq(-camera.position, tolerance));
+        }
+    }
+
+    #[test]
+    fn framing_fits_bounds_inside_the_padding() {
+        let bounds = AABB2D::new(Vec2::new(100.0, -50.0), Vec2::new(300.0, 250.0));
+        let padding = 20.0;
+
+        for camera in cameras() {
+            let framed = camera.framing(&bounds, padding);
+            assert!(framed
+                .screen_to_world(framed.viewport / 2.0)
+                .abs_diff_eq(Vec2::new(200.0, 100.0), 1e-3));
+
+            // Every corner lands inside the padded viewport, and one side touches the padding
+            let corners = [
+                bounds.min,
+                Vec2::new(bounds.max.x, bounds.min.y),
+                bounds.max,
+                Vec2::new(bounds.min.x, bounds.max.y),
+            ]
+            .map(|corner| framed.world_to_screen(corner));
+            let min = corners.iter().fold(corners[0], |min, c| min.min(*c));
+            let max = corners.iter().fold(corners[0], |max, c| max.max(*c));
+            assert!(min.cmpge(Vec2::splat(padding - 1e-2)).all());
+            assert!(max.cmple(framed.viewport - padding + 1e-2).all());
+            let gap = min.min(framed.viewport - max).min_element();
+            assert!((gap - padding).abs() < 1e-2, "{gap}");
+        }
+
+        // A single point has no size to zoom to
+        let camera = cameras()[1];
+        let point = AABB2D::new(Vec2::ONE, Vec2::ONE);
+        assert_eq!(camera.framing(&point, padding).zoom, camera.zoom);
+    }
+
+    #[test]
+    fn transitions_end_exactly_on_the_target() {
+        let [from, _, to] = cameras();
+        let mut transition = CameraTransition::new(from, to, 0.25);
+        let mut camera = from;
+
+        let mut steps = 0;
+        while !transition.advance(&mut camera, 0.1) {
+            assert!(camera.zoom < from.zoom && camera.zoom > to.zoom);
+            steps += 1;
+        }
+        assert_eq!(steps, 2);
+        assert_eq!(camera.position, to.position);
+        assert!((camera.zoom - to.zoom).abs() < 1e-6);
+        assert!((camera.rotation - to.rotation).abs() < 1e-6);
+
+        // Rotation turns the short way from just under a full turn to just over none
+        let from = Camera2D::new(Vec2::ZERO, 1.0, TAU - 0.1, Vec2::ONE);
+        let to = Camera2D {
+            rotation: 0.1,
+            ..from
+        };
+        let mut camera = from;
+        CameraTransition::new(from, to, 1.0).advance(&mut camera, 0.5);
+        assert!((camera.rotation - TAU).abs() < 1e-5);
+    }
+
+    #[test]
+    fn zoom_and_pan_keep_the_point_under_the_cursor() {
+        let anchor = Vec2::new(650.0, 120.0);
+        for mut camera in cameras() {
+            let tolerance = 1e-3 / camera.zoom.min(1.0);
+            let world = camera.screen_to_world(anchor);
+
+            camera.zoom_about(camera.zoom * 3.0, anchor);
+            assert!(camera.screen_to_world(anchor).abs_diff_eq(world, tolerance));
+
+            let moved = anchor + Vec2::new(-40.0, 25.0);
+            camera.pan(moved - anchor);
+            assert!(camera.screen_to_world(moved).abs_diff_eq(world, tolerance));
+        }
+    }
+}
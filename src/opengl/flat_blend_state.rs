@@ -1,88 +1,625 @@
-use std::{
-    cell::RefCell,
-    collections::HashMap,
-    rc::Rc,
-    sync::{Arc, Mutex},
-};
+use std::{collections::BTreeMap, io::ErrorKind, path::PathBuf};
 
 use egui_miniquad as egui_mq;
-use glam::{Mat4, Vec2};
+use glam::Vec2;
 use miniquad::*;
 
-use crate::opengl::matrices::{get_view_matrix, screen_to_world};
+use crate::data::mesh::bm_select::MeshSelectMode;
+use crate::history::{Command, History};
+use crate::input::{
+    input_state::InputState,
+    keymap::{Action, ActionContext, Input, Keymap},
+};
+use crate::io::flatblend::{
+    save_flatblend_file, save_new_flatblend_file, CameraState, FlatBlendError, ViewState,
+};
+use crate::operators::edit_mesh::EditMode;
+use crate::operators::pen::{snap_angle, PenPress, PenTool};
+use crate::operators::select::{SelectGesture, SelectMode, SelectShape};
+use crate::operators::snap::{snap, SnapIgnore, SnapSettings, SnapTarget};
+use crate::operators::transform::{AxisConstraint, TransformMode, TransformOperator};
+use crate::raster::{render_scene, RasterOptions};
+use crate::ui::grid::GridUI;
+use crate::ui::modifiers::ModifiersUI;
 use crate::ui::objects::ObjectsUI;
+use crate::ui::pen::PenUI;
+use crate::ui::properties::PropertiesUI;
+use crate::ui::selection::SelectionUI;
+use crate::ui::snap::SnapUI;
 use crate::ui::viewport::ViewportUI;
 
 use super::{
-    matrices::get_ortho_matrix,
+    camera::{Camera2D, CameraTransition},
+    frustum::AABB2D,
+    pipelines::grid::GridSettings,
     render_context::RenderContext,
-    scene::ObjectKey,
-    structs::{Mesh, Object},
+    scene::SceneData,
+    zoom::{SmoothZoom, ZoomSettings},
 };
 
+/// Name documents are saved under when they were not loaded from a file, numbered if taken
+const UNTITLED_NAME: &str = "untitled";
+/// Most numbered untitled names tried before giving up
+const MAX_UNTITLED: u32 = 1000;
+/// Where the viewport is rendered to
+const RENDER_PATH: &str = "render.png";
+/// Pixels left around objects the view is fitted to
+const FRAME_PADDING: f32 = 40.0;
+/// Seconds the camera takes to move to a framed or bookmarked view
+const CAMERA_TRANSITION_DURATION: f32 = 0.25;
+
+/// A drag started in the viewport, which lasts until `input` is released
+struct ViewportDrag {
+    input: Input,
+    kind: DragKind,
+}
+
+enum DragKind {
+    Pan,
+    RotateView,
+    Select(SelectGesture),
+}
+
 pub struct FlatBlendState {
     render_context: RenderContext,
-    projection_matrix: Arc<Mutex<Mat4>>,
-    view_matrix: Arc<Mutex<Mat4>>,
-    zoom: Arc<Mutex<f32>>,
-    position: Arc<Mutex<Vec2>>,
-    mouse_state: HashMap<MouseButton, bool>,
-    last_mouse_position: Vec2,
+    camera: Camera2D,
+    /// Cameras stored by number, saved with the document
+    bookmarks: BTreeMap<u8, CameraState>,
+    /// How the grid is drawn, saved with the document
+    grid: GridSettings,
+    zoom_settings: ZoomSettings,
+    smooth_zoom: SmoothZoom,
+    /// Camera moving to a framed or bookmarked view
+    camera_transition: Option<CameraTransition>,
+    /// Time of the last update, to step camera animations by
+    last_update: f64,
+    input: InputState,
+    keymap: Keymap,
     egui_mq: egui_mq::EguiMq,
+    /// File the document is saved to
+    document_path: Option<PathBuf>,
+    /// Mesh being edited, if not working with whole objects
+    edit_mode: Option<EditMode>,
+    /// Grab, rotate or scale in progress
+    transform_operator: Option<TransformOperator>,
+    /// Polygon being drawn
+    pen: Option<PenTool>,
+    /// Pan, box or lasso in progress
+    drag: Option<ViewportDrag>,
+    snap_settings: SnapSettings,
+    /// What the transform or pen is snapped to, while Ctrl is held
+    snap_target: Option<SnapTarget>,
+    objects_ui: ObjectsUI,
+    history: History,
 }
 
 impl FlatBlendState {
     pub fn new(
         ctx: &mut Context,
-        objects: Vec<Object>,
-        meshes: Vec<Rc<RefCell<Mesh>>>,
+        scene_data: SceneData,
+        view: ViewState,
+        document_path: Option<PathBuf>,
+        keymap: Keymap,
     ) -> FlatBlendState {
         ctx.set_cull_face(CullFace::Nothing);
-        let zoom = Arc::new(Mutex::new(1.0));
-
-        let (width, height) = ctx.screen_size();
-        let position = Arc::new(Mutex::new(Vec2::new(0.0, 0.0)));
-
-        let projection_matrix = Arc::new(Mutex::new(get_ortho_matrix(width, height)));
-        let view_matrix = Arc::new(Mutex::new(get_view_matrix(
-            *(position.lock().unwrap()),
-            *(zoom.lock().unwrap()),
-        )));
-
-        let render_context = RenderContext::new(
-            ctx,
-            projection_matrix.clone(),
-            view_matrix.clone(),
-            zoom.clone(),
-            position.clone(),
-            objects,
-            meshes,
-            width as u32,
-            height as u32,
+        let zoom_settings = ZoomSettings::default();
+        let camera = Camera2D::new(
+            view.camera.position,
+            zoom_settings.clamp(view.camera.zoom),
+            view.camera.rotation,
+            ctx.screen_size().into(),
         );
 
+        let render_context = RenderContext::new(ctx, scene_data, &camera);
+
         FlatBlendState {
             render_context,
-            projection_matrix,
-            view_matrix,
-            position,
-            mouse_state: HashMap::new(),
-            last_mouse_position: Vec2::new(0.0, 0.0),
+            camera,
+            bookmarks: view.bookmarks,
+            grid: view.grid,
+            zoom_settings,
+            smooth_zoom: SmoothZoom::new(),
+            camera_transition: None,
+            last_update: date::now(),
+            input: InputState::new(),
+            keymap,
             egui_mq: egui_mq::EguiMq::new(ctx),
-            zoom,
+            document_path,
+            edit_mode: None,
+            transform_operator: None,
+            pen: None,
+            drag: None,
+            snap_settings: SnapSettings::default(),
+            snap_target: None,
+            objects_ui: ObjectsUI::new(),
+            history: History::new(),
+        }
+    }
+
+    /// Start grabbing, rotating or scaling the selection from the mouse position
+    /// In edit mode this moves the selected vertices rather than the selected objects.
+    fn start_transform(&mut self, ctx: &mut Context, mode: TransformMode) {
+        let mouse = self.input.mouse_world();
+        let scene_data = &mut self.render_context.scene_data;
+        self.transform_operator = match self.edit_mode {
+            Some(edit_mode) => {
+                TransformOperator::start_vertices(scene_data, edit_mode.object(), mode, mouse)
+            }
+            None => TransformOperator::start(scene_data, mode, mouse),
+        };
+        if let Some(operator) = &mut self.transform_operator {
+            operator.set_snap(self.input.ctrl());
+            self.render_context.sync_meshes(ctx);
+        }
+    }
+
+    /// Snap a running transform or the pen while Ctrl is held
+    fn update_snap(&mut self, ctx: &mut Context) {
+        if let Some(operator) = &mut self.transform_operator {
+            operator.set_snap(self.input.ctrl());
+            self.apply_transform(ctx);
+        }
+        if self.pen.is_some() {
+            self.move_pen();
+        }
+    }
+
+    /// Handle a key or click while a transform is running
+    /// Keys without an action type the value the transform is set to.
+    fn transform_input(&mut self, ctx: &mut Context, input: Input) {
+        let action = self
+            .keymap
+            .action(ActionContext::Transform, input, self.input.mods());
+        let Some(operator) = &mut self.transform_operator else {
+            return;
+        };
+
+        match (action, input) {
+            (Some(Action::Confirm), _) => self.confirm_transform(ctx),
+            (Some(Action::Cancel), _) => self.cancel_transform(ctx),
+            (Some(Action::ConstrainX), _) => operator.toggle_constraint(AxisConstraint::X),
+            (Some(Action::ConstrainY), _) => operator.toggle_constraint(AxisConstraint::Y),
+            (_, Input::Key(keycode)) => type_key(operator, keycode),
+            _ => {}
+        }
+
+        self.apply_transform(ctx);
+    }
+
+    /// The action `input` triggers in the viewport
+    ///
+    /// While drawing, pen actions come first and only view actions are left to fall back to.
+    /// In edit mode, edit actions come first.
+    fn viewport_action(&self, input: Input) -> Option<Action> {
+        let mods = self.input.mods();
+        let viewport = || self.keymap.action(ActionContext::Viewport, input, mods);
+
+        if self.pen.is_some() {
+            self.keymap
+                .action(ActionContext::Pen, input, mods)
+                .or_else(|| viewport().filter(|action| action.moves_view()))
+        } else if self.edit_mode.is_some() {
+            self.keymap
+                .action(ActionContext::Edit, input, mods)
+                .or_else(viewport)
+        } else {
+            viewport()
+        }
+    }
+
+    /// Run a viewport action triggered by `input`
+    fn run_action(&mut self, ctx: &mut Context, action: Action, input: Input) {
+        match action {
+            Action::Pan => self.start_drag(input, DragKind::Pan),
+            Action::RotateView => self.start_drag(input, DragKind::RotateView),
+            Action::FrameSelected => {
+                let scene_data = &self.render_context.scene_data;
+                let bounds = match self.edit_mode {
+                    Some(edit_mode) => edit_mode.selected_bounds(scene_data),
+                    None => scene_data.bounds_of(
+                        scene_data
+                            .object_order()
+                            .iter()
+                            .copied()
+                            .filter(|&key| scene_data.objects()[key].selected),
+                    ),
+                };
+                if let Some(bounds) = bounds {
+                    self.frame(&bounds);
+                }
+            }
+            Action::FrameAll => {
+                let scene_data = &self.render_context.scene_data;
+                if let Some(bounds) =
+                    scene_data.bounds_of(scene_data.object_order().iter().copied())
+                {
+                    self.frame(&bounds);
+                }
+            }
+            Action::StoreBookmark => {
+                if let Some(slot) = bookmark_slot(input) {
+                    self.bookmarks.insert(slot, self.camera_state());
+                }
+            }
+            Action::RecallBookmark => {
+                if let Some(bookmark) =
+                    bookmark_slot(input).and_then(|slot| self.bookmarks.get(&slot))
+                {
+                    let target = Camera2D::new(
+                        bookmark.position,
+                        self.zoom_settings.clamp(bookmark.zoom),
+                        bookmark.rotation,
+                        self.camera.viewport,
+                    );
+                    self.move_camera_to(target);
+                }
+            }
+            Action::BoxSelect | Action::LassoSelect => {
+                let shape = if action == Action::BoxSelect {
+                    SelectShape::Box
+                } else {
+                    SelectShape::Lasso
+                };
+                let gesture = SelectGesture::start(shape, self.input.mouse_position());
+                self.start_drag(input, DragKind::Select(gesture));
+            }
+            Action::Grab => self.start_transform(ctx, TransformMode::Grab),
+            Action::Rotate => self.start_transform(ctx, TransformMode::Rotate),
+            Action::Scale => self.start_transform(ctx, TransformMode::Scale),
+            Action::Delete => self.delete_selected(ctx),
+            Action::ToggleEditMode => {
+                self.edit_mode = match self.edit_mode {
+                    Some(_) => None,
+                    None => {
+                        EditMode::enter(&mut self.render_context.scene_data, MeshSelectMode::Vertex)
+                    }
+                };
+                self.render_context.sync_meshes(ctx);
+            }
+            Action::Pen => {
+                self.edit_mode = None;
+                self.pen = Some(PenTool::new(self.input.mouse_world()));
+            }
+            Action::AddPoint => {
+                let point = self.pen_point();
+                let pixel = 1.0 / self.camera.zoom;
+                if let Some(pen) = &mut self.pen {
+                    if pen.press(point, pixel) == PenPress::Closed {
+                        self.finish_pen(ctx);
+                    }
+                }
+            }
+            Action::ClosePath => self.finish_pen(ctx),
+            Action::RemovePoint => {
+                if let Some(pen) = &mut self.pen {
+                    pen.remove_last_point();
+                }
+            }
+            Action::CancelPath => self.pen = None,
+            Action::VertexSelectMode => self.set_select_mode(ctx, MeshSelectMode::Vertex),
+            Action::EdgeSelectMode => self.set_select_mode(ctx, MeshSelectMode::Edge),
+            Action::FaceSelectMode => self.set_select_mode(ctx, MeshSelectMode::Face),
+            Action::Undo => self.undo(ctx),
+            Action::Redo => self.redo(ctx),
+            Action::Save => self.save(),
+            Action::RenderImage => self.render_to_image(),
+            // Scrolling is handled by the wheel event, and the rest only apply while transforming
+            Action::Zoom
+            | Action::ScrollPan
+            | Action::Confirm
+            | Action::Cancel
+            | Action::ConstrainX
+            | Action::ConstrainY => {}
+        }
+    }
+
+    fn start_drag(&mut self, input: Input, kind: DragKind) {
+        if self.drag.is_none() {
+            self.drag = Some(ViewportDrag { input, kind });
+        }
+    }
+
+    /// Finish the drag if `input` is what started it
+    fn end_drag(&mut self, ctx: &mut Context, input: Input) {
+        if self.drag.as_ref().map(|drag| drag.input) != Some(input) {
+            return;
+        }
+        if let Some(ViewportDrag {
+            kind: DragKind::Select(gesture),
+            ..
+        }) = self.drag.take()
+        {
+            self.finish_select(ctx, gesture);
+        }
+    }
+
+    fn pan(&mut self, screen_delta: Vec2) {
+        self.camera_transition = None;
+        self.camera.pan(screen_delta);
+        self.camera_changed();
+    }
+
+    /// Turn the view by how far the mouse moved around the middle of the viewport
+    fn rotate_view(&mut self) {
+        let centre = self.camera.viewport / 2.0;
+        let to = self.input.mouse_position() - centre;
+        let from = to - self.input.mouse_delta();
+        if from == Vec2::ZERO || to == Vec2::ZERO {
+            return;
+        }
+
+        self.camera_transition = None;
+
+        // Screen y points down, so the angle is positive for a clockwise drag, which has to turn
+        // the camera anticlockwise for the scene to follow the mouse
+        self.camera.rotate(from.angle_between(to));
+        self.camera_changed();
+    }
+
+    /// Fit the view to `bounds`
+    fn frame(&mut self, bounds: &AABB2D) {
+        let mut target = self.camera.framing(bounds, FRAME_PADDING);
+        target.zoom = self.zoom_settings.clamp(target.zoom);
+        self.move_camera_to(target);
+    }
+
+    /// Animate the camera to `target`, taking over from any zoom still running
+    fn move_camera_to(&mut self, target: Camera2D) {
+        self.smooth_zoom.stop();
+        self.camera_transition = Some(CameraTransition::new(
+            self.camera,
+            target,
+            CAMERA_TRANSITION_DURATION,
+        ));
+    }
+
+    fn set_select_mode(&mut self, ctx: &mut Context, select_mode: MeshSelectMode) {
+        if let Some(edit_mode) = &mut self.edit_mode {
+            edit_mode.set_select_mode(&mut self.render_context.scene_data, select_mode);
+            self.render_context.sync_meshes(ctx);
+        }
+    }
+
+    /// Where the pen would place a point, at even angles from the last point with Shift and
+    /// snapped with Ctrl
+    fn pen_point(&mut self) -> Vec2 {
+        let previous = self
+            .pen
+            .as_ref()
+            .and_then(|pen| pen.points().last())
+            .map(|point| point.pos);
+        let mut point = self.input.mouse_world();
+        if let Some(previous) = previous.filter(|_| self.input.shift()) {
+            point = snap_angle(point, previous);
+        }
+
+        self.snap_target = if self.input.ctrl() {
+            snap(
+                &self.render_context.scene_data,
+                &self.camera,
+                &self.grid,
+                &self.snap_settings,
+                point,
+                &SnapIgnore::default(),
+            )
+        } else {
+            None
+        };
+        self.snap_target.map_or(point, |target| target.point)
+    }
+
+    /// Move the pen to where it would place a point
+    fn move_pen(&mut self) {
+        let point = self.pen_point();
+        if let Some(pen) = &mut self.pen {
+            pen.move_to(point, 1.0 / self.camera.zoom);
+        }
+    }
+
+    /// Make an object from the pen's path and select it, if the path encloses anything
+    fn finish_pen(&mut self, ctx: &mut Context) {
+        let Some(object) = self.pen.as_ref().and_then(PenTool::finish) else {
+            return;
+        };
+        self.pen = None;
+
+        let scene_data = &mut self.render_context.scene_data;
+        let key = self.history.add_object(scene_data, object);
+        for (_, object) in scene_data.objects_mut().iter_mut() {
+            object.selected = false;
+        }
+        scene_data.objects_mut()[key].selected = true;
+        scene_data.set_active_object(Some(key));
+        self.render_context.sync_meshes(ctx);
+    }
+
+    /// Leave edit mode if the object being edited is gone
+    fn check_edit_mode(&mut self) {
+        let scene_data = &self.render_context.scene_data;
+        if !self
+            .edit_mode
+            .is_some_and(|edit_mode| edit_mode.is_valid(scene_data))
+        {
+            self.edit_mode = None;
+        }
+    }
+
+    fn apply_transform(&mut self, ctx: &mut Context) {
+        let Some(operator) = &mut self.transform_operator else {
+            return;
+        };
+        let scene_data = &mut self.render_context.scene_data;
+        self.snap_target = match operator.grab_point() {
+            Some(point) if self.input.ctrl() => snap(
+                scene_data,
+                &self.camera,
+                &self.grid,
+                &self.snap_settings,
+                point,
+                &operator.snap_ignore(),
+            ),
+            _ => None,
+        };
+        operator.set_snap_target(self.snap_target.map(|target| target.point));
+        operator.apply(scene_data);
+        self.render_context.sync_meshes(ctx);
+    }
+
+    fn confirm_transform(&mut self, ctx: &mut Context) {
+        self.apply_transform(ctx);
+        if let Some(operator) = self.transform_operator.take() {
+            let command = operator.command(&self.render_context.scene_data);
+            self.history.push(command);
+        }
+    }
+
+    fn cancel_transform(&mut self, ctx: &mut Context) {
+        if let Some(operator) = self.transform_operator.take() {
+            operator.cancel(&mut self.render_context.scene_data);
+            self.render_context.sync_meshes(ctx);
+        }
+    }
+
+    /// Finish a box or lasso, or treat it as a click if the mouse hardly moved
+    fn finish_select(&mut self, ctx: &mut Context, gesture: SelectGesture) {
+        if let Some(edit_mode) = self.edit_mode {
+            let scene_data = &mut self.render_context.scene_data;
+            let camera = &self.camera;
+            if gesture.is_drag() {
+                let mode = SelectMode::from_modifiers(self.input.shift(), self.input.ctrl());
+                edit_mode.select_inside(scene_data, camera, |point| gesture.contains(point), mode);
+            } else {
+                // Shift toggles what was clicked, like clicking on objects
+                let mode = if self.input.shift() {
+                    SelectMode::Invert
+                } else {
+                    SelectMode::Set
+                };
+                edit_mode.select_at(scene_data, camera, self.input.mouse_position(), mode);
+            }
+            self.render_context.sync_meshes(ctx);
+        } else if gesture.is_drag() {
+            let mode = SelectMode::from_modifiers(self.input.shift(), self.input.ctrl());
+            let camera = self.camera;
+            gesture.finish(&mut self.render_context.scene_data, mode, |point| {
+                camera.screen_to_world(point)
+            });
+        } else {
+            self.select_object_at(self.input.mouse_world(), self.input.shift());
+        }
+    }
+
+    fn undo(&mut self, ctx: &mut Context) {
+        if self.history.undo(&mut self.render_context.scene_data) {
+            self.render_context.sync_meshes(ctx);
+            self.check_edit_mode();
+        }
+    }
+
+    fn redo(&mut self, ctx: &mut Context) {
+        if self.history.redo(&mut self.render_context.scene_data) {
+            self.render_context.sync_meshes(ctx);
+            self.check_edit_mode();
+        }
+    }
+
+    /// Delete the selected objects, or the selected elements in edit mode, as one undo step
+    fn delete_selected(&mut self, ctx: &mut Context) {
+        let scene_data = &mut self.render_context.scene_data;
+        if let Some(edit_mode) = self.edit_mode {
+            if let Some(command) = edit_mode.delete_selected(scene_data) {
+                self.history.push(command);
+                self.render_context.sync_meshes(ctx);
+            }
+            return;
+        }
+
+        let commands = scene_data
+            .object_order()
+            .iter()
+            .copied()
+            .filter(|&key| scene_data.objects()[key].selected)
+            .map(Command::remove_object)
+            .collect::<Vec<_>>();
+
+        if !commands.is_empty() {
+            self.history.execute(scene_data, Command::Group(commands));
+            self.render_context.sync_meshes(ctx);
+        }
+    }
+
+    fn camera_state(&self) -> CameraState {
+        CameraState {
+            position: self.camera.position,
+            zoom: self.camera.zoom,
+            rotation: self.camera.rotation,
         }
     }
 
-    pub fn update_view_matrix(&mut self) {
-        let mut view_matrix = self.view_matrix.lock().unwrap();
-        *view_matrix = get_view_matrix(
-            *(self.position.lock().unwrap()),
-            *(self.zoom.lock().unwrap()),
+    /// Save the document to the file it was loaded from
+    ///
+    /// A document without a file is saved as a new untitled file in the working directory,
+    /// never over one that is already there.
+    fn save(&mut self) {
+        let view = ViewState {
+            camera: self.camera_state(),
+            bookmarks: self.bookmarks.clone(),
+            grid: self.grid,
+        };
+        let scene_data = &self.render_context.scene_data;
+
+        if let Some(path) = &self.document_path {
+            match save_flatblend_file(scene_data, &view, path) {
+                Ok(()) => println!("saved {}", path.display()),
+                Err(err) => eprintln!("failed to save {}: {}", path.display(), err),
+            }
+            return;
+        }
+
+        for number in 1..=MAX_UNTITLED {
+            let path = match number {
+                1 => PathBuf::from(format!("{UNTITLED_NAME}.flatblend")),
+                _ => PathBuf::from(format!("{UNTITLED_NAME} {number}.flatblend")),
+            };
+            match save_new_flatblend_file(scene_data, &view, &path) {
+                Ok(()) => {
+                    println!("saved {}", path.display());
+                    self.document_path = Some(path);
+                    return;
+                }
+                Err(FlatBlendError::Io(err)) if err.kind() == ErrorKind::AlreadyExists => {}
+                Err(err) => {
+                    eprintln!("failed to save {}: {}", path.display(), err);
+                    return;
+                }
+            }
+        }
+        eprintln!("failed to save: every untitled name is taken");
+    }
+
+    /// Render the current view to a PNG with the software rasterizer
+    fn render_to_image(&self) {
+        let image = render_scene(
+            &self.render_context.scene_data,
+            self.camera.projection_matrix(),
+            self.camera.view_matrix(),
+            &RasterOptions::new(self.camera.viewport.x as u32, self.camera.viewport.y as u32),
         );
-        drop(view_matrix);
+
+        match image.write_png(RENDER_PATH) {
+            Ok(()) => println!("rendered {}", RENDER_PATH),
+            Err(err) => eprintln!("failed to render {}: {}", RENDER_PATH, err),
+        }
+    }
+
+    /// Catch up with the camera having moved
+    fn camera_changed(&mut self) {
+        // The mouse is over a different part of the world now
+        let mouse_world = self.camera.screen_to_world(self.input.mouse_position());
+        self.input.set_mouse_world(mouse_world);
 
         // Update scene visibility when camera changes
-        self.render_context.update_visibility();
+        self.render_context.update_visibility(&self.camera);
     }
 
     /// Select object at the given world position
@@ -96,57 +633,62 @@ impl FlatBlendState {
             }
         }
 
-        // Find the topmost object at this position by iterating in reverse order
-        // object_order goes from bottom to top, so we reverse to check top first
-        let mut selected_key: Option<ObjectKey> = None;
-        for &key in scene_data.object_order().iter().rev() {
-            if let Some(obj) = scene_data.objects().get(key) {
-                if obj.contains_point(world_pos) {
-                    selected_key = Some(key);
-                    break; // Found the topmost object
-                }
-            }
-        }
+        let selected_key = scene_data
+            .objects_at_point(world_pos)
+            .into_iter()
+            .rev()
+            .find(|&key| scene_data.objects()[key].is_selectable());
 
         // Toggle selection on the found object
         if let Some(key) = selected_key {
             if let Some(obj) = scene_data.objects_mut().get_mut(key) {
                 obj.selected = !obj.selected;
             }
+            scene_data.set_active_object(Some(key));
         }
     }
 }
 
 impl EventHandler for FlatBlendState {
-    fn mouse_motion_event(&mut self, _ctx: &mut Context, x: f32, y: f32) {
+    fn mouse_motion_event(&mut self, ctx: &mut Context, x: f32, y: f32) {
         self.egui_mq.mouse_motion_event(x, y);
 
         let mouse_position = Vec2::new(x, y);
+        let mouse_world = self.camera.screen_to_world(mouse_position);
+        self.input.mouse_motion(mouse_position, mouse_world);
 
-        if let Some(middle_click) = self.mouse_state.get(&MouseButton::Middle) {
-            if *middle_click {
-                let diff = mouse_position - self.last_mouse_position;
-                let zoom = *(self.zoom.lock().unwrap());
-
-                {
-                    let mut position = self.position.lock().unwrap();
-                    position.x += diff.x / zoom;
-                    position.y -= diff.y / zoom;
-                }
+        if let Some(operator) = &mut self.transform_operator {
+            operator.set_mouse(mouse_world);
+            self.apply_transform(ctx);
+        }
 
-                self.update_view_matrix();
-            }
+        if self.pen.is_some() {
+            self.move_pen();
         }
 
-        self.last_mouse_position = mouse_position;
+        match self.drag.as_mut().map(|drag| &mut drag.kind) {
+            Some(DragKind::Pan) => self.pan(self.input.mouse_delta()),
+            Some(DragKind::RotateView) => self.rotate_view(),
+            Some(DragKind::Select(gesture)) => gesture.drag_to(mouse_position),
+            None => {}
+        }
     }
 
-    fn mouse_wheel_event(&mut self, _: &mut Context, dx: f32, dy: f32) {
-        if dy != 0.0 && !self.egui_mq.egui_ctx().wants_pointer_input() {
-            let mut zoom = self.zoom.lock().unwrap();
-            *zoom = (*zoom + dy / 1000.0).max(0.001).min(20.0);
-            drop(zoom);
-            self.update_view_matrix();
+    fn mouse_wheel_event(&mut self, _ctx: &mut Context, dx: f32, dy: f32) {
+        if !self.egui_mq.egui_ctx().wants_pointer_input() {
+            let action =
+                self.keymap
+                    .action(ActionContext::Viewport, Input::Wheel, self.input.mods());
+            match action {
+                Some(Action::Zoom) if dy != 0.0 => {
+                    self.camera_transition = None;
+                    let anchor = self.input.mouse_position();
+                    self.smooth_zoom
+                        .scroll(self.camera.zoom, dy, anchor, &self.zoom_settings);
+                }
+                Some(Action::ScrollPan) => self.pan(Vec2::new(dx, dy)),
+                _ => {}
+            }
         }
 
         self.egui_mq.mouse_wheel_event(dx, dy);
@@ -154,29 +696,34 @@ impl EventHandler for FlatBlendState {
 
     fn mouse_button_down_event(&mut self, ctx: &mut Context, button: MouseButton, x: f32, y: f32) {
         self.egui_mq.mouse_button_down_event(ctx, button, x, y);
+        self.input.mouse_button_down(button, Vec2::new(x, y));
 
-        // Handle right-click selection (only if egui doesn't want the input)
-        if button == MouseButton::Right && !self.egui_mq.egui_ctx().wants_pointer_input() {
-            let screen_size = ctx.screen_size();
-            let world_pos = screen_to_world(
-                Vec2::new(x, y),
-                screen_size,
-                *self.view_matrix.lock().unwrap(),
-                *self.projection_matrix.lock().unwrap(),
-            );
+        // A running transform takes the click
+        if self.transform_operator.is_some() {
+            self.transform_input(ctx, Input::Mouse(button));
+            return;
+        }
 
-            // Check if Shift is held for multi-selection
-            // Note: miniquad doesn't provide modifier state in mouse events,
-            // so we'll default to single selection for now
-            self.select_object_at(world_pos, false);
+        if self.egui_mq.egui_ctx().wants_pointer_input() {
+            return;
         }
 
-        self.mouse_state.insert(button, true);
+        let input = Input::Mouse(button);
+        if let Some(action) = self.viewport_action(input) {
+            self.run_action(ctx, action, input);
+        }
     }
 
     fn mouse_button_up_event(&mut self, ctx: &mut Context, button: MouseButton, x: f32, y: f32) {
         self.egui_mq.mouse_button_up_event(ctx, button, x, y);
-        self.mouse_state.insert(button, false);
+        self.input.mouse_button_up(button, Vec2::new(x, y));
+        self.end_drag(ctx, Input::Mouse(button));
+        if let Some(pen) = &mut self.pen {
+            pen.release();
+        }
+
+        // Whatever was being dragged is finished, so the next edit is a new undo step
+        self.history.seal();
     }
 
     fn key_down_event(
@@ -184,46 +731,167 @@ impl EventHandler for FlatBlendState {
         ctx: &mut Context,
         keycode: KeyCode,
         keymods: KeyMods,
-        _repeat: bool,
+        repeat: bool,
     ) {
         self.egui_mq.key_down_event(ctx, keycode, keymods);
+        self.input.key_down(keycode, keymods);
+        self.update_snap(ctx);
+
+        if self.egui_mq.egui_ctx().wants_keyboard_input() {
+            return;
+        }
+
+        let input = Input::Key(keycode);
+        if self.transform_operator.is_some() {
+            self.transform_input(ctx, input);
+            return;
+        }
+
+        // A held key repeats, but should only start a drag or run an action once
+        if repeat {
+            return;
+        }
+
+        if let Some(action) = self.viewport_action(input) {
+            self.run_action(ctx, action, input);
+        }
     }
 
-    fn key_up_event(&mut self, _ctx: &mut Context, keycode: KeyCode, keymods: KeyMods) {
+    fn key_up_event(&mut self, ctx: &mut Context, keycode: KeyCode, keymods: KeyMods) {
         self.egui_mq.key_up_event(keycode, keymods);
+        self.input.key_up(keycode, keymods);
+        self.update_snap(ctx);
+        self.end_drag(ctx, Input::Key(keycode));
     }
 
     fn resize_event(&mut self, ctx: &mut Context, width: f32, height: f32) {
-        let mut projection_matrix = self.projection_matrix.lock().unwrap();
-        *projection_matrix = get_ortho_matrix(width, height);
-        drop(projection_matrix);
+        self.camera.viewport = Vec2::new(width, height);
 
         // Resize pipelines
         self.render_context.resize(ctx, width as u32, height as u32);
 
         // Update scene visibility when projection changes
-        self.render_context.update_visibility();
+        self.camera_changed();
     }
 
-    fn update(&mut self, _ctx: &mut Context) {}
+    fn update(&mut self, _ctx: &mut Context) {
+        let now = date::now();
+        let dt = (now - self.last_update) as f32;
+        self.last_update = now;
+
+        let zoom = self.camera.zoom;
+        if let Some((zoom, anchor)) = self.smooth_zoom.advance(zoom, dt, &self.zoom_settings) {
+            self.camera.zoom_about(zoom, anchor);
+            self.camera_changed();
+        }
+
+        if let Some(transition) = &mut self.camera_transition {
+            if transition.advance(&mut self.camera, dt) {
+                self.camera_transition = None;
+            }
+            self.camera_changed();
+        }
+    }
 
     fn draw(&mut self, ctx: &mut Context) {
         ctx.begin_default_pass(Default::default());
 
-        self.render_context.draw(ctx);
+        self.render_context
+            .draw(ctx, &self.camera, &self.grid, self.edit_mode.as_ref());
 
         ctx.end_render_pass();
 
-        let position = *(self.position.lock().unwrap());
-        let zoom = *(self.zoom.lock().unwrap());
+        let camera = self.camera;
+
+        let scene_data = &mut self.render_context.scene_data;
+        let history = &mut self.history;
+        let mut modifiers_changed = false;
+        let status = self
+            .transform_operator
+            .as_ref()
+            .map(TransformOperator::status)
+            .or_else(|| self.pen.as_ref().map(PenTool::status));
+        let pen = self.pen.as_ref();
+        let snap_target = self
+            .snap_target
+            .filter(|_| self.transform_operator.is_some() || pen.is_some());
+        let snap_settings = &mut self.snap_settings;
+        let grid = &mut self.grid;
+        let objects_ui = &mut self.objects_ui;
+        let mode = match &self.edit_mode {
+            Some(edit_mode) => edit_mode.status(scene_data),
+            None => "Object Mode".to_string(),
+        };
+
+        let select_gesture = match self.drag.as_ref().map(|drag| &drag.kind) {
+            Some(DragKind::Select(gesture)) if gesture.is_drag() => Some(gesture),
+            _ => None,
+        };
 
         self.egui_mq.run(ctx, |_mq_ctx, egui_ctx| {
-            ObjectsUI::ui(egui_ctx);
-            ViewportUI::ui(egui_ctx, position, zoom);
+            if let Some(gesture) = select_gesture {
+                SelectionUI::ui(egui_ctx, gesture);
+            }
+            if let Some(pen) = pen {
+                PenUI::ui(egui_ctx, pen, &camera);
+            }
+            if let Some(target) = &snap_target {
+                SnapUI::ui(egui_ctx, target, &camera);
+            }
+            objects_ui.ui(egui_ctx, scene_data, history);
+            PropertiesUI::ui(egui_ctx, scene_data, history);
+            ViewportUI::ui(egui_ctx, &camera, &mode, status.as_deref());
+            SnapUI::settings_ui(egui_ctx, snap_settings);
+            GridUI::ui(egui_ctx, grid);
+            modifiers_changed = ModifiersUI::ui(egui_ctx, scene_data, history);
         });
 
         self.egui_mq.draw(ctx);
 
+        if modifiers_changed {
+            self.render_context.sync_meshes(ctx);
+        }
+
         ctx.commit_frame();
     }
 }
+
+/// Bookmark numbered by the digit key that stored or recalled it
+fn bookmark_slot(input: Input) -> Option<u8> {
+    match input {
+        Input::Key(keycode) => digit(keycode)?.to_digit(10).map(|slot| slot as u8),
+        _ => None,
+    }
+}
+
+/// Digit typed by a number key, on the main row or the keypad
+fn digit(keycode: KeyCode) -> Option<char> {
+    let digit = match keycode {
+        KeyCode::Key0 | KeyCode::Kp0 => 0,
+        KeyCode::Key1 | KeyCode::Kp1 => 1,
+        KeyCode::Key2 | KeyCode::Kp2 => 2,
+        KeyCode::Key3 | KeyCode::Kp3 => 3,
+        KeyCode::Key4 | KeyCode::Kp4 => 4,
+        KeyCode::Key5 | KeyCode::Kp5 => 5,
+        KeyCode::Key6 | KeyCode::Kp6 => 6,
+        KeyCode::Key7 | KeyCode::Kp7 => 7,
+        KeyCode::Key8 | KeyCode::Kp8 => 8,
+        KeyCode::Key9 | KeyCode::Kp9 => 9,
+        _ => return None,
+    };
+    char::from_digit(digit, 10)
+}
+
+/// Type the character for a key into a running transform
+fn type_key(operator: &mut TransformOperator, keycode: KeyCode) {
+    match keycode {
+        KeyCode::Backspace => operator.backspace(),
+        KeyCode::Minus | KeyCode::KpSubtract => operator.type_char('-'),
+        KeyCode::Period | KeyCode::KpDecimal => operator.type_char('.'),
+        _ => {
+            if let Some(digit) = digit(keycode) {
+                operator.type_char(digit);
+            }
+        }
+    }
+}
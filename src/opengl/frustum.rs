@@ -138,7 +138,7 @@ impl Frustum {
 }
 
 /// Axis-aligned bounding box in 2D
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct AABB2D {
     pub min: Vec2,
     pub max: Vec2,
@@ -157,6 +157,53 @@ impl AABB2D {
         }
     }
 
+    /// Check if the boxes overlap, counting shared edges
+    pub fn intersects(&self, other: &AABB2D) -> bool {
+        self.min.x <= other.max.x
+            && self.max.x >= other.min.x
+            && self.min.y <= other.max.y
+            && self.max.y >= other.min.y
+    }
+
+    /// Check if `other` lies entirely inside this box
+    pub fn contains(&self, other: &AABB2D) -> bool {
+        self.min.x <= other.min.x
+            && self.max.x >= other.max.x
+            && self.min.y <= other.min.y
+            && self.max.y >= other.max.y
+    }
+
+    /// Create the smallest AABB that contains both boxes
+    pub fn union(&self, other: &AABB2D) -> Self {
+        Self {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    pub fn contains_point(&self, point: Vec2) -> bool {
+        point.x >= self.min.x
+            && point.x <= self.max.x
+            && point.y >= self.min.y
+            && point.y <= self.max.y
+    }
+
+    /// Create the AABB that contains this box after it has been transformed by `matrix`
+    pub fn transformed(&self, matrix: Mat4) -> Self {
+        let corners = [
+            Vec2::new(self.min.x, self.min.y),
+            Vec2::new(self.max.x, self.min.y),
+            Vec2::new(self.max.x, self.max.y),
+            Vec2::new(self.min.x, self.max.y),
+        ]
+        .map(|corner| matrix.transform_point3(corner.extend(0.0)).truncate());
+
+        Self {
+            min: corners.iter().fold(corners[0], |min, c| min.min(*c)),
+            max: corners.iter().fold(corners[0], |max, c| max.max(*c)),
+        }
+    }
+
     /// Create an AABB for a transformed square object
     /// This handles translation, rotation, and scale
     pub fn from_transform(translation: Vec2, rotation: f32, scale: Vec2, mesh_extents: Vec2) -> Self {
@@ -1,4 +1,4 @@
-use glam::{Mat4, Vec2, Vec4};
+use glam::{Mat4, Vec2};
 
 pub fn get_ortho_matrix(width: f32, height: f32) -> Mat4 {
     Mat4::orthographic_rh_gl(
@@ -11,28 +11,106 @@ pub fn get_ortho_matrix(width: f32, height: f32) -> Mat4 {
     )
 }
 
-pub fn get_view_matrix(position: Vec2, zoom: f32) -> Mat4 {
+/// Scale by `zoom` after turning the world by `-rotation` about the view centre at `-position`
+pub fn get_view_matrix(position: Vec2, zoom: f32, rotation: f32) -> Mat4 {
     let translation = glam::Vec3::new(position.x, position.y, 0.0);
     let scale = glam::Vec3::new(zoom, zoom, 1.0);
-    Mat4::from_scale(scale) * Mat4::from_translation(translation)
+    Mat4::from_scale(scale) * Mat4::from_rotation_z(-rotation) * Mat4::from_translation(translation)
 }
 
-/// Convert screen coordinates to world coordinates
-pub fn screen_to_world(
-    screen_pos: Vec2,
-    screen_size: (f32, f32),
-    view_matrix: Mat4,
-    projection_matrix: Mat4,
-) -> Vec2 {
-    // Convert screen coordinates to NDC (-1 to 1)
-    // Note: screen Y is top-down, NDC Y is bottom-up
-    let ndc_x = (2.0 * screen_pos.x) / screen_size.0 - 1.0;
-    let ndc_y = 1.0 - (2.0 * screen_pos.y) / screen_size.1;
-
-    // Inverse transform: NDC -> World
-    let vp_matrix = projection_matrix * view_matrix;
-    let inv_vp = vp_matrix.inverse();
-    let world_pos = inv_vp * Vec4::new(ndc_x, ndc_y, 0.0, 1.0);
-
-    Vec2::new(world_pos.x, world_pos.y)
+/// Translation, rotation and non-uniform scale, applied scale first
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform2D {
+    pub translation: Vec2,
+    pub rotation: f32,
+    pub scale: Vec2,
+}
+
+impl Default for Transform2D {
+    fn default() -> Self {
+        Self {
+            translation: Vec2::ZERO,
+            rotation: 0.0,
+            scale: Vec2::ONE,
+        }
+    }
+}
+
+impl Transform2D {
+    pub fn new(translation: Vec2, rotation: f32, scale: Vec2) -> Self {
+        Self {
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        Mat4::from_translation(self.translation.extend(0.0))
+            * Mat4::from_rotation_z(self.rotation)
+            * Mat4::from_scale(self.scale.extend(1.0))
+    }
+
+    /// Decompose the 2D part of an affine matrix
+    ///
+    /// The linear part is split into a rotation times an upper triangular matrix, whose
+    /// diagonal is the scale. Any skew (the off-diagonal term) can't be represented and is
+    /// dropped, and a reflection ends up as a negative y scale.
+    pub fn from_matrix(matrix: Mat4) -> Self {
+        let x_axis = matrix.x_axis.truncate().truncate();
+        let y_axis = matrix.y_axis.truncate().truncate();
+        let translation = matrix.w_axis.truncate().truncate();
+
+        let scale_x = x_axis.length();
+        if scale_x == 0.0 {
+            let rotation = (-y_axis.x).atan2(y_axis.y);
+            return Self::new(translation, rotation, Vec2::new(0.0, y_axis.length()));
+        }
+
+        let rotation = x_axis.y.atan2(x_axis.x);
+        let scale_y = x_axis.perp_dot(y_axis) / scale_x;
+
+        Self::new(translation, rotation, Vec2::new(scale_x, scale_y))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_3;
+
+    use super::*;
+
+    fn assert_close(a: Mat4, b: Mat4) {
+        assert!(a.abs_diff_eq(b, 1e-5), "{a:?} != {b:?}");
+    }
+
+    #[test]
+    fn transform_round_trips_through_its_matrix() {
+        for transform in [
+            Transform2D::default(),
+            Transform2D::new(Vec2::new(3.0, -2.0), FRAC_PI_3, Vec2::new(2.0, 0.5)),
+            Transform2D::new(Vec2::new(-1.0, 4.0), -2.5, Vec2::new(0.25, -3.0)),
+        ] {
+            let decomposed = Transform2D::from_matrix(transform.matrix());
+            assert_close(decomposed.matrix(), transform.matrix());
+            assert!((decomposed.scale - transform.scale).abs().max_element() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn decomposition_drops_skew() {
+        let skew = Mat4::from_cols_array(&[
+            1.0, 0.0, 0.0, 0.0, //
+            0.5, 1.0, 0.0, 0.0, //
+            0.0, 0.0, 1.0, 0.0, //
+            2.0, 3.0, 0.0, 1.0,
+        ]);
+        let rotated = Mat4::from_rotation_z(FRAC_PI_3) * skew;
+
+        let transform = Transform2D::from_matrix(rotated);
+        assert!((transform.rotation - FRAC_PI_3).abs() < 1e-5);
+        assert!((transform.scale - Vec2::ONE).abs().max_element() < 1e-5);
+        let translation = Vec2::from_angle(FRAC_PI_3).rotate(Vec2::new(2.0, 3.0));
+        assert!(transform.translation.abs_diff_eq(translation, 1e-5));
+    }
 }
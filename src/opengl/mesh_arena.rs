@@ -0,0 +1,805 @@
+//! GPU mesh arena
+//!
+//! Every mesh the scene draws lives in one shared vertex buffer and one shared index buffer,
+//! which all pipelines bind. GLES 2 has no base vertex for indexed draws, so each mesh's indices
+//! are rebased onto the vertices of its region, and the mesh records where its indices start in
+//! [`Mesh::buffer_offset`].
+//!
+//! The buffers are stream buffers with spare capacity. A mesh is only uploaded when it is new
+//! or its dirty flag is set, and only its own region is written. Regions of removed meshes go
+//! back on a free list; when the free space gets too fragmented, or a mesh no longer fits, the
+//! arena is compacted and grown if needed, which re-uploads everything.
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    mem,
+    rc::{Rc, Weak},
+};
+
+use miniquad::{gl, Buffer, BufferType, Context, IndexType};
+
+use crate::data::vertex::{Index, Vertex};
+
+use super::structs::Mesh;
+
+/// Smallest capacity, in vertices or indices, the arena buffers are created with
+const MIN_CAPACITY: u32 = 1024;
+
+/// A run of elements in one of the arena buffers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: u32,
+    pub len: u32,
+}
+
+impl Span {
+    pub fn end(&self) -> u32 {
+        self.start + self.len
+    }
+}
+
+/// Where a mesh lives in the arena
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Region {
+    pub vertices: Span,
+    pub indices: Span,
+}
+
+/// Free space in a buffer of `capacity` elements, kept sorted and merged
+#[derive(Debug)]
+struct FreeList {
+    capacity: u32,
+    spans: Vec<Span>,
+}
+
+impl FreeList {
+    fn new(capacity: u32) -> Self {
+        Self {
+            capacity,
+            spans: vec![Span {
+                start: 0,
+                len: capacity,
+            }],
+        }
+    }
+
+    /// Take the first free span that fits `len` elements
+    fn allocate(&mut self, len: u32) -> Option<Span> {
+        if len == 0 {
+            return Some(Span { start: 0, len: 0 });
+        }
+
+        let index = self.spans.iter().position(|span| span.len >= len)?;
+        let span = &mut self.spans[index];
+        let allocated = Span {
+            start: span.start,
+            len,
+        };
+
+        span.start += len;
+        span.len -= len;
+        if span.len == 0 {
+            self.spans.remove(index);
+        }
+
+        Some(allocated)
+    }
+
+    fn free(&mut self, span: Span) {
+        if span.len == 0 {
+            return;
+        }
+
+        let index = self.spans.partition_point(|free| free.start < span.start);
+        self.spans.insert(index, span);
+
+        // Merge with the following span, then the preceding one
+        if index + 1 < self.spans.len() && self.spans[index].end() == self.spans[index + 1].start {
+            self.spans[index].len += self.spans.remove(index + 1).len;
+        }
+        if index > 0 && self.spans[index - 1].end() == self.spans[index].start {
+            self.spans[index - 1].len += self.spans.remove(index).len;
+        }
+    }
+
+    /// Free space that isn't at the end of the buffer
+    fn holes(&self) -> u32 {
+        self.spans
+            .iter()
+            .filter(|span| span.end() != self.capacity)
+            .map(|span| span.len)
+            .sum()
+    }
+
+    fn used(&self) -> u32 {
+        self.capacity - self.spans.iter().map(|span| span.len).sum::<u32>()
+    }
+}
+
+struct Resident {
+    mesh: Weak<RefCell<Mesh>>,
+    region: Region,
+}
+
+/// What the GPU buffers need after [`ArenaLayout::sync`]
+#[derive(Default)]
+pub struct ArenaChanges {
+    /// New buffer capacities, in vertices and indices, if the buffers have to be recreated
+    pub capacity: Option<(u32, u32)>,
+    /// Meshes whose regions have to be written
+    pub uploads: Vec<(Rc<RefCell<Mesh>>, Region)>,
+}
+
+/// Placement of meshes in the arena buffers, without touching the GPU
+pub struct ArenaLayout {
+    residents: HashMap<*const RefCell<Mesh>, Resident>,
+    vertex_space: FreeList,
+    index_space: FreeList,
+}
+
+impl Default for ArenaLayout {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ArenaLayout {
+    pub fn new() -> Self {
+        Self {
+            residents: HashMap::new(),
+            vertex_space: FreeList::new(MIN_CAPACITY),
+            index_space: FreeList::new(MIN_CAPACITY),
+        }
+    }
+
+    /// Capacity of the buffers, in vertices and indices
+    pub fn capacity(&self) -> (u32, u32) {
+        (self.vertex_space.capacity, self.index_space.capacity)
+    }
+
+    pub fn region(&self, mesh: &Rc<RefCell<Mesh>>) -> Option<Region> {
+        self.residents
+            .get(&Rc::as_ptr(mesh))
+            .map(|resident| resident.region)
+    }
+
+    /// Make `meshes` the contents of the arena
+    ///
+    /// Meshes that are new or dirty get a region and are marked clean, and meshes that are no
+    /// longer in the list give their regions back.
+    pub fn sync(&mut self, meshes: &[Rc<RefCell<Mesh>>]) -> ArenaChanges {
+        let wanted = meshes.iter().map(Rc::as_ptr).collect::<HashSet<_>>();
+
+        let mut removed = false;
+        self.residents.retain(|ptr, resident| {
+            let keep = wanted.contains(ptr) && resident.mesh.strong_count() > 0;
+            if !keep {
+                self.vertex_space.free(resident.region.vertices);
+                self.index_space.free(resident.region.indices);
+                removed = true;
+            }
+            keep
+        });
+
+        let mut uploads = vec![];
+        let mut out_of_space = false;
+
+        for mesh in meshes {
+            let ptr = Rc::as_ptr(mesh);
+            let resident = self.residents.get(&ptr);
+            if resident.is_some() && !mesh.borrow().is_dirty() {
+                continue;
+            }
+
+            let (vertex_count, index_count) = {
+                let mesh = mesh.borrow();
+                (mesh.vertices().len() as u32, mesh.indices().len() as u32)
+            };
+
+            // Shrink in place when the new data fits the old region, otherwise move it
+            let region = match resident.map(|resident| resident.region) {
+                Some(region)
+                    if vertex_count <= region.vertices.len && index_count <= region.indices.len =>
+                {
+                    self.vertex_space.free(Span {
+                        start: region.vertices.start + vertex_count,
+                        len: region.vertices.len - vertex_count,
+                    });
+                    self.index_space.free(Span {
+                        start: region.indices.start + index_count,
+                        len: region.indices.len - index_count,
+                    });
+                    Some(Region {
+                        vertices: Span {
+                            start: region.vertices.start,
+                            len: vertex_count,
+                        },
+                        indices: Span {
+                            start: region.indices.start,
+                            len: index_count,
+                        },
+                    })
+                }
+                old => {
+                    if let Some(old) = old {
+                        self.vertex_space.free(old.vertices);
+                        self.index_space.free(old.indices);
+                    }
+                    self.allocate(vertex_count, index_count)
+                }
+            };
+
+            let Some(region) = region else {
+                self.residents.remove(&ptr);
+                out_of_space = true;
+                continue;
+            };
+
+            self.place(mesh, region);
+            uploads.push((mesh.clone(), region));
+        }
+
+        if out_of_space || (removed && self.fragmented()) {
+            return self.compact(meshes);
+        }
+
+        ArenaChanges {
+            capacity: None,
+            uploads,
+        }
+    }
+
+    /// Upload any resident mesh whose dirty flag is set, and drop meshes that no longer exist
+    pub fn sync_dirty(&mut self) -> ArenaChanges {
+        let mut meshes = self
+            .residents
+            .values()
+            .filter_map(|resident| {
+                let mesh = resident.mesh.upgrade()?;
+                Some((resident.region.vertices.start, mesh))
+            })
+            .collect::<Vec<_>>();
+        meshes.sort_by_key(|(start, _)| *start);
+
+        let meshes = meshes.into_iter().map(|(_, mesh)| mesh).collect::<Vec<_>>();
+        self.sync(&meshes)
+    }
+
+    fn allocate(&mut self, vertex_count: u32, index_count: u32) -> Option<Region> {
+        let vertices = self.vertex_space.allocate(vertex_count)?;
+        let Some(indices) = self.index_space.allocate(index_count) else {
+            self.vertex_space.free(vertices);
+            return None;
+        };
+
+        Some(Region { vertices, indices })
+    }
+
+    fn place(&mut self, mesh: &Rc<RefCell<Mesh>>, region: Region) {
+        let mut borrowed = mesh.borrow_mut();
+        borrowed.buffer_offset = region.indices.start;
+        borrowed.mark_clean();
+
+        self.residents.insert(
+            Rc::as_ptr(mesh),
+            Resident {
+                mesh: Rc::downgrade(mesh),
+                region,
+            },
+        );
+    }
+
+    /// Whether the gaps between regions hold more than the regions themselves
+    fn fragmented(&self) -> bool {
+        self.vertex_space.holes() > self.vertex_space.used()
+            || self.index_space.holes() > self.index_space.used()
+    }
+
+    /// Pack `meshes` from the start of the buffers, growing them if they don't fit
+    fn compact(&mut self, meshes: &[Rc<RefCell<Mesh>>]) -> ArenaChanges {
+        let (vertex_total, index_total) = meshes.iter().fold((0, 0), |(v, i), mesh| {
+            let mesh = mesh.borrow();
+            (
+                v + mesh.vertices().len() as u32,
+                i + mesh.indices().len() as u32,
+            )
+        });
+
+        let (vertex_capacity, index_capacity) = self.capacity();
+        let grown = (
+            grow(vertex_capacity, vertex_total),
+            grow(index_capacity, index_total),
+        );
+
+        self.residents.clear();
+        self.vertex_space = FreeList::new(grown.0);
+        self.index_space = FreeList::new(grown.1);
+
+        let uploads = meshes
+            .iter()
+            .map(|mesh| {
+                let (vertex_count, index_count) = {
+                    let mesh = mesh.borrow();
+                    (mesh.vertices().len() as u32, mesh.indices().len() as u32)
+                };
+                let region = self
+                    .allocate(vertex_count, index_count)
+                    .expect("compacted arena has room for every mesh");
+                self.place(mesh, region);
+                (mesh.clone(), region)
+            })
+            .collect();
+
+        ArenaChanges {
+            capacity: (grown != (vertex_capacity, index_capacity)).then_some(grown),
+            uploads,
+        }
+    }
+}
+
+/// Capacity that holds `needed` elements, doubling so repeated growth stays cheap
+fn grow(capacity: u32, needed: u32) -> u32 {
+    if needed <= capacity {
+        capacity
+    } else {
+        needed.next_power_of_two()
+    }
+}
+
+/// Indices of `mesh` rebased onto the vertices of its region
+pub fn rebased_indices(mesh: &Mesh, region: Region) -> Vec<Index> {
+    mesh.indices()
+        .iter()
+        .map(|index| index + region.vertices.start)
+        .collect()
+}
+
+/// Where [`write_uploads`] puts the meshes it is given
+trait ArenaWriter {
+    fn write_vertices(&mut self, start: u32, vertices: &[Vertex]);
+    fn write_indices(&mut self, start: u32, indices: &[Index]);
+}
+
+/// Write each uploaded mesh into its region, leaving the rest of the buffers alone
+fn write_uploads(writer: &mut impl ArenaWriter, uploads: Vec<(Rc<RefCell<Mesh>>, Region)>) {
+    for (mesh, region) in uploads {
+        let mesh = mesh.borrow();
+        writer.write_vertices(region.vertices.start, mesh.vertices());
+        writer.write_indices(region.indices.start, &rebased_indices(&mesh, region));
+    }
+}
+
+/// A miniquad buffer along with its GL name, which miniquad keeps to itself
+#[derive(Clone, Copy)]
+struct ArenaBuffer {
+    buffer: Buffer,
+    name: gl::GLuint,
+}
+
+pub struct GpuMeshArena {
+    layout: ArenaLayout,
+    vertex_buffer: ArenaBuffer,
+    index_buffer: ArenaBuffer,
+}
+
+impl GpuMeshArena {
+    pub fn new(ctx: &mut Context) -> Self {
+        let layout = ArenaLayout::new();
+        let (vertex_capacity, index_capacity) = layout.capacity();
+        let (vertex_buffer, index_buffer) = create_buffers(ctx, vertex_capacity, index_capacity);
+
+        Self {
+            layout,
+            vertex_buffer,
+            index_buffer,
+        }
+    }
+
+    /// Make `meshes` the contents of the arena, uploading only new and dirty meshes
+    pub fn sync(&mut self, ctx: &mut Context, meshes: &[Rc<RefCell<Mesh>>]) {
+        let changes = self.layout.sync(meshes);
+        self.apply(ctx, changes);
+    }
+
+    /// Re-upload resident meshes whose dirty flag is set
+    pub fn sync_dirty(&mut self, ctx: &mut Context) {
+        let changes = self.layout.sync_dirty();
+        self.apply(ctx, changes);
+    }
+
+    pub fn vertex_buffer(&self) -> Buffer {
+        self.vertex_buffer.buffer
+    }
+
+    pub fn index_buffer(&self) -> Buffer {
+        self.index_buffer.buffer
+    }
+
+    fn apply(&mut self, ctx: &mut Context, changes: ArenaChanges) {
+        if let Some((vertex_capacity, index_capacity)) = changes.capacity {
+            // Create the new buffers before deleting the old ones so they never reuse their
+            // names, which miniquad's binding cache would mistake for the old buffers
+            let (vertex_buffer, index_buffer) =
+                create_buffers(ctx, vertex_capacity, index_capacity);
+            self.vertex_buffer.buffer.delete();
+            self.index_buffer.buffer.delete();
+            self.vertex_buffer = vertex_buffer;
+            self.index_buffer = index_buffer;
+        }
+
+        // Growing compacts every mesh to the start, so this also fills new buffers
+        write_uploads(self, changes.uploads);
+    }
+}
+
+impl ArenaWriter for GpuMeshArena {
+    fn write_vertices(&mut self, start: u32, vertices: &[Vertex]) {
+        buffer_sub_data(self.vertex_buffer, gl::GL_ARRAY_BUFFER, start, vertices);
+    }
+
+    fn write_indices(&mut self, start: u32, indices: &[Index]) {
+        buffer_sub_data(
+            self.index_buffer,
+            gl::GL_ELEMENT_ARRAY_BUFFER,
+            start,
+            indices,
+        );
+    }
+}
+
+fn create_buffers(
+    ctx: &mut Context,
+    vertex_capacity: u32,
+    index_capacity: u32,
+) -> (ArenaBuffer, ArenaBuffer) {
+    let vertex_buffer = Buffer::stream(
+        ctx,
+        BufferType::VertexBuffer,
+        vertex_capacity as usize * mem::size_of::<Vertex>(),
+    );
+    let vertex_name = bound_buffer(GL_ARRAY_BUFFER_BINDING);
+    let index_buffer = Buffer::index_stream(
+        ctx,
+        IndexType::Int,
+        index_capacity as usize * mem::size_of::<Index>(),
+    );
+    let index_name = bound_buffer(GL_ELEMENT_ARRAY_BUFFER_BINDING);
+
+    (
+        ArenaBuffer {
+            buffer: vertex_buffer,
+            name: vertex_name,
+        },
+        ArenaBuffer {
+            buffer: index_buffer,
+            name: index_name,
+        },
+    )
+}
+
+// miniquad's GL bindings leave these out
+const GL_ARRAY_BUFFER_BINDING: gl::GLenum = 0x8894;
+const GL_ELEMENT_ARRAY_BUFFER_BINDING: gl::GLenum = 0x8895;
+
+/// Name of the buffer bound to `binding`
+///
+/// miniquad binds a buffer to create it and leaves it bound, so straight after creation this is
+/// the new buffer's name.
+fn bound_buffer(binding: gl::GLenum) -> gl::GLuint {
+    let mut name: gl::GLint = 0;
+    unsafe { gl::glGetIntegerv(binding, &mut name) };
+    name as gl::GLuint
+}
+
+/// Write `data` into `buffer` starting at element `offset`
+///
+/// miniquad can only write a buffer from its start. The previous binding is put back afterwards
+/// so miniquad's binding cache still matches what is bound.
+fn buffer_sub_data<T>(buffer: ArenaBuffer, target: gl::GLenum, offset: u32, data: &[T]) {
+    if data.is_empty() {
+        return;
+    }
+    assert!((offset as usize + data.len()) * mem::size_of::<T>() <= buffer.buffer.size());
+
+    let binding = match target {
+        gl::GL_ARRAY_BUFFER => GL_ARRAY_BUFFER_BINDING,
+        _ => GL_ELEMENT_ARRAY_BUFFER_BINDING,
+    };
+    let previous = bound_buffer(binding);
+    unsafe {
+        gl::glBindBuffer(target, buffer.name);
+        gl::glBufferSubData(
+            target,
+            (offset as usize * mem::size_of::<T>()) as _,
+            mem::size_of_val(data) as _,
+            data.as_ptr() as *const _,
+        );
+        gl::glBindBuffer(target, previous);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec2;
+
+    use crate::{
+        data::mesh::bmesh::bm_from_polygons,
+        opengl::{
+            scene::SceneData,
+            structs::{Colour, Material, Object},
+        },
+        shapes::{square::create_square, star::create_star},
+    };
+
+    use super::*;
+
+    /// CPU copy of the arena buffers, written through the same writes as the GPU buffers
+    #[derive(Default)]
+    struct Mirror {
+        vertices: Vec<Vertex>,
+        indices: Vec<Index>,
+        written_vertices: u32,
+    }
+
+    impl ArenaWriter for Mirror {
+        fn write_vertices(&mut self, start: u32, vertices: &[Vertex]) {
+            let start = start as usize;
+            self.vertices[start..start + vertices.len()].copy_from_slice(vertices);
+            self.written_vertices += vertices.len() as u32;
+        }
+
+        fn write_indices(&mut self, start: u32, indices: &[Index]) {
+            let start = start as usize;
+            self.indices[start..start + indices.len()].copy_from_slice(indices);
+        }
+    }
+
+    impl Mirror {
+        fn apply(&mut self, layout: &ArenaLayout, changes: ArenaChanges) {
+            let (vertex_capacity, index_capacity) = layout.capacity();
+            self.vertices
+                .resize(vertex_capacity as usize, Vertex::default());
+            self.indices.resize(index_capacity as usize, 0);
+            self.written_vertices = 0;
+
+            write_uploads(self, changes.uploads);
+        }
+
+        /// Triangles a draw call starting at the mesh's offset would produce
+        fn drawn_triangles(&self, mesh: &Mesh) -> Vec<[Vec2; 3]> {
+            let start = mesh.buffer_offset as usize;
+            self.indices[start..start + (mesh.tris * 3) as usize]
+                .chunks_exact(3)
+                .map(|tri| [0, 1, 2].map(|i| self.vertices[tri[i] as usize].pos))
+                .collect()
+        }
+    }
+
+    fn own_triangles(mesh: &Mesh) -> Vec<[Vec2; 3]> {
+        mesh.indices()
+            .chunks_exact(3)
+            .map(|tri| [0, 1, 2].map(|i| mesh.vertices()[tri[i] as usize].pos))
+            .collect()
+    }
+
+    fn assert_draws_own_shapes(mirror: &Mirror, meshes: &[Rc<RefCell<Mesh>>]) {
+        for mesh in meshes {
+            let mesh = mesh.borrow();
+            assert_eq!(mirror.drawn_triangles(&mesh), own_triangles(&mesh));
+        }
+    }
+
+    fn square_mesh(size: f32) -> Rc<RefCell<Mesh>> {
+        let square = vec![
+            Vec2::new(0.0, 0.0),
+            Vec2::new(size, 0.0),
+            Vec2::new(size, size),
+            Vec2::new(0.0, size),
+        ];
+        Mesh::new(bm_from_polygons(&[square]), 0).0
+    }
+
+    fn object_with(mesh: &Rc<RefCell<Mesh>>) -> Object {
+        let material = Rc::new(RefCell::new(Material {
+            colour: Colour::ONE,
+        }));
+        Object::new(mesh.clone(), Vec2::ZERO, 0.0, Vec2::ONE, material)
+    }
+
+    /// Sync the layout the way the render context does after the scene changed
+    fn sync_scene(layout: &mut ArenaLayout, mirror: &mut Mirror, scene_data: &mut SceneData) {
+        let evaluated = scene_data.evaluate_modifiers();
+        if scene_data.take_meshes_changed() || evaluated {
+            let changes = layout.sync(&scene_data.evaluated_meshes());
+            mirror.apply(layout, changes);
+        }
+    }
+
+    #[test]
+    fn square_and_star_draw_their_own_shapes() {
+        let (square, _, _) = Mesh::new(create_square(), 0);
+        let (star, _, _) = Mesh::new(create_star(), 0);
+        let meshes = [square.clone(), star.clone()];
+
+        let mut layout = ArenaLayout::new();
+        let mut mirror = Mirror::default();
+        let changes = layout.sync(&meshes);
+        mirror.apply(&layout, changes);
+
+        assert_eq!(square.borrow().buffer_offset, 0);
+        assert_eq!(star.borrow().buffer_offset, square.borrow().tris * 3);
+        assert_draws_own_shapes(&mirror, &meshes);
+    }
+
+    #[test]
+    fn only_dirty_meshes_are_uploaded() {
+        let (square, _, _) = Mesh::new(create_square(), 0);
+        let (star, _, _) = Mesh::new(create_star(), 0);
+        let meshes = [square.clone(), star.clone()];
+
+        let mut layout = ArenaLayout::new();
+        let mut mirror = Mirror::default();
+        let changes = layout.sync(&meshes);
+        mirror.apply(&layout, changes);
+
+        // Nothing changed, nothing to upload
+        let changes = layout.sync_dirty();
+        assert!(changes.uploads.is_empty());
+
+        // Editing the star re-uploads the star alone
+        star.borrow_mut().set_raw_mesh(create_star());
+        let changes = layout.sync_dirty();
+        assert_eq!(changes.uploads.len(), 1);
+        assert!(Rc::ptr_eq(&changes.uploads[0].0, &star));
+        assert!(changes.capacity.is_none());
+        mirror.apply(&layout, changes);
+
+        assert_eq!(
+            mirror.written_vertices,
+            star.borrow().vertices().len() as u32
+        );
+        assert!(!star.borrow().is_dirty());
+        assert_draws_own_shapes(&mirror, &meshes);
+    }
+
+    #[test]
+    fn growing_mesh_moves_and_others_stay() {
+        let (square, _, _) = Mesh::new(create_square(), 0);
+        let (star, _, _) = Mesh::new(create_star(), 0);
+        let meshes = [square.clone(), star.clone()];
+
+        let mut layout = ArenaLayout::new();
+        let mut mirror = Mirror::default();
+        let changes = layout.sync(&meshes);
+        mirror.apply(&layout, changes);
+        let star_region = layout.region(&star).unwrap();
+
+        // The square no longer fits its old region, so it moves after the star
+        let ring = [
+            vec![
+                Vec2::new(-2.0, -2.0),
+                Vec2::new(2.0, -2.0),
+                Vec2::new(2.0, 2.0),
+                Vec2::new(-2.0, 2.0),
+            ],
+            vec![
+                Vec2::new(-1.0, -1.0),
+                Vec2::new(-1.0, 1.0),
+                Vec2::new(1.0, 1.0),
+                Vec2::new(1.0, -1.0),
+            ],
+        ];
+        square.borrow_mut().set_raw_mesh(bm_from_polygons(&ring));
+        let changes = layout.sync(&meshes);
+        assert_eq!(changes.uploads.len(), 1);
+        mirror.apply(&layout, changes);
+
+        assert_eq!(layout.region(&star), Some(star_region));
+        assert!(layout.region(&square).unwrap().vertices.start >= star_region.vertices.end());
+        assert_draws_own_shapes(&mirror, &meshes);
+    }
+
+    #[test]
+    fn removed_meshes_are_reused_and_compacted() {
+        let meshes = (1..=8)
+            .map(|size| square_mesh(size as f32))
+            .collect::<Vec<_>>();
+
+        let mut layout = ArenaLayout::new();
+        let mut mirror = Mirror::default();
+        let changes = layout.sync(&meshes);
+        mirror.apply(&layout, changes);
+
+        // Removing one mesh leaves a hole the next new mesh fills
+        let hole = layout.region(&meshes[2]).unwrap();
+        let mut kept = meshes.clone();
+        kept.remove(2);
+        let changes = layout.sync(&kept);
+        assert!(changes.uploads.is_empty());
+
+        let added = square_mesh(10.0);
+        kept.push(added.clone());
+        let changes = layout.sync(&kept);
+        assert_eq!(changes.uploads.len(), 1);
+        assert_eq!(layout.region(&added), Some(hole));
+        mirror.apply(&layout, changes);
+        assert_draws_own_shapes(&mirror, &kept);
+
+        // Removing most meshes compacts what's left to the start of the buffers
+        let survivors = [kept[1].clone(), kept[5].clone()];
+        let changes = layout.sync(&survivors);
+        assert_eq!(changes.uploads.len(), 2);
+        mirror.apply(&layout, changes);
+
+        assert_eq!(layout.region(&survivors[0]).unwrap().vertices.start, 0);
+        assert_eq!(
+            layout.region(&survivors[1]).unwrap().vertices.start,
+            layout.region(&survivors[0]).unwrap().vertices.end()
+        );
+        assert_draws_own_shapes(&mirror, &survivors);
+    }
+
+    #[test]
+    fn arena_grows_when_meshes_do_not_fit() {
+        let meshes = (0..300)
+            .map(|i| square_mesh(i as f32 + 1.0))
+            .collect::<Vec<_>>();
+
+        let mut layout = ArenaLayout::new();
+        let mut mirror = Mirror::default();
+        let changes = layout.sync(&meshes[..10]);
+        mirror.apply(&layout, changes);
+
+        let changes = layout.sync(&meshes);
+        let (vertex_capacity, index_capacity) = changes.capacity.unwrap();
+        assert!(vertex_capacity >= 300 * 4 && index_capacity >= 300 * 6);
+        assert_eq!(changes.uploads.len(), meshes.len());
+        mirror.apply(&layout, changes);
+
+        assert_draws_own_shapes(&mirror, &meshes);
+    }
+
+    #[test]
+    fn objects_put_back_after_removal_get_a_fresh_region() {
+        let meshes = (1..=3)
+            .map(|size| square_mesh(size as f32))
+            .collect::<Vec<_>>();
+        let mut scene_data = SceneData::new(meshes[..2].iter().map(object_with).collect());
+        let mut layout = ArenaLayout::new();
+        let mut mirror = Mirror::default();
+        sync_scene(&mut layout, &mut mirror, &mut scene_data);
+
+        // Removing an object frees its region, which the next new object takes
+        let removed_key = scene_data.object_order()[0];
+        let removed = scene_data.remove_object(removed_key).unwrap();
+        sync_scene(&mut layout, &mut mirror, &mut scene_data);
+        assert_eq!(layout.region(&meshes[0]), None);
+        scene_data.add_object(object_with(&meshes[2]));
+        sync_scene(&mut layout, &mut mirror, &mut scene_data);
+
+        // Undoing the removal puts the object back, and it mustn't draw from its old offset
+        scene_data.add_object(removed);
+        sync_scene(&mut layout, &mut mirror, &mut scene_data);
+        assert!(layout.region(&meshes[0]).is_some());
+        assert_draws_own_shapes(&mirror, &meshes);
+    }
+
+    #[test]
+    fn objects_added_after_construction_are_resident() {
+        let (square, _, _) = Mesh::new(create_square(), 0);
+        let mut scene_data = SceneData::new(vec![object_with(&square)]);
+        let mut layout = ArenaLayout::new();
+        let mut mirror = Mirror::default();
+        sync_scene(&mut layout, &mut mirror, &mut scene_data);
+
+        // As the pen does: a new object with a new mesh and no modifiers
+        let (star, _, _) = Mesh::new(create_star(), 0);
+        scene_data.add_object(object_with(&star));
+        sync_scene(&mut layout, &mut mirror, &mut scene_data);
+
+        assert!(layout.region(&star).is_some());
+        assert_ne!(star.borrow().buffer_offset, square.borrow().buffer_offset);
+        assert_draws_own_shapes(&mirror, &[square, star]);
+    }
+}
@@ -1,7 +1,11 @@
+pub mod camera;
 pub mod flat_blend_state;
 pub mod frustum;
 pub mod matrices;
+pub mod mesh_arena;
 pub mod pipelines;
+pub mod quadtree;
 pub mod render_context;
 pub mod scene;
 pub mod structs;
+pub mod zoom;
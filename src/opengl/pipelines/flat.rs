@@ -1,141 +1,90 @@
-use std::{
-    cell::RefCell,
-    rc::Rc,
-    sync::{Arc, Mutex},
-};
-
-use glam::Mat4;
-use miniquad::{
-    Bindings, Buffer, BufferLayout, BufferType, Context, Pipeline, Shader, VertexAttribute,
-    VertexFormat,
-};
-
-use crate::{
-    data::vertex::{Index, Vertex},
-    opengl::{
-        scene::{ObjectKey, SceneData},
-        structs::Mesh,
-    },
-};
+use miniquad::{Comparison, Context, Pipeline, PipelineParams, Shader};
 
+use super::instances::{self, batch_instances, order_depth, Instance, InstanceBuffers};
+use crate::opengl::{camera::Camera2D, mesh_arena::GpuMeshArena, scene::SceneData};
+
+/// Draws every visible object, with one instanced draw call per mesh
+///
+/// Instances of different meshes are drawn in separate calls, so draw order is kept with the
+/// depth buffer instead: each instance's depth comes from its place in the draw order.
 pub struct FlatPipeline {
     pipeline: Pipeline,
-    bindings: Bindings,
-    vertex_buffer: Buffer,
-    index_buffer: Buffer,
-    projection_matrix: Arc<Mutex<Mat4>>,
-    view_matrix: Arc<Mutex<Mat4>>,
+    instance_buffers: InstanceBuffers,
 }
 
 impl FlatPipeline {
-    pub fn new(
-        ctx: &mut Context,
-        projection_matrix: Arc<Mutex<Mat4>>,
-        view_matrix: Arc<Mutex<Mat4>>,
-    ) -> FlatPipeline {
-        #[rustfmt::skip]
-        let vertices: [Vertex; 0] = [];
-        let vertex_buffer = Buffer::immutable(ctx, BufferType::VertexBuffer, &vertices);
-        let indices: [Index; 0] = [];
-        let index_buffer = Buffer::immutable(ctx, BufferType::IndexBuffer, &indices);
-
-        let bindings = Bindings {
-            vertex_buffers: vec![vertex_buffer],
-            index_buffer,
-            images: vec![],
-        };
+    pub fn new(ctx: &mut Context) -> FlatPipeline {
+        let shader = Shader::new(ctx, &shader::vertex(), shader::FRAGMENT, shader::meta()).unwrap();
 
-        let shader = Shader::new(ctx, shader::VERTEX, shader::FRAGMENT, shader::meta()).unwrap();
-
-        let pipeline = Pipeline::new(
+        let pipeline = Pipeline::with_params(
             ctx,
-            &[BufferLayout::default()],
-            &[VertexAttribute::new("pos", VertexFormat::Float2)],
+            &instances::buffer_layouts(),
+            &instances::attributes(),
             shader,
+            PipelineParams {
+                depth_test: Comparison::LessOrEqual,
+                depth_write: true,
+                ..Default::default()
+            },
         );
 
         FlatPipeline {
             pipeline,
-            bindings,
-            index_buffer,
-            vertex_buffer,
-            projection_matrix,
-            view_matrix,
+            instance_buffers: InstanceBuffers::new(),
         }
     }
 
-    pub fn update(&mut self, ctx: &mut Context, meshes: Vec<Rc<RefCell<Mesh>>>) {
-        let mut vertices: Vec<Vertex> = vec![];
-        let mut indices: Vec<Index> = vec![];
-
-        meshes.iter().for_each(|mesh| {
-            let (mesh_vertices, mesh_indices) = mesh.borrow_mut().update(indices.len() as Index);
-
-            vertices.extend_from_slice(&mesh_vertices);
-            indices.extend_from_slice(&mesh_indices);
-        });
-
-        self.vertex_buffer = Buffer::immutable(ctx, BufferType::VertexBuffer, &vertices);
-        self.index_buffer = Buffer::immutable(ctx, BufferType::IndexBuffer, &indices);
-
-        self.bindings = Bindings {
-            vertex_buffers: vec![self.vertex_buffer],
-            index_buffer: self.index_buffer,
-            images: vec![],
-        };
-    }
-
     pub fn draw(
         &mut self,
         ctx: &mut Context,
         scene_data: &SceneData,
-        projection_matrix: Mat4,
-        view_matrix: Mat4,
+        mesh_arena: &GpuMeshArena,
+        camera: &Camera2D,
     ) {
-        ctx.apply_pipeline(&self.pipeline);
-        ctx.apply_bindings(&self.bindings);
-
-        let objects = scene_data.objects();
         let visible_keys = scene_data.visible_objects();
+        let batches = batch_instances(scene_data, visible_keys, |object, index| {
+            Instance::new(
+                object,
+                order_depth(index, visible_keys.len()),
+                object.borrow_material().colour.into(),
+            )
+        });
 
-        // Only draw visible objects
-        for &key in visible_keys {
-            let object = &objects[key];
-
-            ctx.apply_uniforms(&shader::Uniforms {
-                model_matrix: object.get_model_matrix(),
-                view_matrix,
-                projection_matrix,
-                colour: object.borrow_material().colour.into(),
-                selected: if object.selected { 1.0 } else { 0.0 },
-            });
-
-            ctx.draw(
-                object.borrow_mesh().buffer_offset.try_into().unwrap(),
-                (object.borrow_mesh().tris * 3).try_into().unwrap(),
-                1,
-            );
-        }
+        ctx.apply_pipeline(&self.pipeline);
+        ctx.apply_uniforms(&shader::Uniforms {
+            view_matrix: camera.view_matrix(),
+            projection_matrix: camera.projection_matrix(),
+        });
+        self.instance_buffers
+            .draw_batches(ctx, mesh_arena, &batches);
     }
 }
 
 mod shader {
     use miniquad::*;
 
-    pub const VERTEX: &str = r#"#version 100
-    attribute vec2 pos;
+    pub fn vertex() -> String {
+        format!(
+            r#"#version 100
+    {}
 
-    uniform mat4 model_matrix;
-    uniform mat4 view_matrix;
-    uniform mat4 projection_matrix;
+    attribute vec4 inst_colour;
 
-    void main() {
-        gl_Position = projection_matrix * view_matrix * model_matrix * vec4(pos, 0, 1);
-    }"#;
+    varying lowp vec4 colour;
+    varying lowp float selected;
+
+    void main() {{
+        colour = inst_colour;
+        selected = inst_translation.w;
+        gl_Position = instance_position();
+    }}"#,
+            super::instances::VERTEX_TRANSFORM
+        )
+    }
 
     pub const FRAGMENT: &str = r#"#version 100
-    uniform highp vec4 colour;
-    uniform lowp float selected;
+    varying lowp vec4 colour;
+    varying lowp float selected;
 
     void main() {
         if (selected > 0.5) {
@@ -151,11 +100,8 @@ mod shader {
             images: vec![],
             uniforms: UniformBlockLayout {
                 uniforms: vec![
-                    UniformDesc::new("model_matrix", UniformType::Mat4),
                     UniformDesc::new("view_matrix", UniformType::Mat4),
                     UniformDesc::new("projection_matrix", UniformType::Mat4),
-                    UniformDesc::new("colour", UniformType::Float4),
-                    UniformDesc::new("selected", UniformType::Float1),
                 ],
             },
         }
@@ -163,10 +109,7 @@ mod shader {
 
     #[repr(C)]
     pub struct Uniforms {
-        pub model_matrix: glam::Mat4,
         pub view_matrix: glam::Mat4,
         pub projection_matrix: glam::Mat4,
-        pub colour: [f32; 4],
-        pub selected: f32,
     }
 }
@@ -1,28 +1,100 @@
-use std::sync::{Arc, Mutex};
-
 use glam::Vec2;
 use miniquad::{
     Bindings, Buffer, BufferLayout, BufferType, Context, Pipeline, Shader, VertexAttribute,
     VertexFormat,
 };
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    data::vertex::{Index, Vertex},
+    opengl::{camera::Camera2D, structs::Colour},
+};
+
+/// Size in pixels the major squares are kept near, by halving or doubling them as the view zooms
+const TARGET_SCREEN_SIZE: f32 = 80.0;
+/// Height of an equilateral triangle with sides of 1
+const TRIANGLE_HEIGHT: f32 = 0.866_025_4;
+
+/// How the viewport grid looks, saved with the document
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GridSettings {
+    /// Size of the major squares at zoom 1, in world units
+    pub spacing: f32,
+    /// Minor squares along each side of a major square
+    pub subdivisions: u32,
+    pub major_colour: Colour,
+    pub minor_colour: Colour,
+    /// Width of the major lines, in pixels
+    pub major_width: f32,
+    /// Width of the minor lines, in pixels
+    pub minor_width: f32,
+    pub show_axes: bool,
+    pub background: Colour,
+    /// Triangles with sides of `spacing` instead of squares
+    pub isometric: bool,
+}
+
+impl Default for GridSettings {
+    fn default() -> Self {
+        Self {
+            spacing: 100.0,
+            subdivisions: 2,
+            major_colour: Colour::new(0.5, 0.5, 0.5, 1.0),
+            minor_colour: Colour::new(0.125, 0.125, 0.125, 1.0),
+            major_width: 1.5,
+            minor_width: 1.5,
+            show_axes: true,
+            background: Colour::new(0.0, 0.0, 0.0, 1.0),
+            isometric: false,
+        }
+    }
+}
 
-use crate::data::vertex::{Index, Vertex};
+impl GridSettings {
+    /// Size of the major squares drawn at `zoom`
+    ///
+    /// Follows the level of detail the fragment shader picks, so snapping lands on visible lines.
+    pub fn major_size(&self, zoom: f32) -> f32 {
+        let lod_level = (self.spacing * zoom / TARGET_SCREEN_SIZE).log2().floor();
+        self.spacing * 2f32.powf(-lod_level)
+    }
+
+    /// Spacing of the finest lines drawn at `zoom`
+    pub fn visible_step(&self, zoom: f32) -> f32 {
+        self.major_size(zoom) / self.subdivisions.max(1) as f32
+    }
+
+    /// The grid point drawn at `zoom` nearest to `point`
+    pub fn snap(&self, point: Vec2, zoom: f32) -> Vec2 {
+        let step = self.visible_step(zoom);
+        if !self.isometric {
+            return (point / step).round() * step;
+        }
+
+        // Rows of points a triangle's height apart, every other row shifted by half a side
+        let height = step * TRIANGLE_HEIGHT;
+        let row = (point.y / height).floor();
+        [row, row + 1.0]
+            .map(|row| {
+                let shift = row * step / 2.0;
+                let column = ((point.x - shift) / step).round();
+                Vec2::new(column * step + shift, row * height)
+            })
+            .into_iter()
+            .min_by(|a, b| a.distance(point).total_cmp(&b.distance(point)))
+            .unwrap()
+    }
+}
 
 pub struct GridPipeline {
     pipeline: Pipeline,
     bindings: Bindings,
     vertex_buffer: Buffer,
     index_buffer: Buffer,
-    position: Arc<Mutex<Vec2>>,
-    zoom: Arc<Mutex<f32>>,
 }
 
 impl GridPipeline {
-    pub fn new(
-        ctx: &mut Context,
-        position: Arc<Mutex<Vec2>>,
-        zoom: Arc<Mutex<f32>>,
-    ) -> GridPipeline {
+    pub fn new(ctx: &mut Context) -> GridPipeline {
         #[rustfmt::skip]
         let vertices: [Vertex; 4] = [Vertex{pos: Vec2::new(-1.0, -1.0)},Vertex{pos: Vec2::new(1.0, -1.0)},Vertex{pos: Vec2::new(-1.0, 1.0)},Vertex{pos: Vec2::new(1.0, 1.0)}];
         let vertex_buffer = Buffer::immutable(ctx, BufferType::VertexBuffer, &vertices);
@@ -49,21 +121,27 @@ impl GridPipeline {
             bindings,
             index_buffer,
             vertex_buffer,
-            position,
-            zoom,
         }
     }
 
-    pub fn draw(&mut self, ctx: &mut Context) {
+    pub fn draw(&mut self, ctx: &mut Context, camera: &Camera2D, settings: &GridSettings) {
         ctx.apply_pipeline(&self.pipeline);
         ctx.apply_bindings(&self.bindings);
-        let position = *(self.position.lock().unwrap());
-        let zoom = *(self.zoom.lock().unwrap());
         ctx.apply_uniforms(&shader::Uniforms {
-            u_resolution: ctx.screen_size().into(),
-            u_position: position,
-            u_zoom: zoom,
-            u_square_size: 100.0,
+            u_resolution: camera.viewport,
+            u_position: camera.position,
+            u_zoom: camera.zoom,
+            u_rotation: camera.rotation,
+            u_square_size: settings.spacing,
+            u_target_size: TARGET_SCREEN_SIZE,
+            u_subdivisions: settings.subdivisions.max(1) as f32,
+            u_major_colour: settings.major_colour.to_array(),
+            u_minor_colour: settings.minor_colour.to_array(),
+            u_major_width: settings.major_width,
+            u_minor_width: settings.minor_width,
+            u_show_axes: if settings.show_axes { 1.0 } else { 0.0 },
+            u_background: settings.background.to_array(),
+            u_isometric: if settings.isometric { 1.0 } else { 0.0 },
         });
 
         ctx.draw(0, 6, 1);
@@ -88,27 +166,48 @@ mod shader {
     uniform vec2 u_resolution;
     uniform vec2 u_position;
     uniform float u_zoom;
+    uniform float u_rotation;
     uniform float u_square_size;
-
-    float getGrid(vec2 uv, float size) {
-        // Line thickness in screen pixels
-        float lineWidth = 1.5;
-
+    uniform float u_target_size;
+    uniform float u_subdivisions;
+    uniform vec4 u_major_colour;
+    uniform vec4 u_minor_colour;
+    uniform float u_major_width;
+    uniform float u_minor_width;
+    uniform float u_show_axes;
+    uniform vec4 u_background;
+    uniform float u_isometric;
+
+    // Height of an equilateral triangle with sides of 1
+    const float TRIANGLE_HEIGHT = 0.8660254;
+
+    // Coverage of parallel lines `spacing` apart, measured along `d`
+    float getLines(float d, float spacing, float lineWidth) {
         // Normalize coordinates immediately to avoid precision issues
-        // fract returns the fractional part, keeping values in [0, 1)
-        vec2 coord = fract(uv / size);
+        float scaled = d / spacing;
+        float coord = fract(scaled);
 
         // Distance to nearest grid line (either at 0 or 1)
-        vec2 distToLine = min(coord, 1.0 - coord);
-        float dist = min(distToLine.x, distToLine.y);
+        float dist = min(coord, 1.0 - coord);
 
         // Use derivatives to get pixel-space distance
-        vec2 derivative = fwidth(coord);
-        float pixelDist = dist / max(derivative.x, derivative.y);
+        float pixelDist = dist / fwidth(scaled);
 
         return 1.0 - smoothstep(0.0, lineWidth, pixelDist);
     }
 
+    float getGrid(vec2 uv, float size, float lineWidth) {
+        if (u_isometric > 0.5) {
+            // Three sets of lines a triangle's height apart, at 60 degrees to each other
+            float height = size * TRIANGLE_HEIGHT;
+            float horizontal = getLines(uv.y, height, lineWidth);
+            float rising = getLines(dot(uv, vec2(TRIANGLE_HEIGHT, -0.5)), height, lineWidth);
+            float falling = getLines(dot(uv, vec2(TRIANGLE_HEIGHT, 0.5)), height, lineWidth);
+            return max(horizontal, max(rising, falling));
+        }
+        return max(getLines(uv.x, size, lineWidth), getLines(uv.y, size, lineWidth));
+    }
+
     float getAxis(vec2 uv, int axis) {
         // Axis line thickness in screen pixels
         float lineWidth = 6.0;
@@ -132,31 +231,37 @@ mod shader {
         // Calculate screen-space size of a square
         float screenSquareSize = baseSquareSize * u_zoom;
 
-        // Target screen size for squares (in pixels)
-        float targetScreenSize = 80.0;
-
-        // Calculate how many times we need to double/halve
-        float lodLevel = floor(log2(screenSquareSize / targetScreenSize));
+        // Calculate how many times we need to double/halve to stay near the target size
+        float lodLevel = floor(log2(screenSquareSize / u_target_size));
 
         // Apply LOD scaling
         float lodScale = pow(2.0, -lodLevel);
         float squareSize = baseSquareSize * lodScale;
-        float smallSquareSize = squareSize / 2.0;
+        float smallSquareSize = squareSize / u_subdivisions;
 
         // Convert screen space to world space centered at screen center
         vec2 screenCenter = u_resolution / 2.0;
-        vec2 uv = (gl_FragCoord.xy - screenCenter) / u_zoom - u_position.xy;
-
-        float big = getGrid(uv, squareSize);
-        float small = getGrid(uv, smallSquareSize);
-        float xAxis = getAxis(uv, 1);
-        float yAxis = getAxis(uv, 0);
-        vec3 axis = vec3(xAxis, yAxis, 0.0);
-
-        vec3 grid = vec3(max(big / 2.0, small / 8.0));
-        vec3 gridCol = vec3(grid);
-        float mask = max(axis.x, axis.y);
-        gl_FragColor = vec4(mix(grid , axis, mask), 1.0);
+        vec2 centred = (gl_FragCoord.xy - screenCenter) / u_zoom;
+        float c = cos(u_rotation);
+        float s = sin(u_rotation);
+        vec2 uv = vec2(c * centred.x - s * centred.y, s * centred.x + c * centred.y) - u_position.xy;
+
+        float big = getGrid(uv, squareSize, u_major_width);
+        float small = u_subdivisions > 1.0 ? getGrid(uv, smallSquareSize, u_minor_width) : 0.0;
+
+        vec3 colour = u_background.rgb;
+        colour = mix(colour, u_minor_colour.rgb, small * u_minor_colour.a);
+        colour = mix(colour, u_major_colour.rgb, big * u_major_colour.a);
+
+        if (u_show_axes > 0.5) {
+            float xAxis = getAxis(uv, 1);
+            float yAxis = getAxis(uv, 0);
+            vec3 axis = vec3(xAxis, yAxis, 0.0);
+            float mask = max(axis.x, axis.y);
+            colour = mix(colour, axis, mask);
+        }
+
+        gl_FragColor = vec4(colour, 1.0);
     }
     "#;
 
@@ -168,7 +273,17 @@ mod shader {
                     UniformDesc::new("u_resolution", UniformType::Float2),
                     UniformDesc::new("u_position", UniformType::Float2),
                     UniformDesc::new("u_zoom", UniformType::Float1),
+                    UniformDesc::new("u_rotation", UniformType::Float1),
                     UniformDesc::new("u_square_size", UniformType::Float1),
+                    UniformDesc::new("u_target_size", UniformType::Float1),
+                    UniformDesc::new("u_subdivisions", UniformType::Float1),
+                    UniformDesc::new("u_major_colour", UniformType::Float4),
+                    UniformDesc::new("u_minor_colour", UniformType::Float4),
+                    UniformDesc::new("u_major_width", UniformType::Float1),
+                    UniformDesc::new("u_minor_width", UniformType::Float1),
+                    UniformDesc::new("u_show_axes", UniformType::Float1),
+                    UniformDesc::new("u_background", UniformType::Float4),
+                    UniformDesc::new("u_isometric", UniformType::Float1),
                 ],
             },
         }
@@ -179,6 +294,59 @@ mod shader {
         pub u_resolution: glam::Vec2,
         pub u_position: glam::Vec2,
         pub u_zoom: f32,
+        pub u_rotation: f32,
         pub u_square_size: f32,
+        pub u_target_size: f32,
+        pub u_subdivisions: f32,
+        pub u_major_colour: [f32; 4],
+        pub u_minor_colour: [f32; 4],
+        pub u_major_width: f32,
+        pub u_minor_width: f32,
+        pub u_show_axes: f32,
+        pub u_background: [f32; 4],
+        pub u_isometric: f32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grid_step_halves_as_the_view_zooms_in() {
+        let settings = GridSettings::default();
+        assert_eq!(settings.visible_step(1.0), 50.0);
+        assert_eq!(settings.visible_step(1.5), 50.0);
+        assert_eq!(settings.visible_step(2.0), 25.0);
+        assert_eq!(settings.visible_step(0.5), 100.0);
+        assert_eq!(settings.visible_step(0.15), 400.0);
+
+        let settings = GridSettings {
+            spacing: 10.0,
+            subdivisions: 5,
+            ..Default::default()
+        };
+        assert_eq!(settings.major_size(1.0), 80.0);
+        assert_eq!(settings.visible_step(1.0), 16.0);
+        assert_eq!(
+            settings.snap(Vec2::new(-9.0, 25.0), 1.0),
+            Vec2::new(-16.0, 32.0)
+        );
+    }
+
+    #[test]
+    fn isometric_grid_snaps_to_triangle_corners() {
+        let settings = GridSettings {
+            isometric: true,
+            ..Default::default()
+        };
+        let height = 50.0 * TRIANGLE_HEIGHT;
+
+        assert_eq!(settings.snap(Vec2::new(4.0, 3.0), 1.0), Vec2::ZERO);
+        // Every other row is shifted by half a side
+        let snapped = settings.snap(Vec2::new(20.0, height - 5.0), 1.0);
+        assert!(snapped.abs_diff_eq(Vec2::new(25.0, height), 1e-4));
+        let snapped = settings.snap(Vec2::new(-70.0, 2.0 * height + 4.0), 1.0);
+        assert!(snapped.abs_diff_eq(Vec2::new(-50.0, 2.0 * height), 1e-4));
     }
 }
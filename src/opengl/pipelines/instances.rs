@@ -0,0 +1,273 @@
+//! Per-instance data shared by the instanced pipelines
+//!
+//! Objects are grouped by the mesh they draw, and every object in a group becomes one instance
+//! in that group's instance buffer, so a whole group draws with a single instanced call.
+//!
+//! miniquad cannot offset into an instance buffer when drawing, so each group gets its own
+//! stream buffer. Buffers are kept between frames and only reallocated when a group outgrows
+//! its buffer.
+
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use miniquad::{
+    Bindings, Buffer, BufferLayout, BufferType, Context, VertexAttribute, VertexFormat, VertexStep,
+};
+
+use crate::opengl::{
+    mesh_arena::GpuMeshArena,
+    scene::{ObjectKey, SceneData},
+    structs::{Mesh, Object},
+};
+
+/// Attributes of a single instance, matching [`attributes`]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Instance {
+    /// Linear part of the model matrix, as its two columns
+    pub transform: [f32; 4],
+    /// Translation of the model matrix, then depth and the selected flag
+    pub translation: [f32; 4],
+    pub colour: [f32; 4],
+}
+
+impl Instance {
+    pub fn new(object: &Object, depth: f32, colour: [f32; 4]) -> Self {
+        let matrix = object.get_model_matrix();
+
+        Self {
+            transform: [
+                matrix.x_axis.x,
+                matrix.x_axis.y,
+                matrix.y_axis.x,
+                matrix.y_axis.y,
+            ],
+            translation: [
+                matrix.w_axis.x,
+                matrix.w_axis.y,
+                depth,
+                if object.selected { 1.0 } else { 0.0 },
+            ],
+            colour,
+        }
+    }
+}
+
+/// Buffer layouts for a pipeline with mesh vertices in buffer 0 and instances in buffer 1
+pub fn buffer_layouts() -> [BufferLayout; 2] {
+    [
+        BufferLayout::default(),
+        BufferLayout {
+            step_func: VertexStep::PerInstance,
+            ..Default::default()
+        },
+    ]
+}
+
+/// Vertex attributes for a pipeline using [`buffer_layouts`]
+pub fn attributes() -> [VertexAttribute; 4] {
+    [
+        VertexAttribute::with_buffer("pos", VertexFormat::Float2, 0),
+        VertexAttribute::with_buffer("inst_transform", VertexFormat::Float4, 1),
+        VertexAttribute::with_buffer("inst_translation", VertexFormat::Float4, 1),
+        VertexAttribute::with_buffer("inst_colour", VertexFormat::Float4, 1),
+    ]
+}
+
+/// GLSL that computes `gl_Position` from the instance attributes
+pub const VERTEX_TRANSFORM: &str = r#"
+    attribute vec2 pos;
+    attribute vec4 inst_transform;
+    attribute vec4 inst_translation;
+
+    uniform mat4 view_matrix;
+    uniform mat4 projection_matrix;
+
+    vec4 instance_position() {
+        vec2 world = inst_transform.xy * pos.x + inst_transform.zw * pos.y + inst_translation.xy;
+        return projection_matrix * view_matrix * vec4(world, inst_translation.z, 1);
+    }"#;
+
+/// All instances of one mesh
+pub struct InstanceBatch {
+    /// First index of the mesh in the mesh arena
+    pub base_element: i32,
+    pub num_elements: i32,
+    pub instances: Vec<Instance>,
+}
+
+/// Group objects by the mesh they draw, keeping the order objects appear in within each group
+///
+/// `instance` is given each object and its position in `keys`.
+pub fn batch_instances(
+    scene_data: &SceneData,
+    keys: &[ObjectKey],
+    mut instance: impl FnMut(&Object, usize) -> Instance,
+) -> Vec<InstanceBatch> {
+    let mut batches: Vec<InstanceBatch> = vec![];
+    let mut batch_indices: HashMap<*const RefCell<Mesh>, usize> = HashMap::new();
+
+    for (index, &key) in keys.iter().enumerate() {
+        let Some(object) = scene_data.objects().get(key) else {
+            continue;
+        };
+
+        let batch = *batch_indices
+            .entry(Rc::as_ptr(object.evaluated_mesh()))
+            .or_insert_with(|| {
+                let mesh = object.borrow_evaluated_mesh();
+                batches.push(InstanceBatch {
+                    base_element: mesh.buffer_offset as i32,
+                    num_elements: (mesh.tris * 3) as i32,
+                    instances: vec![],
+                });
+                batches.len() - 1
+            });
+
+        batches[batch].instances.push(instance(object, index));
+    }
+
+    batches
+}
+
+/// Depth that puts the object at `index` of `count` above every object before it
+///
+/// Larger depths are nearer the camera with the orthographic projection, and stay inside its
+/// -1 to 1 depth range.
+pub fn order_depth(index: usize, count: usize) -> f32 {
+    (index + 1) as f32 / (count + 1) as f32
+}
+
+/// Instance buffers reused from frame to frame, one per batch
+#[derive(Default)]
+pub struct InstanceBuffers {
+    buffers: Vec<Buffer>,
+}
+
+impl InstanceBuffers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Upload every batch and draw each one with a single instanced call
+    ///
+    /// The pipeline and its uniforms must already be applied.
+    pub fn draw_batches(
+        &mut self,
+        ctx: &mut Context,
+        mesh_arena: &GpuMeshArena,
+        batches: &[InstanceBatch],
+    ) {
+        let vertex_buffer = mesh_arena.vertex_buffer();
+        let index_buffer = mesh_arena.index_buffer();
+
+        let mut reallocated = false;
+        let buffers = batches
+            .iter()
+            .enumerate()
+            .map(|(i, batch)| {
+                reallocated |= self.reserve(ctx, i, std::mem::size_of_val(&batch.instances[..]));
+                self.buffers[i].update(ctx, &batch.instances);
+                self.buffers[i]
+            })
+            .collect::<Vec<_>>();
+
+        if reallocated {
+            // miniquad caches attribute bindings by buffer name, and a new buffer can reuse the
+            // name of one just deleted. Bind something else first so every attribute is rebound.
+            ctx.apply_bindings(&Bindings {
+                vertex_buffers: vec![vertex_buffer, vertex_buffer],
+                index_buffer,
+                images: vec![],
+            });
+        }
+
+        for (batch, buffer) in batches.iter().zip(buffers) {
+            ctx.apply_bindings(&Bindings {
+                vertex_buffers: vec![vertex_buffer, buffer],
+                index_buffer,
+                images: vec![],
+            });
+            ctx.draw(
+                batch.base_element,
+                batch.num_elements,
+                batch.instances.len() as i32,
+            );
+        }
+    }
+
+    /// Make sure the buffer for a batch can hold `size` bytes
+    /// Returns true if the buffer had to be created or replaced
+    fn reserve(&mut self, ctx: &mut Context, batch: usize, size: usize) -> bool {
+        if batch < self.buffers.len() && self.buffers[batch].size() >= size {
+            return false;
+        }
+
+        let capacity = size
+            .next_power_of_two()
+            .max(std::mem::size_of::<Instance>() * 64);
+        let buffer = Buffer::stream(ctx, BufferType::VertexBuffer, capacity);
+
+        if batch < self.buffers.len() {
+            self.buffers[batch].delete();
+            self.buffers[batch] = buffer;
+        } else {
+            self.buffers.push(buffer);
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec2;
+
+    use crate::{
+        opengl::structs::{Colour, Material},
+        shapes::{square::create_square, star::create_star},
+    };
+
+    use super::*;
+
+    #[test]
+    fn batches_objects_by_mesh() {
+        let (square, _, _) = Mesh::new(create_square(), 0);
+        let (star, _, _) = Mesh::new(create_star(), 0);
+        let material = Rc::new(RefCell::new(Material {
+            colour: Colour::ONE,
+        }));
+
+        let objects = [&square, &star, &square, &star, &square]
+            .iter()
+            .enumerate()
+            .map(|(i, mesh)| {
+                Object::new(
+                    (*mesh).clone(),
+                    Vec2::new(i as f32, 0.0),
+                    0.0,
+                    Vec2::ONE,
+                    material.clone(),
+                )
+            })
+            .collect();
+        let scene_data = SceneData::new(objects);
+
+        let batches = batch_instances(&scene_data, scene_data.object_order(), |object, i| {
+            Instance::new(object, order_depth(i, 5), [0.0; 4])
+        });
+
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0].instances.len(), 3);
+        assert_eq!(batches[1].instances.len(), 2);
+        assert_eq!(batches[1].num_elements, (star.borrow().tris * 3) as i32);
+
+        // Instances keep their translation and their depth follows draw order
+        let xs = batches[0]
+            .instances
+            .iter()
+            .map(|instance| instance.translation[0])
+            .collect::<Vec<_>>();
+        assert_eq!(xs, [0.0, 2.0, 4.0]);
+        assert!(batches[0].instances[1].translation[2] > batches[1].instances[0].translation[2]);
+    }
+}
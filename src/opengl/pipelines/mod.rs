@@ -1,3 +1,5 @@
 pub mod flat;
 pub mod grid;
+pub mod instances;
 pub mod outline;
+pub mod overlay;
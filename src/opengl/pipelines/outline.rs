@@ -29,14 +29,11 @@
 //!     height as u32,
 //! );
 //!
-//! // Update mesh data (same as other pipelines)
-//! outline_pipeline.update(ctx, meshes);
-//!
 //! // In resize event:
 //! outline_pipeline.resize(ctx, width as u32, height as u32);
 //!
 //! // In draw loop (after drawing regular objects):
-//! outline_pipeline.draw(ctx, &scene_data, projection_matrix, view_matrix);
+//! outline_pipeline.draw(ctx, &scene_data, &mesh_arena, projection_matrix, view_matrix);
 //! ```
 //!
 //! ## 2D Simplifications vs Blender
@@ -47,35 +44,29 @@
 //! - Stencil buffer for complex masking
 //!
 //! For 2D, we simplify:
-//! - No depth buffer needed (2D has natural painter's algorithm ordering)
+//! - The depth buffer only keeps draw order, since objects are drawn one batch per mesh
+//!   rather than bottom to top
 //! - Single outline style (boundary detection)
 //! - Direct alpha blending instead of complex compositing
 //!
 //! ## Performance Characteristics
 //!
-//! - ID Pass: O(n) where n = number of selected object triangles
+//! - ID Pass: O(n) where n = number of selected object triangles, in one draw call per mesh
 //! - Outline Pass: O(p) where p = number of screen pixels
-//! - Memory: One RGBA8 texture at screen resolution
+//! - Memory: One RGBA8 and one depth texture at screen resolution
 //! - Bandwidth: Two full-screen passes per frame (only when objects are selected)
 
-use std::{
-    cell::RefCell,
-    rc::Rc,
-    sync::{Arc, Mutex},
-};
-
-use glam::{Mat4, Vec2};
+use glam::Vec2;
 use miniquad::{
-    Bindings, Buffer, BufferLayout, BufferType, Context, PassAction, Pipeline, RenderPass, Shader,
-    Texture, TextureFormat, TextureParams, VertexAttribute, VertexFormat,
+    Bindings, Buffer, BufferLayout, BufferType, Comparison, Context, PassAction, Pipeline,
+    PipelineParams, RenderPass, Shader, Texture, TextureFormat, TextureParams, VertexAttribute,
+    VertexFormat,
 };
 
+use super::instances::{self, batch_instances, order_depth, Instance, InstanceBuffers};
 use crate::{
     data::vertex::{Index, Vertex},
-    opengl::{
-        scene::{ObjectKey, SceneData},
-        structs::Mesh,
-    },
+    opengl::{camera::Camera2D, mesh_arena::GpuMeshArena, scene::SceneData},
 };
 
 /// Pipeline for rendering object outlines using edge detection
@@ -86,57 +77,29 @@ use crate::{
 pub struct OutlinePipeline {
     // ID rendering pass (renders to texture)
     id_pipeline: Pipeline,
-    id_bindings: Bindings,
     id_render_pass: RenderPass,
     id_texture: Texture,
+    /// Keeps the ID of the topmost object where selected objects overlap
+    id_depth: Texture,
 
     // Outline generation pass (renders to screen)
     outline_pipeline: Pipeline,
     outline_bindings: Bindings,
 
-    // Shared buffers
-    vertex_buffer: Buffer,
-    index_buffer: Buffer,
+    instance_buffers: InstanceBuffers,
 
     // Full-screen quad for outline pass
     quad_vertex_buffer: Buffer,
     quad_index_buffer: Buffer,
 
-    // Matrices
-    projection_matrix: Arc<Mutex<Mat4>>,
-    view_matrix: Arc<Mutex<Mat4>>,
-
     // Screen dimensions (for texture sizing)
     width: u32,
     height: u32,
 }
 
 impl OutlinePipeline {
-    pub fn new(
-        ctx: &mut Context,
-        projection_matrix: Arc<Mutex<Mat4>>,
-        view_matrix: Arc<Mutex<Mat4>>,
-        width: u32,
-        height: u32,
-    ) -> Self {
-        // Create ID texture (RGBA8 format for object IDs)
-        let id_texture = Texture::new_render_texture(
-            ctx,
-            TextureParams {
-                width,
-                height,
-                format: TextureFormat::RGBA8,
-                ..Default::default()
-            },
-        );
-
-        let id_render_pass = RenderPass::new(ctx, id_texture, None);
-
-        // Initialize empty vertex and index buffers for objects
-        let vertices: [Vertex; 0] = [];
-        let vertex_buffer = Buffer::immutable(ctx, BufferType::VertexBuffer, &vertices);
-        let indices: [Index; 0] = [];
-        let index_buffer = Buffer::immutable(ctx, BufferType::IndexBuffer, &indices);
+    pub fn new(ctx: &mut Context, width: u32, height: u32) -> Self {
+        let (id_texture, id_depth, id_render_pass) = id_targets(ctx, width, height);
 
         // Create full-screen quad for outline pass
         #[rustfmt::skip]
@@ -150,13 +113,6 @@ impl OutlinePipeline {
         let quad_indices: [Index; 6] = [0, 1, 2, 1, 2, 3];
         let quad_index_buffer = Buffer::immutable(ctx, BufferType::IndexBuffer, &quad_indices);
 
-        // Create ID pass bindings
-        let id_bindings = Bindings {
-            vertex_buffers: vec![vertex_buffer],
-            index_buffer,
-            images: vec![],
-        };
-
         // Create outline pass bindings (includes the ID texture)
         let outline_bindings = Bindings {
             vertex_buffers: vec![quad_vertex_buffer],
@@ -167,17 +123,22 @@ impl OutlinePipeline {
         // Create shaders and pipelines
         let id_shader = Shader::new(
             ctx,
-            id_shader::VERTEX,
+            &id_shader::vertex(),
             id_shader::FRAGMENT,
             id_shader::meta(),
         )
         .unwrap();
 
-        let id_pipeline = Pipeline::new(
+        let id_pipeline = Pipeline::with_params(
             ctx,
-            &[BufferLayout::default()],
-            &[VertexAttribute::new("pos", VertexFormat::Float2)],
+            &instances::buffer_layouts(),
+            &instances::attributes(),
             id_shader,
+            PipelineParams {
+                depth_test: Comparison::LessOrEqual,
+                depth_write: true,
+                ..Default::default()
+            },
         );
 
         let outline_shader = Shader::new(
@@ -193,7 +154,7 @@ impl OutlinePipeline {
             &[BufferLayout::default()],
             &[VertexAttribute::new("pos", VertexFormat::Float2)],
             outline_shader,
-            miniquad::PipelineParams {
+            PipelineParams {
                 color_blend: Some(miniquad::BlendState::new(
                     miniquad::Equation::Add,
                     miniquad::BlendFactor::Value(miniquad::BlendValue::SourceAlpha),
@@ -205,74 +166,34 @@ impl OutlinePipeline {
 
         Self {
             id_pipeline,
-            id_bindings,
             id_render_pass,
             id_texture,
+            id_depth,
             outline_pipeline,
             outline_bindings,
-            vertex_buffer,
-            index_buffer,
+            instance_buffers: InstanceBuffers::new(),
             quad_vertex_buffer,
             quad_index_buffer,
-            projection_matrix,
-            view_matrix,
             width,
             height,
         }
     }
 
-    /// Update the mesh data for rendering
-    pub fn update(&mut self, ctx: &mut Context, meshes: Vec<Rc<RefCell<Mesh>>>) {
-        let mut vertices: Vec<Vertex> = vec![];
-        let mut indices: Vec<Index> = vec![];
-
-        meshes.iter().for_each(|mesh| {
-            let (mesh_vertices, mesh_indices) = mesh.borrow_mut().update(indices.len() as Index);
-            vertices.extend_from_slice(&mesh_vertices);
-            indices.extend_from_slice(&mesh_indices);
-        });
-
-        self.vertex_buffer = Buffer::immutable(ctx, BufferType::VertexBuffer, &vertices);
-        self.index_buffer = Buffer::immutable(ctx, BufferType::IndexBuffer, &indices);
-
-        self.id_bindings = Bindings {
-            vertex_buffers: vec![self.vertex_buffer],
-            index_buffer: self.index_buffer,
-            images: vec![],
-        };
-    }
-
     /// Resize the render textures when the window size changes
     pub fn resize(&mut self, ctx: &mut Context, width: u32, height: u32) {
         if self.width != width || self.height != height {
             self.width = width;
             self.height = height;
 
-            // Delete old render pass first (it references the texture)
+            // Delete old render pass first (it references the textures)
             self.id_render_pass.delete(ctx);
 
-            // Now delete and recreate the texture
+            // Now delete and recreate the textures and the pass drawing to them
             self.id_texture.delete();
-            self.id_texture = Texture::new_render_texture(
-                ctx,
-                TextureParams {
-                    width,
-                    height,
-                    format: TextureFormat::RGBA8,
-                    ..Default::default()
-                },
-            );
-
-            // Create new render pass with new texture
-            self.id_render_pass = RenderPass::new(ctx, self.id_texture, None);
+            self.id_depth.delete();
+            (self.id_texture, self.id_depth, self.id_render_pass) = id_targets(ctx, width, height);
 
             // Update bindings with new texture
-            self.id_bindings = Bindings {
-                vertex_buffers: vec![self.vertex_buffer],
-                index_buffer: self.index_buffer,
-                images: vec![],
-            };
-
             self.outline_bindings = Bindings {
                 vertex_buffers: vec![self.quad_vertex_buffer],
                 index_buffer: self.quad_index_buffer,
@@ -286,10 +207,9 @@ impl OutlinePipeline {
         &mut self,
         ctx: &mut Context,
         scene_data: &SceneData,
-        projection_matrix: Mat4,
-        view_matrix: Mat4,
+        mesh_arena: &GpuMeshArena,
+        camera: &Camera2D,
     ) {
-        let objects = scene_data.objects();
         let selected_keys = scene_data.visible_selected_objects();
 
         // If no objects are selected, skip rendering
@@ -297,39 +217,35 @@ impl OutlinePipeline {
             return;
         }
 
-        // Pass 1: Render selected objects to ID texture
+        // Give every selected object a unique colour ID
+        // Use (id_index + 1) so we don't use pure black (0,0,0) as an ID
+        // Batches are drawn per mesh, so depth keeps later objects on top as in the flat pass
+        let batches = batch_instances(scene_data, &selected_keys, |object, id_index| {
+            let id = id_index + 1;
+            let r = ((id >> 16) & 0xFF) as f32 / 255.0;
+            let g = ((id >> 8) & 0xFF) as f32 / 255.0;
+            let b = (id & 0xFF) as f32 / 255.0;
+
+            Instance::new(
+                object,
+                order_depth(id_index, selected_keys.len()),
+                [r, g, b, 1.0],
+            )
+        });
+
+        // Pass 1: Render selected objects to ID texture, one instanced draw per mesh
         ctx.begin_pass(
             self.id_render_pass,
             PassAction::clear_color(0.0, 0.0, 0.0, 0.0),
         );
 
         ctx.apply_pipeline(&self.id_pipeline);
-        ctx.apply_bindings(&self.id_bindings);
-
-        // Render only selected objects with their unique IDs
-        for (id_index, &key) in selected_keys.iter().enumerate() {
-            let object = &objects[key];
-
-            // Convert object index to a unique color ID
-            // Use (id_index + 1) so we don't use pure black (0,0,0) as an ID
-            let id = id_index + 1;
-            let r = ((id >> 16) & 0xFF) as f32 / 255.0;
-            let g = ((id >> 8) & 0xFF) as f32 / 255.0;
-            let b = (id & 0xFF) as f32 / 255.0;
-
-            ctx.apply_uniforms(&id_shader::Uniforms {
-                model_matrix: object.get_model_matrix(),
-                view_matrix,
-                projection_matrix,
-                object_id: [r, g, b, 1.0],
-            });
-
-            ctx.draw(
-                object.borrow_mesh().buffer_offset.try_into().unwrap(),
-                (object.borrow_mesh().tris * 3).try_into().unwrap(),
-                1,
-            );
-        }
+        ctx.apply_uniforms(&id_shader::Uniforms {
+            view_matrix: camera.view_matrix(),
+            projection_matrix: camera.projection_matrix(),
+        });
+        self.instance_buffers
+            .draw_batches(ctx, mesh_arena, &batches);
 
         ctx.end_render_pass();
 
@@ -348,25 +264,49 @@ impl OutlinePipeline {
     }
 }
 
+/// Create the ID texture, its depth texture and the render pass that draws to them
+fn id_targets(ctx: &mut Context, width: u32, height: u32) -> (Texture, Texture, RenderPass) {
+    let [id_texture, id_depth] = [TextureFormat::RGBA8, TextureFormat::Depth].map(|format| {
+        Texture::new_render_texture(
+            ctx,
+            TextureParams {
+                width,
+                height,
+                format,
+                ..Default::default()
+            },
+        )
+    });
+    let id_render_pass = RenderPass::new(ctx, id_texture, Some(id_depth));
+
+    (id_texture, id_depth, id_render_pass)
+}
+
 /// Shader for rendering objects with unique ID colors
 mod id_shader {
     use miniquad::*;
 
-    pub const VERTEX: &str = r#"#version 100
-    attribute vec2 pos;
+    pub fn vertex() -> String {
+        format!(
+            r#"#version 100
+    {}
 
-    uniform mat4 model_matrix;
-    uniform mat4 view_matrix;
-    uniform mat4 projection_matrix;
+    attribute vec4 inst_colour;
 
-    void main() {
-        gl_Position = projection_matrix * view_matrix * model_matrix * vec4(pos, 0, 1);
-    }"#;
+    varying highp vec4 object_id;
+
+    void main() {{
+        object_id = inst_colour;
+        gl_Position = instance_position();
+    }}"#,
+            super::instances::VERTEX_TRANSFORM
+        )
+    }
 
     pub const FRAGMENT: &str = r#"#version 100
     precision highp float;
 
-    uniform vec4 object_id;
+    varying vec4 object_id;
 
     void main() {
         gl_FragColor = object_id;
@@ -377,10 +317,8 @@ mod id_shader {
             images: vec![],
             uniforms: UniformBlockLayout {
                 uniforms: vec![
-                    UniformDesc::new("model_matrix", UniformType::Mat4),
                     UniformDesc::new("view_matrix", UniformType::Mat4),
                     UniformDesc::new("projection_matrix", UniformType::Mat4),
-                    UniformDesc::new("object_id", UniformType::Float4),
                 ],
             },
         }
@@ -388,10 +326,8 @@ mod id_shader {
 
     #[repr(C)]
     pub struct Uniforms {
-        pub model_matrix: glam::Mat4,
         pub view_matrix: glam::Mat4,
         pub projection_matrix: glam::Mat4,
-        pub object_id: [f32; 4],
     }
 }
 
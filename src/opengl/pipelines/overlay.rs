@@ -0,0 +1,289 @@
+//! Edit mode overlay
+//!
+//! Draws the edges of the mesh being edited as lines and its vertices as small squares on top of
+//! the scene, with selected elements highlighted. In face mode each face gets a dot at its
+//! centre instead of the vertices.
+//!
+//! The geometry is rebuilt every frame into stream buffers, since it follows the selection and
+//! the mesh while they are being edited. Points are squares in screen pixels: every corner
+//! carries the element's world position and a pixel offset that the vertex shader adds after
+//! projecting, so they keep their size at any zoom.
+
+use glam::{Mat4, Vec2};
+use miniquad::{
+    Bindings, Buffer, BufferLayout, BufferType, Context, IndexType, Pipeline, PipelineParams,
+    PrimitiveType, Shader, VertexAttribute, VertexFormat,
+};
+
+use crate::{
+    data::{
+        mesh::{
+            bm_select::{bm_face_centre, MeshSelectMode},
+            bmesh::BMesh,
+        },
+        vertex::Index,
+    },
+    opengl::{camera::Camera2D, scene::SceneData},
+    operators::edit_mesh::EditMode,
+};
+
+/// Half the width of a vertex or face dot, in pixels
+const POINT_SIZE: f32 = 3.0;
+const EDGE_COLOUR: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+const POINT_COLOUR: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+const SELECTED_COLOUR: [f32; 4] = [1.0, 0.6, 0.1, 1.0];
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+struct OverlayVertex {
+    /// World position
+    pos: Vec2,
+    /// Pixels to move the vertex by after projecting it
+    offset: Vec2,
+    colour: [f32; 4],
+}
+
+/// Lines and dots to draw, with the line indices first
+#[derive(Default)]
+struct OverlayGeometry {
+    vertices: Vec<OverlayVertex>,
+    indices: Vec<Index>,
+    line_indices: usize,
+}
+
+impl OverlayGeometry {
+    fn build(bmesh: &BMesh, model_matrix: Mat4, select_mode: MeshSelectMode) -> Self {
+        let to_world = |pos: Vec2| model_matrix.transform_point3(pos.extend(0.0)).truncate();
+        let colour = |selected: bool| {
+            if selected {
+                SELECTED_COLOUR
+            } else {
+                EDGE_COLOUR
+            }
+        };
+        let mut geometry = Self::default();
+
+        // Selected elements last, so they are drawn over the others
+        let mut edges = bmesh.edges.values().collect::<Vec<_>>();
+        edges.sort_by_key(|edge| edge.selected);
+        for edge in edges {
+            let start = geometry.vertices.len() as Index;
+            for v in [edge.v0, edge.v1] {
+                geometry.vertices.push(OverlayVertex {
+                    pos: to_world(bmesh.vertices[v].vertex.pos),
+                    offset: Vec2::ZERO,
+                    colour: colour(edge.selected),
+                });
+            }
+            geometry.indices.extend([start, start + 1]);
+        }
+        geometry.line_indices = geometry.indices.len();
+
+        let mut points = match select_mode {
+            MeshSelectMode::Vertex => bmesh
+                .vertices
+                .values()
+                .map(|vert| (vert.vertex.pos, vert.selected))
+                .collect::<Vec<_>>(),
+            MeshSelectMode::Edge => vec![],
+            MeshSelectMode::Face => bmesh
+                .faces
+                .iter()
+                .map(|(f, face)| (bm_face_centre(bmesh, f), face.selected))
+                .collect(),
+        };
+        points.sort_by_key(|&(_, selected)| selected);
+        for (pos, selected) in points {
+            geometry.push_point(to_world(pos), selected);
+        }
+
+        geometry
+    }
+
+    fn push_point(&mut self, pos: Vec2, selected: bool) {
+        let start = self.vertices.len() as Index;
+        let colour = if selected {
+            SELECTED_COLOUR
+        } else {
+            POINT_COLOUR
+        };
+        for corner in [
+            Vec2::new(-1.0, -1.0),
+            Vec2::new(1.0, -1.0),
+            Vec2::new(1.0, 1.0),
+            Vec2::new(-1.0, 1.0),
+        ] {
+            self.vertices.push(OverlayVertex {
+                pos,
+                offset: corner * POINT_SIZE,
+                colour,
+            });
+        }
+        self.indices
+            .extend([start, start + 1, start + 2, start, start + 2, start + 3]);
+    }
+}
+
+pub struct OverlayPipeline {
+    line_pipeline: Pipeline,
+    point_pipeline: Pipeline,
+    vertex_buffer: Buffer,
+    index_buffer: Buffer,
+}
+
+impl OverlayPipeline {
+    pub fn new(ctx: &mut Context) -> OverlayPipeline {
+        let shader = Shader::new(ctx, shader::VERTEX, shader::FRAGMENT, shader::meta()).unwrap();
+        let attributes = [
+            VertexAttribute::new("pos", VertexFormat::Float2),
+            VertexAttribute::new("offset", VertexFormat::Float2),
+            VertexAttribute::new("colour_in", VertexFormat::Float4),
+        ];
+
+        let line_pipeline = Pipeline::with_params(
+            ctx,
+            &[BufferLayout::default()],
+            &attributes,
+            shader,
+            PipelineParams {
+                primitive_type: PrimitiveType::Lines,
+                ..Default::default()
+            },
+        );
+        let point_pipeline = Pipeline::with_params(
+            ctx,
+            &[BufferLayout::default()],
+            &attributes,
+            shader,
+            PipelineParams::default(),
+        );
+
+        OverlayPipeline {
+            line_pipeline,
+            point_pipeline,
+            vertex_buffer: Buffer::stream(ctx, BufferType::VertexBuffer, 0),
+            index_buffer: Buffer::index_stream(ctx, IndexType::Int, 0),
+        }
+    }
+
+    pub fn draw(
+        &mut self,
+        ctx: &mut Context,
+        scene_data: &SceneData,
+        edit_mode: &EditMode,
+        camera: &Camera2D,
+    ) {
+        let Some(object) = scene_data.objects().get(edit_mode.object()) else {
+            return;
+        };
+        let geometry = OverlayGeometry::build(
+            &object.borrow_mesh().raw_mesh,
+            object.get_model_matrix(),
+            edit_mode.select_mode(),
+        );
+        if geometry.indices.is_empty() {
+            return;
+        }
+
+        self.reserve(
+            ctx,
+            std::mem::size_of_val(&geometry.vertices[..]),
+            std::mem::size_of_val(&geometry.indices[..]),
+        );
+        self.vertex_buffer.update(ctx, &geometry.vertices);
+        self.index_buffer.update(ctx, &geometry.indices);
+
+        let bindings = Bindings {
+            vertex_buffers: vec![self.vertex_buffer],
+            index_buffer: self.index_buffer,
+            images: vec![],
+        };
+        let uniforms = shader::Uniforms {
+            view_matrix: camera.view_matrix(),
+            projection_matrix: camera.projection_matrix(),
+            resolution: camera.viewport,
+        };
+
+        let line_count = geometry.line_indices as i32;
+        let point_count = (geometry.indices.len() - geometry.line_indices) as i32;
+        for (pipeline, start, count) in [
+            (self.line_pipeline, 0, line_count),
+            (self.point_pipeline, line_count, point_count),
+        ] {
+            if count > 0 {
+                ctx.apply_pipeline(&pipeline);
+                ctx.apply_bindings(&bindings);
+                ctx.apply_uniforms(&uniforms);
+                ctx.draw(start, count, 1);
+            }
+        }
+    }
+
+    /// Grow the buffers to hold at least the given number of bytes
+    fn reserve(&mut self, ctx: &mut Context, vertex_size: usize, index_size: usize) {
+        if self.vertex_buffer.size() < vertex_size {
+            // Create the new buffer before deleting the old one so it never reuses its name,
+            // which miniquad's binding cache would mistake for the old buffer
+            let buffer = Buffer::stream(
+                ctx,
+                BufferType::VertexBuffer,
+                vertex_size.next_power_of_two(),
+            );
+            self.vertex_buffer.delete();
+            self.vertex_buffer = buffer;
+        }
+        if self.index_buffer.size() < index_size {
+            let buffer = Buffer::index_stream(ctx, IndexType::Int, index_size.next_power_of_two());
+            self.index_buffer.delete();
+            self.index_buffer = buffer;
+        }
+    }
+}
+
+mod shader {
+    use miniquad::*;
+
+    pub const VERTEX: &str = r#"#version 100
+    attribute vec2 pos;
+    attribute vec2 offset;
+    attribute vec4 colour_in;
+
+    uniform mat4 view_matrix;
+    uniform mat4 projection_matrix;
+    uniform vec2 resolution;
+
+    varying lowp vec4 colour;
+
+    void main() {
+        colour = colour_in;
+        vec4 position = projection_matrix * view_matrix * vec4(pos, 0, 1);
+        gl_Position = position + vec4(offset * 2.0 / resolution, 0, 0);
+    }"#;
+
+    pub const FRAGMENT: &str = r#"#version 100
+    varying lowp vec4 colour;
+
+    void main() {
+        gl_FragColor = colour;
+    }"#;
+
+    pub fn meta() -> ShaderMeta {
+        ShaderMeta {
+            images: vec![],
+            uniforms: UniformBlockLayout {
+                uniforms: vec![
+                    UniformDesc::new("view_matrix", UniformType::Mat4),
+                    UniformDesc::new("projection_matrix", UniformType::Mat4),
+                    UniformDesc::new("resolution", UniformType::Float2),
+                ],
+            },
+        }
+    }
+
+    #[repr(C)]
+    pub struct Uniforms {
+        pub view_matrix: glam::Mat4,
+        pub projection_matrix: glam::Mat4,
+        pub resolution: glam::Vec2,
+    }
+}
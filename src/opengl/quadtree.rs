@@ -0,0 +1,570 @@
+//! Dynamic quadtree over object bounds
+//!
+//! Each item is stored in the smallest node that fully contains its AABB, so large items sit
+//! near the root and small ones sink to the leaves. The root grows outwards when an item lands
+//! outside it, so the tree never needs to know the scene bounds up front.
+//!
+//! Removing items undoes both: a subtree that has emptied out enough is collapsed back into one
+//! node, and a root with only one quadrant in use gives way to that quadrant. Freed nodes are
+//! reused, so moving and deleting objects doesn't leave the tree full of empty nodes.
+
+use glam::Vec2;
+use slotmap::{Key, SecondaryMap};
+
+use super::frustum::AABB2D;
+
+/// Items a node holds before it splits into quadrants
+const MAX_ITEMS: usize = 16;
+/// Items a subtree holds before it collapses into a single node, well below [`MAX_ITEMS`] so
+/// items moving back and forth don't split and collapse the same node over and over
+const COLLAPSE_ITEMS: usize = MAX_ITEMS / 2;
+/// Deepest a node can be, so many identical AABBs can't split forever
+const MAX_DEPTH: u32 = 20;
+
+struct Node<K> {
+    bounds: AABB2D,
+    depth: u32,
+    items: Vec<(K, AABB2D)>,
+    children: Option<[usize; 4]>,
+    parent: Option<usize>,
+    /// Items in this node and all of its descendants
+    count: usize,
+}
+
+impl<K> Node<K> {
+    fn new(bounds: AABB2D, depth: u32, parent: Option<usize>) -> Self {
+        Self {
+            bounds,
+            depth,
+            items: vec![],
+            children: None,
+            parent,
+            count: 0,
+        }
+    }
+}
+
+pub struct QuadTree<K: Key> {
+    nodes: Vec<Node<K>>,
+    /// Indices in `nodes` that are not part of the tree and can be reused
+    free_nodes: Vec<usize>,
+    root: Option<usize>,
+    /// Node each item is stored in
+    locations: SecondaryMap<K, usize>,
+}
+
+impl<K: Key> Default for QuadTree<K> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Key> QuadTree<K> {
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![],
+            free_nodes: vec![],
+            root: None,
+            locations: SecondaryMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.locations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.locations.is_empty()
+    }
+
+    /// Insert an item, or move it if it is already in the tree
+    pub fn insert(&mut self, key: K, aabb: AABB2D) {
+        self.remove(key);
+
+        let root = match self.root {
+            Some(root) => root,
+            None => {
+                // Start with a square around the first item
+                let size = (aabb.max - aabb.min).max_element().max(1.0);
+                let centre = (aabb.min + aabb.max) * 0.5;
+                let bounds = AABB2D::from_center_extents(centre, Vec2::splat(size));
+                let root = self.add_node(Node::new(bounds, 0, None));
+                self.root = Some(root);
+                root
+            }
+        };
+
+        // Items with non-finite bounds can't be contained by growing, so they stay at the root
+        let mut root = root;
+        let finite = aabb.min.is_finite() && aabb.max.is_finite();
+        while finite && !self.nodes[root].bounds.contains(&aabb) {
+            root = self.grow(root, aabb);
+        }
+
+        self.insert_into(root, key, aabb);
+    }
+
+    pub fn remove(&mut self, key: K) {
+        let Some(node) = self.locations.remove(key) else {
+            return;
+        };
+
+        let items = &mut self.nodes[node].items;
+        if let Some(index) = items.iter().position(|(item, _)| *item == key) {
+            items.swap_remove(index);
+        }
+
+        // Collapse the largest subtree that has become small enough
+        let mut collapse = None;
+        let mut ancestor = Some(node);
+        while let Some(index) = ancestor {
+            let node = &mut self.nodes[index];
+            node.count -= 1;
+            if node.children.is_some() && node.count <= COLLAPSE_ITEMS {
+                collapse = Some(index);
+            }
+            ancestor = node.parent;
+        }
+        if let Some(index) = collapse {
+            self.collapse(index);
+        }
+
+        self.shrink_root();
+    }
+
+    pub fn clear(&mut self) {
+        self.nodes.clear();
+        self.free_nodes.clear();
+        self.root = None;
+        self.locations.clear();
+    }
+
+    /// Keys of items whose AABB passes `item_test`, skipping nodes that fail `node_test`
+    ///
+    /// `node_test` must pass any node containing an item that would pass `item_test`.
+    pub fn query(
+        &self,
+        node_test: impl Fn(&AABB2D) -> bool,
+        item_test: impl Fn(&AABB2D) -> bool,
+        results: &mut Vec<K>,
+    ) {
+        let Some(root) = self.root else {
+            return;
+        };
+
+        let mut stack = vec![root];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if !node_test(&node.bounds) {
+                continue;
+            }
+
+            results.extend(
+                node.items
+                    .iter()
+                    .filter(|(_, aabb)| item_test(aabb))
+                    .map(|(key, _)| *key),
+            );
+
+            if let Some(children) = node.children {
+                stack.extend(children);
+            }
+        }
+    }
+
+    /// Keys of items whose AABB overlaps `rect`
+    pub fn query_rect(&self, rect: &AABB2D, results: &mut Vec<K>) {
+        self.query(
+            |bounds| bounds.intersects(rect),
+            |aabb| aabb.intersects(rect),
+            results,
+        );
+    }
+
+    /// Keys of items whose AABB contains `point`
+    pub fn query_point(&self, point: Vec2, results: &mut Vec<K>) {
+        self.query(
+            |bounds| bounds.contains_point(point),
+            |aabb| aabb.contains_point(point),
+            results,
+        );
+    }
+
+    /// Make a new root twice the size of `root`, extending towards `aabb`
+    fn grow(&mut self, root: usize, aabb: AABB2D) -> usize {
+        let bounds = self.nodes[root].bounds;
+        let size = bounds.max - bounds.min;
+
+        // Grow towards the side the item sticks out of furthest
+        let grow_left = bounds.min.x - aabb.min.x > aabb.max.x - bounds.max.x;
+        let grow_down = bounds.min.y - aabb.min.y > aabb.max.y - bounds.max.y;
+        let min = Vec2::new(
+            if grow_left {
+                bounds.min.x - size.x
+            } else {
+                bounds.min.x
+            },
+            if grow_down {
+                bounds.min.y - size.y
+            } else {
+                bounds.min.y
+            },
+        );
+        let new_bounds = AABB2D::new(min, min + size * 2.0);
+
+        // The old root becomes one of the new root's quadrants, so everything below it moves
+        // down a level
+        self.set_depths(root, 1);
+
+        let mut node = Node::new(new_bounds, 0, None);
+        node.count = self.nodes[root].count;
+        let new_root = self.add_node(node);
+        self.nodes[root].parent = Some(new_root);
+
+        let old_quadrant = usize::from(grow_down) * 2 + usize::from(grow_left);
+        let mut children = [root; 4];
+        for (index, quadrant) in quadrants(&new_bounds).into_iter().enumerate() {
+            if index != old_quadrant {
+                children[index] = self.add_node(Node::new(quadrant, 1, Some(new_root)));
+            }
+        }
+        self.nodes[new_root].children = Some(children);
+        self.root = Some(new_root);
+
+        new_root
+    }
+
+    /// Undo growing while the root holds nothing itself and only one quadrant holds anything
+    fn shrink_root(&mut self) {
+        while let Some(root) = self.root {
+            if self.nodes[root].count == 0 {
+                self.clear();
+                return;
+            }
+            let Some(children) = self.nodes[root].children else {
+                return;
+            };
+            if !self.nodes[root].items.is_empty() {
+                return;
+            }
+            let mut used = children
+                .into_iter()
+                .filter(|&child| self.nodes[child].count > 0);
+            let (Some(child), None) = (used.next(), used.next()) else {
+                return;
+            };
+
+            for other in children {
+                if other != child {
+                    self.free_subtree(other);
+                }
+            }
+            self.free_nodes.push(root);
+            self.nodes[child].parent = None;
+            self.set_depths(child, 0);
+            self.root = Some(child);
+        }
+    }
+
+    /// Move every item below `node` into it and free its descendants
+    fn collapse(&mut self, node: usize) {
+        let Some(children) = self.nodes[node].children.take() else {
+            return;
+        };
+
+        let mut stack = children.to_vec();
+        while let Some(index) = stack.pop() {
+            let items = std::mem::take(&mut self.nodes[index].items);
+            for (key, _) in &items {
+                self.locations.insert(*key, node);
+            }
+            self.nodes[node].items.extend(items);
+            if let Some(children) = self.nodes[index].children.take() {
+                stack.extend(children);
+            }
+            self.free_nodes.push(index);
+        }
+    }
+
+    /// Free a node and its descendants, which must hold no items
+    fn free_subtree(&mut self, node: usize) {
+        let mut stack = vec![node];
+        while let Some(index) = stack.pop() {
+            if let Some(children) = self.nodes[index].children.take() {
+                stack.extend(children);
+            }
+            self.free_nodes.push(index);
+        }
+    }
+
+    /// Put a node in a free slot, or at the end if there is none
+    fn add_node(&mut self, node: Node<K>) -> usize {
+        match self.free_nodes.pop() {
+            Some(index) => {
+                self.nodes[index] = node;
+                index
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    /// Set the depth of `node` and everything below it
+    fn set_depths(&mut self, node: usize, depth: u32) {
+        let mut stack = vec![(node, depth)];
+        while let Some((index, depth)) = stack.pop() {
+            self.nodes[index].depth = depth;
+            if let Some(children) = self.nodes[index].children {
+                stack.extend(children.map(|child| (child, depth + 1)));
+            }
+        }
+    }
+
+    fn insert_into(&mut self, mut node: usize, key: K, aabb: AABB2D) {
+        // Walk down while a child fully contains the item
+        self.nodes[node].count += 1;
+        while let Some(children) = self.nodes[node].children {
+            match children
+                .iter()
+                .find(|&&child| self.nodes[child].bounds.contains(&aabb))
+            {
+                Some(&child) => {
+                    node = child;
+                    self.nodes[node].count += 1;
+                }
+                None => break,
+            }
+        }
+
+        self.nodes[node].items.push((key, aabb));
+        self.locations.insert(key, node);
+
+        if self.nodes[node].children.is_none()
+            && self.nodes[node].items.len() > MAX_ITEMS
+            && self.nodes[node].depth < MAX_DEPTH
+        {
+            self.split(node);
+        }
+    }
+
+    /// Give a leaf four children and push down the items that fit in one of them
+    fn split(&mut self, node: usize) {
+        let depth = self.nodes[node].depth + 1;
+        let children = quadrants(&self.nodes[node].bounds)
+            .map(|quadrant| self.add_node(Node::new(quadrant, depth, Some(node))));
+        self.nodes[node].children = Some(children);
+
+        let items = std::mem::take(&mut self.nodes[node].items);
+        for (key, aabb) in items {
+            let target = children
+                .iter()
+                .copied()
+                .find(|&child| self.nodes[child].bounds.contains(&aabb))
+                .unwrap_or(node);
+            self.nodes[target].items.push((key, aabb));
+            if target != node {
+                self.nodes[target].count += 1;
+            }
+            self.locations.insert(key, target);
+        }
+    }
+}
+
+fn quadrants(bounds: &AABB2D) -> [AABB2D; 4] {
+    let centre = (bounds.min + bounds.max) * 0.5;
+
+    [
+        AABB2D::new(bounds.min, centre),
+        AABB2D::new(
+            Vec2::new(centre.x, bounds.min.y),
+            Vec2::new(bounds.max.x, centre.y),
+        ),
+        AABB2D::new(
+            Vec2::new(bounds.min.x, centre.y),
+            Vec2::new(centre.x, bounds.max.y),
+        ),
+        AABB2D::new(centre, bounds.max),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use slotmap::{DefaultKey, SlotMap};
+
+    use super::*;
+
+    fn square(x: f32, y: f32, size: f32) -> AABB2D {
+        AABB2D::new(Vec2::new(x, y), Vec2::new(x + size, y + size))
+    }
+
+    fn sorted(mut keys: Vec<DefaultKey>) -> Vec<DefaultKey> {
+        keys.sort();
+        keys
+    }
+
+    #[test]
+    fn queries_match_a_linear_scan() {
+        let mut keys: SlotMap<DefaultKey, AABB2D> = SlotMap::new();
+        let mut tree = QuadTree::new();
+
+        // A grid of small squares, a few large ones and some far away, so the root has to grow
+        for i in 0..40 {
+            for j in 0..40 {
+                let aabb = square(i as f32 * 3.0, j as f32 * 3.0, 2.0);
+                tree.insert(keys.insert(aabb), aabb);
+            }
+        }
+        for aabb in [
+            square(-50.0, -50.0, 200.0),
+            square(10.0, 10.0, 30.0),
+            square(-1000.0, 500.0, 5.0),
+            square(2000.0, -3000.0, 1.0),
+        ] {
+            tree.insert(keys.insert(aabb), aabb);
+        }
+
+        // Move some items and remove others
+        let all = keys.keys().collect::<Vec<_>>();
+        for (n, &key) in all.iter().enumerate().step_by(7) {
+            if n % 2 == 0 {
+                keys.remove(key);
+                tree.remove(key);
+            } else {
+                let aabb = square(n as f32, -(n as f32), 4.0);
+                keys[key] = aabb;
+                tree.insert(key, aabb);
+            }
+        }
+        assert_eq!(tree.len(), keys.len());
+        live_nodes(&tree);
+
+        for rect in [
+            square(0.0, 0.0, 10.0),
+            square(50.0, 20.0, 0.5),
+            square(-2000.0, -4000.0, 5000.0),
+            square(5000.0, 5000.0, 1.0),
+        ] {
+            let mut results = vec![];
+            tree.query_rect(&rect, &mut results);
+            let expected = keys
+                .iter()
+                .filter(|(_, aabb)| aabb.intersects(&rect))
+                .map(|(key, _)| key)
+                .collect();
+            assert_eq!(sorted(results), sorted(expected));
+        }
+
+        for point in [
+            Vec2::new(1.0, 1.0),
+            Vec2::new(20.5, 20.5),
+            Vec2::new(-998.0, 502.0),
+        ] {
+            let mut results = vec![];
+            tree.query_point(point, &mut results);
+            let expected = keys
+                .iter()
+                .filter(|(_, aabb)| aabb.contains_point(point))
+                .map(|(key, _)| key)
+                .collect();
+            assert_eq!(sorted(results), sorted(expected));
+        }
+    }
+
+    /// Nodes in use, checking every node's count and every item's location on the way
+    fn live_nodes(tree: &QuadTree<DefaultKey>) -> usize {
+        let Some(root) = tree.root else {
+            return 0;
+        };
+
+        fn count(tree: &QuadTree<DefaultKey>, index: usize, nodes: &mut usize) -> usize {
+            *nodes += 1;
+            let node = &tree.nodes[index];
+            for (key, _) in &node.items {
+                assert_eq!(tree.locations[*key], index);
+            }
+            let below = node.children.map_or(0, |children| {
+                children
+                    .iter()
+                    .map(|&child| {
+                        assert_eq!(tree.nodes[child].parent, Some(index));
+                        assert_eq!(tree.nodes[child].depth, node.depth + 1);
+                        count(tree, child, nodes)
+                    })
+                    .sum()
+            });
+            assert_eq!(node.count, node.items.len() + below);
+            node.count
+        }
+
+        let mut nodes = 0;
+        assert_eq!(count(tree, root, &mut nodes), tree.len());
+        assert_eq!(nodes, tree.nodes.len() - tree.free_nodes.len());
+        nodes
+    }
+
+    #[test]
+    fn removing_items_frees_nodes() {
+        let mut keys: SlotMap<DefaultKey, AABB2D> = SlotMap::new();
+        let mut tree = QuadTree::new();
+
+        let fill = |keys: &mut SlotMap<DefaultKey, AABB2D>, tree: &mut QuadTree<_>| {
+            for i in 0..30 {
+                for j in 0..30 {
+                    let aabb = square(i as f32 * 3.0, j as f32 * 3.0, 2.0);
+                    tree.insert(keys.insert(aabb), aabb);
+                }
+            }
+        };
+        fill(&mut keys, &mut tree);
+        let far = square(5000.0, 5000.0, 1.0);
+        let far_key = keys.insert(far);
+        tree.insert(far_key, far);
+        let grown = live_nodes(&tree);
+        let allocated = tree.nodes.len();
+
+        // The far item made the root grow, which is undone once it's gone
+        keys.remove(far_key);
+        tree.remove(far_key);
+        live_nodes(&tree);
+        let root = &tree.nodes[tree.root.unwrap()];
+        assert!(!root.bounds.contains_point(Vec2::splat(5000.0)));
+
+        // Emptying most of the tree collapses what is left into one node
+        let all = keys.keys().collect::<Vec<_>>();
+        for &key in &all[3..] {
+            keys.remove(key);
+            tree.remove(key);
+        }
+        assert_eq!(live_nodes(&tree), 1);
+        let mut results = vec![];
+        tree.query_rect(&square(-10.0, -10.0, 200.0), &mut results);
+        assert_eq!(sorted(results), sorted(keys.keys().collect()));
+
+        // Filling it again reuses the freed nodes
+        fill(&mut keys, &mut tree);
+        assert!(live_nodes(&tree) <= grown);
+        assert_eq!(tree.nodes.len(), allocated);
+
+        for key in keys.keys() {
+            tree.remove(key);
+        }
+        assert!(tree.is_empty() && tree.root.is_none());
+    }
+
+    #[test]
+    fn identical_items_do_not_split_forever() {
+        let mut keys: SlotMap<DefaultKey, ()> = SlotMap::new();
+        let mut tree = QuadTree::new();
+
+        let aabb = square(0.0, 0.0, 0.0);
+        for _ in 0..1000 {
+            tree.insert(keys.insert(()), aabb);
+        }
+
+        let mut results = vec![];
+        tree.query_point(Vec2::ZERO, &mut results);
+        assert_eq!(results.len(), 1000);
+    }
+}
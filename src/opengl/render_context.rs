@@ -1,92 +1,104 @@
-use std::{
-    cell::RefCell,
-    rc::Rc,
-    sync::{Arc, Mutex},
-};
-
-use glam::Mat4;
 use miniquad::Context;
 
+use crate::operators::edit_mesh::EditMode;
+
 use super::{
-    pipelines::{flat::FlatPipeline, grid::GridPipeline, outline::OutlinePipeline},
+    camera::Camera2D,
+    mesh_arena::GpuMeshArena,
+    pipelines::{
+        flat::FlatPipeline,
+        grid::{GridPipeline, GridSettings},
+        outline::OutlinePipeline,
+        overlay::OverlayPipeline,
+    },
     scene::SceneData,
-    structs::{Mesh, Object},
 };
 
 /// Manages scene data and all rendering pipelines
 pub struct RenderContext {
     pub scene_data: SceneData,
+    mesh_arena: GpuMeshArena,
     pub flat_pipeline: FlatPipeline,
     pub grid_pipeline: GridPipeline,
     pub outline_pipeline: OutlinePipeline,
-    projection_matrix: Arc<Mutex<Mat4>>,
-    view_matrix: Arc<Mutex<Mat4>>,
+    pub overlay_pipeline: OverlayPipeline,
 }
 
 impl RenderContext {
-    pub fn new(
-        ctx: &mut Context,
-        projection_matrix: Arc<Mutex<Mat4>>,
-        view_matrix: Arc<Mutex<Mat4>>,
-        zoom: Arc<Mutex<f32>>,
-        position: Arc<Mutex<glam::Vec2>>,
-        objects: Vec<Object>,
-        meshes: Vec<Rc<RefCell<Mesh>>>,
-        width: u32,
-        height: u32,
-    ) -> Self {
-        let mut flat_pipeline =
-            FlatPipeline::new(ctx, projection_matrix.clone(), view_matrix.clone());
-        let grid_pipeline = GridPipeline::new(ctx, position, zoom);
-
-        // Update the flat pipeline with mesh data
-        flat_pipeline.update(ctx, meshes.clone());
-
-        let mut outline_pipeline = OutlinePipeline::new(
-            ctx,
-            projection_matrix.clone(),
-            view_matrix.clone(),
-            width,
-            height,
-        );
-        outline_pipeline.update(ctx, meshes);
-
-        let scene_data = SceneData::new(objects);
+    pub fn new(ctx: &mut Context, mut scene_data: SceneData, camera: &Camera2D) -> Self {
+        scene_data.take_meshes_changed();
+        let meshes = scene_data.evaluated_meshes();
+
+        let flat_pipeline = FlatPipeline::new(ctx);
+        let grid_pipeline = GridPipeline::new(ctx);
+        let overlay_pipeline = OverlayPipeline::new(ctx);
+
+        let mut mesh_arena = GpuMeshArena::new(ctx);
+        mesh_arena.sync(ctx, &meshes);
+
+        let outline_pipeline =
+            OutlinePipeline::new(ctx, camera.viewport.x as u32, camera.viewport.y as u32);
 
         let mut render_context = Self {
             scene_data,
+            mesh_arena,
             flat_pipeline,
             grid_pipeline,
             outline_pipeline,
-            projection_matrix,
-            view_matrix,
+            overlay_pipeline,
         };
 
         // Initial visibility calculation
-        render_context.update_visibility();
+        render_context.update_visibility(camera);
 
         render_context
     }
 
-    /// Update scene visibility based on current camera matrices
+    /// Update scene visibility based on the camera
     /// Call this after the camera has moved or projection has changed
-    pub fn update_visibility(&mut self) {
-        let projection_matrix = *self.projection_matrix.lock().unwrap();
-        let view_matrix = *self.view_matrix.lock().unwrap();
+    pub fn update_visibility(&mut self, camera: &Camera2D) {
         self.scene_data
-            .update_visibility(projection_matrix, view_matrix);
+            .update_visibility(camera.projection_matrix(), camera.view_matrix());
     }
 
-    /// Draw all pipelines
-    pub fn draw(&mut self, ctx: &mut Context) {
-        let projection_matrix = *self.projection_matrix.lock().unwrap();
-        let view_matrix = *self.view_matrix.lock().unwrap();
+    /// Re-evaluate changed modifier stacks and update the arena if the set of meshes changed,
+    /// either through modifiers or because objects were added or removed
+    ///
+    /// Meshes that were only edited in place are uploaded by [`RenderContext::draw`] through
+    /// their dirty flags, which also recalculates visibility for any bounds that changed.
+    pub fn sync_meshes(&mut self, ctx: &mut Context) {
+        let evaluated = self.scene_data.evaluate_modifiers();
+        let added_or_removed = self.scene_data.take_meshes_changed();
+        if !evaluated && !added_or_removed {
+            return;
+        }
+
+        let meshes = self.scene_data.evaluated_meshes();
+        self.mesh_arena.sync(ctx, &meshes);
+    }
+
+    /// Draw all pipelines, with the edit mode overlay on top while editing a mesh
+    pub fn draw(
+        &mut self,
+        ctx: &mut Context,
+        camera: &Camera2D,
+        grid: &GridSettings,
+        edit_mode: Option<&EditMode>,
+    ) {
+        self.mesh_arena.sync_dirty(ctx);
+        if self.scene_data.visibility_dirty() {
+            self.update_visibility(camera);
+        }
 
-        self.grid_pipeline.draw(ctx);
+        self.grid_pipeline.draw(ctx, camera, grid);
         self.flat_pipeline
-            .draw(ctx, &self.scene_data, projection_matrix, view_matrix);
+            .draw(ctx, &self.scene_data, &self.mesh_arena, camera);
         self.outline_pipeline
-            .draw(ctx, &self.scene_data, projection_matrix, view_matrix);
+            .draw(ctx, &self.scene_data, &self.mesh_arena, camera);
+        if let Some(edit_mode) = edit_mode {
+            self.overlay_pipeline
+                .draw(ctx, &self.scene_data, edit_mode, camera);
+        }
     }
 
     /// Handle window resize
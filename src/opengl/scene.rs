@@ -1,7 +1,13 @@
-use glam::Mat4;
-use slotmap::{new_key_type, SlotMap};
+use std::{cell::RefCell, collections::HashSet, fmt, rc::Rc};
 
-use super::{frustum::Frustum, structs::Object};
+use glam::{Mat4, Vec2};
+use slotmap::{new_key_type, SecondaryMap, SlotMap};
+
+use super::{
+    frustum::{Frustum, AABB2D},
+    quadtree::QuadTree,
+    structs::{Mesh, Object},
+};
 
 // Define a strongly-typed key for objects in the scene
 new_key_type! {
@@ -15,30 +21,218 @@ pub struct SceneData {
     /// Ordered list of object keys by depth (bottom to top)
     /// First element renders first (bottom-most), last element renders last (top-most)
     object_order: Vec<ObjectKey>,
+    /// Children of each object that has any
+    children: SecondaryMap<ObjectKey, Vec<ObjectKey>>,
+    /// Position of each object in `object_order`, used to put query results in draw order
+    order_ranks: SecondaryMap<ObjectKey, usize>,
+    /// World-space bounds of every object
+    spatial_index: QuadTree<ObjectKey>,
     /// Cached frustum from the current camera view
     frustum: Frustum,
     /// Keys of objects that are visible (passed frustum culling)
     visible_objects: Vec<ObjectKey>,
+    /// Set when object bounds or visibility changed since `visible_objects` was calculated
+    visibility_dirty: bool,
     /// Cached view-projection matrix used to generate the frustum
     cached_vp_matrix: Mat4,
+    /// The object most recently selected, which panels act on
+    active_object: Option<ObjectKey>,
+    /// Set when objects were added or removed, so the set of meshes the scene draws may differ
+    /// from what the GPU arena holds
+    meshes_changed: bool,
 }
 
 impl SceneData {
     pub fn new(initial_objects: Vec<Object>) -> Self {
-        let mut objects: SlotMap<ObjectKey, Object> = SlotMap::with_key();
-        let mut object_order = Vec::new();
+        let mut scene_data = Self {
+            objects: SlotMap::with_key(),
+            object_order: Vec::new(),
+            children: SecondaryMap::new(),
+            order_ranks: SecondaryMap::new(),
+            spatial_index: QuadTree::new(),
+            frustum: Frustum::from_matrix(Mat4::IDENTITY),
+            visible_objects: Vec::new(),
+            visibility_dirty: true,
+            cached_vp_matrix: Mat4::IDENTITY,
+            active_object: None,
+            meshes_changed: false,
+        };
 
         for object in initial_objects {
-            let key = objects.insert(object);
-            object_order.push(key);
+            scene_data.add_object(object);
         }
 
-        Self {
-            objects,
-            object_order,
-            frustum: Frustum::from_matrix(Mat4::IDENTITY),
-            visible_objects: Vec::new(),
-            cached_vp_matrix: Mat4::IDENTITY,
+        scene_data
+    }
+
+    /// Add an object on top of all others
+    /// An object that names a parent still in the scene is added as its child.
+    pub fn add_object(&mut self, object: Object) -> ObjectKey {
+        let parent = object
+            .parent()
+            .filter(|&parent| self.objects.contains_key(parent));
+        let key = self.objects.insert(object);
+
+        self.order_ranks.insert(key, self.object_order.len());
+        self.object_order.push(key);
+        self.attach(key, parent);
+        self.meshes_changed = true;
+
+        key
+    }
+
+    /// Remove an object, handing its children to its own parent without moving them
+    pub fn remove_object(&mut self, key: ObjectKey) -> Option<Object> {
+        let (_, object) = self.remove_objects(&[key], |_, _, _| ()).pop()??;
+        Some(object)
+    }
+
+    /// Remove objects one after another, as [`SceneData::remove_object`] would
+    ///
+    /// The draw order and visible objects are only brought up to date once at the end, so
+    /// removing many objects doesn't cost a pass over the scene for each. Before each object
+    /// goes, `record` is given its position in the draw order counting only the objects still
+    /// left, and can look at its parent and children; the draw order itself isn't up to date
+    /// until the end.
+    pub fn remove_objects<R>(
+        &mut self,
+        keys: &[ObjectKey],
+        mut record: impl FnMut(&SceneData, ObjectKey, usize) -> R,
+    ) -> Vec<Option<(R, Object)>> {
+        // Ranks of the objects removed so far, sorted
+        let mut removed_ranks: Vec<usize> = Vec::new();
+
+        let removed = keys
+            .iter()
+            .map(|&key| {
+                let rank = *self.order_ranks.get(key)?;
+                let below = removed_ranks.partition_point(|&other| other < rank);
+                let recorded = record(self, key, rank - below);
+                removed_ranks.insert(below, rank);
+
+                let parent = self.objects[key].parent();
+                for child in self.children(key).to_vec() {
+                    self.reparent(child, parent, true);
+                }
+                self.detach(key);
+                self.children.remove(key);
+                self.order_ranks.remove(key);
+                self.spatial_index.remove(key);
+                Some((recorded, self.objects.remove(key)?))
+            })
+            .collect::<Vec<_>>();
+
+        if !removed_ranks.is_empty() {
+            let objects = &self.objects;
+            self.object_order.retain(|&key| objects.contains_key(key));
+            self.visible_objects
+                .retain(|&key| objects.contains_key(key));
+            self.update_order_ranks();
+            self.meshes_changed = true;
+        }
+
+        removed
+    }
+
+    pub fn parent(&self, key: ObjectKey) -> Option<ObjectKey> {
+        self.objects.get(key).and_then(Object::parent)
+    }
+
+    pub fn children(&self, key: ObjectKey) -> &[ObjectKey] {
+        self.children.get(key).map_or(&[], Vec::as_slice)
+    }
+
+    /// Whether `ancestor` is `key` itself or one of its parents
+    pub fn is_ancestor(&self, ancestor: ObjectKey, key: ObjectKey) -> bool {
+        let mut current = Some(key);
+        while let Some(key) = current {
+            if key == ancestor {
+                return true;
+            }
+            current = self.parent(key);
+        }
+        false
+    }
+
+    /// Change an object's parent
+    ///
+    /// With `keep_world` the object's transform is adjusted so it stays where it is, otherwise
+    /// it keeps its transform and moves with the new parent. A parent with non-uniform scale
+    /// can skew a rotated child, which an object's transform can't represent, so in that case
+    /// the kept world transform loses the skew.
+    pub fn set_parent(
+        &mut self,
+        key: ObjectKey,
+        parent: Option<ObjectKey>,
+        keep_world: bool,
+    ) -> Result<(), HierarchyError> {
+        if !self.objects.contains_key(key) {
+            return Err(HierarchyError::MissingObject);
+        }
+        if let Some(parent) = parent {
+            if !self.objects.contains_key(parent) {
+                return Err(HierarchyError::MissingObject);
+            }
+            if self.is_ancestor(key, parent) {
+                return Err(HierarchyError::Cycle);
+            }
+        }
+
+        self.reparent(key, parent, keep_world);
+        Ok(())
+    }
+
+    fn reparent(&mut self, key: ObjectKey, parent: Option<ObjectKey>, keep_world: bool) {
+        let world_matrix = self.objects[key].get_model_matrix();
+        self.detach(key);
+        self.attach(key, parent);
+
+        if keep_world {
+            let object = &mut self.objects[key];
+            let local = object.parent_matrix().inverse() * world_matrix;
+            object.set_matrix(local);
+            self.update_world_matrices(key);
+        }
+    }
+
+    /// Remove an object from its parent's children, leaving the object's own parent alone
+    fn detach(&mut self, key: ObjectKey) {
+        if let Some(parent) = self.parent(key) {
+            if let Some(siblings) = self.children.get_mut(parent) {
+                siblings.retain(|&child| child != key);
+            }
+        }
+    }
+
+    /// Make `parent` the object's parent and bring its world matrix up to date
+    fn attach(&mut self, key: ObjectKey, parent: Option<ObjectKey>) {
+        let parent_matrix = parent.map_or(Mat4::IDENTITY, |parent| {
+            self.objects[parent].get_model_matrix()
+        });
+        self.objects[key].set_parent(parent, parent_matrix);
+
+        if let Some(parent) = parent {
+            self.children
+                .entry(parent)
+                .expect("parent is in the scene")
+                .or_default()
+                .push(key);
+        }
+
+        self.update_world_matrices(key);
+    }
+
+    /// Pass an object's world matrix down to its descendants and re-index them all
+    fn update_world_matrices(&mut self, key: ObjectKey) {
+        let mut stack = vec![key];
+        while let Some(key) = stack.pop() {
+            self.update_object_bounds(key);
+
+            let matrix = self.objects[key].get_model_matrix();
+            for &child in self.children.get(key).map_or(&[][..], Vec::as_slice) {
+                self.objects[child].set_parent_matrix(matrix);
+                stack.push(child);
+            }
         }
     }
 
@@ -48,6 +242,10 @@ impl SceneData {
     }
 
     /// Get a mutable reference to the object storage
+    /// Use [`SceneData::add_object`] and [`SceneData::remove_objects`] to add and remove objects,
+    /// and [`SceneData::edit_object`] to change anything that affects an object's bounds. The
+    /// spatial index isn't told about changes made here, so transform setters such as
+    /// [`Object::set_translation`] must not be called through it.
     pub fn objects_mut(&mut self) -> &mut SlotMap<ObjectKey, Object> {
         &mut self.objects
     }
@@ -57,9 +255,120 @@ impl SceneData {
         &self.object_order
     }
 
-    /// Get a mutable reference to the object order for reordering
-    pub fn object_order_mut(&mut self) -> &mut Vec<ObjectKey> {
-        &mut self.object_order
+    /// Position of an object in the draw order, counting up from the bottom
+    pub fn order_rank(&self, key: ObjectKey) -> Option<usize> {
+        self.order_ranks.get(key).copied()
+    }
+
+    /// Reorder objects; `reorder` must keep the same keys
+    pub fn reorder_objects(&mut self, reorder: impl FnOnce(&mut Vec<ObjectKey>)) {
+        reorder(&mut self.object_order);
+        debug_assert_eq!(self.object_order.len(), self.objects.len());
+        self.update_order_ranks();
+        self.sort_visible_objects();
+    }
+
+    fn update_order_ranks(&mut self) {
+        self.order_ranks.clear();
+        for (rank, &key) in self.object_order.iter().enumerate() {
+            self.order_ranks.insert(key, rank);
+        }
+    }
+
+    fn sort_visible_objects(&mut self) {
+        let ranks = &self.order_ranks;
+        self.visible_objects.sort_unstable_by_key(|&key| ranks[key]);
+    }
+
+    /// Change an object, re-indexing it if its bounds changed and moving its children with it
+    pub fn edit_object<R>(
+        &mut self,
+        key: ObjectKey,
+        edit: impl FnOnce(&mut Object) -> R,
+    ) -> Option<R> {
+        let object = self.objects.get_mut(key)?;
+        let old_aabb = object.get_aabb();
+        let old_matrix = object.get_model_matrix();
+        let result = edit(object);
+
+        if object.get_model_matrix() != old_matrix {
+            self.update_world_matrices(key);
+        } else if object.get_aabb() != old_aabb {
+            self.update_object_bounds(key);
+        }
+
+        Some(result)
+    }
+
+    /// Re-index an object after its bounds changed
+    pub fn update_object_bounds(&mut self, key: ObjectKey) {
+        if let Some(object) = self.objects.get(key) {
+            self.spatial_index.insert(key, object.get_aabb());
+            self.visibility_dirty = true;
+        }
+    }
+
+    /// Show or hide an object
+    pub fn set_hidden(&mut self, key: ObjectKey, hidden: bool) {
+        if let Some(object) = self.objects.get_mut(key) {
+            object.hidden = hidden;
+            self.visibility_dirty = true;
+        }
+    }
+
+    /// Whether object bounds or visibility changed since visibility was last calculated
+    pub fn visibility_dirty(&self) -> bool {
+        self.visibility_dirty
+    }
+
+    /// Bounds of every object in `keys` together, or `None` if there are none
+    pub fn bounds_of(&self, keys: impl IntoIterator<Item = ObjectKey>) -> Option<AABB2D> {
+        keys.into_iter()
+            .filter_map(|key| self.objects.get(key))
+            .map(Object::get_aabb)
+            .reduce(|bounds, aabb| bounds.union(&aabb))
+    }
+
+    /// Get the active object, if it still exists
+    pub fn active_object(&self) -> Option<ObjectKey> {
+        self.active_object
+            .filter(|key| self.objects.contains_key(*key))
+    }
+
+    pub fn set_active_object(&mut self, key: Option<ObjectKey>) {
+        self.active_object = key;
+    }
+
+    /// Re-run any modifier stacks that changed since they were last evaluated
+    /// Returns true if any evaluated mesh changed and the GPU buffers need rebuilding
+    pub fn evaluate_modifiers(&mut self) -> bool {
+        let mut changed = false;
+        for (key, object) in self.objects.iter_mut() {
+            if object.modifiers_dirty() && object.evaluate_modifiers() {
+                // Modifiers can change the object's bounds
+                self.spatial_index.insert(key, object.get_aabb());
+                self.visibility_dirty = true;
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Whether objects were added or removed since this was last called, clearing the flag
+    pub fn take_meshes_changed(&mut self) -> bool {
+        std::mem::take(&mut self.meshes_changed)
+    }
+
+    /// Every distinct mesh drawn by the scene, after modifiers
+    pub fn evaluated_meshes(&self) -> Vec<Rc<RefCell<Mesh>>> {
+        let mut seen = HashSet::new();
+        self.object_order
+            .iter()
+            .filter_map(|&key| self.objects.get(key))
+            .map(|object| object.evaluated_mesh())
+            .filter(|mesh| seen.insert(Rc::as_ptr(mesh)))
+            .cloned()
+            .collect()
     }
 
     /// Get the keys of visible objects (in depth order)
@@ -79,17 +388,46 @@ impl SceneData {
 
         self.cached_vp_matrix = vp_matrix;
         self.frustum = Frustum::from_matrix(vp_matrix);
+        self.visibility_dirty = false;
 
         // Recalculate visibility, maintaining depth order
         self.visible_objects.clear();
-        for &key in &self.object_order {
-            if let Some(object) = self.objects.get(key) {
-                let aabb = object.get_aabb();
-                if self.frustum.intersects_aabb_2d(aabb.min, aabb.max) {
-                    self.visible_objects.push(key);
-                }
-            }
-        }
+        let frustum = &self.frustum;
+        self.spatial_index.query(
+            |bounds| frustum.intersects_aabb_2d(bounds.min, bounds.max),
+            |aabb| frustum.intersects_aabb_2d(aabb.min, aabb.max),
+            &mut self.visible_objects,
+        );
+        let objects = &self.objects;
+        self.visible_objects.retain(|&key| !objects[key].hidden);
+        self.sort_visible_objects();
+    }
+
+    /// Objects whose bounds contain `point`, bottom to top
+    pub fn objects_at_point(&self, point: Vec2) -> Vec<ObjectKey> {
+        let mut keys = vec![];
+        self.spatial_index.query_point(point, &mut keys);
+        self.sort_by_draw_order(&mut keys);
+        keys
+    }
+
+    /// Topmost object whose bounds contain `point`
+    pub fn topmost_object_at(&self, point: Vec2) -> Option<ObjectKey> {
+        let mut keys = vec![];
+        self.spatial_index.query_point(point, &mut keys);
+        keys.into_iter().max_by_key(|&key| self.order_ranks[key])
+    }
+
+    /// Objects whose bounds overlap `rect`, bottom to top
+    pub fn objects_in_rect(&self, rect: &AABB2D) -> Vec<ObjectKey> {
+        let mut keys = vec![];
+        self.spatial_index.query_rect(rect, &mut keys);
+        self.sort_by_draw_order(&mut keys);
+        keys
+    }
+
+    fn sort_by_draw_order(&self, keys: &mut [ObjectKey]) {
+        keys.sort_unstable_by_key(|&key| self.order_ranks[key]);
     }
 
     /// Get selected objects from the visible set
@@ -101,3 +439,280 @@ impl SceneData {
             .collect()
     }
 }
+
+/// Why a parent could not be set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HierarchyError {
+    /// The object or the new parent is not in the scene
+    MissingObject,
+    /// The new parent is the object itself or one of its descendants
+    Cycle,
+}
+
+impl fmt::Display for HierarchyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HierarchyError::MissingObject => write!(f, "object is not in the scene"),
+            HierarchyError::Cycle => write!(f, "an object can't be parented to its own descendant"),
+        }
+    }
+}
+
+impl std::error::Error for HierarchyError {}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use crate::{
+        opengl::{
+            matrices::{get_ortho_matrix, get_view_matrix},
+            structs::{Colour, Material},
+        },
+        shapes::square::create_square,
+    };
+
+    use super::*;
+
+    /// Overlapping squares of varying size scattered over a large area
+    fn scattered_scene() -> SceneData {
+        let (square, _, _) = Mesh::new(create_square(), 0);
+        let material = Rc::new(RefCell::new(Material {
+            colour: Colour::ONE,
+        }));
+
+        let objects = (0..2000)
+            .map(|i| {
+                let x = ((i * 7919) % 2003) as f32 - 1000.0;
+                let y = ((i * 104729) % 1999) as f32 - 1000.0;
+                let size = 1.0 + (i % 13) as f32 * 10.0;
+                Object::new(
+                    square.clone(),
+                    Vec2::new(x, y),
+                    i as f32 * 0.1,
+                    Vec2::splat(size),
+                    material.clone(),
+                )
+            })
+            .collect();
+
+        SceneData::new(objects)
+    }
+
+    #[test]
+    fn visible_objects_match_a_linear_scan_in_draw_order() {
+        let mut scene_data = scattered_scene();
+        scene_data.reorder_objects(|order| order.reverse());
+
+        for (position, zoom) in [
+            (Vec2::ZERO, 1.0),
+            (Vec2::new(300.0, -200.0), 2.5),
+            (Vec2::new(-900.0, 900.0), 0.5),
+        ] {
+            let projection_matrix = get_ortho_matrix(800.0, 600.0);
+            let view_matrix = get_view_matrix(position, zoom, 0.0);
+            scene_data.update_visibility(projection_matrix, view_matrix);
+
+            let frustum = Frustum::from_matrix(projection_matrix * view_matrix);
+            let expected = scene_data
+                .object_order()
+                .iter()
+                .copied()
+                .filter(|&key| {
+                    let aabb = scene_data.objects()[key].get_aabb();
+                    frustum.intersects_aabb_2d(aabb.min, aabb.max)
+                })
+                .collect::<Vec<_>>();
+
+            assert!(!expected.is_empty());
+            assert_eq!(scene_data.visible_objects(), expected);
+        }
+    }
+
+    #[test]
+    fn point_and_rect_queries_follow_draw_order() {
+        let mut scene_data = scattered_scene();
+        let point = Vec2::new(10.0, -20.0);
+        let rect = AABB2D::new(Vec2::new(-100.0, -50.0), Vec2::new(40.0, 60.0));
+
+        let at_point = |scene_data: &SceneData| {
+            scene_data
+                .object_order()
+                .iter()
+                .copied()
+                .filter(|&key| scene_data.objects()[key].contains_point(point))
+                .collect::<Vec<_>>()
+        };
+
+        let expected = at_point(&scene_data);
+        assert!(expected.len() > 1);
+        assert_eq!(scene_data.objects_at_point(point), expected);
+        assert_eq!(
+            scene_data.topmost_object_at(point),
+            expected.last().copied()
+        );
+
+        let in_rect = scene_data.objects_in_rect(&rect);
+        let expected_rect = scene_data
+            .object_order()
+            .iter()
+            .copied()
+            .filter(|&key| scene_data.objects()[key].get_aabb().intersects(&rect))
+            .collect::<Vec<_>>();
+        assert_eq!(in_rect, expected_rect);
+
+        // Removing the topmost object and reordering are both reflected in the results
+        let topmost = *expected.last().unwrap();
+        scene_data.remove_object(topmost);
+        let bottom = expected[0];
+        scene_data.reorder_objects(|order| {
+            order.retain(|&key| key != bottom);
+            order.push(bottom);
+        });
+
+        assert_eq!(scene_data.topmost_object_at(point), Some(bottom));
+        assert_eq!(scene_data.objects_at_point(point), at_point(&scene_data));
+        assert!(!scene_data.objects_at_point(point).contains(&topmost));
+    }
+
+    #[test]
+    fn editing_an_object_reindexes_it() {
+        let mut scene_data = scattered_scene();
+        scene_data.update_visibility(
+            get_ortho_matrix(800.0, 600.0),
+            get_view_matrix(Vec2::ZERO, 1.0, 0.0),
+        );
+        assert!(!scene_data.visibility_dirty());
+
+        let key = scene_data.object_order()[0];
+        let far_away = Vec2::new(50_000.0, 50_000.0);
+        scene_data.edit_object(key, |object| object.set_translation(far_away));
+
+        assert!(scene_data.visibility_dirty());
+        assert_eq!(scene_data.topmost_object_at(far_away), Some(key));
+        assert!(!scene_data
+            .objects_in_rect(&scene_data.objects()[key].get_aabb())
+            .is_empty());
+
+        // Edits that don't move the object leave visibility alone
+        scene_data.update_visibility(
+            get_ortho_matrix(800.0, 600.0),
+            get_view_matrix(Vec2::ZERO, 1.0, 0.0),
+        );
+        assert!(!scene_data.visible_objects().contains(&key));
+        scene_data.edit_object(key, |object| object.selected = true);
+        assert!(!scene_data.visibility_dirty());
+    }
+
+    fn square_object(translation: Vec2, rotation: f32, scale: Vec2) -> Object {
+        let (square, _, _) = Mesh::new(create_square(), 0);
+        let material = Rc::new(RefCell::new(Material {
+            colour: Colour::ONE,
+        }));
+        Object::new(square, translation, rotation, scale, material)
+    }
+
+    #[test]
+    fn children_inherit_their_parents_transform() {
+        let mut scene_data = SceneData::new(vec![
+            square_object(Vec2::new(10.0, 0.0), FRAC_PI_2, Vec2::splat(2.0)),
+            square_object(Vec2::new(1.0, 0.0), 0.0, Vec2::ONE),
+            square_object(Vec2::new(0.0, 1.0), 0.0, Vec2::ONE),
+        ]);
+        let [parent, child, grandchild] = [0, 1, 2].map(|i| scene_data.object_order()[i]);
+
+        scene_data.set_parent(child, Some(parent), false).unwrap();
+        scene_data
+            .set_parent(grandchild, Some(child), false)
+            .unwrap();
+
+        // The child sits one unit along the parent's rotated, scaled x axis
+        let origin = |scene_data: &SceneData, key| {
+            scene_data.objects()[key]
+                .get_model_matrix()
+                .transform_point3(glam::Vec3::ZERO)
+                .truncate()
+        };
+        assert!(origin(&scene_data, child).abs_diff_eq(Vec2::new(10.0, 2.0), 1e-5));
+        assert!(origin(&scene_data, grandchild).abs_diff_eq(Vec2::new(8.0, 2.0), 1e-5));
+
+        // Moving the parent moves its descendants, and the spatial index follows
+        scene_data.edit_object(parent, |object| object.translate(Vec2::new(100.0, 0.0)));
+        assert!(origin(&scene_data, grandchild).abs_diff_eq(Vec2::new(108.0, 2.0), 1e-5));
+        assert!(scene_data
+            .objects_at_point(Vec2::new(108.0, 2.0))
+            .contains(&grandchild));
+        assert!(scene_data.objects_at_point(Vec2::new(8.0, 2.0)).is_empty());
+
+        // Parenting an object to its own descendant is refused
+        assert_eq!(
+            scene_data.set_parent(parent, Some(grandchild), false),
+            Err(HierarchyError::Cycle)
+        );
+        assert_eq!(
+            scene_data.set_parent(parent, Some(parent), false),
+            Err(HierarchyError::Cycle)
+        );
+    }
+
+    #[test]
+    fn reparenting_and_deleting_keep_world_transforms() {
+        let mut scene_data = SceneData::new(vec![
+            square_object(Vec2::new(5.0, 5.0), 0.7, Vec2::new(2.0, 2.0)),
+            square_object(Vec2::new(-3.0, 1.0), 0.2, Vec2::splat(1.5)),
+            square_object(Vec2::new(4.0, -2.0), -0.4, Vec2::ONE),
+        ]);
+        let [parent, child, grandchild] = [0, 1, 2].map(|i| scene_data.object_order()[i]);
+        let world = |scene_data: &SceneData, key| scene_data.objects()[key].get_model_matrix();
+        let before = [child, grandchild].map(|key| world(&scene_data, key));
+
+        scene_data.set_parent(child, Some(parent), true).unwrap();
+        scene_data
+            .set_parent(grandchild, Some(child), true)
+            .unwrap();
+        assert!(world(&scene_data, child).abs_diff_eq(before[0], 1e-5));
+        assert!(world(&scene_data, grandchild).abs_diff_eq(before[1], 1e-5));
+        assert_eq!(scene_data.children(child), [grandchild]);
+
+        // Deleting the middle object hands its child to the top one without moving it
+        scene_data.remove_object(child);
+        assert_eq!(scene_data.parent(grandchild), Some(parent));
+        assert_eq!(scene_data.children(parent), [grandchild]);
+        assert!(world(&scene_data, grandchild).abs_diff_eq(before[1], 1e-5));
+
+        scene_data.remove_object(parent);
+        assert_eq!(scene_data.parent(grandchild), None);
+        assert!(world(&scene_data, grandchild).abs_diff_eq(before[1], 1e-5));
+    }
+
+    #[test]
+    fn batch_removal_matches_removing_one_at_a_time() {
+        let mut scene_data = SceneData::new(
+            (0..5)
+                .map(|i| square_object(Vec2::new(i as f32 * 3.0, 0.0), 0.0, Vec2::ONE))
+                .collect(),
+        );
+        let order = scene_data.object_order().to_vec();
+        let (parent, child) = (order[1], order[3]);
+        scene_data.set_parent(child, Some(parent), true).unwrap();
+
+        // Each object sees the scene as the objects before it left it: its position counts
+        // only the objects still there, and the child has lost its parent
+        let removed = scene_data.remove_objects(
+            &[parent, child, order[2], parent],
+            |scene_data, key, index| (index, scene_data.parent(key)),
+        );
+        let records = removed
+            .iter()
+            .map(|removed| removed.as_ref().map(|(record, _)| *record))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            records,
+            [Some((1, None)), Some((2, None)), Some((1, None)), None]
+        );
+
+        assert_eq!(scene_data.object_order(), [order[0], order[4]]);
+        assert_eq!(scene_data.order_rank(order[4]), Some(1));
+        assert!(scene_data.objects_at_point(Vec2::new(6.0, 0.0)).is_empty());
+    }
+}
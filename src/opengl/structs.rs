@@ -1,11 +1,12 @@
 use std::{cell::RefCell, rc::Rc, task::Context};
 
 use crate::data::{
-    mesh::bmesh::{bm_triangulate, BMesh},
+    mesh::bmesh::{bm_bounds, bm_triangulate, BMesh},
+    modifiers::{ModifierKind, ModifierStack},
     vertex::{Index, Vertex},
 };
 
-use super::frustum::AABB2D;
+use super::{frustum::AABB2D, matrices::Transform2D, scene::ObjectKey};
 
 pub trait FlatBlendPipeline {
     fn draw(&mut self, ctx: &mut Context);
@@ -17,15 +18,42 @@ pub struct Material {
     pub colour: Colour,
 }
 
+/// Outline drawn around an object's faces when it is exported
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Stroke {
+    pub colour: Colour,
+    /// Width in the object's local units, so it scales with the object
+    pub width: f32,
+}
+
+/// Name of objects that weren't given one
+const DEFAULT_NAME: &str = "Object";
+
 pub struct Object {
     mesh: Rc<RefCell<Mesh>>,
-    translation: glam::Vec2,
-    rotation: f32,
-    scale: glam::Vec2,
+    /// Result of running the modifier stack over `mesh`, if any modifier is enabled
+    evaluated_mesh: Option<Rc<RefCell<Mesh>>>,
+    modifiers: ModifierStack,
+    modifiers_dirty: bool,
+    /// Set when the base mesh is swapped for another, so the next evaluation reports a change
+    mesh_replaced: bool,
+    /// Transform relative to the parent, or to the world if there is no parent
+    transform: Transform2D,
+    parent: Option<ObjectKey>,
+    /// World matrix of the parent, kept up to date by the scene
+    parent_matrix: glam::Mat4,
     material: Rc<RefCell<Material>>,
+    stroke: Option<Stroke>,
+    /// World matrix: the parent's world matrix times the object's own transform
     model_matrix: glam::Mat4,
+    /// World-space bounds
     aabb: AABB2D,
+    pub name: String,
     pub selected: bool,
+    /// Hidden objects are not drawn, picked, snapped to or exported
+    pub hidden: bool,
+    /// Locked objects can't be picked or transformed in the viewport
+    pub locked: bool,
 }
 
 impl Object {
@@ -38,11 +66,19 @@ impl Object {
     ) -> Object {
         let mut obj = Object {
             mesh,
-            translation,
-            rotation,
-            scale,
+            evaluated_mesh: None,
+            modifiers: ModifierStack::new(),
+            modifiers_dirty: false,
+            mesh_replaced: false,
+            transform: Transform2D::new(translation, rotation, scale),
+            parent: None,
+            parent_matrix: glam::Mat4::IDENTITY,
             material,
+            stroke: None,
+            name: DEFAULT_NAME.to_string(),
             selected: false,
+            hidden: false,
+            locked: false,
             model_matrix: glam::Mat4::IDENTITY,
             aabb: AABB2D::new(glam::Vec2::ZERO, glam::Vec2::ZERO),
         };
@@ -54,35 +90,278 @@ impl Object {
     }
 
     fn update_aabb(&mut self) {
-        // For a square mesh, the extents are 2x2 (from -1 to 1)
-        let mesh_extents = glam::Vec2::new(2.0, 2.0);
-        self.aabb = AABB2D::from_transform(self.translation, self.rotation, self.scale, mesh_extents);
+        let local_bounds = self.borrow_evaluated_mesh().bounds;
+        self.aabb = local_bounds.transformed(self.model_matrix);
+    }
+
+    /// The base mesh, before any modifiers
+    pub fn mesh(&self) -> &Rc<RefCell<Mesh>> {
+        &self.mesh
+    }
+
+    /// Replace the base mesh
+    /// The evaluated mesh is rebuilt on the next call to [`Object::evaluate_modifiers`]
+    pub fn set_mesh(&mut self, mesh: Rc<RefCell<Mesh>>) {
+        self.mesh = mesh;
+        self.mesh_replaced = true;
+        self.modifiers_dirty = true;
+        self.update_aabb();
+    }
+
+    /// Replace the base mesh with a copy of itself
+    ///
+    /// The copy belongs to this object alone, so editing it leaves alone other objects that
+    /// shared the mesh and any [`MeshState`](crate::history::MeshState) holding the original.
+    pub fn copy_mesh(&mut self) {
+        let raw_mesh = self.mesh.borrow().raw_mesh.clone();
+        self.set_mesh(Mesh::new(raw_mesh, 0).0);
+    }
+
+    /// Change the base mesh in place, re-triangulating it afterwards
+    /// Every object sharing the mesh sees the change; see [`Object::copy_mesh`].
+    pub fn edit_mesh<R>(&mut self, edit: impl FnOnce(&mut BMesh) -> R) -> R {
+        let result = self.mesh.borrow_mut().edit_raw_mesh(edit);
+        self.modifiers_dirty = true;
+        self.update_aabb();
+        result
     }
 
-    pub fn borrow_mesh(&self) -> std::cell::Ref<Mesh> {
+    /// Borrow the base mesh, before any modifiers
+    pub fn borrow_mesh(&self) -> std::cell::Ref<'_, Mesh> {
         self.mesh.borrow()
     }
 
-    pub fn borrow_material(&self) -> std::cell::Ref<Material> {
+    /// Borrow the mesh that is actually drawn: the modifier result if there is one, otherwise the base mesh
+    pub fn borrow_evaluated_mesh(&self) -> std::cell::Ref<'_, Mesh> {
+        self.evaluated_mesh().borrow()
+    }
+
+    /// The mesh that is actually drawn
+    pub fn evaluated_mesh(&self) -> &Rc<RefCell<Mesh>> {
+        self.evaluated_mesh.as_ref().unwrap_or(&self.mesh)
+    }
+
+    pub fn modifiers(&self) -> &ModifierStack {
+        &self.modifiers
+    }
+
+    /// Get the modifier stack for editing
+    /// The evaluated mesh is rebuilt on the next call to [`Object::evaluate_modifiers`]
+    pub fn modifiers_mut(&mut self) -> &mut ModifierStack {
+        self.modifiers_dirty = true;
+        &mut self.modifiers
+    }
+
+    pub fn add_modifier(&mut self, kind: ModifierKind) {
+        self.modifiers_mut().push(kind);
+    }
+
+    /// True if the modifier stack changed since it was last evaluated
+    pub fn modifiers_dirty(&self) -> bool {
+        self.modifiers_dirty
+    }
+
+    /// Re-run the modifier stack over the base mesh
+    /// Returns true if the evaluated mesh changed and needs uploading
+    pub fn evaluate_modifiers(&mut self) -> bool {
+        if !self.modifiers_dirty {
+            return false;
+        }
+        self.modifiers_dirty = false;
+
+        let mesh_replaced = std::mem::take(&mut self.mesh_replaced);
+        let had_evaluated_mesh = self.evaluated_mesh.is_some();
+        let evaluated = self.modifiers.evaluate(&self.mesh.borrow().raw_mesh);
+        self.evaluated_mesh = match (self.evaluated_mesh.take(), evaluated) {
+            // The evaluated mesh belongs to this object alone, so it can be updated in place
+            (Some(mesh), Some(raw_mesh)) => {
+                mesh.borrow_mut().set_raw_mesh(raw_mesh);
+                Some(mesh)
+            }
+            (None, Some(raw_mesh)) => Some(Mesh::new(raw_mesh, 0).0),
+            (_, None) => None,
+        };
+
+        self.update_aabb();
+
+        mesh_replaced || had_evaluated_mesh || self.evaluated_mesh.is_some()
+    }
+
+    /// Bake a modifier, and every modifier before it, into the base mesh and remove them from
+    /// the stack
+    ///
+    /// The modifiers after it then run on the baked mesh, so the evaluated mesh stays the same.
+    /// The object gets its own copy of the result so other objects sharing the mesh are unaffected.
+    pub fn apply_modifier(&mut self, index: usize) {
+        if index >= self.modifiers.len() {
+            return;
+        }
+
+        let applied = self
+            .modifiers_mut()
+            .take_first(index + 1)
+            .evaluate(&self.mesh.borrow().raw_mesh);
+        if let Some(raw_mesh) = applied {
+            self.set_mesh(Mesh::new(raw_mesh, 0).0);
+        }
+    }
+
+    pub fn material(&self) -> &Rc<RefCell<Material>> {
+        &self.material
+    }
+
+    pub fn borrow_material(&self) -> std::cell::Ref<'_, Material> {
         self.material.borrow()
     }
 
+    pub fn stroke(&self) -> Option<Stroke> {
+        self.stroke
+    }
+
+    pub fn set_stroke(&mut self, stroke: Option<Stroke>) {
+        self.stroke = stroke;
+    }
+
     fn update_model_matrix(&mut self) {
-        let translation_mat = glam::Mat4::from_translation(self.translation.extend(0.0));
-        let rotation_mat = glam::Mat4::from_rotation_z(self.rotation);
-        let scale_mat = glam::Mat4::from_scale(self.scale.extend(1.0));
-        self.model_matrix = translation_mat * rotation_mat * scale_mat;
+        self.model_matrix = self.parent_matrix * self.transform.matrix();
+    }
+
+    /// Parent object, whose transform this object's transform is relative to
+    pub fn parent(&self) -> Option<ObjectKey> {
+        self.parent
+    }
+
+    /// Set the parent and its world matrix, keeping the object's own transform
+    /// Only the scene changes parents, so it can keep its child lists in sync.
+    pub(super) fn set_parent(&mut self, parent: Option<ObjectKey>, parent_matrix: glam::Mat4) {
+        self.parent = parent;
+        self.set_parent_matrix(parent_matrix);
+    }
+
+    /// World matrix of the parent, which maps the parent's space to world space
+    pub fn parent_matrix(&self) -> glam::Mat4 {
+        self.parent_matrix
+    }
+
+    pub(super) fn set_parent_matrix(&mut self, parent_matrix: glam::Mat4) {
+        self.parent_matrix = parent_matrix;
+        self.update_model_matrix();
+        self.update_aabb();
+    }
+
+    pub fn transform(&self) -> Transform2D {
+        self.transform
+    }
+
+    /// Replace the transform relative to the parent, refreshing the model matrix and bounds
+    ///
+    /// Objects in a [`SceneData`](super::scene::SceneData) should be changed through
+    /// [`SceneData::edit_object`](super::scene::SceneData::edit_object) so the scene re-indexes them.
+    pub fn set_transform(&mut self, transform: Transform2D) {
+        self.transform = transform;
+        self.update_model_matrix();
+        self.update_aabb();
+    }
+
+    /// Set the transform from an affine matrix, dropping any skew
+    ///
+    /// Moves the object's bounds, so in a scene call it through
+    /// [`SceneData::edit_object`](super::scene::SceneData::edit_object) as with
+    /// [`Object::set_transform`].
+    pub fn set_matrix(&mut self, matrix: glam::Mat4) {
+        self.set_transform(Transform2D::from_matrix(matrix));
+    }
+
+    pub fn translation(&self) -> glam::Vec2 {
+        self.transform.translation
     }
 
+    /// Moves the object's bounds, so in a scene call it through
+    /// [`SceneData::edit_object`](super::scene::SceneData::edit_object) as with
+    /// [`Object::set_transform`].
+    pub fn set_translation(&mut self, translation: glam::Vec2) {
+        self.set_transform(Transform2D {
+            translation,
+            ..self.transform
+        });
+    }
+
+    pub fn rotation(&self) -> f32 {
+        self.transform.rotation
+    }
+
+    /// Moves the object's bounds, so in a scene call it through
+    /// [`SceneData::edit_object`](super::scene::SceneData::edit_object) as with
+    /// [`Object::set_transform`].
+    pub fn set_rotation(&mut self, rotation: f32) {
+        self.set_transform(Transform2D {
+            rotation,
+            ..self.transform
+        });
+    }
+
+    pub fn scale(&self) -> glam::Vec2 {
+        self.transform.scale
+    }
+
+    /// Moves the object's bounds, so in a scene call it through
+    /// [`SceneData::edit_object`](super::scene::SceneData::edit_object) as with
+    /// [`Object::set_transform`].
+    pub fn set_scale(&mut self, scale: glam::Vec2) {
+        self.set_transform(Transform2D {
+            scale,
+            ..self.transform
+        });
+    }
+
+    pub fn translate(&mut self, offset: glam::Vec2) {
+        self.set_translation(self.transform.translation + offset);
+    }
+
+    /// Rotate by `angle` radians about a pivot in the parent's space
+    ///
+    /// The parent's space is world space for objects without a parent, and the pivot and
+    /// translation setters all work in it.
+    pub fn rotate_about(&mut self, pivot: glam::Vec2, angle: f32) {
+        let offset = glam::Vec2::from_angle(angle).rotate(self.transform.translation - pivot);
+        self.set_transform(Transform2D {
+            translation: pivot + offset,
+            rotation: self.transform.rotation + angle,
+            ..self.transform
+        });
+    }
+
+    /// Scale by `factor` along the object's own axes, about a pivot in the parent's space
+    ///
+    /// Scaling along the object's axes rather than the world's keeps the result free of skew, so
+    /// it can always be represented by the object's transform.
+    pub fn scale_about(&mut self, pivot: glam::Vec2, factor: glam::Vec2) {
+        let local_offset = glam::Vec2::from_angle(-self.transform.rotation)
+            .rotate(self.transform.translation - pivot);
+        let offset = glam::Vec2::from_angle(self.transform.rotation).rotate(local_offset * factor);
+
+        self.set_transform(Transform2D {
+            translation: pivot + offset,
+            scale: self.transform.scale * factor,
+            ..self.transform
+        });
+    }
+
+    /// World matrix of the object, including its parents' transforms
     pub fn get_model_matrix(&self) -> glam::Mat4 {
         self.model_matrix
     }
 
-    /// Get the cached axis-aligned bounding box for this object
+    /// Get the cached world-space axis-aligned bounding box for this object
     pub fn get_aabb(&self) -> AABB2D {
         self.aabb
     }
 
+    /// Whether the object can be picked and transformed in the viewport
+    pub fn is_selectable(&self) -> bool {
+        !self.hidden && !self.locked
+    }
+
     /// Check if a world-space point is inside this object's bounding box
     pub fn contains_point(&self, point: glam::Vec2) -> bool {
         point.x >= self.aabb.min.x
@@ -95,15 +374,21 @@ impl Object {
 pub struct Mesh {
     pub raw_mesh: BMesh,
     pub tris: u32,
+    /// First index of the mesh in the GPU mesh arena's index buffer
     pub buffer_offset: Index,
+    /// Local-space bounds of the mesh vertices
+    pub bounds: AABB2D,
 
     vertices: Vec<Vertex>,
     indices: Vec<Index>,
+    /// Set when the triangulation changes and the GPU copy is out of date
+    dirty: bool,
 }
 
 impl Mesh {
     pub fn new(raw_mesh: BMesh, offset: u32) -> (Rc<RefCell<Mesh>>, Vec<Vertex>, Vec<Index>) {
         let (vertices, indices) = bm_triangulate(&raw_mesh);
+        let (min, max) = bm_bounds(&raw_mesh).unwrap_or_default();
 
         (
             Rc::new(RefCell::new(Mesh {
@@ -112,15 +397,178 @@ impl Mesh {
                 indices: indices.clone(),
                 tris: indices.len() as u32 / 3,
                 buffer_offset: offset,
+                bounds: AABB2D::new(min, max),
+                dirty: true,
             })),
             vertices,
             indices,
         )
     }
 
-    pub fn update(&mut self, offset: u32) -> (Vec<Vertex>, Vec<Index>) {
-        self.buffer_offset = offset;
+    /// Triangulated vertices of the mesh
+    pub fn vertices(&self) -> &[Vertex] {
+        &self.vertices
+    }
+
+    /// Triangle list indices into [`Mesh::vertices`]
+    pub fn indices(&self) -> &[Index] {
+        &self.indices
+    }
+
+    /// Replace the mesh data, re-triangulating it and marking it for upload
+    pub fn set_raw_mesh(&mut self, raw_mesh: BMesh) {
+        self.edit_raw_mesh(|mesh| *mesh = raw_mesh);
+    }
+
+    /// Change the mesh data in place, re-triangulating it and marking it for upload
+    pub fn edit_raw_mesh<R>(&mut self, edit: impl FnOnce(&mut BMesh) -> R) -> R {
+        let result = edit(&mut self.raw_mesh);
+        let (vertices, indices) = bm_triangulate(&self.raw_mesh);
+        let (min, max) = bm_bounds(&self.raw_mesh).unwrap_or_default();
+
+        self.tris = indices.len() as u32 / 3;
+        self.bounds = AABB2D::new(min, max);
+        self.vertices = vertices;
+        self.indices = indices;
+        self.dirty = true;
+        result
+    }
+
+    /// Whether the triangulation changed since it was last uploaded
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn mark_clean(&mut self) {
+        self.dirty = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use glam::{Mat4, Vec2};
+
+    use crate::{
+        data::{
+            mesh::bmesh::{bm_face_polygons, bm_from_polygons_welded},
+            modifiers::{LinearArray, Offset},
+        },
+        shapes::square::create_square,
+    };
+
+    use super::*;
+
+    fn object(translation: Vec2, rotation: f32, scale: Vec2) -> Object {
+        let (mesh, _, _) = Mesh::new(create_square(), 0);
+        let material = Rc::new(RefCell::new(Material {
+            colour: Colour::ONE,
+        }));
+        Object::new(mesh, translation, rotation, scale, material)
+    }
+
+    fn world_point(object: &Object, local: Vec2) -> Vec2 {
+        object
+            .get_model_matrix()
+            .transform_point3(local.extend(0.0))
+            .truncate()
+    }
+
+    #[test]
+    fn applying_a_modifier_keeps_the_evaluated_mesh() {
+        for index in [0, 1] {
+            let mut object = object(Vec2::ZERO, 0.0, Vec2::ONE);
+            object.add_modifier(ModifierKind::Offset(Offset { distance: 0.5 }));
+            object.add_modifier(ModifierKind::LinearArray(LinearArray {
+                count: 3,
+                offset: Vec2::new(4.0, 0.0),
+            }));
+            object.evaluate_modifiers();
+            let before = bm_face_polygons(&object.borrow_evaluated_mesh().raw_mesh);
+
+            object.apply_modifier(index);
+            object.evaluate_modifiers();
+            assert_eq!(object.modifiers().len(), 1 - index);
+            assert_eq!(
+                bm_face_polygons(&object.borrow_evaluated_mesh().raw_mesh),
+                before
+            );
+        }
+    }
+
+    #[test]
+    fn applying_a_modifier_keeps_shared_edges() {
+        // Two unit squares side by side, sharing the edge between them
+        let halves = [0.0, 1.0].map(|x| {
+            [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)]
+                .map(|(px, py)| Vec2::new(x + px, py))
+                .to_vec()
+        });
+        let base = bm_from_polygons_welded(&halves, 1e-4);
+        assert_eq!((base.vertices.len(), base.edges.len()), (6, 7));
+
+        let (mesh, _, _) = Mesh::new(base, 0);
+        let material = Rc::new(RefCell::new(Material {
+            colour: Colour::ONE,
+        }));
+        let mut object = Object::new(mesh, Vec2::ZERO, 0.0, Vec2::ONE, material);
+        // The copy lines up against the original, so it shares their edge too
+        object.add_modifier(ModifierKind::LinearArray(LinearArray {
+            count: 2,
+            offset: Vec2::new(2.0, 0.0),
+        }));
+        object.apply_modifier(0);
+
+        let mesh = object.borrow_mesh();
+        let bmesh = &mesh.raw_mesh;
+        assert_eq!(bmesh.faces.len(), 4);
+        assert_eq!((bmesh.vertices.len(), bmesh.edges.len()), (10, 13));
+    }
+
+    #[test]
+    fn pivot_transforms_keep_the_pivot_fixed() {
+        let pivot = Vec2::new(1.0, 1.0);
+        let mut rotated = object(Vec2::new(3.0, 1.0), 0.3, Vec2::new(2.0, 1.0));
+        let before = rotated.get_model_matrix();
+
+        rotated.rotate_about(pivot, FRAC_PI_2);
+        let expected = Mat4::from_translation(pivot.extend(0.0))
+            * Mat4::from_rotation_z(FRAC_PI_2)
+            * Mat4::from_translation(-pivot.extend(0.0))
+            * before;
+        assert!(rotated.get_model_matrix().abs_diff_eq(expected, 1e-5));
+
+        // Scaling along the object's own axes moves points along those axes, away from the pivot
+        let mut scaled = object(Vec2::new(3.0, 1.0), FRAC_PI_2, Vec2::ONE);
+        let local_pivot = scaled
+            .get_model_matrix()
+            .inverse()
+            .transform_point3(pivot.extend(0.0))
+            .truncate();
+        scaled.scale_about(pivot, Vec2::new(3.0, 0.5));
+
+        assert!(world_point(&scaled, local_pivot).abs_diff_eq(pivot, 1e-5));
+        assert!(scaled.scale().abs_diff_eq(Vec2::new(3.0, 0.5), 1e-5));
+        assert!(scaled
+            .translation()
+            .abs_diff_eq(Vec2::new(1.0 + 1.0, 1.0), 1e-5));
+    }
+
+    #[test]
+    fn setters_refresh_the_bounds() {
+        let mut object = object(Vec2::ZERO, 0.0, Vec2::ONE);
+        let size = object.get_aabb().max - object.get_aabb().min;
+
+        object.set_translation(Vec2::new(10.0, 0.0));
+        object.set_scale(Vec2::new(2.0, 1.0));
+        let aabb = object.get_aabb();
+
+        assert!(((aabb.min + aabb.max) * 0.5).abs_diff_eq(Vec2::new(10.0, 0.0), 1e-5));
+        assert!((aabb.max - aabb.min).abs_diff_eq(size * Vec2::new(2.0, 1.0), 1e-5));
 
-        (self.vertices.clone(), self.indices.clone())
+        object.set_rotation(FRAC_PI_2);
+        let aabb = object.get_aabb();
+        assert!((aabb.max - aabb.min).abs_diff_eq(size * Vec2::new(1.0, 2.0), 1e-5));
     }
 }
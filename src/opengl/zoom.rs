@@ -0,0 +1,132 @@
+//! Zooming the view about the cursor
+//!
+//! Zoom is multiplicative, so every wheel notch changes the scale by the same factor whatever the
+//! current zoom is. Changes are animated in log space, which keeps the speed of the animation
+//! even across the whole range as well. Keeping the point under the cursor in place is left to
+//! [`Camera2D::zoom_about`](super::camera::Camera2D::zoom_about).
+
+use glam::Vec2;
+
+/// Wheel delta miniquad reports for one notch of a mouse wheel on this platform
+#[cfg(target_os = "windows")]
+const WHEEL_DELTA_PER_NOTCH: f32 = 120.0;
+#[cfg(target_os = "macos")]
+const WHEEL_DELTA_PER_NOTCH: f32 = 10.0;
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const WHEEL_DELTA_PER_NOTCH: f32 = 1.0;
+
+/// How close in log space the zoom has to get to its target for the animation to stop
+const SETTLE_THRESHOLD: f32 = 1e-3;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ZoomSettings {
+    pub min: f32,
+    pub max: f32,
+    /// Factor the zoom changes by for each wheel notch
+    pub step: f32,
+    /// Time in seconds the animation takes to cover most of the way to its target, 0 to jump
+    pub smoothing: f32,
+}
+
+impl Default for ZoomSettings {
+    fn default() -> Self {
+        Self {
+            min: 0.001,
+            max: 1000.0,
+            step: 1.2,
+            smoothing: 0.08,
+        }
+    }
+}
+
+impl ZoomSettings {
+    pub fn clamp(&self, zoom: f32) -> f32 {
+        zoom.clamp(self.min, self.max)
+    }
+}
+
+/// Zoom easing towards a target, about a fixed point on the screen
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SmoothZoom {
+    /// Zoom being animated towards and the screen point it zooms about, while animating
+    target: Option<(f32, Vec2)>,
+}
+
+impl SmoothZoom {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_animating(&self) -> bool {
+        self.target.is_some()
+    }
+
+    /// Zoom in by the wheel `delta`, about `anchor` in screen pixels
+    /// Scrolling again before the animation ends carries on from its target.
+    pub fn scroll(&mut self, zoom: f32, delta: f32, anchor: Vec2, settings: &ZoomSettings) {
+        let from = self.target.map_or(zoom, |(target, _)| target);
+        let target = settings.clamp(from * settings.step.powf(delta / WHEEL_DELTA_PER_NOTCH));
+        self.target = Some((target, anchor));
+    }
+
+    /// The zoom after `dt` seconds and the point it is about, or `None` if there is nothing to do
+    pub fn advance(&mut self, zoom: f32, dt: f32, settings: &ZoomSettings) -> Option<(f32, Vec2)> {
+        let (target, anchor) = self.target?;
+
+        let t = if settings.smoothing > 0.0 {
+            1.0 - (-dt / settings.smoothing).exp()
+        } else {
+            1.0
+        };
+        let log_zoom = zoom.ln() + (target.ln() - zoom.ln()) * t;
+
+        if (target.ln() - log_zoom).abs() < SETTLE_THRESHOLD {
+            self.target = None;
+            return Some((target, anchor));
+        }
+        Some((log_zoom.exp(), anchor))
+    }
+
+    pub fn stop(&mut self) {
+        self.target = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn notches_scale_evenly_and_animation_settles_on_the_clamped_target() {
+        let settings = ZoomSettings {
+            max: 10.0,
+            ..Default::default()
+        };
+        let notch = WHEEL_DELTA_PER_NOTCH;
+
+        // The same notch multiplies any zoom by the same factor
+        for zoom in [0.01, 1.0, 5.0] {
+            let mut smooth = SmoothZoom::new();
+            smooth.scroll(zoom, notch, Vec2::ZERO, &settings);
+            let (target, _) = smooth.target.unwrap();
+            assert!((target / zoom - settings.step).abs() < 1e-5);
+        }
+
+        let mut smooth = SmoothZoom::new();
+        let mut zoom = 1.0;
+        for _ in 0..20 {
+            smooth.scroll(zoom, notch, Vec2::ZERO, &settings);
+        }
+
+        let mut frames = 0;
+        while let Some((next, _)) = smooth.advance(zoom, 1.0 / 60.0, &settings) {
+            assert!(next > zoom && next <= settings.max);
+            zoom = next;
+            frames += 1;
+            assert!(frames < 120, "zoom never settled");
+        }
+        assert!(frames > 1);
+        assert_eq!(zoom, settings.max);
+        assert!(!smooth.is_animating());
+    }
+}
@@ -0,0 +1,348 @@
+//! Edit mode: selecting and deleting the vertices, edges and faces of one object's mesh
+//!
+//! Elements are picked in screen space, so clicks have the same reach at any zoom and box and
+//! lasso shapes can be tested without bringing them into the object's space. Selection lives on
+//! the mesh itself, so a mesh shared with other objects or held by the undo history is copied
+//! before its selection changes. Edits that change the shape give the object its own copy of the
+//! mesh first too, so the undo history can hold on to the mesh as it was.
+
+use std::rc::Rc;
+
+use glam::{Mat4, Vec2, Vec3};
+
+use crate::{
+    data::mesh::{
+        bm_select::{
+            bm_delete_selected, bm_elements_inside, bm_is_selected, bm_pick, bm_select_all,
+            bm_select_element, bm_select_flush, bm_selected_verts, MeshElement, MeshSelectMode,
+        },
+        bmesh::BMesh,
+    },
+    history::{Command, MeshState},
+    opengl::{
+        camera::Camera2D,
+        frustum::AABB2D,
+        scene::{ObjectKey, SceneData},
+    },
+};
+
+use super::select::SelectMode;
+
+/// Distance in pixels a click may be from a vertex or edge and still pick it
+const PICK_RADIUS: f32 = 8.0;
+
+/// The object being edited and which kind of element is picked
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EditMode {
+    object: ObjectKey,
+    select_mode: MeshSelectMode,
+}
+
+impl EditMode {
+    /// Start editing the active object, if there is one
+    pub fn enter(scene_data: &mut SceneData, select_mode: MeshSelectMode) -> Option<Self> {
+        let edit_mode = Self {
+            object: scene_data.active_object()?,
+            select_mode,
+        };
+        edit_mode.edit_selection(scene_data, |bmesh| bm_select_flush(bmesh, select_mode));
+        Some(edit_mode)
+    }
+
+    pub fn object(&self) -> ObjectKey {
+        self.object
+    }
+
+    pub fn select_mode(&self) -> MeshSelectMode {
+        self.select_mode
+    }
+
+    /// Pick a different kind of element, carrying the selection over
+    pub fn set_select_mode(&mut self, scene_data: &mut SceneData, select_mode: MeshSelectMode) {
+        self.select_mode = select_mode;
+        self.edit_selection(scene_data, |bmesh| bm_select_flush(bmesh, select_mode));
+    }
+
+    /// Whether the object being edited is still in the scene
+    pub fn is_valid(&self, scene_data: &SceneData) -> bool {
+        scene_data.objects().contains_key(self.object)
+    }
+
+    /// Change the selection by the element under `screen`, a point in pixels
+    pub fn select_at(
+        &self,
+        scene_data: &mut SceneData,
+        camera: &Camera2D,
+        screen: Vec2,
+        mode: SelectMode,
+    ) {
+        let Some(matrix) = self.screen_matrix(scene_data, camera) else {
+            return;
+        };
+        self.edit_selection(scene_data, |bmesh| {
+            let hit = bm_pick(bmesh, self.select_mode, matrix, screen, PICK_RADIUS);
+            apply_element_selection(bmesh, self.select_mode, hit.as_slice(), mode);
+        });
+    }
+
+    /// Change the selection by the elements whose screen position `inside` accepts
+    pub fn select_inside(
+        &self,
+        scene_data: &mut SceneData,
+        camera: &Camera2D,
+        inside: impl Fn(Vec2) -> bool,
+        mode: SelectMode,
+    ) {
+        let Some(matrix) = self.screen_matrix(scene_data, camera) else {
+            return;
+        };
+        self.edit_selection(scene_data, |bmesh| {
+            let hits = bm_elements_inside(bmesh, self.select_mode, matrix, &inside);
+            apply_element_selection(bmesh, self.select_mode, &hits, mode);
+        });
+    }
+
+    /// World bounds of the selected vertices, or `None` if none are selected
+    pub fn selected_bounds(&self, scene_data: &SceneData) -> Option<AABB2D> {
+        let object = scene_data.objects().get(self.object)?;
+        let matrix = object.get_model_matrix();
+        let mesh = object.borrow_mesh();
+        bm_selected_verts(&mesh.raw_mesh)
+            .into_iter()
+            .map(|v| world_point(matrix, mesh.raw_mesh.vertices[v].vertex.pos))
+            .map(|point| AABB2D::new(point, point))
+            .reduce(|bounds, point| bounds.union(&point))
+    }
+
+    /// Delete the selected elements, returning the edit for the undo history
+    pub fn delete_selected(&self, scene_data: &mut SceneData) -> Option<Command> {
+        let object = scene_data.objects().get(self.object)?;
+        if bm_selected_verts(&object.borrow_mesh().raw_mesh).is_empty() {
+            return None;
+        }
+
+        let before = MeshState::of(object);
+        scene_data.edit_object(self.object, |object| {
+            object.copy_mesh();
+            object.edit_mesh(|bmesh| bm_delete_selected(bmesh, self.select_mode));
+        });
+        let after = MeshState::of(&scene_data.objects()[self.object]);
+
+        Some(Command::Mesh {
+            key: self.object,
+            before,
+            after,
+        })
+    }
+
+    /// Header text describing the mode and how much is selected
+    pub fn status(&self, scene_data: &SceneData) -> String {
+        let Some(object) = scene_data.objects().get(self.object) else {
+            return String::new();
+        };
+        let mesh = object.borrow_mesh();
+        let bmesh = &mesh.raw_mesh;
+        let (name, selected, total) = match self.select_mode {
+            MeshSelectMode::Vertex => (
+                "Vertices",
+                bmesh.vertices.values().filter(|v| v.selected).count(),
+                bmesh.vertices.len(),
+            ),
+            MeshSelectMode::Edge => (
+                "Edges",
+                bmesh.edges.values().filter(|e| e.selected).count(),
+                bmesh.edges.len(),
+            ),
+            MeshSelectMode::Face => (
+                "Faces",
+                bmesh.faces.values().filter(|f| f.selected).count(),
+                bmesh.faces.len(),
+            ),
+        };
+        format!("Edit Mode: {name} {selected}/{total}")
+    }
+
+    /// Matrix taking the object's mesh to screen pixels
+    fn screen_matrix(&self, scene_data: &SceneData, camera: &Camera2D) -> Option<Mat4> {
+        let object = scene_data.objects().get(self.object)?;
+        Some(camera.screen_matrix() * object.get_model_matrix())
+    }
+
+    /// Change the selection flags of the mesh being edited
+    ///
+    /// Selection does not change the shape, so the mesh is edited in place without being
+    /// triangulated again. A mesh anything else holds on to is copied first, which replaces the
+    /// object's mesh and needs the GPU arena syncing.
+    fn edit_selection(&self, scene_data: &mut SceneData, edit: impl FnOnce(&mut BMesh)) {
+        let Some(object) = scene_data.objects().get(self.object) else {
+            return;
+        };
+        if Rc::strong_count(object.mesh()) > 1 {
+            scene_data.edit_object(self.object, |object| object.copy_mesh());
+        }
+        edit(
+            &mut scene_data.objects()[self.object]
+                .mesh()
+                .borrow_mut()
+                .raw_mesh,
+        );
+    }
+}
+
+/// Change the selection by `hits`, then bring the rest of the mesh in line
+pub fn apply_element_selection(
+    bmesh: &mut BMesh,
+    select_mode: MeshSelectMode,
+    hits: &[MeshElement],
+    mode: SelectMode,
+) {
+    if mode == SelectMode::Set {
+        bm_select_all(bmesh, false);
+    }
+
+    for &element in hits {
+        let selected = match mode {
+            SelectMode::Set | SelectMode::Extend => true,
+            SelectMode::Subtract => false,
+            SelectMode::Invert => !bm_is_selected(bmesh, element),
+        };
+        bm_select_element(bmesh, element, selected);
+    }
+
+    bm_select_flush(bmesh, select_mode);
+}
+
+fn world_point(matrix: Mat4, local: Vec2) -> Vec2 {
+    matrix
+        .transform_point3(Vec3::new(local.x, local.y, 0.0))
+        .truncate()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+
+    use crate::{
+        opengl::structs::{Colour, Material, Mesh, Object},
+        shapes::square::create_square,
+    };
+
+    use super::*;
+
+    /// One square object scaled up and moved, shared with a second object, viewed at zoom 1
+    fn scene() -> (SceneData, Camera2D) {
+        let (square, _, _) = Mesh::new(create_square(), 0);
+        let material = Rc::new(RefCell::new(Material {
+            colour: Colour::ONE,
+        }));
+        let objects = [Vec2::new(100.0, 0.0), Vec2::new(-100.0, 0.0)]
+            .into_iter()
+            .map(|translation| {
+                Object::new(
+                    square.clone(),
+                    translation,
+                    0.0,
+                    Vec2::splat(50.0),
+                    material.clone(),
+                )
+            })
+            .collect();
+        let mut scene_data = SceneData::new(objects);
+        scene_data.set_active_object(Some(scene_data.object_order()[0]));
+        let camera = Camera2D::new(Vec2::ZERO, 1.0, 0.0, Vec2::new(800.0, 600.0));
+        (scene_data, camera)
+    }
+
+    fn selected_verts(scene_data: &SceneData, key: ObjectKey) -> usize {
+        bm_selected_verts(&scene_data.objects()[key].borrow_mesh().raw_mesh).len()
+    }
+
+    fn vert_count(scene_data: &SceneData, key: ObjectKey) -> usize {
+        scene_data.objects()[key]
+            .borrow_mesh()
+            .raw_mesh
+            .vertices
+            .len()
+    }
+
+    #[test]
+    fn clicks_and_shapes_select_in_screen_space() {
+        let (mut scene_data, camera) = scene();
+        let mut edit_mode = EditMode::enter(&mut scene_data, MeshSelectMode::Vertex).unwrap();
+        let key = edit_mode.object();
+        let bounds = scene_data.objects()[key].get_aabb();
+
+        // A click a few pixels off a corner still picks it
+        let corner = camera.world_to_screen(bounds.max) + Vec2::new(3.0, -3.0);
+        edit_mode.select_at(&mut scene_data, &camera, corner, SelectMode::Set);
+        assert_eq!(selected_verts(&scene_data, key), 1);
+
+        // A box around the right half adds the other right corner and the edge between them
+        let centre = camera.world_to_screen((bounds.min + bounds.max) / 2.0);
+        edit_mode.select_inside(
+            &mut scene_data,
+            &camera,
+            |point| point.x > centre.x,
+            SelectMode::Extend,
+        );
+        assert_eq!(selected_verts(&scene_data, key), 2);
+        assert_eq!(edit_mode.status(&scene_data), "Edit Mode: Vertices 2/4");
+
+        // Edge mode keeps the edge, face mode drops the half selected face
+        edit_mode.set_select_mode(&mut scene_data, MeshSelectMode::Edge);
+        assert_eq!(edit_mode.status(&scene_data), "Edit Mode: Edges 1/4");
+        edit_mode.set_select_mode(&mut scene_data, MeshSelectMode::Face);
+        assert_eq!(edit_mode.status(&scene_data), "Edit Mode: Faces 0/1");
+        assert_eq!(selected_verts(&scene_data, key), 0);
+
+        edit_mode.select_at(&mut scene_data, &camera, centre, SelectMode::Set);
+        assert_eq!(edit_mode.status(&scene_data), "Edit Mode: Faces 1/1");
+    }
+
+    #[test]
+    fn deleting_copies_the_mesh_and_records_the_old_one() {
+        let (mut scene_data, camera) = scene();
+        let edit_mode = EditMode::enter(&mut scene_data, MeshSelectMode::Vertex).unwrap();
+        let [key, other] = [0, 1].map(|i| scene_data.object_order()[i]);
+        assert!(edit_mode.delete_selected(&mut scene_data).is_none());
+
+        let bounds = scene_data.objects()[key].get_aabb();
+        let corner = camera.world_to_screen(bounds.max);
+        edit_mode.select_at(&mut scene_data, &camera, corner, SelectMode::Set);
+        let mut command = edit_mode.delete_selected(&mut scene_data).unwrap();
+
+        // The edited object has a mesh of its own and the other keeps the full square
+        assert_eq!(vert_count(&scene_data, key), 3);
+        assert_eq!(vert_count(&scene_data, other), 4);
+        assert!(!Rc::ptr_eq(
+            scene_data.objects()[key].mesh(),
+            scene_data.objects()[other].mesh()
+        ));
+
+        command.undo(&mut scene_data);
+        assert_eq!(vert_count(&scene_data, key), 4);
+        assert_eq!(selected_verts(&scene_data, key), 1);
+    }
+
+    #[test]
+    fn selecting_leaves_shared_meshes_alone() {
+        let (mut scene_data, camera) = scene();
+        let [key, other] = [0, 1].map(|i| scene_data.object_order()[i]);
+        let shared = scene_data.objects()[other].mesh().clone();
+
+        let edit_mode = EditMode::enter(&mut scene_data, MeshSelectMode::Vertex).unwrap();
+        let bounds = scene_data.objects()[key].get_aabb();
+        let corner = camera.world_to_screen(bounds.max);
+        edit_mode.select_at(&mut scene_data, &camera, corner, SelectMode::Set);
+
+        assert_eq!(selected_verts(&scene_data, key), 1);
+        assert_eq!(selected_verts(&scene_data, other), 0);
+        assert!(Rc::ptr_eq(scene_data.objects()[other].mesh(), &shared));
+
+        // A mesh the undo history holds is copied too, so the snapshot keeps its selection
+        let snapshot = MeshState::of(&scene_data.objects()[key]);
+        edit_mode.select_at(&mut scene_data, &camera, corner, SelectMode::Invert);
+        assert_eq!(bm_selected_verts(&snapshot.mesh.borrow().raw_mesh).len(), 1);
+        assert_eq!(selected_verts(&scene_data, key), 0);
+    }
+}
@@ -0,0 +1,5 @@
+pub mod edit_mesh;
+pub mod pen;
+pub mod select;
+pub mod snap;
+pub mod transform;
@@ -0,0 +1,301 @@
+//! Drawing new polygon objects point by point
+//!
+//! Each click places a point, and dragging before letting go pulls out handles that turn the
+//! segments either side of the point into Bezier curves. The handles are mirrored through the
+//! point, so the path stays smooth through it. Curves are flattened into straight segments when
+//! the path is closed, since meshes only have straight edges.
+
+use std::{cell::RefCell, f32::consts::PI, rc::Rc};
+
+use glam::Vec2;
+
+use crate::{
+    data::{
+        mesh::bmesh::{bm_polygon_create, BMesh},
+        polygon::{orient, signed_area},
+    },
+    opengl::structs::{Colour, Material, Mesh, Object},
+};
+
+/// Distance in pixels the mouse has to move after placing a point before it drags out handles
+const HANDLE_THRESHOLD: f32 = 4.0;
+/// Distance in pixels from the first point within which a click closes the path
+const CLOSE_RADIUS: f32 = 8.0;
+/// Straight segments each curved segment is flattened into
+const CURVE_SEGMENTS: usize = 16;
+/// Step the direction from the previous point snaps to while snapping, in radians
+const ANGLE_SNAP: f32 = PI / 12.0;
+/// Name of the objects the pen makes
+const OBJECT_NAME: &str = "Path";
+/// Colour of the objects the pen makes
+const DEFAULT_COLOUR: Colour = Colour::new(0.8, 0.8, 0.8, 1.0);
+
+/// A placed point and its handles
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PenPoint {
+    pub pos: Vec2,
+    /// Offset of the handle the path leaves the point along, the way in being its mirror
+    pub handle: Vec2,
+}
+
+/// What pressing the mouse did
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PenPress {
+    Placed,
+    /// The press was on the first point and the path is ready to be finished
+    Closed,
+}
+
+/// A path being drawn, in world space
+#[derive(Debug, Clone, Default)]
+pub struct PenTool {
+    points: Vec<PenPoint>,
+    /// Where the next point would go
+    cursor: Vec2,
+    /// Whether the mouse is still held after placing the last point
+    pressed: bool,
+    /// Whether the held mouse moved far enough to drag out handles
+    dragging: bool,
+}
+
+impl PenTool {
+    pub fn new(cursor: Vec2) -> Self {
+        Self {
+            cursor,
+            ..Default::default()
+        }
+    }
+
+    pub fn points(&self) -> &[PenPoint] {
+        &self.points
+    }
+
+    pub fn can_close(&self) -> bool {
+        self.points.len() >= 3
+    }
+
+    /// Place a point at `pos`, or close the path if it is on the first point
+    /// `pixel` is the size of a screen pixel in world units.
+    pub fn press(&mut self, pos: Vec2, pixel: f32) -> PenPress {
+        let on_first = self
+            .points
+            .first()
+            .is_some_and(|first| first.pos.distance(pos) <= CLOSE_RADIUS * pixel);
+        if on_first && self.can_close() {
+            return PenPress::Closed;
+        }
+
+        self.points.push(PenPoint {
+            pos,
+            handle: Vec2::ZERO,
+        });
+        self.cursor = pos;
+        self.pressed = true;
+        self.dragging = false;
+        PenPress::Placed
+    }
+
+    /// Follow the mouse, dragging out the last point's handles while the mouse is held
+    pub fn move_to(&mut self, pos: Vec2, pixel: f32) {
+        self.cursor = pos;
+        if !self.pressed {
+            return;
+        }
+        let Some(last) = self.points.last_mut() else {
+            return;
+        };
+
+        self.dragging |= last.pos.distance(pos) > HANDLE_THRESHOLD * pixel;
+        if self.dragging {
+            last.handle = pos - last.pos;
+        }
+    }
+
+    pub fn release(&mut self) {
+        self.pressed = false;
+        self.dragging = false;
+    }
+
+    /// Take back the last point, returning false if there were none
+    pub fn remove_last_point(&mut self) -> bool {
+        self.release();
+        self.points.pop().is_some()
+    }
+
+    /// The open path through the placed points and on to the cursor, flattened
+    pub fn preview(&self) -> Vec<Vec2> {
+        let mut path = self.flatten(false);
+        if !self.pressed {
+            if let Some(last) = self.points.last() {
+                let cursor = PenPoint {
+                    pos: self.cursor,
+                    handle: Vec2::ZERO,
+                };
+                flatten_segment(&mut path, last, &cursor);
+            }
+        }
+        path
+    }
+
+    /// Flattened outline of the placed points, as a closed polygon
+    pub fn outline(&self) -> Vec<Vec2> {
+        self.flatten(true)
+    }
+
+    fn flatten(&self, closed: bool) -> Vec<Vec2> {
+        let Some(first) = self.points.first() else {
+            return vec![];
+        };
+
+        let mut path = vec![first.pos];
+        for pair in self.points.windows(2) {
+            flatten_segment(&mut path, &pair[0], &pair[1]);
+        }
+        if closed && self.points.len() > 1 {
+            flatten_segment(&mut path, &self.points[self.points.len() - 1], first);
+            // The segment back to the start ends on the first point, which is already there
+            path.pop();
+        }
+        path
+    }
+
+    /// Turn the closed path into an object with one face, or `None` if the path encloses nothing
+    ///
+    /// The object sits at the centre of the path's bounds, so it rotates and scales about it.
+    pub fn finish(&self) -> Option<Object> {
+        let mut outline = self.outline();
+        outline.dedup_by(|a, b| a.distance(*b) <= f32::EPSILON);
+        if outline.len() < 3 || signed_area(&outline).abs() <= f32::EPSILON {
+            return None;
+        }
+
+        let (min, max) = outline
+            .iter()
+            .fold((outline[0], outline[0]), |(min, max), point| {
+                (min.min(*point), max.max(*point))
+            });
+        let centre = (min + max) / 2.0;
+        let local = outline.iter().map(|point| *point - centre).collect();
+
+        let mut bmesh = BMesh::new();
+        bm_polygon_create(&mut bmesh, &orient(local, true));
+        let material = Rc::new(RefCell::new(Material {
+            colour: DEFAULT_COLOUR,
+        }));
+
+        let mut object = Object::new(Mesh::new(bmesh, 0).0, centre, 0.0, Vec2::ONE, material);
+        object.name = OBJECT_NAME.to_string();
+        Some(object)
+    }
+
+    /// Header text describing the path being drawn
+    pub fn status(&self) -> String {
+        format!("Pen: {} points", self.points.len())
+    }
+}
+
+/// Snap the direction to `pos` from `previous` to even angles
+pub fn snap_angle(pos: Vec2, previous: Vec2) -> Vec2 {
+    if pos == previous {
+        return pos;
+    }
+    let offset = pos - previous;
+    let snapped = (offset.y.atan2(offset.x) / ANGLE_SNAP).round() * ANGLE_SNAP;
+    let direction = Vec2::from_angle(snapped);
+    previous + direction * offset.dot(direction)
+}
+
+/// Add the points after `from` on the way to `to`
+fn flatten_segment(path: &mut Vec<Vec2>, from: &PenPoint, to: &PenPoint) {
+    if from.handle == Vec2::ZERO && to.handle == Vec2::ZERO {
+        path.push(to.pos);
+        return;
+    }
+
+    let (p0, p1, p2, p3) = (from.pos, from.pos + from.handle, to.pos - to.handle, to.pos);
+    path.extend((1..=CURVE_SEGMENTS).map(|i| {
+        let t = i as f32 / CURVE_SEGMENTS as f32;
+        let u = 1.0 - t;
+        p0 * (u * u * u) + p1 * (3.0 * u * u * t) + p2 * (3.0 * u * t * t) + p3 * (t * t * t)
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn click(pen: &mut PenTool, pos: Vec2) -> PenPress {
+        let press = pen.press(pos, 1.0);
+        pen.release();
+        press
+    }
+
+    #[test]
+    fn clicks_make_a_polygon_centred_on_its_object() {
+        let mut pen = PenTool::new(Vec2::ZERO);
+        // Clockwise, which the mesh turns around
+        for pos in [
+            Vec2::new(10.0, 10.0),
+            Vec2::new(10.0, 30.0),
+            Vec2::new(50.0, 30.0),
+        ] {
+            assert_eq!(click(&mut pen, pos), PenPress::Placed);
+        }
+
+        // Take the last point back and place it again
+        assert!(pen.remove_last_point());
+        click(&mut pen, Vec2::new(50.0, 30.0));
+        click(&mut pen, Vec2::new(50.0, 10.0));
+        assert_eq!(pen.preview().len(), 5);
+
+        assert_eq!(click(&mut pen, Vec2::new(13.0, 8.0)), PenPress::Closed);
+        let object = pen.finish().unwrap();
+        assert_eq!(object.translation(), Vec2::new(30.0, 20.0));
+
+        let mesh = object.borrow_mesh();
+        assert_eq!(mesh.raw_mesh.vertices.len(), 4);
+        assert_eq!(mesh.raw_mesh.faces.len(), 1);
+        assert_eq!(mesh.tris, 2);
+        assert_eq!(mesh.bounds.max, Vec2::new(20.0, 10.0));
+    }
+
+    #[test]
+    fn dragging_pulls_out_mirrored_handles() {
+        let mut pen = PenTool::new(Vec2::ZERO);
+        click(&mut pen, Vec2::new(0.0, 0.0));
+
+        // A small wobble while placing a point leaves it sharp
+        pen.press(Vec2::new(10.0, 0.0), 1.0);
+        pen.move_to(Vec2::new(11.0, 1.0), 1.0);
+        assert_eq!(pen.points()[1].handle, Vec2::ZERO);
+
+        pen.move_to(Vec2::new(10.0, 5.0), 1.0);
+        pen.release();
+        pen.move_to(Vec2::new(100.0, 100.0), 1.0);
+        assert_eq!(pen.points()[1].handle, Vec2::new(0.0, 5.0));
+
+        // The curve into the point comes in from below, the way out leaves upwards
+        click(&mut pen, Vec2::new(20.0, 0.0));
+        let outline = pen.outline();
+        assert_eq!(outline.len(), 1 + 2 * CURVE_SEGMENTS);
+        assert!(outline[CURVE_SEGMENTS - 1].y < 0.0);
+        assert!(outline[CURVE_SEGMENTS + 1].y > 0.0);
+        assert_eq!(outline[CURVE_SEGMENTS], Vec2::new(10.0, 0.0));
+
+        // Too few points to close on the first one
+        let mut pen = PenTool::new(Vec2::ZERO);
+        click(&mut pen, Vec2::ZERO);
+        click(&mut pen, Vec2::X * 10.0);
+        assert_eq!(click(&mut pen, Vec2::ZERO), PenPress::Placed);
+        assert!(pen.finish().is_none());
+    }
+
+    #[test]
+    fn angle_snapping_keeps_to_even_angles() {
+        let snapped = snap_angle(Vec2::new(10.0, 1.0), Vec2::ZERO);
+        assert!(snapped.abs_diff_eq(Vec2::new(10.0, 0.0), 1e-5));
+        let snapped = snap_angle(Vec2::new(10.0, 9.0), Vec2::ZERO);
+        assert!(snapped.abs_diff_eq(Vec2::splat(9.5), 1e-5));
+        assert_eq!(snap_angle(Vec2::ONE, Vec2::ONE), Vec2::ONE);
+    }
+}
@@ -0,0 +1,310 @@
+//! Box and lasso selection
+//!
+//! The shape is dragged out in screen space so it can be drawn over the viewport, and turned
+//! into world space when it is released. Candidates come from the scene's spatial index: a box
+//! selects every object whose bounds it touches, a lasso selects objects whose bounds centre it
+//! surrounds.
+
+use glam::Vec2;
+
+use crate::{
+    data::polygon::contains_point,
+    opengl::{
+        frustum::AABB2D,
+        scene::{ObjectKey, SceneData},
+    },
+};
+
+/// Distance in pixels the mouse has to move before a click becomes a drag
+const DRAG_THRESHOLD: f32 = 4.0;
+/// Distance in pixels between lasso points
+const LASSO_SPACING: f32 = 3.0;
+
+/// How the objects inside the shape change the selection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectMode {
+    /// Select only the objects inside
+    Set,
+    /// Add the objects inside to the selection
+    Extend,
+    /// Remove the objects inside from the selection
+    Subtract,
+    /// Flip the objects inside
+    Invert,
+}
+
+impl SelectMode {
+    /// Shift extends, Ctrl subtracts and both together invert
+    pub fn from_modifiers(shift: bool, ctrl: bool) -> Self {
+        match (shift, ctrl) {
+            (false, false) => SelectMode::Set,
+            (true, false) => SelectMode::Extend,
+            (false, true) => SelectMode::Subtract,
+            (true, true) => SelectMode::Invert,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SelectShape {
+    Box,
+    Lasso,
+}
+
+/// A box or lasso being dragged out, in screen pixels
+pub struct SelectGesture {
+    shape: SelectShape,
+    start: Vec2,
+    /// Lasso outline, or the start and current corner of the box
+    points: Vec<Vec2>,
+    dragged: bool,
+}
+
+impl SelectGesture {
+    pub fn start(shape: SelectShape, position: Vec2) -> Self {
+        Self {
+            shape,
+            start: position,
+            points: vec![position],
+            dragged: false,
+        }
+    }
+
+    pub fn shape(&self) -> SelectShape {
+        self.shape
+    }
+
+    pub fn drag_to(&mut self, position: Vec2) {
+        self.dragged |= position.distance(self.start) > DRAG_THRESHOLD;
+
+        match self.shape {
+            SelectShape::Box => self.points = vec![self.start, position],
+            SelectShape::Lasso => {
+                let last = *self.points.last().unwrap_or(&self.start);
+                if position.distance(last) >= LASSO_SPACING {
+                    self.points.push(position);
+                }
+            }
+        }
+    }
+
+    /// Whether the mouse moved far enough for this to be a drag rather than a click
+    pub fn is_drag(&self) -> bool {
+        self.dragged
+    }
+
+    /// Outline to draw, in screen pixels
+    pub fn outline(&self) -> Vec<Vec2> {
+        match self.shape {
+            SelectShape::Box => {
+                let (a, b) = self.corners();
+                vec![a, Vec2::new(b.x, a.y), b, Vec2::new(a.x, b.y)]
+            }
+            SelectShape::Lasso => self.points.clone(),
+        }
+    }
+
+    /// Whether a point in screen pixels is inside the shape
+    pub fn contains(&self, point: Vec2) -> bool {
+        match self.shape {
+            SelectShape::Box => {
+                let (a, b) = self.corners();
+                point.cmpge(a.min(b)).all() && point.cmple(a.max(b)).all()
+            }
+            SelectShape::Lasso => contains_point(&self.points, point),
+        }
+    }
+
+    fn corners(&self) -> (Vec2, Vec2) {
+        (self.start, *self.points.last().unwrap_or(&self.start))
+    }
+
+    /// Change the selection by the objects inside the shape
+    /// `to_world` maps screen pixels to world space.
+    pub fn finish(
+        self,
+        scene_data: &mut SceneData,
+        mode: SelectMode,
+        to_world: impl Fn(Vec2) -> Vec2,
+    ) {
+        match self.shape {
+            SelectShape::Box => {
+                let (a, b) = self.corners();
+                let (a, b) = (to_world(a), to_world(b));
+                select_in_rect(scene_data, &AABB2D::new(a.min(b), a.max(b)), mode);
+            }
+            SelectShape::Lasso => {
+                let lasso = self.points.into_iter().map(to_world).collect::<Vec<_>>();
+                select_in_lasso(scene_data, &lasso, mode);
+            }
+        }
+    }
+}
+
+/// Change the selection by the objects whose bounds touch `rect`
+pub fn select_in_rect(scene_data: &mut SceneData, rect: &AABB2D, mode: SelectMode) {
+    let hits = scene_data
+        .objects_in_rect(rect)
+        .into_iter()
+        .filter(|&key| scene_data.objects()[key].is_selectable())
+        .collect::<Vec<_>>();
+    apply_selection(scene_data, &hits, mode);
+}
+
+/// Change the selection by the objects whose bounds centre is inside `lasso`
+pub fn select_in_lasso(scene_data: &mut SceneData, lasso: &[Vec2], mode: SelectMode) {
+    let Some(first) = lasso.first() else {
+        apply_selection(scene_data, &[], mode);
+        return;
+    };
+    let bounds = lasso
+        .iter()
+        .fold(AABB2D::new(*first, *first), |bounds, point| {
+            AABB2D::new(bounds.min.min(*point), bounds.max.max(*point))
+        });
+
+    let hits = scene_data
+        .objects_in_rect(&bounds)
+        .into_iter()
+        .filter(|&key| {
+            let object = &scene_data.objects()[key];
+            let aabb = object.get_aabb();
+            object.is_selectable() && contains_point(lasso, (aabb.min + aabb.max) * 0.5)
+        })
+        .collect::<Vec<_>>();
+    apply_selection(scene_data, &hits, mode);
+}
+
+/// Change the selection by `hits`, given in draw order
+/// The topmost hit left selected becomes the active object.
+pub fn apply_selection(scene_data: &mut SceneData, hits: &[ObjectKey], mode: SelectMode) {
+    if mode == SelectMode::Set {
+        for (_, object) in scene_data.objects_mut().iter_mut() {
+            object.selected = false;
+        }
+    }
+
+    for &key in hits {
+        let object = &mut scene_data.objects_mut()[key];
+        object.selected = match mode {
+            SelectMode::Set | SelectMode::Extend => true,
+            SelectMode::Subtract => false,
+            SelectMode::Invert => !object.selected,
+        };
+    }
+
+    let active = hits
+        .iter()
+        .rev()
+        .copied()
+        .find(|&key| scene_data.objects()[key].selected);
+    if active.is_some() || mode == SelectMode::Set {
+        scene_data.set_active_object(active);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use crate::{
+        opengl::structs::{Colour, Material, Mesh, Object},
+        shapes::square::create_square,
+    };
+
+    use super::*;
+
+    /// A row of squares two units wide, centred on x = 0, 10, 20, ...
+    fn row_scene(count: usize) -> SceneData {
+        let (square, _, _) = Mesh::new(create_square(), 0);
+        let material = Rc::new(RefCell::new(Material {
+            colour: Colour::ONE,
+        }));
+        let objects = (0..count)
+            .map(|i| {
+                let translation = Vec2::new(i as f32 * 10.0, 0.0);
+                Object::new(
+                    square.clone(),
+                    translation,
+                    0.0,
+                    Vec2::ONE,
+                    material.clone(),
+                )
+            })
+            .collect();
+        SceneData::new(objects)
+    }
+
+    fn selected(scene_data: &SceneData) -> Vec<usize> {
+        scene_data
+            .object_order()
+            .iter()
+            .enumerate()
+            .filter(|(_, &key)| scene_data.objects()[key].selected)
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    fn rect(from_x: f32, to_x: f32) -> AABB2D {
+        AABB2D::new(Vec2::new(from_x, -1.0), Vec2::new(to_x, 1.0))
+    }
+
+    #[test]
+    fn box_select_modes() {
+        let mut scene_data = row_scene(5);
+
+        select_in_rect(&mut scene_data, &rect(-1.0, 11.0), SelectMode::Set);
+        assert_eq!(selected(&scene_data), [0, 1]);
+        assert_eq!(
+            scene_data.active_object(),
+            Some(scene_data.object_order()[1])
+        );
+
+        select_in_rect(&mut scene_data, &rect(29.0, 41.0), SelectMode::Extend);
+        assert_eq!(selected(&scene_data), [0, 1, 3, 4]);
+
+        select_in_rect(&mut scene_data, &rect(9.0, 31.0), SelectMode::Subtract);
+        assert_eq!(selected(&scene_data), [0, 4]);
+
+        select_in_rect(&mut scene_data, &rect(-1.0, 21.0), SelectMode::Invert);
+        assert_eq!(selected(&scene_data), [1, 2, 4]);
+
+        // An empty box clears the selection in Set mode only
+        select_in_rect(&mut scene_data, &rect(100.0, 110.0), SelectMode::Extend);
+        assert_eq!(selected(&scene_data), [1, 2, 4]);
+        select_in_rect(&mut scene_data, &rect(100.0, 110.0), SelectMode::Set);
+        assert!(selected(&scene_data).is_empty());
+        assert_eq!(scene_data.active_object(), None);
+    }
+
+    #[test]
+    fn lasso_selects_by_centre_inside_a_concave_outline() {
+        let mut scene_data = row_scene(5);
+
+        // A U shape whose arms surround squares 0 and 4, with the others in the gap between them
+        let lasso = [
+            Vec2::new(-5.0, -5.0),
+            Vec2::new(45.0, -5.0),
+            Vec2::new(45.0, 5.0),
+            Vec2::new(35.0, 5.0),
+            Vec2::new(35.0, -0.5),
+            Vec2::new(5.0, -0.5),
+            Vec2::new(5.0, 5.0),
+            Vec2::new(-5.0, 5.0),
+        ];
+        select_in_lasso(&mut scene_data, &lasso, SelectMode::Set);
+        assert_eq!(selected(&scene_data), [0, 4]);
+
+        let mut gesture = SelectGesture::start(SelectShape::Lasso, Vec2::new(15.0, -5.0));
+        for point in [
+            Vec2::new(25.0, -5.0),
+            Vec2::new(25.0, 5.0),
+            Vec2::new(15.0, 5.0),
+        ] {
+            gesture.drag_to(point);
+        }
+        assert!(gesture.is_drag());
+        gesture.finish(&mut scene_data, SelectMode::Extend, |point| point);
+        assert_eq!(selected(&scene_data), [0, 2, 4]);
+    }
+}
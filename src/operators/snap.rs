@@ -0,0 +1,354 @@
+//! Snapping points to the grid and to the geometry of the scene
+//!
+//! Targets are caught within a tolerance in screen pixels, so snapping has the same reach at
+//! any zoom. Points on the geometry (vertices, edge midpoints, object centres and bounds
+//! corners) win over the nearest point along an edge, which wins over the grid, so a target
+//! close to a grid line isn't lost to it. The grid snaps to the finest lines the viewport is
+//! drawing, which change with the zoom.
+
+use glam::{Mat4, Vec2, Vec3};
+
+use crate::{
+    data::{mesh::bmesh::VertKey, polygon::closest_point_on_segment},
+    opengl::{
+        camera::Camera2D,
+        frustum::AABB2D,
+        pipelines::grid::GridSettings,
+        scene::{ObjectKey, SceneData},
+    },
+};
+
+/// Default distance in pixels a target may be from the point and still catch it
+const DEFAULT_TOLERANCE: f32 = 10.0;
+
+/// What a point snapped to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapKind {
+    Vertex,
+    EdgeMidpoint,
+    ObjectCentre,
+    BoundsCorner,
+    Edge,
+    Grid,
+}
+
+impl SnapKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SnapKind::Vertex => "Vertex",
+            SnapKind::EdgeMidpoint => "Edge Midpoint",
+            SnapKind::ObjectCentre => "Object Centre",
+            SnapKind::BoundsCorner => "Bounds Corner",
+            SnapKind::Edge => "Edge",
+            SnapKind::Grid => "Grid",
+        }
+    }
+}
+
+/// A point snapped to, in world space
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SnapTarget {
+    pub point: Vec2,
+    pub kind: SnapKind,
+}
+
+/// Which targets are snapped to, and from how far
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SnapSettings {
+    /// Distance in pixels a target may be from the point and still catch it
+    pub tolerance: f32,
+    pub grid: bool,
+    pub vertices: bool,
+    pub edge_midpoints: bool,
+    pub edges: bool,
+    pub object_centres: bool,
+    pub bounds_corners: bool,
+}
+
+impl Default for SnapSettings {
+    fn default() -> Self {
+        Self {
+            tolerance: DEFAULT_TOLERANCE,
+            grid: true,
+            vertices: true,
+            edge_midpoints: true,
+            edges: true,
+            object_centres: true,
+            bounds_corners: true,
+        }
+    }
+}
+
+/// Geometry that moves along with the point being snapped, so it can't be snapped to
+#[derive(Debug, Clone, Default)]
+pub struct SnapIgnore {
+    /// Objects that are ignored along with their children
+    pub objects: Vec<ObjectKey>,
+    /// Vertices of an object's mesh, which is snapped to instead of its evaluated mesh
+    pub vertices: Option<(ObjectKey, Vec<VertKey>)>,
+}
+
+impl SnapIgnore {
+    fn ignores_object(&self, scene_data: &SceneData, key: ObjectKey) -> bool {
+        self.objects
+            .iter()
+            .any(|&ignored| scene_data.is_ancestor(ignored, key))
+    }
+
+    fn ignored_vertices(&self, key: ObjectKey) -> Option<&[VertKey]> {
+        match &self.vertices {
+            Some((object, vertices)) if *object == key => Some(vertices),
+            _ => None,
+        }
+    }
+}
+
+/// Snap `point`, in world space, to the best target within the tolerance
+///
+/// Returns `None` if nothing is close enough and grid snapping is off.
+pub fn snap(
+    scene_data: &SceneData,
+    camera: &Camera2D,
+    grid: &GridSettings,
+    settings: &SnapSettings,
+    point: Vec2,
+    ignore: &SnapIgnore,
+) -> Option<SnapTarget> {
+    let radius = settings.tolerance / camera.zoom;
+    let mut points = Candidates::new(point, radius);
+    let mut edges = Candidates::new(point, radius);
+
+    let rect = AABB2D::new(point - radius, point + radius);
+    for key in scene_data.objects_in_rect(&rect) {
+        let object = &scene_data.objects()[key];
+        if object.hidden || ignore.ignores_object(scene_data, key) {
+            continue;
+        }
+        let matrix = object.get_model_matrix();
+
+        // The mesh being edited is snapped to as it is, not through its modifiers
+        let ignored_vertices = ignore.ignored_vertices(key);
+        let mesh = match ignored_vertices {
+            Some(_) => object.borrow_mesh(),
+            None => object.borrow_evaluated_mesh(),
+        };
+        let bmesh = &mesh.raw_mesh;
+        let is_ignored = |v: VertKey| ignored_vertices.is_some_and(|ignored| ignored.contains(&v));
+        let world = |v: VertKey| world_point(matrix, bmesh.vertices[v].vertex.pos);
+
+        if settings.vertices {
+            for v in bmesh.vertices.keys().filter(|&v| !is_ignored(v)) {
+                points.offer(world(v), SnapKind::Vertex);
+            }
+        }
+        for edge in bmesh.edges.values() {
+            if is_ignored(edge.v0) || is_ignored(edge.v1) {
+                continue;
+            }
+            let (a, b) = (world(edge.v0), world(edge.v1));
+            if settings.edge_midpoints {
+                points.offer((a + b) / 2.0, SnapKind::EdgeMidpoint);
+            }
+            if settings.edges {
+                edges.offer(closest_point_on_segment(a, b, point), SnapKind::Edge);
+            }
+        }
+
+        if settings.object_centres {
+            points.offer(matrix.w_axis.truncate().truncate(), SnapKind::ObjectCentre);
+        }
+        if settings.bounds_corners {
+            let aabb = object.get_aabb();
+            for corner in [
+                aabb.min,
+                Vec2::new(aabb.max.x, aabb.min.y),
+                aabb.max,
+                Vec2::new(aabb.min.x, aabb.max.y),
+            ] {
+                points.offer(corner, SnapKind::BoundsCorner);
+            }
+        }
+    }
+
+    points.best.or(edges.best).or_else(|| {
+        settings.grid.then(|| SnapTarget {
+            point: grid.snap(point, camera.zoom),
+            kind: SnapKind::Grid,
+        })
+    })
+}
+
+/// The nearest target offered so far within a radius of the point
+///
+/// Ties go to the target offered first, so the mesh's own points are offered before the
+/// object's centre and bounds.
+struct Candidates {
+    point: Vec2,
+    distance: f32,
+    best: Option<SnapTarget>,
+}
+
+impl Candidates {
+    fn new(point: Vec2, radius: f32) -> Self {
+        Self {
+            point,
+            distance: radius,
+            best: None,
+        }
+    }
+
+    fn offer(&mut self, target: Vec2, kind: SnapKind) {
+        let distance = self.point.distance(target);
+        if distance < self.distance || (self.best.is_none() && distance <= self.distance) {
+            self.distance = distance;
+            self.best = Some(SnapTarget {
+                point: target,
+                kind,
+            });
+        }
+    }
+}
+
+fn world_point(matrix: Mat4, local: Vec2) -> Vec2 {
+    matrix
+        .transform_point3(Vec3::new(local.x, local.y, 0.0))
+        .truncate()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, f32::consts::FRAC_PI_4, rc::Rc};
+
+    use crate::{
+        opengl::structs::{Colour, Material, Mesh, Object},
+        shapes::square::create_square,
+    };
+
+    use super::*;
+
+    /// A square 100 units across at the origin, and one turned 45 degrees further right
+    fn scene() -> (SceneData, Camera2D) {
+        let (square, _, _) = Mesh::new(create_square(), 0);
+        let material = Rc::new(RefCell::new(Material {
+            colour: Colour::ONE,
+        }));
+        let objects = [(Vec2::ZERO, 0.0), (Vec2::new(400.0, 0.0), FRAC_PI_4)]
+            .into_iter()
+            .map(|(translation, rotation)| {
+                Object::new(
+                    square.clone(),
+                    translation,
+                    rotation,
+                    Vec2::splat(50.0),
+                    material.clone(),
+                )
+            })
+            .collect();
+        let scene_data = SceneData::new(objects);
+        let camera = Camera2D::new(Vec2::ZERO, 1.0, 0.0, Vec2::new(800.0, 600.0));
+        (scene_data, camera)
+    }
+
+    fn snap_at(scene_data: &SceneData, camera: &Camera2D, point: Vec2) -> Option<SnapTarget> {
+        snap(
+            scene_data,
+            camera,
+            &GridSettings::default(),
+            &SnapSettings::default(),
+            point,
+            &SnapIgnore::default(),
+        )
+    }
+
+    #[test]
+    fn points_win_over_edges_which_win_over_the_grid() {
+        let (scene_data, camera) = scene();
+        let corner = scene_data.objects()[scene_data.object_order()[0]]
+            .get_aabb()
+            .max;
+
+        let target = snap_at(&scene_data, &camera, corner + Vec2::new(3.0, -4.0)).unwrap();
+        assert_eq!(target.kind, SnapKind::Vertex);
+        assert_eq!(target.point, corner);
+
+        // Along the right edge, away from its ends and middle
+        let target = snap_at(&scene_data, &camera, Vec2::new(corner.x + 5.0, 20.0)).unwrap();
+        assert_eq!(target.kind, SnapKind::Edge);
+        assert!(target.point.abs_diff_eq(Vec2::new(corner.x, 20.0), 1e-4));
+        let target = snap_at(&scene_data, &camera, Vec2::new(corner.x - 2.0, 3.0)).unwrap();
+        assert_eq!(target.kind, SnapKind::EdgeMidpoint);
+
+        // Far from anything lands on the grid, whose step follows the zoom
+        let target = snap_at(&scene_data, &camera, Vec2::new(-230.0, 270.0)).unwrap();
+        assert_eq!(target.kind, SnapKind::Grid);
+        assert_eq!(target.point, Vec2::new(-250.0, 250.0));
+        let zoomed = Camera2D::new(Vec2::ZERO, 2.0, 0.0, camera.viewport);
+        let target = snap_at(&scene_data, &zoomed, Vec2::new(-230.0, 270.0)).unwrap();
+        assert_eq!(target.point, Vec2::new(-225.0, 275.0));
+    }
+
+    #[test]
+    fn bounds_corners_and_centres_of_other_objects() {
+        let (scene_data, camera) = scene();
+        let turned = scene_data.object_order()[1];
+        let aabb = scene_data.objects()[turned].get_aabb();
+
+        let target = snap_at(&scene_data, &camera, aabb.max - Vec2::splat(4.0)).unwrap();
+        assert_eq!(target.kind, SnapKind::BoundsCorner);
+        assert_eq!(target.point, aabb.max);
+        let target = snap_at(&scene_data, &camera, Vec2::new(403.0, 2.0)).unwrap();
+        assert_eq!(target.kind, SnapKind::ObjectCentre);
+
+        // An object being moved is ignored, leaving the grid
+        let ignore = SnapIgnore {
+            objects: vec![turned],
+            vertices: None,
+        };
+        let grid = GridSettings::default();
+        let settings = SnapSettings {
+            grid: false,
+            ..Default::default()
+        };
+        let point = Vec2::new(403.0, 2.0);
+        assert!(snap(&scene_data, &camera, &grid, &settings, point, &ignore).is_none());
+    }
+
+    #[test]
+    fn moving_vertices_are_ignored_with_their_edges() {
+        let (scene_data, camera) = scene();
+        let key = scene_data.object_order()[0];
+        let corner = scene_data.objects()[key].get_aabb().max;
+        let moving = {
+            let mesh = scene_data.objects()[key].borrow_mesh();
+            let bmesh = &mesh.raw_mesh;
+            bmesh
+                .vertices
+                .keys()
+                .filter(|&v| bmesh.vertices[v].vertex.pos.x > 0.0)
+                .collect()
+        };
+        let ignore = SnapIgnore {
+            objects: vec![],
+            vertices: Some((key, moving)),
+        };
+        let grid = GridSettings::default();
+        let settings = SnapSettings {
+            grid: false,
+            bounds_corners: false,
+            ..Default::default()
+        };
+
+        // Neither the moving corner nor the right edge between the moving vertices catch
+        let point = corner + Vec2::new(3.0, -4.0);
+        assert!(snap(&scene_data, &camera, &grid, &settings, point, &ignore).is_none());
+        let point = Vec2::new(corner.x + 5.0, 20.0);
+        assert!(snap(&scene_data, &camera, &grid, &settings, point, &ignore).is_none());
+
+        // The top edge, from a moving vertex to a still one, moves too
+        let point = Vec2::new(0.0, corner.y + 2.0);
+        assert!(snap(&scene_data, &camera, &grid, &settings, point, &ignore).is_none());
+        let point = Vec2::new(-corner.x + 2.0, 0.0);
+        let target = snap(&scene_data, &camera, &grid, &settings, point, &ignore).unwrap();
+        assert_eq!(target.kind, SnapKind::EdgeMidpoint);
+    }
+}
@@ -0,0 +1,627 @@
+//! Modal grab, rotate and scale of the selected objects, or of the selected vertices in edit mode
+//!
+//! While the operator is running every mouse move recomputes the transforms from the ones the
+//! objects had when it started, so cancelling just puts those back. The change is worked out as
+//! a world-space matrix and then brought into each object's parent space, so parented objects
+//! move the same way as top-level ones. Vertices are moved the same way in the space of their
+//! object's mesh, which the operator copies when it starts so the original can be put back.
+
+use std::f32::consts::PI;
+
+use glam::{Mat4, Vec2};
+
+use crate::{
+    data::mesh::{bm_select::bm_selected_verts, bmesh::VertKey},
+    history::{Command, MeshState},
+    opengl::{
+        matrices::Transform2D,
+        scene::{ObjectKey, SceneData},
+    },
+};
+
+use super::snap::SnapIgnore;
+
+/// Rotation step while snapping, in radians
+const ROTATE_SNAP: f32 = PI / 12.0;
+/// Scale factor step while snapping
+const SCALE_SNAP: f32 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransformMode {
+    Grab,
+    Rotate,
+    Scale,
+}
+
+/// What the operator moves
+enum Targets {
+    /// Objects with the transforms they started with
+    Objects(Vec<(ObjectKey, Transform2D)>),
+    /// Vertices of one object's mesh with the positions they started at
+    Vertices {
+        object: ObjectKey,
+        before: MeshState,
+        originals: Vec<(VertKey, Vec2)>,
+    },
+}
+
+/// World axis the transform is limited to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AxisConstraint {
+    X,
+    Y,
+}
+
+pub struct TransformOperator {
+    mode: TransformMode,
+    constraint: Option<AxisConstraint>,
+    /// Number typed while the operator runs, which overrides the mouse
+    typed: String,
+    snap: bool,
+    /// Where a snapping grab puts the pivot
+    snap_target: Option<Vec2>,
+    /// Centre of the selection, that rotation and scale happen about
+    pivot: Vec2,
+    start_mouse: Vec2,
+    mouse: Vec2,
+    /// Rotation the mouse has made around the pivot, counted past a full turn
+    mouse_angle: f32,
+    targets: Targets,
+}
+
+impl TransformOperator {
+    /// Start transforming the selected objects, if any, from the mouse's world position
+    ///
+    /// Objects whose parent is also selected are left to move with their parent, and hidden or
+    /// locked objects are left where they are.
+    pub fn start(scene_data: &SceneData, mode: TransformMode, mouse: Vec2) -> Option<Self> {
+        let selected = scene_data
+            .object_order()
+            .iter()
+            .copied()
+            .filter(|&key| {
+                let object = &scene_data.objects()[key];
+                object.selected && object.is_selectable()
+            })
+            .collect::<Vec<_>>();
+
+        let originals = selected
+            .iter()
+            .copied()
+            .filter(|&key| {
+                !selected.iter().any(|&other| {
+                    other != key
+                        && scene_data
+                            .parent(key)
+                            .is_some_and(|parent| scene_data.is_ancestor(other, parent))
+                })
+            })
+            .map(|key| (key, scene_data.objects()[key].transform()))
+            .collect::<Vec<_>>();
+
+        if originals.is_empty() {
+            return None;
+        }
+
+        let pivot = originals
+            .iter()
+            .map(|&(key, _)| world_origin(scene_data, key))
+            .sum::<Vec2>()
+            / originals.len() as f32;
+
+        Some(Self::new(mode, pivot, mouse, Targets::Objects(originals)))
+    }
+
+    /// Start transforming the selected vertices of `object`'s mesh, if any
+    ///
+    /// The object is given its own copy of the mesh, so objects sharing it are left alone.
+    pub fn start_vertices(
+        scene_data: &mut SceneData,
+        object: ObjectKey,
+        mode: TransformMode,
+        mouse: Vec2,
+    ) -> Option<Self> {
+        let before = MeshState::of(scene_data.objects().get(object)?);
+        let selected = bm_selected_verts(&before.mesh.borrow().raw_mesh);
+        if selected.is_empty() {
+            return None;
+        }
+
+        scene_data.edit_object(object, |object| object.copy_mesh());
+        let object_ref = &scene_data.objects()[object];
+        let matrix = object_ref.get_model_matrix();
+        let originals = {
+            let mesh = object_ref.borrow_mesh();
+            selected
+                .into_iter()
+                .map(|v| (v, mesh.raw_mesh.vertices[v].vertex.pos))
+                .collect::<Vec<_>>()
+        };
+
+        let pivot = originals
+            .iter()
+            .map(|&(_, pos)| matrix.transform_point3(pos.extend(0.0)).truncate())
+            .sum::<Vec2>()
+            / originals.len() as f32;
+
+        let targets = Targets::Vertices {
+            object,
+            before,
+            originals,
+        };
+        Some(Self::new(mode, pivot, mouse, targets))
+    }
+
+    fn new(mode: TransformMode, pivot: Vec2, mouse: Vec2, targets: Targets) -> Self {
+        Self {
+            mode,
+            constraint: None,
+            typed: String::new(),
+            snap: false,
+            snap_target: None,
+            pivot,
+            start_mouse: mouse,
+            mouse,
+            mouse_angle: 0.0,
+            targets,
+        }
+    }
+
+    pub fn mode(&self) -> TransformMode {
+        self.mode
+    }
+
+    pub fn constraint(&self) -> Option<AxisConstraint> {
+        self.constraint
+    }
+
+    /// Constrain to `axis`, or remove the constraint if it is already on that axis
+    pub fn toggle_constraint(&mut self, axis: AxisConstraint) {
+        self.constraint = if self.constraint == Some(axis) {
+            None
+        } else {
+            Some(axis)
+        };
+    }
+
+    pub fn set_snap(&mut self, snap: bool) {
+        self.snap = snap;
+    }
+
+    /// Set where a grab moves the pivot to while snapping
+    ///
+    /// Rotation and scale snap to even steps instead. Without a target a snapping grab follows
+    /// the mouse.
+    pub fn set_snap_target(&mut self, target: Option<Vec2>) {
+        self.snap_target = target;
+    }
+
+    /// Where the mouse puts the pivot of a grab, which is the point snapped
+    ///
+    /// `None` unless grabbing with the mouse, since the other transforms don't snap to targets.
+    pub fn grab_point(&self) -> Option<Vec2> {
+        (self.mode == TransformMode::Grab && self.typed_value().is_none())
+            .then(|| self.pivot + self.constrain(self.mouse - self.start_mouse))
+    }
+
+    /// What moves with the transform, and so can't be snapped to
+    pub fn snap_ignore(&self) -> SnapIgnore {
+        match &self.targets {
+            Targets::Objects(originals) => SnapIgnore {
+                objects: originals.iter().map(|&(key, _)| key).collect(),
+                vertices: None,
+            },
+            Targets::Vertices {
+                object, originals, ..
+            } => SnapIgnore {
+                objects: vec![],
+                vertices: Some((*object, originals.iter().map(|&(v, _)| v).collect())),
+            },
+        }
+    }
+
+    pub fn set_mouse(&mut self, mouse: Vec2) {
+        let (from, to) = (self.mouse - self.pivot, mouse - self.pivot);
+        if from != Vec2::ZERO && to != Vec2::ZERO {
+            self.mouse_angle += from.angle_between(to);
+        }
+        self.mouse = mouse;
+    }
+
+    /// Add a typed character: a digit, `.` or `-`, which flips the sign
+    pub fn type_char(&mut self, c: char) {
+        match c {
+            '-' => {
+                if self.typed.starts_with('-') {
+                    self.typed.remove(0);
+                } else {
+                    self.typed.insert(0, '-');
+                }
+            }
+            '.' if !self.typed.contains('.') => self.typed.push('.'),
+            '0'..='9' => self.typed.push(c),
+            _ => {}
+        }
+    }
+
+    pub fn backspace(&mut self) {
+        self.typed.pop();
+    }
+
+    /// The typed number, if one has been typed
+    fn typed_value(&self) -> Option<f32> {
+        match self.typed.trim_start_matches('-') {
+            "" | "." => None,
+            _ => self.typed.parse().ok(),
+        }
+    }
+
+    /// The change in world space, applied on top of each object's starting world matrix
+    pub fn world_delta(&self) -> Mat4 {
+        match self.mode {
+            TransformMode::Grab => Mat4::from_translation(self.translation().extend(0.0)),
+            TransformMode::Rotate => about_pivot(self.pivot, Mat4::from_rotation_z(self.angle())),
+            TransformMode::Scale => {
+                about_pivot(self.pivot, Mat4::from_scale(self.scale().extend(1.0)))
+            }
+        }
+    }
+
+    fn translation(&self) -> Vec2 {
+        let delta = match (self.typed_value(), self.snap_target) {
+            (Some(value), _) => match self.constraint {
+                Some(AxisConstraint::Y) => Vec2::new(0.0, value),
+                _ => Vec2::new(value, 0.0),
+            },
+            (None, Some(target)) if self.snap => target - self.pivot,
+            (None, _) => self.mouse - self.start_mouse,
+        };
+        self.constrain(delta)
+    }
+
+    fn constrain(&self, delta: Vec2) -> Vec2 {
+        match self.constraint {
+            Some(AxisConstraint::X) => Vec2::new(delta.x, 0.0),
+            Some(AxisConstraint::Y) => Vec2::new(0.0, delta.y),
+            None => delta,
+        }
+    }
+
+    /// Rotation in radians; typed values are in degrees
+    fn angle(&self) -> f32 {
+        match self.typed_value() {
+            Some(degrees) => degrees.to_radians(),
+            None if self.snap => (self.mouse_angle / ROTATE_SNAP).round() * ROTATE_SNAP,
+            None => self.mouse_angle,
+        }
+    }
+
+    fn scale(&self) -> Vec2 {
+        let factor = match self.typed_value() {
+            Some(value) => value,
+            None => {
+                let start = self.start_mouse.distance(self.pivot);
+                let factor = if start > f32::EPSILON {
+                    self.mouse.distance(self.pivot) / start
+                } else {
+                    1.0
+                };
+                if self.snap {
+                    (factor / SCALE_SNAP).round() * SCALE_SNAP
+                } else {
+                    factor
+                }
+            }
+        };
+
+        match self.constraint {
+            Some(AxisConstraint::X) => Vec2::new(factor, 1.0),
+            Some(AxisConstraint::Y) => Vec2::new(1.0, factor),
+            None => Vec2::splat(factor),
+        }
+    }
+
+    /// Move the objects or vertices to where the operator currently puts them
+    pub fn apply(&self, scene_data: &mut SceneData) {
+        let delta = self.world_delta();
+
+        match &self.targets {
+            Targets::Objects(originals) => {
+                for &(key, original) in originals {
+                    scene_data.edit_object(key, |object| {
+                        let parent_matrix = object.parent_matrix();
+                        let world = delta * parent_matrix * original.matrix();
+                        object.set_matrix(parent_matrix.inverse() * world);
+                    });
+                }
+            }
+            Targets::Vertices {
+                object, originals, ..
+            } => {
+                scene_data.edit_object(*object, |object| {
+                    let model_matrix = object.get_model_matrix();
+                    let local = model_matrix.inverse() * delta * model_matrix;
+                    object.edit_mesh(|bmesh| {
+                        for &(v, pos) in originals {
+                            if let Some(vert) = bmesh.vertices.get_mut(v) {
+                                vert.vertex.pos =
+                                    local.transform_point3(pos.extend(0.0)).truncate();
+                            }
+                        }
+                    });
+                });
+            }
+        }
+    }
+
+    /// The edit the operator has made, for the undo history
+    pub fn command(&self, scene_data: &SceneData) -> Command {
+        match &self.targets {
+            Targets::Objects(originals) => Command::Group(
+                originals
+                    .iter()
+                    .filter_map(|&(key, before)| {
+                        let after = scene_data.objects().get(key)?.transform();
+                        Some(Command::Transform { key, before, after })
+                    })
+                    .collect(),
+            ),
+            Targets::Vertices { object, before, .. } => match scene_data.objects().get(*object) {
+                Some(after) => Command::Mesh {
+                    key: *object,
+                    before: before.clone(),
+                    after: MeshState::of(after),
+                },
+                None => Command::Group(vec![]),
+            },
+        }
+    }
+
+    /// Put everything back where it was when the operator started
+    pub fn cancel(self, scene_data: &mut SceneData) {
+        match self.targets {
+            Targets::Objects(originals) => {
+                for (key, original) in originals {
+                    scene_data.edit_object(key, |object| object.set_transform(original));
+                }
+            }
+            Targets::Vertices { object, before, .. } => {
+                scene_data.edit_object(object, |object| before.restore(object));
+            }
+        }
+    }
+
+    /// Header text describing the running operator
+    pub fn status(&self) -> String {
+        let axis = match self.constraint {
+            Some(AxisConstraint::X) => " along X",
+            Some(AxisConstraint::Y) => " along Y",
+            None => "",
+        };
+        let typed = if self.typed.is_empty() {
+            String::new()
+        } else {
+            format!(" [{}]", self.typed)
+        };
+
+        match self.mode {
+            TransformMode::Grab => {
+                let delta = self.translation();
+                format!("Grab{axis}{typed}: D {:.3}, {:.3}", delta.x, delta.y)
+            }
+            TransformMode::Rotate => format!("Rotate{typed}: {:.2}°", self.angle().to_degrees()),
+            TransformMode::Scale => {
+                let scale = self.scale();
+                format!("Scale{axis}{typed}: {:.3}, {:.3}", scale.x, scale.y)
+            }
+        }
+    }
+}
+
+fn world_origin(scene_data: &SceneData, key: ObjectKey) -> Vec2 {
+    scene_data.objects()[key]
+        .get_model_matrix()
+        .w_axis
+        .truncate()
+        .truncate()
+}
+
+fn about_pivot(pivot: Vec2, matrix: Mat4) -> Mat4 {
+    Mat4::from_translation(pivot.extend(0.0)) * matrix * Mat4::from_translation(-pivot.extend(0.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, f32::consts::FRAC_PI_2, rc::Rc};
+
+    use crate::{
+        opengl::structs::{Colour, Material, Mesh, Object},
+        shapes::square::create_square,
+    };
+
+    use super::*;
+
+    fn scene(translations: &[Vec2]) -> SceneData {
+        let (square, _, _) = Mesh::new(create_square(), 0);
+        let material = Rc::new(RefCell::new(Material {
+            colour: Colour::ONE,
+        }));
+        let objects = translations
+            .iter()
+            .map(|&translation| {
+                let mut object = Object::new(
+                    square.clone(),
+                    translation,
+                    0.0,
+                    Vec2::ONE,
+                    material.clone(),
+                );
+                object.selected = true;
+                object
+            })
+            .collect();
+        SceneData::new(objects)
+    }
+
+    fn origins(scene_data: &SceneData) -> Vec<Vec2> {
+        scene_data
+            .object_order()
+            .iter()
+            .map(|&key| world_origin(scene_data, key))
+            .collect()
+    }
+
+    fn assert_close(a: &[Vec2], b: &[Vec2]) {
+        assert_eq!(a.len(), b.len());
+        for (a, b) in a.iter().zip(b) {
+            assert!(a.abs_diff_eq(*b, 1e-4), "{a} != {b}");
+        }
+    }
+
+    #[test]
+    fn grab_follows_the_mouse_with_constraints_and_snapping() {
+        let mut scene_data = scene(&[Vec2::new(0.0, 0.0), Vec2::new(4.0, 0.0)]);
+        let mut operator =
+            TransformOperator::start(&scene_data, TransformMode::Grab, Vec2::ZERO).unwrap();
+
+        operator.set_mouse(Vec2::new(2.3, 1.6));
+        operator.apply(&mut scene_data);
+        assert_close(
+            &origins(&scene_data),
+            &[Vec2::new(2.3, 1.6), Vec2::new(6.3, 1.6)],
+        );
+
+        // Snapping moves the pivot, halfway between the objects, to the target
+        operator.toggle_constraint(AxisConstraint::X);
+        assert_eq!(operator.grab_point(), Some(Vec2::new(4.3, 0.0)));
+        operator.set_snap(true);
+        operator.set_snap_target(Some(Vec2::new(5.0, 7.0)));
+        operator.apply(&mut scene_data);
+        assert_close(
+            &origins(&scene_data),
+            &[Vec2::new(3.0, 0.0), Vec2::new(7.0, 0.0)],
+        );
+
+        // A typed value overrides the mouse
+        for c in "-1.5".chars() {
+            operator.type_char(c);
+        }
+        operator.apply(&mut scene_data);
+        assert_close(
+            &origins(&scene_data),
+            &[Vec2::new(-1.5, 0.0), Vec2::new(2.5, 0.0)],
+        );
+    }
+
+    #[test]
+    fn rotate_and_scale_happen_about_the_selection_centre() {
+        let mut scene_data = scene(&[Vec2::new(-2.0, 0.0), Vec2::new(2.0, 0.0)]);
+
+        let mut operator =
+            TransformOperator::start(&scene_data, TransformMode::Rotate, Vec2::new(5.0, 0.0))
+                .unwrap();
+        operator.set_mouse(Vec2::new(0.0, 5.0));
+        operator.apply(&mut scene_data);
+        assert_close(
+            &origins(&scene_data),
+            &[Vec2::new(0.0, -2.0), Vec2::new(0.0, 2.0)],
+        );
+        let rotations = scene_data
+            .objects()
+            .values()
+            .map(|object| object.rotation())
+            .collect::<Vec<_>>();
+        assert!(rotations.iter().all(|r| (r - FRAC_PI_2).abs() < 1e-4));
+        operator.cancel(&mut scene_data);
+
+        let mut operator =
+            TransformOperator::start(&scene_data, TransformMode::Scale, Vec2::new(1.0, 0.0))
+                .unwrap();
+        operator.set_mouse(Vec2::new(3.0, 0.0));
+        operator.toggle_constraint(AxisConstraint::X);
+        operator.apply(&mut scene_data);
+        assert_close(
+            &origins(&scene_data),
+            &[Vec2::new(-6.0, 0.0), Vec2::new(6.0, 0.0)],
+        );
+        assert!(scene_data
+            .objects()
+            .values()
+            .all(|object| object.scale().abs_diff_eq(Vec2::new(3.0, 1.0), 1e-4)));
+    }
+
+    #[test]
+    fn vertices_move_in_world_space_on_a_copy_of_the_mesh() {
+        let mut scene_data = scene(&[Vec2::new(10.0, 0.0), Vec2::new(-10.0, 0.0)]);
+        let [key, other] = [0, 1].map(|i| scene_data.object_order()[i]);
+        scene_data.edit_object(key, |object| {
+            object.set_rotation(FRAC_PI_2);
+            object.set_scale(Vec2::splat(2.0));
+        });
+        let original = scene_data.objects()[key].mesh().clone();
+
+        // Select the mesh's +x side, which the rotation turns to face up
+        scene_data.objects_mut()[key].edit_mesh(|bmesh| {
+            for vert in bmesh.vertices.values_mut() {
+                vert.selected = vert.vertex.pos.x > 0.0;
+            }
+        });
+        let mut operator = TransformOperator::start_vertices(
+            &mut scene_data,
+            key,
+            TransformMode::Grab,
+            Vec2::ZERO,
+        )
+        .unwrap();
+        operator.set_mouse(Vec2::new(0.0, 3.0));
+        operator.apply(&mut scene_data);
+
+        // Moving up by 3 in the world is 1.5 along the mesh's own x axis
+        let mesh_xs = |key: ObjectKey| {
+            let mut xs = scene_data.objects()[key]
+                .borrow_mesh()
+                .raw_mesh
+                .vertices
+                .values()
+                .map(|vert| vert.vertex.pos.x)
+                .collect::<Vec<_>>();
+            xs.sort_by(f32::total_cmp);
+            xs
+        };
+        assert_eq!(mesh_xs(key), [-1.0, -1.0, 2.5, 2.5]);
+        assert_eq!(mesh_xs(other), [-1.0, -1.0, 1.0, 1.0]);
+        assert_eq!(scene_data.objects()[key].get_aabb().max.y, 5.0);
+
+        let Command::Mesh { before, .. } = operator.command(&scene_data) else {
+            panic!("expected a mesh command");
+        };
+        assert!(Rc::ptr_eq(&before.mesh, &original));
+        operator.cancel(&mut scene_data);
+        assert!(Rc::ptr_eq(scene_data.objects()[key].mesh(), &original));
+    }
+
+    #[test]
+    fn cancel_restores_transforms_and_children_follow_parents() {
+        let mut scene_data = scene(&[Vec2::new(1.0, 1.0), Vec2::new(3.0, 1.0)]);
+        let [parent, child] = [0, 1].map(|i| scene_data.object_order()[i]);
+        scene_data.set_parent(child, Some(parent), true).unwrap();
+        let before = origins(&scene_data);
+
+        // The child is selected too, but only moves once, with its parent
+        let mut operator =
+            TransformOperator::start(&scene_data, TransformMode::Grab, Vec2::ZERO).unwrap();
+        operator.set_mouse(Vec2::new(10.0, 0.0));
+        operator.apply(&mut scene_data);
+        assert_close(
+            &origins(&scene_data),
+            &[Vec2::new(11.0, 1.0), Vec2::new(13.0, 1.0)],
+        );
+
+        operator.cancel(&mut scene_data);
+        assert_close(&origins(&scene_data), &before);
+        assert_eq!(
+            scene_data.objects()[child].translation(),
+            Vec2::new(2.0, 0.0)
+        );
+    }
+}
@@ -0,0 +1,149 @@
+use std::{fmt, io::Cursor, path::Path};
+
+use crate::opengl::structs::Colour;
+
+/// An 8-bit RGBA image, stored row by row from the top left
+#[derive(Debug, Clone, PartialEq)]
+pub struct Image {
+    width: u32,
+    height: u32,
+    pixels: Vec<[u8; 4]>,
+}
+
+#[derive(Debug)]
+pub enum PngError {
+    Io(std::io::Error),
+    Encoding(png::EncodingError),
+    Decoding(png::DecodingError),
+    /// The PNG is valid but is not 8-bit RGBA
+    UnsupportedFormat,
+}
+
+impl fmt::Display for PngError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PngError::Io(err) => write!(f, "could not access PNG file: {err}"),
+            PngError::Encoding(err) => write!(f, "could not encode PNG: {err}"),
+            PngError::Decoding(err) => write!(f, "could not decode PNG: {err}"),
+            PngError::UnsupportedFormat => write!(f, "PNG is not 8-bit RGBA"),
+        }
+    }
+}
+
+impl std::error::Error for PngError {}
+
+impl From<std::io::Error> for PngError {
+    fn from(err: std::io::Error) -> Self {
+        PngError::Io(err)
+    }
+}
+
+impl From<png::EncodingError> for PngError {
+    fn from(err: png::EncodingError) -> Self {
+        PngError::Encoding(err)
+    }
+}
+
+impl From<png::DecodingError> for PngError {
+    fn from(err: png::DecodingError) -> Self {
+        PngError::Decoding(err)
+    }
+}
+
+impl Image {
+    /// Create an image filled with a single colour
+    pub fn new(width: u32, height: u32, colour: Colour) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![colour_to_rgba8(colour); (width * height) as usize],
+        }
+    }
+
+    pub fn from_pixels(width: u32, height: u32, pixels: Vec<[u8; 4]>) -> Self {
+        assert_eq!(pixels.len(), (width * height) as usize);
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[[u8; 4]] {
+        &self.pixels
+    }
+
+    pub fn pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        self.pixels[(y * self.width + x) as usize]
+    }
+
+    /// Largest difference of any channel of any pixel, or `None` if the sizes differ
+    pub fn max_difference(&self, other: &Image) -> Option<u8> {
+        if self.width != other.width || self.height != other.height {
+            return None;
+        }
+
+        Some(
+            self.pixels
+                .iter()
+                .zip(&other.pixels)
+                .flat_map(|(a, b)| (0..4).map(move |i| a[i].abs_diff(b[i])))
+                .max()
+                .unwrap_or(0),
+        )
+    }
+
+    pub fn encode_png(&self) -> Result<Vec<u8>, PngError> {
+        let mut data = vec![];
+
+        let mut encoder = png::Encoder::new(&mut data, self.width, self.height);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(self.pixels.as_flattened())?;
+        writer.finish()?;
+
+        Ok(data)
+    }
+
+    pub fn decode_png(data: &[u8]) -> Result<Image, PngError> {
+        let mut reader = png::Decoder::new(Cursor::new(data)).read_info()?;
+        let mut buffer = vec![0; reader.output_buffer_size().unwrap_or(0)];
+        let info = reader.next_frame(&mut buffer)?;
+
+        if info.color_type != png::ColorType::Rgba || info.bit_depth != png::BitDepth::Eight {
+            return Err(PngError::UnsupportedFormat);
+        }
+
+        let pixels = buffer[..info.buffer_size()]
+            .chunks_exact(4)
+            .map(|p| [p[0], p[1], p[2], p[3]])
+            .collect();
+
+        Ok(Image::from_pixels(info.width, info.height, pixels))
+    }
+
+    pub fn write_png(&self, path: impl AsRef<Path>) -> Result<(), PngError> {
+        std::fs::write(path, self.encode_png()?)?;
+        Ok(())
+    }
+
+    pub fn read_png(path: impl AsRef<Path>) -> Result<Image, PngError> {
+        Image::decode_png(&std::fs::read(path)?)
+    }
+}
+
+pub fn colour_to_rgba8(colour: Colour) -> [u8; 4] {
+    colour
+        .to_array()
+        .map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8)
+}
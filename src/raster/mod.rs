@@ -0,0 +1,425 @@
+//! CPU software rasterizer
+//!
+//! Renders a [`SceneData`] to an [`Image`] without a GPU, using the same projection and view
+//! matrices as the OpenGL pipelines. Visible objects are drawn bottom to top from their
+//! evaluated meshes, filled with their material colour and blended over what is below them.
+//!
+//! Edges are anti-aliased by supersampling: every pixel is covered by a grid of samples which
+//! are shaded independently and averaged at the end. Triangles that share an edge never both
+//! cover a sample on it, so there are no seams inside a mesh.
+//!
+//! The image is rendered in horizontal bands so the sample buffer stays small however large
+//! the image is.
+
+pub mod image;
+
+use glam::{Mat4, Vec2, Vec4, Vec4Swizzles};
+
+use crate::opengl::{scene::SceneData, structs::Colour};
+
+use self::image::{colour_to_rgba8, Image};
+
+/// Rows of pixels rendered at a time
+const BAND_HEIGHT: u32 = 32;
+
+#[derive(Debug, Clone)]
+pub struct RasterOptions {
+    pub width: u32,
+    pub height: u32,
+    /// Samples along each axis of a pixel, so each pixel gets `samples * samples` samples
+    pub samples: u32,
+    /// Colour of pixels that nothing is drawn on
+    pub background: Colour,
+}
+
+impl RasterOptions {
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            samples: 4,
+            background: Colour::ZERO,
+        }
+    }
+}
+
+/// A triangle in pixel coordinates, with its corners ordered so that edge functions are
+/// positive inside it
+struct ScreenTriangle {
+    points: [Vec2; 3],
+    min: Vec2,
+    max: Vec2,
+    /// Premultiplied colour
+    colour: Vec4,
+}
+
+/// Render the visible part of the scene
+pub fn render_scene(
+    scene_data: &SceneData,
+    projection_matrix: Mat4,
+    view_matrix: Mat4,
+    options: &RasterOptions,
+) -> Image {
+    let size = Vec2::new(options.width as f32, options.height as f32);
+    let triangles = screen_triangles(scene_data, projection_matrix * view_matrix, size);
+
+    let samples = options.samples.max(1);
+    let sample_offsets = (0..samples * samples)
+        .map(|i| {
+            Vec2::new(
+                ((i % samples) as f32 + 0.5) / samples as f32,
+                ((i / samples) as f32 + 0.5) / samples as f32,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let background = premultiply(options.background);
+    let mut pixels = Vec::with_capacity((options.width * options.height) as usize);
+    let mut band = vec![];
+
+    for band_top in (0..options.height).step_by(BAND_HEIGHT as usize) {
+        let band_bottom = (band_top + BAND_HEIGHT).min(options.height);
+        let band_pixels = ((band_bottom - band_top) * options.width) as usize;
+
+        band.clear();
+        band.resize(band_pixels * sample_offsets.len(), background);
+
+        for triangle in &triangles {
+            if triangle.max.y < band_top as f32 || triangle.min.y >= band_bottom as f32 {
+                continue;
+            }
+
+            let x0 = triangle.min.x.floor().max(0.0) as u32;
+            let x1 = (triangle.max.x.ceil() as u32).min(options.width);
+            let y0 = (triangle.min.y.floor().max(0.0) as u32).max(band_top);
+            let y1 = (triangle.max.y.ceil() as u32).min(band_bottom);
+
+            for y in y0..y1 {
+                for x in x0..x1 {
+                    let pixel = ((y - band_top) * options.width + x) as usize;
+                    let pixel_samples =
+                        &mut band[pixel * sample_offsets.len()..(pixel + 1) * sample_offsets.len()];
+
+                    for (sample, offset) in pixel_samples.iter_mut().zip(&sample_offsets) {
+                        let point = Vec2::new(x as f32, y as f32) + *offset;
+                        if covers(triangle, point) {
+                            *sample = triangle.colour + *sample * (1.0 - triangle.colour.w);
+                        }
+                    }
+                }
+            }
+        }
+
+        pixels.extend(
+            band.chunks_exact(sample_offsets.len())
+                .map(|pixel_samples| {
+                    let average = pixel_samples.iter().sum::<Vec4>() / pixel_samples.len() as f32;
+                    colour_to_rgba8(unpremultiply(average))
+                }),
+        );
+    }
+
+    Image::from_pixels(options.width, options.height, pixels)
+}
+
+/// Project every triangle of every object that could be on screen, in draw order
+fn screen_triangles(scene_data: &SceneData, vp_matrix: Mat4, size: Vec2) -> Vec<ScreenTriangle> {
+    // NDC to pixels, with y pointing down
+    let to_pixels = |p: Vec4| Vec2::new((p.x + 1.0) / 2.0 * size.x, (1.0 - p.y) / 2.0 * size.y);
+    let on_screen =
+        |min: Vec2, max: Vec2| max.x >= 0.0 && max.y >= 0.0 && min.x <= size.x && min.y <= size.y;
+
+    let mut triangles = vec![];
+
+    for &key in scene_data.object_order() {
+        let Some(object) = scene_data
+            .objects()
+            .get(key)
+            .filter(|object| !object.hidden)
+        else {
+            continue;
+        };
+
+        let aabb = object.get_aabb();
+        let corners = [
+            aabb.min,
+            Vec2::new(aabb.max.x, aabb.min.y),
+            aabb.max,
+            Vec2::new(aabb.min.x, aabb.max.y),
+        ]
+        .map(|p| to_pixels(vp_matrix * p.extend(0.0).extend(1.0)));
+        let (min, max) = bounds(&corners);
+        if !on_screen(min, max) {
+            continue;
+        }
+
+        let colour = premultiply(object.borrow_material().colour);
+        let mvp = vp_matrix * object.get_model_matrix();
+        let mesh = object.borrow_evaluated_mesh();
+        let points = mesh
+            .vertices()
+            .iter()
+            .map(|v| to_pixels(mvp * v.pos.extend(0.0).extend(1.0)))
+            .collect::<Vec<_>>();
+
+        for tri in mesh.indices().chunks_exact(3) {
+            let mut corners = [0, 1, 2].map(|i| points[tri[i] as usize]);
+            let area = (corners[1] - corners[0]).perp_dot(corners[2] - corners[0]);
+            if area == 0.0 {
+                continue;
+            }
+            if area < 0.0 {
+                corners.swap(1, 2);
+            }
+
+            let (min, max) = bounds(&corners);
+            if on_screen(min, max) {
+                triangles.push(ScreenTriangle {
+                    points: corners,
+                    min,
+                    max,
+                    colour,
+                });
+            }
+        }
+    }
+
+    triangles
+}
+
+fn bounds(points: &[Vec2]) -> (Vec2, Vec2) {
+    points.iter().fold(
+        (Vec2::splat(f32::MAX), Vec2::splat(f32::MIN)),
+        |(min, max), p| (min.min(*p), max.max(*p)),
+    )
+}
+
+/// Check if a sample point is inside a triangle
+///
+/// Points exactly on an edge belong to only one of the two triangles sharing it, decided by
+/// the edge's direction, which is reversed in the other triangle.
+fn covers(triangle: &ScreenTriangle, point: Vec2) -> bool {
+    (0..3).all(|i| {
+        let a = triangle.points[i];
+        let b = triangle.points[(i + 1) % 3];
+        let edge = b - a;
+        let w = edge.perp_dot(point - a);
+        w > 0.0 || (w == 0.0 && (edge.y > 0.0 || (edge.y == 0.0 && edge.x < 0.0)))
+    })
+}
+
+fn premultiply(colour: Colour) -> Vec4 {
+    (colour.xyz() * colour.w).extend(colour.w)
+}
+
+fn unpremultiply(colour: Vec4) -> Colour {
+    if colour.w <= 0.0 {
+        Colour::ZERO
+    } else {
+        (colour.xyz() / colour.w).extend(colour.w)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, path::PathBuf, rc::Rc};
+
+    use crate::{
+        data::mesh::bmesh::bm_from_polygons,
+        opengl::{
+            matrices::{get_ortho_matrix, get_view_matrix},
+            structs::{Material, Mesh, Object},
+        },
+        shapes::{square::create_square, star::create_star},
+    };
+
+    use super::*;
+
+    /// Compare against an image in `fixtures/golden`, or overwrite it when `UPDATE_GOLDEN` is set
+    fn assert_matches_golden(name: &str, image: &Image) {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("fixtures/golden")
+            .join(name);
+
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            image.write_png(&path).unwrap();
+            return;
+        }
+
+        let golden = Image::read_png(&path).unwrap();
+        // Allow for rounding differences between platforms
+        let difference = image.max_difference(&golden);
+        assert!(
+            difference.is_some_and(|d| d <= 2),
+            "{name} differs from the golden image by {difference:?}"
+        );
+    }
+
+    fn material(colour: Colour) -> Rc<RefCell<Material>> {
+        Rc::new(RefCell::new(Material { colour }))
+    }
+
+    fn render(objects: Vec<Object>, width: u32, height: u32, zoom: f32) -> Image {
+        let scene_data = SceneData::new(objects);
+        let mut options = RasterOptions::new(width, height);
+        options.background = Colour::new(0.1, 0.1, 0.1, 1.0);
+
+        render_scene(
+            &scene_data,
+            get_ortho_matrix(width as f32, height as f32),
+            get_view_matrix(Vec2::ZERO, zoom, 0.0),
+            &options,
+        )
+    }
+
+    #[test]
+    fn fills_interior_without_seams() {
+        let (square, _, _) = Mesh::new(create_square(), 0);
+        let red = Colour::new(1.0, 0.0, 0.0, 1.0);
+        let image = render(
+            vec![Object::new(
+                square,
+                Vec2::ZERO,
+                0.0,
+                Vec2::ONE,
+                material(red),
+            )],
+            16,
+            16,
+            4.0,
+        );
+
+        // The square spans -4..4 world units, so pixels 4..12 are covered
+        for y in 4..12 {
+            for x in 4..12 {
+                assert_eq!(image.pixel(x, y), [255, 0, 0, 255]);
+            }
+        }
+        assert_eq!(image.pixel(2, 2), [26, 26, 26, 255]);
+    }
+
+    #[test]
+    fn anti_aliases_edges() {
+        let (square, _, _) = Mesh::new(create_square(), 0);
+        // Shift by half a pixel so the left and right edges cut pixels in half
+        let image = render(
+            vec![Object::new(
+                square,
+                Vec2::new(0.5, 0.0),
+                0.0,
+                Vec2::splat(4.0),
+                material(Colour::new(1.0, 1.0, 1.0, 1.0)),
+            )],
+            16,
+            16,
+            1.0,
+        );
+
+        let half = image.pixel(4, 8)[0];
+        assert!((135..=145).contains(&half), "edge pixel is {half}");
+    }
+
+    #[test]
+    fn blends_translucent_materials() {
+        let (square, _, _) = Mesh::new(create_square(), 0);
+        let image = render(
+            vec![
+                Object::new(
+                    square.clone(),
+                    Vec2::ZERO,
+                    0.0,
+                    Vec2::ONE,
+                    material(Colour::new(0.0, 0.0, 1.0, 1.0)),
+                ),
+                Object::new(
+                    square,
+                    Vec2::ZERO,
+                    0.0,
+                    Vec2::ONE,
+                    material(Colour::new(1.0, 0.0, 0.0, 0.5)),
+                ),
+            ],
+            8,
+            8,
+            2.0,
+        );
+
+        assert_eq!(image.pixel(4, 4), [128, 0, 128, 255]);
+    }
+
+    #[test]
+    fn round_trips_png() {
+        let image = Image::from_pixels(2, 1, vec![[255, 0, 0, 255], [0, 255, 0, 128]]);
+        assert_eq!(
+            Image::decode_png(&image.encode_png().unwrap()).unwrap(),
+            image
+        );
+    }
+
+    #[test]
+    fn golden_square_and_star() {
+        let (square, _, _) = Mesh::new(create_square(), 0);
+        let (star, _, _) = Mesh::new(create_star(), 0);
+
+        let image = render(
+            vec![
+                Object::new(
+                    square,
+                    Vec2::new(-60.0, 0.0),
+                    0.3,
+                    Vec2::splat(30.0),
+                    material(Colour::new(0.9, 0.3, 0.2, 1.0)),
+                ),
+                Object::new(
+                    star,
+                    Vec2::new(60.0, 0.0),
+                    0.0,
+                    Vec2::splat(40.0),
+                    material(Colour::new(0.2, 0.6, 0.9, 1.0)),
+                ),
+            ],
+            256,
+            128,
+            1.0,
+        );
+
+        assert_matches_golden("square_and_star.png", &image);
+    }
+
+    #[test]
+    fn golden_holes_and_transparency() {
+        let outer = vec![
+            Vec2::new(-2.0, -2.0),
+            Vec2::new(2.0, -2.0),
+            Vec2::new(2.0, 2.0),
+            Vec2::new(-2.0, 2.0),
+        ];
+        let hole = outer.iter().rev().map(|p| *p * 0.5).collect::<Vec<_>>();
+        let (ring, _, _) = Mesh::new(bm_from_polygons(&[outer, hole]), 0);
+        let (square, _, _) = Mesh::new(create_square(), 0);
+
+        let image = render(
+            vec![
+                Object::new(
+                    ring,
+                    Vec2::ZERO,
+                    0.0,
+                    Vec2::splat(20.0),
+                    material(Colour::new(0.95, 0.85, 0.2, 1.0)),
+                ),
+                Object::new(
+                    square,
+                    Vec2::new(30.0, -30.0),
+                    0.785,
+                    Vec2::splat(25.0),
+                    material(Colour::new(0.3, 0.9, 0.4, 0.5)),
+                ),
+            ],
+            128,
+            128,
+            1.0,
+        );
+
+        assert_matches_golden("holes_and_transparency.png", &image);
+    }
+}
@@ -0,0 +1,71 @@
+use egui::{Context, DragValue, Ui};
+
+use crate::opengl::{pipelines::grid::GridSettings, structs::Colour};
+
+pub struct GridUI {}
+
+impl GridUI {
+    pub fn new() -> GridUI {
+        GridUI {}
+    }
+
+    /// Window editing how the viewport grid is drawn
+    pub fn ui(egui_ctx: &Context, settings: &mut GridSettings) {
+        egui::Window::new("Grid")
+            .default_open(false)
+            .show(egui_ctx, |ui| {
+                egui::Grid::new("grid_settings")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label("Spacing");
+                        ui.add(
+                            DragValue::new(&mut settings.spacing)
+                                .clamp_range(1.0..=10000.0)
+                                .speed(1.0),
+                        );
+                        ui.end_row();
+
+                        ui.label("Subdivisions");
+                        ui.add(DragValue::new(&mut settings.subdivisions).clamp_range(1..=20));
+                        ui.end_row();
+
+                        ui.label("Major Lines");
+                        line_settings(ui, &mut settings.major_colour, &mut settings.major_width);
+                        ui.end_row();
+
+                        ui.label("Minor Lines");
+                        line_settings(ui, &mut settings.minor_colour, &mut settings.minor_width);
+                        ui.end_row();
+
+                        ui.label("Background");
+                        let mut background = settings.background.truncate().to_array();
+                        if ui.color_edit_button_rgb(&mut background).changed() {
+                            settings.background = glam::Vec3::from(background).extend(1.0);
+                        }
+                        ui.end_row();
+                    });
+
+                ui.checkbox(&mut settings.show_axes, "Axes");
+                ui.checkbox(&mut settings.isometric, "Isometric");
+                if ui.button("Reset").clicked() {
+                    *settings = GridSettings::default();
+                }
+            });
+    }
+}
+
+/// Colour and width of one kind of grid line
+fn line_settings(ui: &mut Ui, colour: &mut Colour, width: &mut f32) {
+    ui.horizontal(|ui| {
+        let mut rgba = colour.to_array();
+        if ui.color_edit_button_rgba_unmultiplied(&mut rgba).changed() {
+            *colour = Colour::from_array(rgba);
+        }
+        ui.add(
+            DragValue::new(width)
+                .clamp_range(0.5..=8.0)
+                .speed(0.1)
+                .suffix(" px"),
+        );
+    });
+}
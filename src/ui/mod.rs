@@ -1,2 +1,8 @@
+pub mod grid;
+pub mod modifiers;
 pub mod objects;
+pub mod pen;
+pub mod properties;
+pub mod selection;
+pub mod snap;
 pub mod viewport;
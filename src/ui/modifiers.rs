@@ -0,0 +1,191 @@
+use egui::{Context, DragValue, Ui};
+
+use crate::{
+    data::modifiers::{
+        LinearArray, Mirror, MirrorAxis, ModifierKind, ModifierStack, Offset, RadialArray,
+        RoundCorners,
+    },
+    history::{Command, History, MeshState},
+    opengl::scene::SceneData,
+};
+
+enum StackAction {
+    MoveUp(usize),
+    MoveDown(usize),
+    Remove(usize),
+    Apply(usize),
+    Add(ModifierKind),
+}
+
+pub struct ModifiersUI {}
+
+impl ModifiersUI {
+    pub fn new() -> ModifiersUI {
+        ModifiersUI {}
+    }
+
+    /// Show the modifier stack of the active object
+    /// Returns true if the stack was edited and the object needs re-evaluating
+    ///
+    /// Edits to modifier settings are merged while they are dragged, buttons make a step each.
+    pub fn ui(egui_ctx: &Context, scene_data: &mut SceneData, history: &mut History) -> bool {
+        let mut changed = false;
+
+        egui::Window::new("Modifiers")
+            .default_open(false)
+            .show(egui_ctx, |ui| {
+                let Some(key) = scene_data.active_object() else {
+                    ui.label("No active object");
+                    return;
+                };
+                let object = &mut scene_data.objects_mut()[key];
+
+                // Edit a copy so the object is only marked dirty when something actually changes
+                let mut stack = object.modifiers().clone();
+                let action = Self::stack_ui(ui, &mut stack);
+
+                if &stack != object.modifiers() {
+                    let before = MeshState::of(object);
+                    *object.modifiers_mut() = stack;
+                    history.push_merged(Command::Mesh {
+                        key,
+                        before,
+                        after: MeshState::of(object),
+                    });
+                    changed = true;
+                }
+
+                if let Some(action) = action {
+                    let before = MeshState::of(object);
+                    match action {
+                        StackAction::MoveUp(index) => object.modifiers_mut().move_up(index),
+                        StackAction::MoveDown(index) => object.modifiers_mut().move_down(index),
+                        StackAction::Remove(index) => {
+                            object.modifiers_mut().remove(index);
+                        }
+                        StackAction::Apply(index) => object.apply_modifier(index),
+                        StackAction::Add(kind) => object.add_modifier(kind),
+                    }
+                    history.push(Command::Mesh {
+                        key,
+                        before,
+                        after: MeshState::of(object),
+                    });
+                    changed = true;
+                }
+            });
+
+        changed
+    }
+
+    fn stack_ui(ui: &mut Ui, stack: &mut ModifierStack) -> Option<StackAction> {
+        let mut action = None;
+
+        ui.menu_button("Add Modifier", |ui| {
+            let kinds = [
+                ModifierKind::LinearArray(LinearArray::default()),
+                ModifierKind::RadialArray(RadialArray::default()),
+                ModifierKind::Mirror(Mirror::default()),
+                ModifierKind::Offset(Offset::default()),
+                ModifierKind::RoundCorners(RoundCorners::default()),
+            ];
+            for kind in kinds {
+                if ui.button(kind.name()).clicked() {
+                    action = Some(StackAction::Add(kind));
+                    ui.close_menu();
+                }
+            }
+        });
+
+        let len = stack.len();
+        for index in 0..len {
+            let modifier = stack.get_mut(index).unwrap();
+
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut modifier.enabled, modifier.kind.name());
+                if ui.add_enabled(index > 0, egui::Button::new("⏶")).clicked() {
+                    action = Some(StackAction::MoveUp(index));
+                }
+                if ui
+                    .add_enabled(index + 1 < len, egui::Button::new("⏷"))
+                    .clicked()
+                {
+                    action = Some(StackAction::MoveDown(index));
+                }
+                if ui.button("Apply").clicked() {
+                    action = Some(StackAction::Apply(index));
+                }
+                if ui.button("✖").clicked() {
+                    action = Some(StackAction::Remove(index));
+                }
+            });
+
+            ui.push_id(index, |ui| Self::settings_ui(ui, &mut modifier.kind));
+        }
+
+        action
+    }
+
+    fn settings_ui(ui: &mut Ui, kind: &mut ModifierKind) {
+        match kind {
+            ModifierKind::LinearArray(m) => {
+                ui.horizontal(|ui| {
+                    ui.label("Count");
+                    ui.add(DragValue::new(&mut m.count).clamp_range(1..=1000));
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Offset");
+                    ui.add(DragValue::new(&mut m.offset.x).speed(0.05));
+                    ui.add(DragValue::new(&mut m.offset.y).speed(0.05));
+                });
+            }
+            ModifierKind::RadialArray(m) => {
+                ui.horizontal(|ui| {
+                    ui.label("Count");
+                    ui.add(DragValue::new(&mut m.count).clamp_range(1..=1000));
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Centre");
+                    ui.add(DragValue::new(&mut m.centre.x).speed(0.05));
+                    ui.add(DragValue::new(&mut m.centre.y).speed(0.05));
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Angle");
+                    ui.drag_angle(&mut m.angle);
+                });
+            }
+            ModifierKind::Mirror(m) => {
+                ui.horizontal(|ui| {
+                    ui.label("Axis");
+                    ui.radio_value(&mut m.axis, MirrorAxis::X, "X");
+                    ui.radio_value(&mut m.axis, MirrorAxis::Y, "Y");
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Centre");
+                    ui.add(DragValue::new(&mut m.centre).speed(0.05));
+                });
+            }
+            ModifierKind::Offset(m) => {
+                ui.horizontal(|ui| {
+                    ui.label("Distance");
+                    ui.add(DragValue::new(&mut m.distance).speed(0.01));
+                });
+            }
+            ModifierKind::RoundCorners(m) => {
+                ui.horizontal(|ui| {
+                    ui.label("Radius");
+                    ui.add(
+                        DragValue::new(&mut m.radius)
+                            .speed(0.01)
+                            .clamp_range(0.0..=f32::MAX),
+                    );
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Segments");
+                    ui.add(DragValue::new(&mut m.segments).clamp_range(1..=64));
+                });
+            }
+        }
+    }
+}
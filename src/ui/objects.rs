@@ -1,19 +1,458 @@
-use egui::Context;
+use std::collections::HashSet;
 
-pub struct ObjectsUI {}
+use egui::{Align, Context, Layout, Sense, Stroke, TextEdit, Ui};
+
+use crate::{
+    history::{Command, History, ObjectInfo},
+    opengl::{
+        scene::{ObjectKey, SceneData},
+        structs::Object,
+    },
+};
+
+/// Height of a row, in points
+const ROW_HEIGHT: f32 = 18.0;
+/// Indent of each level of the tree, in points
+const INDENT: f32 = 14.0;
+
+/// A line of the list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Row {
+    key: ObjectKey,
+    depth: usize,
+    has_children: bool,
+}
+
+/// Something done to a row, applied once the list has been drawn
+enum RowAction {
+    Select {
+        key: ObjectKey,
+        extend: bool,
+    },
+    ToggleCollapsed(ObjectKey),
+    ToggleHidden(ObjectKey),
+    ToggleLocked(ObjectKey),
+    StartRename(ObjectKey),
+    FinishRename,
+    CancelRename,
+    /// Move `moved` in the draw order to where `target` is
+    Drop {
+        moved: ObjectKey,
+        target: ObjectKey,
+    },
+}
+
+/// The outliner: every object in the scene, topmost first with children under their parents
+pub struct ObjectsUI {
+    /// Only objects whose names contain this are listed, without the tree
+    filter: String,
+    collapsed: HashSet<ObjectKey>,
+    /// Object being renamed and the name typed so far
+    renaming: Option<(ObjectKey, String)>,
+    /// Object whose row is being dragged to a new place in the draw order
+    dragging: Option<ObjectKey>,
+    /// Active object when the list was last drawn, so it can be scrolled to when the viewport
+    /// makes another object active
+    last_active: Option<ObjectKey>,
+}
 
 impl ObjectsUI {
     pub fn new() -> ObjectsUI {
-        ObjectsUI {}
+        ObjectsUI {
+            filter: String::new(),
+            collapsed: HashSet::new(),
+            renaming: None,
+            dragging: None,
+            last_active: None,
+        }
     }
 
-    pub fn ui(egui_ctx: &Context) {
+    /// Show the outliner, which selects objects the same way clicks in the viewport do
+    ///
+    /// Renaming, hiding, locking and reordering objects are each a step in the history.
+    pub fn ui(&mut self, egui_ctx: &Context, scene_data: &mut SceneData, history: &mut History) {
         egui::Window::new("Objects")
             .default_open(false)
             .show(egui_ctx, |ui| {
-                egui::ScrollArea::both()
-                    .auto_shrink([false; 2])
-                    .show(ui, |ui| ui.label("Howdy"));
+                ui.add(TextEdit::singleline(&mut self.filter).hint_text("Search"));
+                ui.separator();
+
+                let active = scene_data.active_object();
+                let scroll_to = if active != self.last_active {
+                    self.last_active = active;
+                    if let Some(key) = active {
+                        self.reveal(scene_data, key);
+                    }
+                    active
+                } else {
+                    None
+                };
+
+                let rows = outliner_rows(scene_data, &self.filter, &self.collapsed);
+                let row_height = ROW_HEIGHT + ui.spacing().item_spacing.y;
+                let mut scroll_area = egui::ScrollArea::vertical().auto_shrink([false; 2]);
+                if let Some(index) =
+                    scroll_to.and_then(|key| rows.iter().position(|row| row.key == key))
+                {
+                    scroll_area = scroll_area.vertical_scroll_offset(index as f32 * row_height);
+                }
+
+                let mut action = None;
+                scroll_area.show_rows(ui, ROW_HEIGHT, rows.len(), |ui, range| {
+                    for row in &rows[range] {
+                        if let Some(row_action) = self.row_ui(ui, scene_data, row) {
+                            action = Some(row_action);
+                        }
+                    }
+                });
+
+                if self.dragging.is_some() && ui.input(|input| !input.pointer.any_down()) {
+                    self.dragging = None;
+                }
+                if let Some(action) = action {
+                    self.apply(ui, scene_data, history, action);
+                }
             });
     }
+
+    fn row_ui(&mut self, ui: &mut Ui, scene_data: &SceneData, row: &Row) -> Option<RowAction> {
+        let object = &scene_data.objects()[row.key];
+        let mut action = None;
+
+        let response = ui
+            .horizontal(|ui| {
+                ui.set_height(ROW_HEIGHT);
+                ui.add_space(row.depth as f32 * INDENT);
+
+                let button_width = ui.spacing().interact_size.y;
+                if row.has_children {
+                    let icon = if self.collapsed.contains(&row.key) {
+                        "⏵"
+                    } else {
+                        "⏷"
+                    };
+                    if ui.small_button(icon).clicked() {
+                        action = Some(RowAction::ToggleCollapsed(row.key));
+                    }
+                } else {
+                    ui.add_space(button_width);
+                }
+
+                ui.with_layout(Layout::right_to_left(Align::Center), |ui| {
+                    let lock = if object.locked { "🔒" } else { "🔓" };
+                    if ui.small_button(lock).on_hover_text("Lock").clicked() {
+                        action = Some(RowAction::ToggleLocked(row.key));
+                    }
+                    let eye = if object.hidden { "◌" } else { "👁" };
+                    if ui.small_button(eye).on_hover_text("Hide").clicked() {
+                        action = Some(RowAction::ToggleHidden(row.key));
+                    }
+
+                    ui.with_layout(Layout::left_to_right(Align::Center), |ui| {
+                        if let Some(row_action) = self.name_ui(ui, object, row.key) {
+                            action = Some(row_action);
+                        }
+                    });
+                });
+            })
+            .response;
+
+        // While a row is dragged, the row under the pointer shows where it will land
+        if let Some(moved) = self.dragging.filter(|&moved| moved != row.key) {
+            let hovered = ui
+                .ctx()
+                .pointer_hover_pos()
+                .is_some_and(|pos| response.rect.contains(pos));
+            if hovered {
+                let above = scene_data.order_rank(moved) < scene_data.order_rank(row.key);
+                let y = if above {
+                    response.rect.top()
+                } else {
+                    response.rect.bottom()
+                };
+                let stroke = Stroke::new(2.0, ui.visuals().selection.stroke.color);
+                ui.painter().hline(response.rect.x_range(), y, stroke);
+                if ui.input(|input| input.pointer.any_released()) {
+                    action = Some(RowAction::Drop {
+                        moved,
+                        target: row.key,
+                    });
+                }
+            }
+        }
+
+        action
+    }
+
+    /// The object's name, which can be clicked to select, double clicked to rename and dragged
+    fn name_ui(&mut self, ui: &mut Ui, object: &Object, key: ObjectKey) -> Option<RowAction> {
+        if let Some((_, name)) = self
+            .renaming
+            .as_mut()
+            .filter(|(renaming, _)| *renaming == key)
+        {
+            let response = ui.add(TextEdit::singleline(name).desired_width(f32::INFINITY));
+            response.request_focus();
+            if ui.input(|input| input.key_pressed(egui::Key::Escape)) {
+                return Some(RowAction::CancelRename);
+            }
+            if response.lost_focus() {
+                return Some(RowAction::FinishRename);
+            }
+            return None;
+        }
+
+        let mut text = egui::RichText::new(&object.name);
+        if object.hidden {
+            text = text.weak();
+        }
+        let response = ui
+            .add(egui::SelectableLabel::new(object.selected, text))
+            .interact(Sense::drag());
+
+        if response.double_clicked() {
+            Some(RowAction::StartRename(key))
+        } else if response.clicked() {
+            let extend = ui.input(|input| input.modifiers.shift || input.modifiers.command);
+            Some(RowAction::Select { key, extend })
+        } else {
+            if response.drag_started() {
+                self.dragging = Some(key);
+            }
+            None
+        }
+    }
+
+    fn apply(
+        &mut self,
+        ui: &Ui,
+        scene_data: &mut SceneData,
+        history: &mut History,
+        action: RowAction,
+    ) {
+        match action {
+            RowAction::Select { key, extend } => {
+                let object = &mut scene_data.objects_mut()[key];
+                if extend {
+                    object.selected = !object.selected;
+                    if object.selected {
+                        scene_data.set_active_object(Some(key));
+                    }
+                } else {
+                    for (_, object) in scene_data.objects_mut().iter_mut() {
+                        object.selected = false;
+                    }
+                    scene_data.objects_mut()[key].selected = true;
+                    scene_data.set_active_object(Some(key));
+                }
+                // Already in view, so don't scroll to it
+                self.last_active = scene_data.active_object();
+            }
+            RowAction::ToggleCollapsed(key) => {
+                if !self.collapsed.remove(&key) {
+                    self.collapsed.insert(key);
+                }
+            }
+            RowAction::ToggleHidden(key) => {
+                edit_info(scene_data, history, key, |info| info.hidden = !info.hidden);
+            }
+            RowAction::ToggleLocked(key) => {
+                edit_info(scene_data, history, key, |info| info.locked = !info.locked);
+            }
+            RowAction::StartRename(key) => {
+                let name = scene_data.objects()[key].name.clone();
+                self.renaming = Some((key, name));
+            }
+            RowAction::FinishRename => {
+                if let Some((key, name)) = self.renaming.take() {
+                    let name = name.trim();
+                    if !name.is_empty() && scene_data.objects().contains_key(key) {
+                        edit_info(scene_data, history, key, |info| {
+                            info.name = name.to_string()
+                        });
+                    }
+                }
+            }
+            RowAction::CancelRename => self.renaming = None,
+            RowAction::Drop { moved, target } => {
+                self.dragging = None;
+                let before = scene_data.object_order().to_vec();
+                if let Some(after) = move_in_order(&before, moved, target) {
+                    history.execute(scene_data, Command::Reorder { before, after });
+                }
+            }
+        }
+        ui.ctx().request_repaint();
+    }
+
+    /// Expand the parents of `key` so its row is listed
+    fn reveal(&mut self, scene_data: &SceneData, key: ObjectKey) {
+        let mut parent = scene_data.parent(key);
+        while let Some(key) = parent {
+            self.collapsed.remove(&key);
+            parent = scene_data.parent(key);
+        }
+    }
+}
+
+/// Change an object's name, visibility or lock as a step in the history
+fn edit_info(
+    scene_data: &mut SceneData,
+    history: &mut History,
+    key: ObjectKey,
+    edit: impl FnOnce(&mut ObjectInfo),
+) {
+    let before = ObjectInfo::of(&scene_data.objects()[key]);
+    let mut after = before.clone();
+    edit(&mut after);
+    if after != before {
+        history.execute(scene_data, Command::Info { key, before, after });
+    }
+}
+
+/// The rows of the list, topmost object first
+///
+/// Children are listed under their parents, also topmost first, unless the parent is collapsed.
+/// With a filter only the objects whose names contain it are listed, each on its own.
+fn outliner_rows(scene_data: &SceneData, filter: &str, collapsed: &HashSet<ObjectKey>) -> Vec<Row> {
+    let order = scene_data.object_order().iter().rev().copied();
+
+    if !filter.is_empty() {
+        let filter = filter.to_lowercase();
+        return order
+            .filter(|&key| {
+                scene_data.objects()[key]
+                    .name
+                    .to_lowercase()
+                    .contains(&filter)
+            })
+            .map(|key| Row {
+                key,
+                depth: 0,
+                has_children: false,
+            })
+            .collect();
+    }
+
+    let mut rows = Vec::with_capacity(scene_data.object_order().len());
+    let mut stack = order
+        .filter(|&key| scene_data.parent(key).is_none())
+        .map(|key| (key, 0))
+        .collect::<Vec<_>>();
+    // Popped from the end, so the topmost root goes last
+    stack.reverse();
+
+    while let Some((key, depth)) = stack.pop() {
+        let children = scene_data.children(key);
+        rows.push(Row {
+            key,
+            depth,
+            has_children: !children.is_empty(),
+        });
+
+        if !collapsed.contains(&key) {
+            let mut children = children.to_vec();
+            // Bottom first, so the topmost child is popped first
+            children.sort_by_key(|&child| scene_data.order_rank(child));
+            stack.extend(children.into_iter().map(|child| (child, depth + 1)));
+        }
+    }
+
+    rows
+}
+
+/// Draw order after moving `moved` to where `target` is, or `None` if nothing moves
+///
+/// An object dragged up the list onto another lands above it, one dragged down lands below.
+fn move_in_order(
+    order: &[ObjectKey],
+    moved: ObjectKey,
+    target: ObjectKey,
+) -> Option<Vec<ObjectKey>> {
+    let from = order.iter().position(|&key| key == moved)?;
+    let to = order.iter().position(|&key| key == target)?;
+    if from == to {
+        return None;
+    }
+
+    let mut order = order.to_vec();
+    order.remove(from);
+    order.insert(to, moved);
+    Some(order)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use glam::Vec2;
+
+    use crate::{
+        opengl::structs::{Colour, Material, Mesh},
+        shapes::square::create_square,
+    };
+
+    use super::*;
+
+    /// Four objects named A to D, bottom to top, with B and D children of A
+    fn scene() -> (SceneData, Vec<ObjectKey>) {
+        let (square, _, _) = Mesh::new(create_square(), 0);
+        let material = Rc::new(RefCell::new(Material {
+            colour: Colour::ONE,
+        }));
+        let objects = ["A", "B", "C", "D"]
+            .into_iter()
+            .map(|name| {
+                let mut object =
+                    Object::new(square.clone(), Vec2::ZERO, 0.0, Vec2::ONE, material.clone());
+                object.name = name.to_string();
+                object
+            })
+            .collect();
+        let mut scene_data = SceneData::new(objects);
+        let keys = scene_data.object_order().to_vec();
+        for child in [keys[1], keys[3]] {
+            scene_data.set_parent(child, Some(keys[0]), false).unwrap();
+        }
+        (scene_data, keys)
+    }
+
+    fn names(scene_data: &SceneData, rows: &[Row]) -> Vec<(String, usize)> {
+        rows.iter()
+            .map(|row| (scene_data.objects()[row.key].name.clone(), row.depth))
+            .collect()
+    }
+
+    #[test]
+    fn rows_list_children_under_parents_topmost_first() {
+        let (scene_data, keys) = scene();
+        let mut collapsed = HashSet::new();
+
+        let rows = outliner_rows(&scene_data, "", &collapsed);
+        let expected = [("C", 0), ("A", 0), ("D", 1), ("B", 1)];
+        assert_eq!(
+            names(&scene_data, &rows),
+            expected.map(|(name, depth)| (name.to_string(), depth))
+        );
+        assert!(rows[1].has_children);
+
+        collapsed.insert(keys[0]);
+        assert_eq!(outliner_rows(&scene_data, "", &collapsed).len(), 2);
+
+        // Filtering lists matches on their own, even under a collapsed parent
+        let rows = outliner_rows(&scene_data, "d", &collapsed);
+        assert_eq!(names(&scene_data, &rows), [("D".to_string(), 0)]);
+    }
+
+    #[test]
+    fn dropping_moves_past_the_target_in_the_direction_dragged() {
+        let (_, keys) = scene();
+        let [a, b, c, d] = [keys[0], keys[1], keys[2], keys[3]];
+
+        // Dragged up the list, which is higher in the draw order, lands above the target
+        assert_eq!(move_in_order(&keys, a, c), Some(vec![b, c, a, d]));
+        // Dragged down lands below it
+        assert_eq!(move_in_order(&keys, d, b), Some(vec![a, d, b, c]));
+        assert_eq!(move_in_order(&keys, b, b), None);
+    }
 }
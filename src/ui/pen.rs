@@ -0,0 +1,47 @@
+use egui::{Color32, Context, Id, LayerId, Order, Pos2, Shape, Stroke};
+use glam::Vec2;
+
+use crate::{opengl::camera::Camera2D, operators::pen::PenTool};
+
+/// Radius of a placed point, in points
+const POINT_RADIUS: f32 = 3.0;
+/// Radius of the end of a handle, in points
+const HANDLE_RADIUS: f32 = 2.5;
+
+pub struct PenUI {}
+
+impl PenUI {
+    pub fn new() -> PenUI {
+        PenUI {}
+    }
+
+    /// Draw the path being drawn over the viewport, beneath any windows
+    pub fn ui(egui_ctx: &Context, pen: &PenTool, camera: &Camera2D) {
+        let pixels_per_point = egui_ctx.pixels_per_point();
+        let to_screen = |world: Vec2| {
+            let point = camera.world_to_screen(world) / pixels_per_point;
+            Pos2::new(point.x, point.y)
+        };
+
+        let painter = egui_ctx.layer_painter(LayerId::new(Order::Background, Id::new("pen")));
+        let stroke = Stroke::new(1.0, Color32::WHITE);
+        let handle_stroke = Stroke::new(1.0, Color32::from_gray(160));
+
+        let path = pen.preview().into_iter().map(to_screen).collect::<Vec<_>>();
+        if path.len() >= 2 {
+            painter.add(Shape::line(path, stroke));
+        }
+
+        for point in pen.points() {
+            let centre = to_screen(point.pos);
+            if point.handle != Vec2::ZERO {
+                for end in [point.pos + point.handle, point.pos - point.handle] {
+                    let end = to_screen(end);
+                    painter.line_segment([centre, end], handle_stroke);
+                    painter.circle_filled(end, HANDLE_RADIUS, handle_stroke.color);
+                }
+            }
+            painter.circle_filled(centre, POINT_RADIUS, Color32::WHITE);
+        }
+    }
+}
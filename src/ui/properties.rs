@@ -0,0 +1,308 @@
+use std::{cell::RefCell, rc::Rc};
+
+use egui::{Context, DragValue, Response, Ui};
+
+use crate::{
+    history::{Command, History},
+    opengl::{
+        matrices::Transform2D,
+        scene::{ObjectKey, SceneData},
+        structs::{Colour, Material},
+    },
+};
+
+pub struct PropertiesUI {}
+
+impl PropertiesUI {
+    pub fn new() -> PropertiesUI {
+        PropertiesUI {}
+    }
+
+    /// Show the transform, material and mesh statistics of the active object
+    ///
+    /// Edits apply to every selected object: the change made to the active object's transform
+    /// is added to each of theirs, and the colour is given to each of their materials. Locked
+    /// objects are left alone. Drags merge into one undo step, typed values make a step each.
+    pub fn ui(egui_ctx: &Context, scene_data: &mut SceneData, history: &mut History) {
+        egui::Window::new("Properties")
+            .default_open(false)
+            .show(egui_ctx, |ui| {
+                let Some(key) = scene_data.active_object() else {
+                    ui.label("No active object");
+                    return;
+                };
+                let object = &scene_data.objects()[key];
+                ui.heading(&object.name);
+
+                let targets = edit_targets(scene_data, key);
+                if targets.len() > 1 {
+                    ui.label(format!("Editing {} objects", targets.len()));
+                }
+
+                ui.add_enabled_ui(!targets.is_empty(), |ui| {
+                    Self::transform_ui(ui, scene_data, history, key, &targets);
+                    Self::material_ui(ui, scene_data, history, key, &targets);
+                });
+                if targets.is_empty() {
+                    ui.label("Locked");
+                }
+
+                Self::statistics_ui(ui, scene_data, key);
+            });
+    }
+
+    fn transform_ui(
+        ui: &mut Ui,
+        scene_data: &mut SceneData,
+        history: &mut History,
+        key: ObjectKey,
+        targets: &[ObjectKey],
+    ) {
+        let before = scene_data.objects()[key].transform();
+        let mut after = before;
+        let mut degrees = before.rotation.to_degrees();
+
+        let mut responses = Vec::new();
+        egui::Grid::new("transform").num_columns(3).show(ui, |ui| {
+            ui.label("Translation");
+            responses.push(ui.add(DragValue::new(&mut after.translation.x).speed(1.0)));
+            responses.push(ui.add(DragValue::new(&mut after.translation.y).speed(1.0)));
+            ui.end_row();
+
+            ui.label("Rotation");
+            responses.push(ui.add(DragValue::new(&mut degrees).speed(1.0).suffix("°")));
+            ui.end_row();
+
+            ui.label("Scale");
+            responses.push(ui.add(DragValue::new(&mut after.scale.x).speed(0.01)));
+            responses.push(ui.add(DragValue::new(&mut after.scale.y).speed(0.01)));
+            ui.end_row();
+        });
+
+        if degrees != before.rotation.to_degrees() {
+            after.rotation = degrees.to_radians();
+        }
+        if after == before {
+            return;
+        }
+
+        let commands = targets
+            .iter()
+            .map(|&target| {
+                let before_target = scene_data.objects()[target].transform();
+                let after_target = if target == key {
+                    after
+                } else {
+                    apply_change(before_target, &before, &after)
+                };
+                Command::Transform {
+                    key: target,
+                    before: before_target,
+                    after: after_target,
+                }
+            })
+            .collect::<Vec<_>>();
+        let dragging = responses.iter().any(Response::dragged);
+        record(scene_data, history, commands, dragging);
+    }
+
+    fn material_ui(
+        ui: &mut Ui,
+        scene_data: &mut SceneData,
+        history: &mut History,
+        key: ObjectKey,
+        targets: &[ObjectKey],
+    ) {
+        let before = scene_data.objects()[key].material().borrow().colour;
+        let mut rgba = before.to_array();
+
+        let response = ui
+            .horizontal(|ui| {
+                ui.label("Colour");
+                ui.color_edit_button_rgba_unmultiplied(&mut rgba)
+            })
+            .inner;
+
+        let after = Colour::from_array(rgba);
+        if after == before {
+            return;
+        }
+
+        // Objects can share a material, which should only be changed once
+        let mut materials: Vec<Rc<RefCell<Material>>> = Vec::new();
+        for &target in targets {
+            let material = scene_data.objects()[target].material();
+            if !materials.iter().any(|other| Rc::ptr_eq(other, material)) {
+                materials.push(material.clone());
+            }
+        }
+
+        let commands = materials
+            .into_iter()
+            .map(|material| {
+                let before = material.borrow().colour;
+                Command::MaterialColour {
+                    material,
+                    before,
+                    after,
+                }
+            })
+            .collect::<Vec<_>>();
+        // The picker's sliders are in a popup whose drags don't show on the button's response,
+        // but typed values change it without the mouse held down
+        let dragging = response.dragged() || ui.input(|input| input.pointer.any_down());
+        record(scene_data, history, commands, dragging);
+    }
+
+    fn statistics_ui(ui: &mut Ui, scene_data: &SceneData, key: ObjectKey) {
+        let object = &scene_data.objects()[key];
+        let mesh = object.borrow_evaluated_mesh();
+        let bmesh = &mesh.raw_mesh;
+
+        ui.separator();
+        egui::Grid::new("statistics").num_columns(2).show(ui, |ui| {
+            for (label, count) in [
+                ("Vertices", bmesh.vertices.len()),
+                ("Edges", bmesh.edges.len()),
+                ("Faces", bmesh.faces.len()),
+                ("Triangles", mesh.tris as usize),
+            ] {
+                ui.label(label);
+                ui.label(count.to_string());
+                ui.end_row();
+            }
+        });
+        if !object.modifiers().is_empty() {
+            ui.label("With modifiers applied");
+        }
+    }
+}
+
+/// Objects an edit to the active object applies to: it and the other selected objects, unless
+/// they are locked or hidden
+///
+/// Objects below another target move with it already, so they are left out rather than being
+/// changed twice.
+fn edit_targets(scene_data: &SceneData, active: ObjectKey) -> Vec<ObjectKey> {
+    let objects = scene_data.objects();
+    let is_target = |key: ObjectKey| {
+        let object = &objects[key];
+        if key == active {
+            !object.locked
+        } else {
+            object.selected && object.is_selectable()
+        }
+    };
+    let has_target_ancestor = |key: ObjectKey| {
+        let mut parent = scene_data.parent(key);
+        while let Some(key) = parent {
+            if is_target(key) {
+                return true;
+            }
+            parent = scene_data.parent(key);
+        }
+        false
+    };
+
+    scene_data
+        .object_order()
+        .iter()
+        .copied()
+        .filter(|&key| is_target(key) && !has_target_ancestor(key))
+        .collect()
+}
+
+/// Make the edit and record it, merging it into the last step while a value is dragged
+fn record(
+    scene_data: &mut SceneData,
+    history: &mut History,
+    commands: Vec<Command>,
+    dragging: bool,
+) {
+    let mut command = Command::Group(commands);
+    command.redo(scene_data);
+    history.push_merged(command);
+    if !dragging {
+        history.seal();
+    }
+}
+
+/// Move `transform` by the change from `before` to `after`
+///
+/// Translation and rotation are offset by the difference and scale is multiplied by the ratio,
+/// so objects keep their own values for anything that wasn't edited.
+fn apply_change(transform: Transform2D, before: &Transform2D, after: &Transform2D) -> Transform2D {
+    let scale = |value: f32, before: f32, after: f32| {
+        if before == after {
+            value
+        } else if before == 0.0 {
+            after
+        } else {
+            value * after / before
+        }
+    };
+
+    Transform2D {
+        translation: transform.translation + after.translation - before.translation,
+        rotation: transform.rotation + after.rotation - before.rotation,
+        scale: glam::Vec2::new(
+            scale(transform.scale.x, before.scale.x, after.scale.x),
+            scale(transform.scale.y, before.scale.y, after.scale.y),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec2;
+
+    use crate::{
+        opengl::structs::{Mesh, Object},
+        shapes::square::create_square,
+    };
+
+    use super::*;
+
+    #[test]
+    fn changes_are_added_to_other_transforms() {
+        let before = Transform2D::new(Vec2::new(1.0, 2.0), 0.5, Vec2::new(2.0, 0.0));
+        let after = Transform2D::new(Vec2::new(4.0, 2.0), 1.0, Vec2::new(3.0, 5.0));
+        let other = Transform2D::new(Vec2::new(-1.0, -1.0), 0.25, Vec2::new(4.0, 1.0));
+
+        let moved = apply_change(other, &before, &after);
+        assert_eq!(moved.translation, Vec2::new(2.0, -1.0));
+        assert!((moved.rotation - 0.75).abs() < 1e-6);
+        // Scaled by the ratio, except from zero where there is none
+        assert_eq!(moved.scale, Vec2::new(6.0, 5.0));
+
+        // Untouched values are kept exactly
+        assert_eq!(apply_change(other, &before, &before), other);
+    }
+
+    #[test]
+    fn children_of_targets_are_not_edited_twice() {
+        let object = || {
+            let (mesh, _, _) = Mesh::new(create_square(), 0);
+            let material = Rc::new(RefCell::new(Material {
+                colour: Colour::ONE,
+            }));
+            Object::new(mesh, Vec2::ZERO, 0.0, Vec2::ONE, material)
+        };
+        let mut scene_data = SceneData::new(vec![object(), object(), object(), object()]);
+        let [parent, child, grandchild, other] = [0, 1, 2, 3].map(|i| scene_data.object_order()[i]);
+        scene_data.set_parent(child, Some(parent), false).unwrap();
+        scene_data
+            .set_parent(grandchild, Some(child), false)
+            .unwrap();
+        for key in [child, grandchild, other] {
+            scene_data.edit_object(key, |object| object.selected = true);
+        }
+
+        // The active object counts as a target even when it isn't selected
+        assert_eq!(edit_targets(&scene_data, parent), vec![parent, other]);
+        assert_eq!(edit_targets(&scene_data, other), vec![child, other]);
+
+        scene_data.edit_object(child, |object| object.locked = true);
+        assert_eq!(edit_targets(&scene_data, other), vec![grandchild, other]);
+    }
+}
@@ -0,0 +1,40 @@
+use egui::{Color32, Context, Id, LayerId, Order, Pos2, Shape, Stroke};
+use glam::Vec2;
+
+use crate::operators::select::{SelectGesture, SelectShape};
+
+pub struct SelectionUI {}
+
+impl SelectionUI {
+    pub fn new() -> SelectionUI {
+        SelectionUI {}
+    }
+
+    /// Draw the box or lasso being dragged over the viewport, beneath any windows
+    pub fn ui(egui_ctx: &Context, gesture: &SelectGesture) {
+        let pixels_per_point = egui_ctx.pixels_per_point();
+        let points = gesture
+            .outline()
+            .into_iter()
+            .map(|point: Vec2| {
+                let point = point / pixels_per_point;
+                Pos2::new(point.x, point.y)
+            })
+            .collect::<Vec<_>>();
+        if points.len() < 2 {
+            return;
+        }
+
+        let painter = egui_ctx.layer_painter(LayerId::new(Order::Background, Id::new("selection")));
+        let stroke = Stroke::new(1.0, Color32::WHITE);
+
+        match gesture.shape() {
+            SelectShape::Box => painter.add(Shape::convex_polygon(
+                points,
+                Color32::from_white_alpha(16),
+                stroke,
+            )),
+            SelectShape::Lasso => painter.add(Shape::closed_line(points, stroke)),
+        };
+    }
+}
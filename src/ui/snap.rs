@@ -0,0 +1,104 @@
+use egui::{
+    Align2, Color32, Context, FontId, Id, LayerId, Order, Pos2, Rect, Shape, Stroke,
+    Vec2 as EguiVec2,
+};
+
+use crate::{
+    opengl::camera::Camera2D,
+    operators::snap::{SnapKind, SnapSettings, SnapTarget},
+};
+
+/// Half the size of the snap indicator, in points
+const MARKER_SIZE: f32 = 6.0;
+const MARKER_COLOUR: Color32 = Color32::from_rgb(255, 200, 40);
+
+pub struct SnapUI {}
+
+impl SnapUI {
+    pub fn new() -> SnapUI {
+        SnapUI {}
+    }
+
+    /// Mark the point being snapped to, shaped by what kind of target it is
+    pub fn ui(egui_ctx: &Context, target: &SnapTarget, camera: &Camera2D) {
+        let point = camera.world_to_screen(target.point) / egui_ctx.pixels_per_point();
+        let centre = Pos2::new(point.x, point.y);
+        let painter = egui_ctx.layer_painter(LayerId::new(Order::Foreground, Id::new("snap")));
+        let stroke = Stroke::new(1.5, MARKER_COLOUR);
+        let size = MARKER_SIZE;
+
+        match target.kind {
+            SnapKind::Vertex => {
+                painter.rect_stroke(
+                    Rect::from_center_size(centre, EguiVec2::splat(size * 2.0)),
+                    0.0,
+                    stroke,
+                );
+            }
+            SnapKind::EdgeMidpoint => {
+                let corners = [(0.0, -size), (size, size * 0.8), (-size, size * 0.8)]
+                    .map(|(x, y)| centre + EguiVec2::new(x, y));
+                painter.add(Shape::closed_line(corners.to_vec(), stroke));
+            }
+            SnapKind::Edge => {
+                painter.line_segment(
+                    [
+                        centre + EguiVec2::new(-size, size),
+                        centre + EguiVec2::new(size, -size),
+                    ],
+                    stroke,
+                );
+                painter.circle_filled(centre, 2.0, MARKER_COLOUR);
+            }
+            SnapKind::ObjectCentre => {
+                painter.circle_stroke(centre, size, stroke);
+                painter.circle_filled(centre, 2.0, MARKER_COLOUR);
+            }
+            SnapKind::BoundsCorner => {
+                for arm in [EguiVec2::X, EguiVec2::Y] {
+                    painter.line_segment([centre, centre + arm * size * 1.5], stroke);
+                }
+                painter.circle_filled(centre, 2.0, MARKER_COLOUR);
+            }
+            SnapKind::Grid => {
+                painter.line_segment(
+                    [centre - EguiVec2::X * size, centre + EguiVec2::X * size],
+                    stroke,
+                );
+                painter.line_segment(
+                    [centre - EguiVec2::Y * size, centre + EguiVec2::Y * size],
+                    stroke,
+                );
+            }
+        }
+
+        painter.text(
+            centre + EguiVec2::new(size + 4.0, -size - 4.0),
+            Align2::LEFT_BOTTOM,
+            target.kind.name(),
+            FontId::proportional(11.0),
+            MARKER_COLOUR,
+        );
+    }
+
+    /// Window choosing what Ctrl snaps to
+    pub fn settings_ui(egui_ctx: &Context, settings: &mut SnapSettings) {
+        egui::Window::new("Snapping").show(egui_ctx, |ui| {
+            ui.label("Hold Ctrl to snap");
+            ui.horizontal(|ui| {
+                ui.label("Tolerance");
+                ui.add(
+                    egui::DragValue::new(&mut settings.tolerance)
+                        .clamp_range(1.0..=50.0)
+                        .suffix(" px"),
+                );
+            });
+            ui.checkbox(&mut settings.grid, "Grid");
+            ui.checkbox(&mut settings.vertices, "Vertices");
+            ui.checkbox(&mut settings.edge_midpoints, "Edge Midpoints");
+            ui.checkbox(&mut settings.edges, "Edges");
+            ui.checkbox(&mut settings.object_centres, "Object Centres");
+            ui.checkbox(&mut settings.bounds_corners, "Bounds Corners");
+        });
+    }
+}
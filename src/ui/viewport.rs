@@ -1,5 +1,6 @@
 use egui::Context;
-use glam::Vec2;
+
+use crate::opengl::camera::Camera2D;
 
 pub struct ViewportUI {}
 
@@ -8,10 +9,18 @@ impl ViewportUI {
         ViewportUI {}
     }
 
-    pub fn ui(egui_ctx: &Context, position: Vec2, zoom: f32) {
+    /// `mode` describes what is being worked on, and `status` the operator currently running
+    pub fn ui(egui_ctx: &Context, camera: &Camera2D, mode: &str, status: Option<&str>) {
         egui::Window::new("Viewport Info").show(egui_ctx, |ui| {
+            ui.label(mode);
+            let position = camera.position;
             ui.label(format!("Position: ({:.2}, {:.2})", position.x, position.y));
-            ui.label(format!("Zoom: {:.2}", zoom));
+            ui.label(format!("Zoom: {:.2}", camera.zoom));
+            ui.label(format!("Rotation: {:.1}°", camera.rotation.to_degrees()));
+            if let Some(status) = status {
+                ui.separator();
+                ui.label(status);
+            }
         });
     }
 }